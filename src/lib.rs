@@ -1,2 +1,5 @@
+// 代码风格: 保留显式 return 和 &'static str
+#![allow(clippy::needless_return, clippy::redundant_static_lifetimes)]
+
 pub mod vm;
 mod module;
@@ -1,17 +1,43 @@
+#![allow(clippy::needless_return, clippy::redundant_static_lifetimes)]
+
 extern crate dy;
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io;
+use std::io::IsTerminal;
+use std::process;
 
 use dy::vm::dy_parser::DyParser;
 use dy::vm::dy_common::DyRef;
+use dy::vm::dy_format::{self, FormatOptions, BraceStyle, IndentStyle};
+use dy::vm::dy_export;
+use dy::vm::dy_diagnostic::{self, Diagnostic, DiagnosticOptions, SourceFile};
+use dy::vm::dy_locale::{self, Locale};
+use dy::vm::dy_symbols::Declarations;
+use dy::vm::dy_syntax::SyntaxTree;
+
+const FMT_USAGE: &'static str = "usage: dy fmt [--check] [--indent=tabs|<n>] [--brace=allman|kr] [--blank-lines=<n>] \
+	[--width=<n>] [files...]";
+const PARSE_USAGE: &'static str = "usage: dy parse [--emit=sexpr|json|tokens] [--trivia] file";
+const CHECK_USAGE: &'static str = "usage: dy check [--format=text|json] [--color=auto|always|never] [--nullable] \
+	[--lang=en-US|zh-CN] [--namespace=<name>]... [--reference=<alias>=<file>]... files...";
 
 fn main() {
 
-//	let args: Vec<String> = env::args().collect();
+	let args: Vec<String> = env::args().collect();
+	if args.len() > 1 && args[1] == "fmt" {
+		process::exit(fmt(&args[2..]));
+	}
+	if args.len() > 1 && args[1] == "parse" {
+		process::exit(parse(&args[2..]));
+	}
+	if args.len() > 1 && args[1] == "check" {
+		process::exit(check(&args[2..]));
+	}
 
 	let mut filename = env::current_dir().expect("error");
-	filename.push("examples/hello_world.dy"); //&args[1];
+	filename.push(args.get(1).map_or("examples/hello_world.dy", |x| x.as_str()));
 	let filename = filename.as_path();
 
 	println!("In file {:?}", filename);
@@ -21,15 +47,19 @@ fn main() {
 	let mut contents = String::new();
 	f.read_to_string(&mut contents)
 		.expect("something went wrong reading the file");
+	let source = SourceFile::new(&filename.to_string_lossy(), &contents);
 	let mut dy_parser = DyParser::new(contents);
 	dy_parser.lexer();
-
-	println!("With parser:\n{:#?}", dy_parser);
+	let tree = dy_parser.parse();
+	let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+	for diagnostic in dy_diagnostic::diagnose(&[(&tree, &source)], &DiagnosticOptions::default()).remove(0) {
+		eprintln!("{}", dy_diagnostic::render(&diagnostic, &source, color, dy_locale::locale()));
+	}
 	println!("Hello World, DY!");
 
 	// ---------------  test -------------------
 	let a1 = DyRef::new(11);
-	let a2 = DyRef::new(12);
+	let _a2 = DyRef::new(12);
 	{
 		let mut b1 = a1.borrow_mut();
 		*b1 = 10;
@@ -37,3 +67,247 @@ fn main() {
 
 	println!("----->{}, {:?}, {:?}", a1, a1.parent(), a1.root());
 }
+
+// dy fmt: 没有文件时从 stdin 读, 写到 stdout
+// --check 只检查, 有文件需要格式化时返回 1
+fn fmt(args: &[String]) -> i32 {
+	let mut options = FormatOptions::default();
+	let mut check = false;
+	let mut files = Vec::new();
+	for arg in args {
+		let (name, value) = match arg.find('=') {
+			Some(i) => (&arg[..i], &arg[i+1..]),
+			None => (arg.as_str(), ""),
+		};
+		let valid = match name {
+			"--check" => { check = true; true }
+			"--indent" if value == "tabs" => { options.indent = IndentStyle::Tabs; true }
+			"--indent" => value.parse().map(|x| options.indent = IndentStyle::Spaces(x)).is_ok(),
+			"--brace" if value == "allman" => { options.brace_style = BraceStyle::Allman; true }
+			"--brace" if value == "kr" => { options.brace_style = BraceStyle::KAndR; true }
+			"--blank-lines" => value.parse().map(|x| options.max_blank_lines = x).is_ok(),
+			"--width" => value.parse().map(|x| options.max_width = x).is_ok(),
+			_ if !arg.starts_with("--") => { files.push(arg.clone()); true }
+			_ => false,
+		};
+		if !valid {
+			eprintln!("invalid argument '{}'\n{}", arg, FMT_USAGE);
+			return 2;
+		}
+	}
+
+	if files.is_empty() {
+		let mut contents = String::new();
+		if io::stdin().read_to_string(&mut contents).is_err() {
+			eprintln!("could not read stdin");
+			return 2;
+		}
+		return match format_file("<stdin>", &contents, &options) {
+			Some(ref formatted) if check && *formatted == contents => 0,
+			Some(_) if check => 1,
+			Some(formatted) => if write_stdout(&formatted) { 0 } else { 2 },
+			None => 1,
+		};
+	}
+
+	let mut status = 0;
+	for file in &files {
+		let mut contents = String::new();
+		if File::open(file).and_then(|mut f| f.read_to_string(&mut contents)).is_err() {
+			eprintln!("{}: could not read file", file);
+			status = 1;
+			continue;
+		}
+		let formatted = match format_file(file, &contents, &options) {
+			Some(x) => x,
+			None => {
+				status = 1;
+				continue;
+			}
+		};
+		if formatted == contents {
+			continue;
+		}
+		if check {
+			write_stdout(&format!("{}: not formatted\n", file));
+			status = 1;
+		}
+		else if File::create(file).and_then(|mut f| f.write_all(formatted.as_bytes())).is_err() {
+			eprintln!("{}: could not write file", file);
+			status = 1;
+		}
+	}
+	status
+}
+
+// dy parse: 输出 S 表达式, JSON 语法树或者 JSON token 流, 有语法错误时返回 1
+fn parse(args: &[String]) -> i32 {
+	let mut emit = "sexpr";
+	let mut trivia = false;
+	let mut file = None;
+	for arg in args {
+		match arg.as_str() {
+			"--emit=sexpr" => emit = "sexpr",
+			"--emit=json" => emit = "json",
+			"--emit=tokens" => emit = "tokens",
+			"--trivia" => trivia = true,
+			_ if !arg.starts_with("--") && file.is_none() => file = Some(arg.clone()),
+			_ => {
+				eprintln!("invalid argument '{}'\n{}", arg, PARSE_USAGE);
+				return 2;
+			}
+		}
+	}
+	let file = match file {
+		Some(x) => x,
+		None => {
+			eprintln!("{}", PARSE_USAGE);
+			return 2;
+		}
+	};
+	let mut contents = String::new();
+	if File::open(&file).and_then(|mut f| f.read_to_string(&mut contents)).is_err() {
+		eprintln!("{}: could not read file", file);
+		return 1;
+	}
+	let mut dy_parser = DyParser::new(contents);
+	dy_parser.lexer();
+	if emit == "tokens" {
+		return if write_stdout(&format!("{}\n", dy_export::tokens_to_json(&dy_parser).pretty())) { 0 } else { 2 };
+	}
+	let tree = dy_parser.parse();
+	let output = if emit == "json" {
+		format!("{}\n", dy_export::tree_to_json(&tree, trivia).pretty())
+	}
+	else {
+		dy_export::tree_to_sexpr(&tree, trivia)
+	};
+	if !write_stdout(&output) {
+		return 2;
+	}
+	if emit != "json" {
+		for error in &tree.errors {
+			let (line, column) = tree.line_column(error.span.start);
+			eprintln!("{}:{}:{}: error: {}", file, line + 1, column + 1, error.message);
+		}
+	}
+	if tree.errors.is_empty() { 0 } else { 1 }
+}
+
+// dy check: 检查所有阶段的错误和警告, 文本写到 stderr, JSON 写到 stdout; 有错误时返回 1
+// 没有 --lang 时按 DY_LANG/LC_ALL/LC_MESSAGES/LANG 选语言
+fn check(args: &[String]) -> i32 {
+	let mut json = false;
+	let mut locale = dy_locale::locale();
+	let mut color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+	let mut options = DiagnosticOptions::default();
+	let mut files = Vec::new();
+	for arg in args {
+		match arg.as_str() {
+			"--format=text" => json = false,
+			"--format=json" => json = true,
+			"--color=always" => color = true,
+			"--color=never" => color = false,
+			"--color=auto" => {}
+			"--nullable" => options.nullable = true,
+			_ if arg.starts_with("--lang=") && Locale::parse(&arg[7..]).is_some() => {
+				locale = Locale::parse(&arg[7..]).unwrap_or_default();
+				dy_locale::set_locale(Some(locale));
+			}
+			// 宿主提供的命名空间, 比如 --namespace=System
+			_ if arg.starts_with("--namespace=") && arg.len() > 12 => options.namespaces.push(arg[12..].to_string()),
+			// extern alias 引用的程序集: --reference=Legacy=legacy.dy
+			_ if arg.starts_with("--reference=") && arg[12..].contains('=') => {
+				let (alias, file) = arg[12..].split_at(arg[12..].find('=').unwrap());
+				match read_declarations(&file[1..]) {
+					Some(declarations) => options.references.push((alias.to_string(), declarations)),
+					None => return 1,
+				}
+			}
+			_ if !arg.starts_with("--") => files.push(arg.clone()),
+			_ => {
+				eprintln!("invalid argument '{}'\n{}", arg, CHECK_USAGE);
+				return 2;
+			}
+		}
+	}
+	if files.is_empty() {
+		eprintln!("{}", CHECK_USAGE);
+		return 2;
+	}
+
+	let mut status = 0;
+	// 所有文件是同一个程序, 一起检查
+	let mut units = Vec::new();
+	for file in &files {
+		let mut contents = String::new();
+		if File::open(file).and_then(|mut f| f.read_to_string(&mut contents)).is_err() {
+			eprintln!("{}: could not read file", file);
+			status = 1;
+			continue;
+		}
+		let source = SourceFile::new(file, &contents);
+		let mut dy_parser = DyParser::new(contents);
+		dy_parser.lexer();
+		units.push((dy_parser.parse(), source));
+	}
+	let borrowed: Vec<(&SyntaxTree, &SourceFile)> = units.iter().map(|x| (&x.0, &x.1)).collect();
+	let mut reports = Vec::new();
+	for (diagnostics, &(_, source)) in dy_diagnostic::diagnose(&borrowed, &options).iter().zip(&borrowed) {
+		if diagnostics.iter().any(Diagnostic::is_error) {
+			status = 1;
+		}
+		if json {
+			reports.push(dy_diagnostic::diagnostics_to_json(diagnostics, source, locale));
+			continue;
+		}
+		for diagnostic in diagnostics {
+			eprintln!("{}", dy_diagnostic::render(diagnostic, source, color, locale));
+		}
+	}
+	if json && !write_stdout(&format!("{}\n", dy::vm::dy_json::Json::Array(reports).pretty())) {
+		return 2;
+	}
+	status
+}
+
+// 通过锁住的 stdout 写; 读的一方先关掉管道 (比如 | head) 时当作正常结束, 其它写错误返回 false
+fn write_stdout(text: &str) -> bool {
+	let mut stdout = io::stdout().lock();
+	match stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()) {
+		Ok(()) => true,
+		Err(ref error) if error.kind() == io::ErrorKind::BrokenPipe => true,
+		Err(error) => {
+			eprintln!("could not write stdout: {}", error);
+			false
+		}
+	}
+}
+
+fn read_declarations(file: &str) -> Option<Declarations> {
+	let mut contents = String::new();
+	if File::open(file).and_then(|mut f| f.read_to_string(&mut contents)).is_err() {
+		eprintln!("{}: could not read file", file);
+		return None;
+	}
+	let mut dy_parser = DyParser::new(contents);
+	dy_parser.lexer();
+	let mut declarations = Declarations::new();
+	declarations.add(&dy_parser.parse().compilation_unit());
+	Some(declarations)
+}
+
+fn format_file(file: &str, contents: &str, options: &FormatOptions) -> Option<String> {
+	match dy_format::format(contents, options) {
+		Ok(formatted) => Some(formatted),
+		Err(errors) => {
+			let mut dy_parser = DyParser::new(contents.to_string());
+			dy_parser.lexer();
+			for error in errors {
+				let (line, column) = dy_parser.line_column(error.span.start);
+				eprintln!("{}:{}:{}: error: {}", file, line + 1, column + 1, error.message);
+			}
+			None
+		}
+	}
+}
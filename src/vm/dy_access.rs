@@ -0,0 +1,332 @@
+use std::ptr;
+use vm::dy_binder::ScopeTree;
+use vm::dy_diagnostic::{Argument, Diagnostic, Severity};
+use vm::dy_inherit::member_text;
+use vm::dy_symbols::{Declarations, TypeSymbol, MemberSymbol};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, Parameter, TypeSyntax};
+
+// ------------------------------------ accessibility ------------------------------------------
+// public/protected/internal/private. 可访问域是四个区域的集合: 程序集里的派生类, 程序集里的其他地方,
+// 程序集外的派生类, 程序集外的其他地方; 声明所在的类型 (包括嵌套在里面的类型) 总能访问.
+// 程序集是 Declarations::add_to_assembly 的参数, extern alias 引用的声明是别的程序集
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Accessibility {
+    Public,
+    ProtectedInternal,
+    Internal,
+    Protected,
+    PrivateProtected,
+    Private,
+}
+
+const DERIVED: u8 = 1;
+const ASSEMBLY: u8 = 2;
+const DERIVED_OUTSIDE: u8 = 4;
+const OUTSIDE: u8 = 8;
+
+impl Accessibility {
+    fn domain(self) -> u8 {
+        match self {
+            Accessibility::Public => DERIVED | ASSEMBLY | DERIVED_OUTSIDE | OUTSIDE,
+            Accessibility::ProtectedInternal => DERIVED | ASSEMBLY | DERIVED_OUTSIDE,
+            Accessibility::Internal => DERIVED | ASSEMBLY,
+            Accessibility::Protected => DERIVED | DERIVED_OUTSIDE,
+            Accessibility::PrivateProtected => DERIVED,
+            Accessibility::Private => 0,
+        }
+    }
+
+    /// What is left of `self` inside something that is `other`, e.g. a public member of an internal type.
+    pub fn intersect(self, other: Accessibility) -> Accessibility {
+        let domain = self.domain() & other.domain();
+        [Accessibility::Public, Accessibility::ProtectedInternal, Accessibility::Internal, Accessibility::Protected,
+            Accessibility::PrivateProtected].iter().cloned().find(|x| x.domain() == domain).unwrap_or(Accessibility::Private)
+    }
+
+    /// Whether everything that can access `other` can access `self`.
+    pub fn includes(self, other: Accessibility) -> bool {
+        other.domain() & !self.domain() == 0
+    }
+}
+
+/// The accessibility written in `modifiers`, `default` when there is none.
+pub fn declared_accessibility(modifiers: &[String], default: Accessibility) -> Accessibility {
+    let has = |modifier: &str| modifiers.iter().any(|x| x == modifier);
+    match (has("public"), has("protected"), has("internal"), has("private")) {
+        (true, ..) => Accessibility::Public,
+        (_, true, true, _) => Accessibility::ProtectedInternal,
+        (_, true, _, true) => Accessibility::PrivateProtected,
+        (_, true, ..) => Accessibility::Protected,
+        (_, _, true, _) => Accessibility::Internal,
+        (_, _, _, true) => Accessibility::Private,
+        _ => default,
+    }
+}
+
+/// Top-level types are internal by default, nested types private.
+pub fn type_accessibility(symbol: &TypeSymbol) -> Accessibility {
+    let default = if symbol.containing_type.is_some() { Accessibility::Private } else { Accessibility::Internal };
+    declared_accessibility(&symbol.modifiers, default)
+}
+
+/// Interface members are public by default, members of classes and structs private.
+pub fn member_accessibility(owner: &TypeSymbol, member: &MemberSymbol) -> Accessibility {
+    let default = if owner.kind == SyntaxKind::InterfaceDeclaration { Accessibility::Public } else { Accessibility::Private };
+    declared_accessibility(&member.modifiers, default)
+}
+
+/// The accessibility of a type restricted by the types it is nested in.
+pub fn effective_accessibility(declarations: &Declarations, symbol: &TypeSymbol) -> Accessibility {
+    let own = type_accessibility(symbol);
+    match symbol.containing_type.as_ref().and_then(|x| declarations.find_type(x)) {
+        Some(outer) => own.intersect(effective_accessibility(declarations, outer)),
+        None => own,
+    }
+}
+
+// symbol 是 base 或者从 base 派生
+fn is_derived(declarations: &Declarations, symbol: &TypeSymbol, base: &TypeSymbol) -> bool {
+    declarations.with_bases(symbol).iter().any(|x| x.full_name() == base.full_name())
+}
+
+// 不在 declarations.types 里的类型来自 extern alias 的引用
+fn is_same_assembly(declarations: &Declarations, symbol: &TypeSymbol, node: &SyntaxRef) -> bool {
+    declarations.types.iter().any(|x| ptr::eq(x, symbol))
+        && declarations.assembly_of(&symbol.declarations[0]) == declarations.assembly_of(node)
+}
+
+/// Why code at `from` cannot use the type `owner`, or its `member` through an expression of type `receiver`;
+/// None when it can. The containing types of `owner` are not checked.
+pub fn access_error(declarations: &Declarations, owner: &TypeSymbol, member: Option<&MemberSymbol>, from: &SyntaxRef,
+    receiver: Option<&TypeSymbol>) -> Option<Diagnostic> {
+    let accessibility = match member {
+        Some(member) => member_accessibility(owner, member),
+        None => type_accessibility(owner),
+    };
+    // 嵌套类型的可访问性相对于外层类型
+    let declaring = match member {
+        Some(_) => Some(owner),
+        None => owner.containing_type.as_ref().and_then(|x| declarations.find_type(x)),
+    };
+    let enclosing = declarations.enclosing_types(from);
+    let is_inside = declaring.is_some_and(|x| enclosing.iter().any(|y| y.full_name() == x.full_name()));
+    if accessibility == Accessibility::Public || is_inside {
+        return None;
+    }
+    let derived: Vec<&TypeSymbol> = enclosing.into_iter()
+        .filter(|x| declaring.is_some_and(|base| is_derived(declarations, x, base))).collect();
+    let same_assembly = is_same_assembly(declarations, owner, from);
+    let region = match (same_assembly, derived.is_empty()) {
+        (true, false) => DERIVED,
+        (true, true) => ASSEMBLY,
+        (false, false) => DERIVED_OUTSIDE,
+        (false, true) => OUTSIDE,
+    };
+    let text = member.map_or_else(|| owner.full_name(), |x| member_text(owner, x));
+    if accessibility.domain() & region == 0 {
+        return Some(Diagnostic::error("DY0538", from.span(), &[&text]));
+    }
+    // 只因为派生才能访问的实例成员, 要通过派生类型的表达式访问
+    let through_derived = !same_assembly || accessibility.domain() & ASSEMBLY == 0;
+    match (member, receiver) {
+        (Some(member), Some(receiver)) if through_derived && !derived.is_empty() && !member.is_static()
+            && member.kind != SyntaxKind::ConstructorDeclaration => {
+            if derived.iter().any(|x| is_derived(declarations, receiver, x)) {
+                return None;
+            }
+            Some(Diagnostic::error("DY0539", from.span(), &[&text, &receiver.full_name(), &derived[0].full_name()]))
+        }
+        _ => None,
+    }
+}
+
+fn is_name(kind: SyntaxKind) -> bool {
+    matches!(kind,
+        SyntaxKind::IdentifierName | SyntaxKind::GenericName | SyntaxKind::QualifiedName | SyntaxKind::AliasQualifiedName
+        | SyntaxKind::MemberAccessExpression)
+}
+
+/// Errors for inaccessible type names in `root`, and inconsistent accessibility of the declarations in it:
+/// a type or member that exposes a less accessible type.
+pub fn check_accessibility(root: &SyntaxRef, declarations: &Declarations) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let scopes = ScopeTree::bind(root);
+    for node in root.traverse(true, false).filter(|x| is_name(x.kind())) {
+        // A.B 的 B 不单独解析
+        let is_qualified = node.parent().is_some_and(|x| is_name(x.kind()) && x.kind() != SyntaxKind::GenericName
+            && x.child_nodes().first() != Some(&node));
+        if is_qualified || node.kind() == SyntaxKind::MemberAccessExpression && node.child_nodes().len() != 2 {
+            continue;
+        }
+        if node.kind() == SyntaxKind::IdentifierName && scopes.lookup(&node.token_text(), node.span().start).iter().any(|x| x.is_local()) {
+            continue;
+        }
+        let symbol = match declarations.resolve_type_at(&node.text(), &node) {
+            Some(symbol) => symbol,
+            None => continue,
+        };
+        errors.extend(access_error(declarations, symbol, None, &node, None));
+    }
+    let in_unit = |x: &SyntaxRef| x.acestors().last().as_ref() == Some(root);
+    for symbol in declarations.types.iter().filter(|x| x.declarations.iter().any(&in_unit)) {
+        let effective = effective_accessibility(declarations, symbol);
+        let mut check = |type_name: &str, at: &SyntaxRef, what: &'static str, of: &Argument,
+            accessibility: Accessibility| {
+            // 索引器没有名字, 报在 this 上
+            let name = at.identifier().or_else(|| at.significant_tokens().into_iter().find(|x| x.token_text() == "this"))
+                .unwrap_or_else(|| at.clone());
+            let words = type_name.split(|x: char| !x.is_alphanumeric() && x != '_' && x != '.').filter(|x| !x.is_empty());
+            for word in words {
+                let used = match declarations.resolve_type_at(word, at) {
+                    Some(used) => used,
+                    None => continue,
+                };
+                if !effective_accessibility(declarations, used).includes(accessibility) {
+                    let arguments = vec![Argument::phrase(what, &[]), Argument::from(used.full_name()), of.clone()];
+                    errors.push(Diagnostic::new(Severity::Error, "DY0540", name.span(), arguments));
+                }
+            }
+        };
+        let declaration = symbol.declarations.iter().find(|x| in_unit(x)).unwrap();
+        match symbol.kind {
+            SyntaxKind::ClassDeclaration | SyntaxKind::InterfaceDeclaration => {
+                let (what, of) = if symbol.kind == SyntaxKind::ClassDeclaration {
+                    ("base-class", Argument::phrase("class-named", &[&symbol.full_name()]))
+                } else {
+                    ("base-interface", Argument::phrase("interface-named", &[&symbol.full_name()]))
+                };
+                for base in &symbol.base_types {
+                    let is_interface = declarations.resolve_type_at(base, declaration)
+                        .is_some_and(|x| x.kind == SyntaxKind::InterfaceDeclaration);
+                    // 类可以实现访问性更低的接口
+                    if symbol.kind == SyntaxKind::InterfaceDeclaration || !is_interface {
+                        check(base, declaration, what, &of, effective);
+                    }
+                }
+            }
+            SyntaxKind::DelegateDeclaration => {
+                let of = Argument::phrase("delegate-named", &[&symbol.full_name()]);
+                if let Some(returns) = declaration.child_nodes().into_iter().find_map(TypeSyntax::cast) {
+                    check(&returns.text(), declaration, "return-type", &of, effective);
+                }
+                let parameters = declaration.child_of(SyntaxKind::ParameterList).map_or(Vec::new(), |x| x.children_of(SyntaxKind::Parameter));
+                for parameter in parameters.into_iter().filter_map(|x| Parameter::cast(x).and_then(|x| x.parameter_type())) {
+                    check(&parameter.text(), declaration, "parameter-type", &of, effective);
+                }
+            }
+            _ => {}
+        }
+        for member in symbol.members.iter().filter(|x| in_unit(&x.declaration) && x.explicit_interface.is_none()) {
+            let accessibility = member_accessibility(symbol, member).intersect(effective);
+            let text = member_text(symbol, member);
+            let (what, of) = match member.kind {
+                SyntaxKind::MethodDeclaration | SyntaxKind::ConstructorDeclaration => ("return-type", "method-named"),
+                SyntaxKind::OperatorDeclaration | SyntaxKind::ConversionOperatorDeclaration =>
+                    ("return-type", "operator-named"),
+                SyntaxKind::FieldDeclaration => ("field-type", "field-named"),
+                SyntaxKind::PropertyDeclaration => ("property-type", "property-named"),
+                SyntaxKind::IndexerDeclaration => ("indexer-return-type", "indexer-named"),
+                SyntaxKind::EventFieldDeclaration | SyntaxKind::EventDeclaration => ("event-type", "event-named"),
+                _ => continue,
+            };
+            let of = Argument::phrase(of, &[&text]);
+            if let Some(ref type_name) = member.type_name {
+                check(type_name, &member.declaration, what, &of, accessibility);
+            }
+            for parameter in &member.parameters {
+                check(&parameter.type_name, &member.declaration, "parameter-type", &of, accessibility);
+            }
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_check::check_types;
+    use vm::dy_locale::Locale;
+    use vm::dy_parser::DyParser;
+    use vm::dy_syntax::SyntaxTree;
+
+    fn parse(code: &str) -> SyntaxTree {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        tree
+    }
+
+    fn messages(errors: &[Diagnostic]) -> Vec<String> {
+        errors.iter().map(|x| x.message.clone()).collect()
+    }
+
+    #[test]
+    fn test_accessibility() {
+        let modifiers = |x: &str| x.split(' ').map(|x| x.to_string()).collect::<Vec<String>>();
+        assert_eq!(declared_accessibility(&modifiers("protected internal"), Accessibility::Private), Accessibility::ProtectedInternal);
+        assert_eq!(declared_accessibility(&modifiers("private protected"), Accessibility::Private), Accessibility::PrivateProtected);
+        assert_eq!(declared_accessibility(&modifiers("static"), Accessibility::Internal), Accessibility::Internal);
+        assert_eq!(Accessibility::Public.intersect(Accessibility::Internal), Accessibility::Internal);
+        assert_eq!(Accessibility::Internal.intersect(Accessibility::Protected), Accessibility::PrivateProtected);
+        assert!(Accessibility::ProtectedInternal.includes(Accessibility::Protected));
+        assert!(!Accessibility::Internal.includes(Accessibility::Protected));
+
+        let tree = parse("class Outer { class Secret { } protected class Shared { } public class Open { }
+                public Secret Get() { return null; } private Secret Keep() { return null; } public void Put(Secret s) { }
+                public Secret Field; protected Shared Prop { get; set; } public Shared this[int i] => null; }
+            class Derived : Outer { Shared s; Open o; Secret x; }
+            class Other { Outer.Secret a; Outer.Open b; Outer.Shared c; }
+            public class Pub : Internal { } class Internal { } interface IHidden { } public interface IPub : IHidden { }
+            public class Impl : IHidden { } public delegate Internal Make(Outer.Open open);");
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        assert_eq!(messages(&check_accessibility(&tree.root, &declarations)), [
+            "'Outer.Secret' is inaccessible due to its protection level",
+            "'Outer.Secret' is inaccessible due to its protection level",
+            "'Outer.Shared' is inaccessible due to its protection level",
+            "Inconsistent accessibility: return type 'Outer.Secret' is less accessible than method 'Outer.Get()'",
+            "Inconsistent accessibility: parameter type 'Outer.Secret' is less accessible than method 'Outer.Put(Secret)'",
+            "Inconsistent accessibility: field type 'Outer.Secret' is less accessible than field 'Outer.Field'",
+            "Inconsistent accessibility: indexer return type 'Outer.Shared' is less accessible than indexer 'Outer.this[int]'",
+            "Inconsistent accessibility: base class 'Internal' is less accessible than class 'Pub'",
+            "Inconsistent accessibility: base interface 'IHidden' is less accessible than interface 'IPub'",
+            "Inconsistent accessibility: return type 'Internal' is less accessible than delegate 'Make'",
+            "Inconsistent accessibility: parameter type 'Outer.Open' is less accessible than delegate 'Make'",
+        ]);
+        let errors = check_accessibility(&tree.root, &declarations);
+        assert_eq!(errors[3].message_in(Locale::ZhCn), "可访问性不一致: 返回类型 'Outer.Secret' 的可访问性低于 方法 'Outer.Get()'");
+        assert_eq!(errors[7].message_in(Locale::ZhCn), "可访问性不一致: 基类 'Internal' 的可访问性低于 类 'Pub'");
+    }
+
+    #[test]
+    fn test_assemblies() {
+        let engine = parse("namespace Engine { public class Api { internal void Reset() { } public void Run() { Reset(); }
+            protected internal int Frame; internal Api() { } public Api(int frame) { } } internal class Cache { } }");
+        let game = parse("using Engine; class Game { void Start() { var api = new Api(1); api.Run(); api.Reset();
+            var frame = api.Frame; Cache cache; new Api(); } }
+            class Mod : Api { Mod() : base(0) { } int Next() => Frame + 1; }");
+        let mut declarations = Declarations::new();
+        declarations.add_to_assembly(&engine.compilation_unit(), "Engine");
+        declarations.add_to_assembly(&game.compilation_unit(), "Game");
+        assert_eq!(declarations.assembly_of(&game.root), "Game");
+        assert!(check_accessibility(&engine.root, &declarations).is_empty());
+        assert!(check_types(&engine.root, &declarations).errors.is_empty());
+        assert_eq!(messages(&check_accessibility(&game.root, &declarations)), [
+            "'Engine.Cache' is inaccessible due to its protection level",
+        ]);
+        assert_eq!(messages(&check_types(&game.root, &declarations).errors), [
+            "'Engine.Api.Reset()' is inaccessible due to its protection level",
+            "'Engine.Api.Frame' is inaccessible due to its protection level",
+            "'Engine.Api.Api()' is inaccessible due to its protection level",
+        ]);
+
+        // 同一个程序集里 internal 可以访问
+        let mut declarations = Declarations::new();
+        declarations.add(&engine.compilation_unit());
+        declarations.add(&game.compilation_unit());
+        assert!(check_accessibility(&game.root, &declarations).is_empty());
+        assert!(check_types(&game.root, &declarations).errors.is_empty());
+    }
+}
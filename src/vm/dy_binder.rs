@@ -0,0 +1,429 @@
+use vm::dy_common::DyRef;
+use vm::dy_diagnostic::{Diagnostic, Phrase};
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_parser::Span;
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, NamespaceDeclaration, TypeDeclaration};
+
+// ------------------------------------ scopes -------------------------------------------------
+// 按语法树上的 SemanticFlags 建作用域树, 每个作用域记录在它里面声明的符号
+// 编译器做名字查找, 编辑器做补全, 都从 ScopeTree::visible_at 开始
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    /// One of the SymbolDeclarations flags.
+    pub kind: SemanticFlags,
+    pub node: SyntaxRef,
+    /// Span of the declared name.
+    pub span: Span,
+}
+
+impl Symbol {
+    pub fn is_local(&self) -> bool {
+        is_local(self.kind)
+    }
+
+    pub fn is_label(&self) -> bool {
+        self.kind == SemanticFlags::LabeledStatement
+    }
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    /// One of the Scopes flags.
+    pub kind: SemanticFlags,
+    pub node: SyntaxRef,
+    pub symbols: Vec<Symbol>,
+}
+
+pub type ScopeRef = DyRef<Scope>;
+
+impl DyRef<Scope> {
+    pub fn kind(&self) -> SemanticFlags {
+        self.borrow().kind
+    }
+
+    pub fn node(&self) -> SyntaxRef {
+        self.borrow().node.clone()
+    }
+
+    pub fn symbols(&self) -> Vec<Symbol> {
+        self.borrow().symbols.clone()
+    }
+
+    pub fn symbols_named(&self, name: &str) -> Vec<Symbol> {
+        self.borrow().symbols.iter().filter(|x| x.name == name).cloned().collect()
+    }
+}
+
+/// Locals and parameters, which must not shadow each other within a function.
+pub fn is_local(kind: SemanticFlags) -> bool {
+    matches!(kind,
+        SemanticFlags::LocalVariableDeclarator | SemanticFlags::ForEachVariableDeclaration
+        | SemanticFlags::FromClauseVariableDeclaration | SemanticFlags::CatchExceptionParameterDeclaration
+        | SemanticFlags::FixedParameterDeclaration | SemanticFlags::ParameterArrayDeclaration
+        | SemanticFlags::ImplicitParameterDeclaration | SemanticFlags::ExplicitParameterDeclaration)
+}
+
+// 同一个作用域里可以重名的声明
+fn is_overloadable(kind: SemanticFlags) -> bool {
+    matches!(kind,
+        SemanticFlags::NamespaceDeclaration | SemanticFlags::MethodDeclarator | SemanticFlags::ConstructorDeclarator
+        | SemanticFlags::OperatorDeclarator | SemanticFlags::ConversionOperatorDeclarator
+        | SemanticFlags::IndexerDeclaration | SemanticFlags::InterfaceMethodDeclaration
+        | SemanticFlags::InterfaceIndexerDeclaration)
+}
+
+fn is_type(kind: SemanticFlags) -> bool {
+    matches!(kind,
+        SemanticFlags::ClassDeclaration | SemanticFlags::StructDeclaration | SemanticFlags::InterfaceDeclaration
+        | SemanticFlags::EnumDeclaration | SemanticFlags::DelegateDeclaration)
+}
+
+// 局部变量所在的作用域, 出了这些作用域就是成员和类型
+fn is_local_scope(kind: SemanticFlags) -> bool {
+    matches!(kind,
+        SemanticFlags::MethodDeclarationScope | SemanticFlags::MethodBodyScope
+        | SemanticFlags::ConstructorInitializerScope | SemanticFlags::LambdaExpressionScope
+        | SemanticFlags::LambdaExpressionBodyScope | SemanticFlags::AnonymousMethodScope
+        | SemanticFlags::AnonymousMethodBodyScope | SemanticFlags::CodeBlockScope | SemanticFlags::SwitchBlockScope
+        | SemanticFlags::ForStatementScope | SemanticFlags::EmbeddedStatementScope
+        | SemanticFlags::UsingStatementScope | SemanticFlags::LocalVariableInitializerScope
+        | SemanticFlags::SpecificCatchScope | SemanticFlags::AccessorBodyScope
+        | SemanticFlags::QueryExpressionScope | SemanticFlags::QueryBodyScope
+        | SemanticFlags::MemberDeclarationScope)
+}
+
+// 函数的最外层作用域, 查找遮蔽到这里为止, lambda 的参数和局部变量可以遮蔽外面的名字
+fn is_function_scope(kind: SemanticFlags) -> bool {
+    matches!(kind,
+        SemanticFlags::MethodDeclarationScope | SemanticFlags::LambdaExpressionScope
+        | SemanticFlags::AnonymousMethodScope | SemanticFlags::MemberDeclarationScope
+        | SemanticFlags::AccessorBodyScope)
+}
+
+// 只是把声明分组的作用域, 里面的名字属于外层
+fn is_transparent(node: &SyntaxRef, kind: SemanticFlags) -> bool {
+    match kind {
+        SemanticFlags::FormalParameterListScope | SemanticFlags::ArgumentListScope | SemanticFlags::AttributesScope
+        | SemanticFlags::AttributeArgumentsScope | SemanticFlags::MemberInitializerScope
+        | SemanticFlags::ClassBaseScope | SemanticFlags::StructInterfacesScope | SemanticFlags::InterfaceBaseScope
+        | SemanticFlags::TypeParameterConstraintsScope | SemanticFlags::AccessorsListScope => true,
+        // 字段和事件字段, 声明在各个 declarator 上
+        SemanticFlags::MemberDeclarationScope => node.declaration().is_none(),
+        _ => false,
+    }
+}
+
+fn type_arity(node: &SyntaxRef) -> usize {
+    node.child_of(SyntaxKind::TypeParameterList).map_or(0, |x| x.children_of(SyntaxKind::TypeParameter).len())
+}
+
+fn is_partial_type(symbol: &Symbol) -> bool {
+    is_type(symbol.kind) && TypeDeclaration::cast(symbol.node.clone())
+        .is_some_and(|x| x.modifiers().iter().any(|x| x == "partial"))
+}
+
+fn declared_symbol(node: &SyntaxRef) -> Option<Symbol> {
+    let kind = node.declaration()?;
+    let identifier = node.identifier();
+    let name = match kind {
+        SemanticFlags::LambdaExpressionDeclaration | SemanticFlags::AnonymousMethodDeclaration
+        | SemanticFlags::BaseListDeclaration | SemanticFlags::AnonymousObjectCreation
+        | SemanticFlags::MemberDeclarator | SemanticFlags::UsingNamespace
+        | SemanticFlags::GetAccessorDeclaration | SemanticFlags::SetAccessorDeclaration
+        | SemanticFlags::AddAccessorDeclaration | SemanticFlags::RemoveAccessorDeclaration
+        | SemanticFlags::InterfaceGetAccessorDeclaration | SemanticFlags::InterfaceSetAccessorDeclaration => return None,
+        SemanticFlags::NamespaceDeclaration => NamespaceDeclaration::cast(node.clone()).map(|x| x.name())
+            .filter(|x| !x.is_empty()),
+        SemanticFlags::IndexerDeclaration | SemanticFlags::InterfaceIndexerDeclaration => Some("this".to_string()),
+        SemanticFlags::OperatorDeclarator => node.significant_tokens().iter()
+            .skip_while(|x| x.token_text() != "operator").nth(1).map(|x| format!("operator {}", x.token_text())),
+        SemanticFlags::ConversionOperatorDeclarator => Some("operator".to_string()),
+        SemanticFlags::DestructorDeclarator => identifier.as_ref().map(|x| format!("~{}", x.token_text())),
+        _ => identifier.as_ref().map(|x| x.token_text()),
+    }?;
+    let span = match kind {
+        SemanticFlags::NamespaceDeclaration => node.child_nodes().first().map_or(node.span(), |x| x.span()),
+        _ => identifier.map_or(node.span(), |x| x.span()),
+    };
+    Some(Symbol { name, kind, node: node.clone(), span })
+}
+
+// set/add/remove 访问器里隐含的 value 参数
+fn implicit_value(accessor: &SyntaxRef) -> Option<Symbol> {
+    match accessor.declaration()? {
+        SemanticFlags::SetAccessorDeclaration | SemanticFlags::AddAccessorDeclaration
+        | SemanticFlags::RemoveAccessorDeclaration | SemanticFlags::InterfaceSetAccessorDeclaration => {
+            let span = accessor.identifier().map_or(accessor.span(), |x| x.span());
+            Some(Symbol { name: "value".to_string(), kind: SemanticFlags::ImplicitParameterDeclaration,
+                node: accessor.clone(), span })
+        }
+        _ => None,
+    }
+}
+
+fn covers(span: Span, offset: usize) -> bool {
+    span.contains(offset) || (span.end == offset && !span.is_empty())
+}
+
+
+pub struct ScopeTree {
+    pub root: ScopeRef,
+    pub errors: Vec<Diagnostic>,
+}
+
+impl ScopeTree {
+    /// Builds the scopes of a compilation unit, reporting duplicate and shadowing declarations.
+    pub fn bind(root: &SyntaxRef) -> ScopeTree {
+        let kind = root.scope().unwrap_or(SemanticFlags::CompilationUnitScope);
+        let mut tree = ScopeTree {
+            root: DyRef::new(Scope { kind, node: root.clone(), symbols: Vec::new() }),
+            errors: Vec::new(),
+        };
+        let scope = tree.root.clone();
+        tree.bind_children(root, &scope);
+        tree
+    }
+
+    fn bind_children(&mut self, node: &SyntaxRef, scope: &ScopeRef) {
+        for child in node.child_nodes() {
+            self.bind_node(&child, scope);
+        }
+    }
+
+    fn bind_node(&mut self, node: &SyntaxRef, scope: &ScopeRef) {
+        if let Some(symbol) = declared_symbol(node) {
+            self.declare(scope, symbol);
+        }
+        let kind = match node.scope() {
+            Some(kind) if !is_transparent(node, kind) => kind,
+            _ => return self.bind_children(node, scope),
+        };
+        let inner = DyRef::new(Scope { kind, node: node.clone(), symbols: Vec::new() });
+        scope.append(&inner);
+        if kind == SemanticFlags::AccessorBodyScope {
+            if let Some(value) = node.parent().and_then(|x| implicit_value(&x)) {
+                self.declare(&inner, value);
+            }
+        }
+        if node.kind() == SyntaxKind::SwitchStatement {
+            self.bind_switch(node, &inner);
+        }
+        else {
+            self.bind_children(node, &inner);
+        }
+    }
+
+    // case 标签里的模式变量属于所在的 switch section, 语句里声明的局部变量属于整个 switch 块
+    fn bind_switch(&mut self, node: &SyntaxRef, scope: &ScopeRef) {
+        for child in node.child_nodes() {
+            if child.kind() != SyntaxKind::SwitchSection {
+                self.bind_node(&child, scope);
+                continue;
+            }
+            let section = DyRef::new(Scope { kind: SemanticFlags::SwitchBlockScope, node: child.clone(), symbols: Vec::new() });
+            scope.append(&section);
+            for x in child.child_nodes() {
+                match x.kind() {
+                    SyntaxKind::CaseSwitchLabel | SyntaxKind::CasePatternSwitchLabel | SyntaxKind::DefaultSwitchLabel =>
+                        self.bind_node(&x, &section),
+                    _ => self.bind_node(&x, scope),
+                }
+            }
+        }
+    }
+
+    fn declare(&mut self, scope: &ScopeRef, symbol: Symbol) {
+        let previous = scope.borrow().symbols.iter()
+            .find(|x| x.name == symbol.name && x.is_label() == symbol.is_label() && !can_coexist(x, &symbol))
+            .map(|x| x.span);
+        if let Some(previous) = previous {
+            let code = if symbol.is_label() {
+                "DY0401"
+            }
+            else if symbol.is_local() {
+                "DY0402"
+            }
+            else {
+                "DY0403"
+            };
+            self.errors.push(Diagnostic::error(code, symbol.span, &[&symbol.name])
+                .with_label(previous, Phrase::new("previous-definition", &[&symbol.name])));
+        }
+        else if symbol.is_local() {
+            if enclosing_local(scope, &symbol.name).is_some() {
+                self.errors.push(shadowing_error(&symbol));
+            }
+            // 先声明在内层, 外层后声明同名变量也不行, 错误报在内层的声明上
+            else if let Some(inner) = nested_local(scope, &symbol.name) {
+                self.errors.push(shadowing_error(&inner));
+            }
+        }
+        scope.borrow_mut().symbols.push(symbol);
+    }
+
+    /// Innermost scope whose node covers `offset`.
+    pub fn scope_at(&self, offset: usize) -> ScopeRef {
+        let mut scope = self.root.clone();
+        loop {
+            let child = scope.children().find(|x| covers(x.node().span(), offset));
+            match child {
+                Some(x) => scope = x,
+                None => return scope,
+            }
+        }
+    }
+
+    /// Symbols visible at `offset`, innermost first; a name hides the same name of outer scopes
+    /// and locals are visible only after their declaration.
+    pub fn visible_at(&self, offset: usize) -> Vec<Symbol> {
+        let mut visible: Vec<Symbol> = Vec::new();
+        for scope in self.scope_at(offset).acestors() {
+            let symbols: Vec<Symbol> = scope.borrow().symbols.iter()
+                .filter(|x| !x.is_local() || x.span.end <= offset)
+                .filter(|x| !visible.iter().any(|v| v.name == x.name && v.is_label() == x.is_label()))
+                .cloned()
+                .collect();
+            visible.extend(symbols);
+        }
+        visible
+    }
+
+    /// The symbols a simple name refers to at `offset`; several for overloads.
+    pub fn lookup(&self, name: &str, offset: usize) -> Vec<Symbol> {
+        self.visible_at(offset).into_iter().filter(|x| x.name == name && !x.is_label()).collect()
+    }
+}
+
+fn can_coexist(existing: &Symbol, symbol: &Symbol) -> bool {
+    if is_overloadable(existing.kind) && is_overloadable(symbol.kind) {
+        return true;
+    }
+    if is_type(existing.kind) && is_type(symbol.kind) {
+        return type_arity(&existing.node) != type_arity(&symbol.node) || (is_partial_type(existing) && is_partial_type(symbol));
+    }
+    false
+}
+
+fn shadowing_error(symbol: &Symbol) -> Diagnostic {
+    Diagnostic::error("DY0404", symbol.span, &[&symbol.name])
+}
+
+// 从外层作用域找同名的局部变量或参数, 到函数边界为止
+fn enclosing_local(scope: &ScopeRef, name: &str) -> Option<Symbol> {
+    if !is_local_scope(scope.kind()) || is_function_scope(scope.kind()) {
+        return None;
+    }
+    for outer in scope.acestors().skip(1) {
+        if !is_local_scope(outer.kind()) {
+            break;
+        }
+        if let Some(x) = outer.symbols_named(name).into_iter().find(|x| x.is_local()) {
+            return Some(x);
+        }
+        if is_function_scope(outer.kind()) {
+            break;
+        }
+    }
+    None
+}
+
+// 已经建好的内层作用域里的同名局部变量, 不进入 lambda
+fn nested_local(scope: &ScopeRef, name: &str) -> Option<Symbol> {
+    for inner in scope.children() {
+        if is_function_scope(inner.kind()) {
+            continue;
+        }
+        if let Some(x) = inner.symbols_named(name).into_iter().find(|x| x.is_local()) {
+            return Some(x);
+        }
+        if let Some(x) = nested_local(&inner, name) {
+            return Some(x);
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+
+    fn bind(code: &str) -> ScopeTree {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        ScopeTree::bind(&tree.root)
+    }
+
+    fn visible(tree: &ScopeTree, code: &str, at: &str) -> Vec<String> {
+        tree.visible_at(code.find(at).unwrap()).iter().map(|x| x.name.clone()).collect()
+    }
+
+    fn messages(tree: &ScopeTree) -> Vec<String> {
+        tree.errors.iter().map(|x| x.message.clone()).collect()
+    }
+
+    #[test]
+    fn test_scopes() {
+        let code = "using S = System.Text; namespace N { class A<T> { int f; int P { get; set { Use(); } }
+            void F(int a) { int b = 1; for (int i = 0; i < a; i++) { Loop(); } Func<int, int> g = x => x + Body();
+            switch (a) { case int n when n > 0: var q = 1; Case(); break; default: Default(); break; } L: After(); } } }";
+        let tree = bind(code);
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        assert_eq!(tree.root.kind(), SemanticFlags::CompilationUnitScope);
+        let names: Vec<String> = tree.root.symbols().iter().map(|x| x.name.clone()).collect();
+        assert_eq!(names, ["S", "N"]);
+
+        let scope = tree.scope_at(code.find("Loop").unwrap());
+        assert_eq!(scope.kind(), SemanticFlags::CodeBlockScope);
+        let kinds: Vec<SemanticFlags> = scope.acestors().map(|x| x.kind()).collect();
+        assert_eq!(kinds, [SemanticFlags::CodeBlockScope, SemanticFlags::ForStatementScope,
+            SemanticFlags::MethodBodyScope, SemanticFlags::MethodDeclarationScope, SemanticFlags::ClassBodyScope,
+            SemanticFlags::TypeDeclarationScope, SemanticFlags::NamespaceBodyScope, SemanticFlags::CompilationUnitScope]);
+        assert_eq!(visible(&tree, code, "Loop"), ["i", "b", "L", "a", "f", "P", "F", "T", "A", "S", "N"]);
+
+        // 声明之前的局部变量不可见, 标签总是可见
+        assert_eq!(visible(&tree, code, "int b"), ["L", "a", "f", "P", "F", "T", "A", "S", "N"]);
+        assert_eq!(visible(&tree, code, "Body")[..4], ["x", "b", "g", "L"]);
+        assert_eq!(visible(&tree, code, "Use")[0], "value");
+
+        // case 的模式变量只在所在的 section 里
+        assert_eq!(visible(&tree, code, "Case")[..2], ["n", "q"]);
+        assert_eq!(visible(&tree, code, "Default")[..2], ["q", "b"]);
+        assert!(!visible(&tree, code, "After").contains(&"q".to_string()));
+
+        let found = tree.lookup("a", code.find("Loop").unwrap());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, SemanticFlags::FixedParameterDeclaration);
+        assert!(tree.lookup("L", code.find("After").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let tree = bind("class A { int f; void f() {} void M() {} void M(int x) {} A() {} A(int x) {} ~A() {}
+            int this[int i] => i; int this[string s] => 0; void N(int x, int x) { L: ; L: ; } }
+            class B {} class B<T> {} partial class C {} partial class C {} class D {} class D {}");
+        assert_eq!(messages(&tree), ["'f' is already defined in this scope",
+            "a local variable or parameter named 'x' is already defined in this scope", "the label 'L' is a duplicate",
+            "'D' is already defined in this scope"]);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let code = "class A { int x; void F(int a) { int x = 1; { int a = 2; } for (int i = 0; ; ) {} int i = 0;
+            Func<int, int> f = b => { int a = 1; return a; }; try {} catch (Exception e) { int e = 0; } }
+            int P { set { int value = 0; } } }";
+        let tree = bind(code);
+        let shadowing = |name: &str| format!("a local or parameter named '{}' cannot be declared in this scope because \
+            that name is used in an enclosing local scope to define a local or parameter", name);
+        assert_eq!(messages(&tree), [shadowing("a"), shadowing("i"), shadowing("e"),
+            "a local variable or parameter named 'value' is already defined in this scope".to_string()]);
+
+        // 外层后声明的 i, 错误报在 for 里的 i 上
+        assert_eq!(tree.errors[1].span.start, code.find("i = 0").unwrap());
+    }
+}
@@ -0,0 +1,2135 @@
+use std::ptr;
+use vm::dy_access::access_error;
+use vm::dy_binder::{ScopeTree, Symbol};
+use vm::dy_const::{evaluate, fits, ConstantValue};
+use vm::dy_diagnostic::{Diagnostic, Phrase, Severity, Argument as MessageArgument};
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_lower::{is_function, is_async, enclosing_function, function_return_type};
+use vm::dy_overload::{TypeContext, CallArgument, Mismatch, Resolution, resolve, signature_text, is_lambda,
+    lambda_parameters};
+use vm::dy_parser::{TokenKind, BUILTIN_TYPES};
+use vm::dy_symbols::{Declarations, TypeSymbol, MemberSymbol, MethodLookup, LookupContext, NameTarget, NameError};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, Expression, TypeSyntax, TypeDeclaration, VariableDeclaration,
+    Parameter, Pattern, PropertyDeclaration, MethodDeclaration, InvocationExpression, Argument};
+use vm::dy_types::{classify_conversion, lifted_binary, coalesce_result, is_numeric, is_integral, is_value_type,
+    underlying_type, make_nullable, generic_name, type_arguments, tuple_elements, uses_type_parameters,
+    substitute_type, delegate_signature, Conversion, UserTypes};
+
+// ------------------------------------ type checking ------------------------------------------
+// 给表达式定类型, 检查赋值和参数的隐式转换, 强制转换, 运算符, 条件和 return
+// 类型用源码写法的字符串表示, 用户类型换成全名. 宿主提供的类型和名字没有声明, 涉及它们的地方不报错;
+// 没有宿主命名空间和引用时, 宿主只提供下面几张表里的名字, 其他找不到的名字报错
+
+/// Types and errors found by `check_types`.
+pub struct TypeCheck {
+    /// Types of the expressions that could be typed, the `null` literal is `null`.
+    pub types: Vec<(SyntaxRef, String)>,
+    pub errors: Vec<Diagnostic>,
+}
+
+impl TypeCheck {
+    pub fn type_of(&self, node: &SyntaxRef) -> Option<&str> {
+        self.types.iter().find(|x| x.0 == *node).map(|x| x.1.as_str())
+    }
+}
+
+/// Type checks a compilation unit whose declarations are in `declarations`.
+pub fn check_types(root: &SyntaxRef, declarations: &Declarations) -> TypeCheck {
+    let scopes = ScopeTree::bind(root);
+    let mut checker = Checker {
+        declarations,
+        scopes: &scopes,
+        locals: Vec::new(),
+        functions: Vec::new(),
+        targets: Vec::new(),
+        result: TypeCheck { types: Vec::new(), errors: Vec::new() },
+    };
+    checker.check_node(root);
+    checker.result
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Typed(String),
+    /// A type name, e.g. the receiver of a static member access.
+    Type(String),
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Convertible {
+    Implicit,
+    Explicit,
+    No,
+}
+
+struct Function {
+    /// The name for messages, a lambda or anonymous method is named by its kind.
+    name: MessageArgument,
+    /// None when returns are not checked: lambdas, async methods and iterators.
+    returns: Option<String>,
+    /// Types of the returned values of a lambda whose return type is inferred.
+    returned: Option<Vec<Option<String>>>,
+}
+
+// 宿主类型的常用成员, "" 匹配任何类型, "[]" 匹配数组
+const BUILTIN_PROPERTIES: [(&'static str, &'static str, &'static str); 7] = [("string", "Length", "int"),
+    ("[]", "Length", "int"), ("List", "Count", "int"), ("Dictionary", "Count", "int"), ("HashSet", "Count", "int"),
+    ("Queue", "Count", "int"), ("Stack", "Count", "int")];
+
+const BUILTIN_METHODS: [(&'static str, &'static str, &'static str); 15] = [("", "ToString", "string"),
+    ("", "GetHashCode", "int"), ("", "Equals", "bool"), ("string", "Contains", "bool"), ("string", "StartsWith", "bool"),
+    ("string", "EndsWith", "bool"), ("string", "IndexOf", "int"), ("string", "Substring", "string"),
+    ("string", "Trim", "string"), ("string", "ToUpper", "string"), ("string", "ToLower", "string"),
+    ("string", "Replace", "string"), ("string", "Split", "string[]"), ("List", "Contains", "bool"),
+    ("Dictionary", "ContainsKey", "bool")];
+
+// 宿主提供的全局函数
+const BUILTIN_FUNCTIONS: [(&'static str, &'static str); 2] = [("print", "void"), ("println", "void")];
+
+// 总是由宿主提供的类型
+const HOST_TYPES: [&'static str; 26] = ["Nullable", "List", "IList", "IReadOnlyList", "IEnumerable", "IEnumerator",
+    "ICollection", "IReadOnlyCollection", "Dictionary", "IDictionary", "IReadOnlyDictionary", "HashSet", "Queue",
+    "Stack", "KeyValuePair", "Func", "Action", "Predicate", "Comparison", "Task", "ValueTask", "Type", "Exception",
+    "IDisposable", "IComparable", "IEquatable"];
+
+// 数值, bool 和 char 的实例成员
+const PRIMITIVE_MEMBERS: [&'static str; 6] = ["ToString", "Equals", "GetHashCode", "GetType", "CompareTo",
+    "GetTypeCode"];
+
+const COMPARISON_OPERATORS: [&'static str; 6] = ["==", "!=", "<", ">", "<=", ">="];
+
+fn is_name(kind: SyntaxKind) -> bool {
+    matches!(kind,
+        SyntaxKind::IdentifierName | SyntaxKind::GenericName | SyntaxKind::QualifiedName
+        | SyntaxKind::AliasQualifiedName | SyntaxKind::PredefinedType)
+}
+
+// 子节点里的名字是值而不是类型的语句
+fn has_value_names(kind: SyntaxKind) -> bool {
+    matches!(kind,
+        SyntaxKind::ExpressionStatement | SyntaxKind::ThrowStatement | SyntaxKind::LockStatement
+        | SyntaxKind::SwitchStatement | SyntaxKind::CaseSwitchLabel | SyntaxKind::YieldReturnStatement
+        | SyntaxKind::WhenClause | SyntaxKind::CatchFilterClause | SyntaxKind::UsingStatement
+        | SyntaxKind::EnumMemberDeclaration | SyntaxKind::AttributeArgument)
+}
+
+fn expressions(node: &SyntaxRef) -> Vec<SyntaxRef> {
+    node.child_nodes().into_iter().filter(|x| Expression::cast(x.clone()).is_some()).collect()
+}
+
+fn is_predefined(name: &str) -> bool {
+    let name = underlying_type(name, &()).unwrap_or(name);
+    BUILTIN_TYPES.contains(&name) || name == "null"
+}
+
+pub fn integer_value(text: &str) -> Option<u64> {
+    let digits: String = text.trim_end_matches(['u', 'U', 'l', 'L'])
+        .chars().filter(|x| *x != '_').collect();
+    let lower = digits.to_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+    if let Some(binary) = lower.strip_prefix("0b") {
+        return u64::from_str_radix(binary, 2).ok();
+    }
+    lower.parse().ok()
+}
+
+pub fn literal_type(token: &SyntaxRef) -> Option<String> {
+    let text = token.token_text().to_lowercase();
+    let name = match token.token_kind()? {
+        TokenKind::BoolLiteral => "bool",
+        TokenKind::NullLiteral => "null",
+        TokenKind::CharLiteral => "char",
+        TokenKind::StringLiteral => "string",
+        TokenKind::RealLiteral if text.ends_with('f') => "float",
+        TokenKind::RealLiteral if text.ends_with('m') => "decimal",
+        TokenKind::RealLiteral => "double",
+        TokenKind::IntegerLiteral => {
+            let suffix: String = text.chars().rev().take_while(|x| *x == 'u' || *x == 'l').collect();
+            let value = integer_value(&text).unwrap_or(u64::MAX);
+            match (suffix.contains('u'), suffix.contains('l')) {
+                (true, true) => "ulong",
+                (false, true) if value <= i64::MAX as u64 => "long",
+                (true, false) if value <= u32::MAX as u64 => "uint",
+                (_, true) | (true, _) => "ulong",
+                _ if value <= i32::MAX as u64 => "int",
+                _ if value <= u32::MAX as u64 => "uint",
+                _ if value <= i64::MAX as u64 => "long",
+                _ => "ulong",
+            }
+        }
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+pub fn unary_result(op: &str, operand: &str) -> Option<&'static str> {
+    let result = match (op, operand) {
+        ("!", "bool") => "bool",
+        ("-", "ulong") => return None,
+        ("-", "uint") => "long",
+        ("+", x) | ("-", x) | ("~", x) if ["sbyte", "byte", "short", "ushort", "char", "int"].contains(&x) => "int",
+        ("+", "uint") | ("~", "uint") => "uint",
+        ("+", "long") | ("-", "long") | ("~", "long") => "long",
+        ("+", "ulong") | ("~", "ulong") => "ulong",
+        ("+", "float") | ("-", "float") => "float",
+        ("+", "double") | ("-", "double") => "double",
+        ("+", "decimal") | ("-", "decimal") => "decimal",
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn is_assignable(node: &SyntaxRef) -> bool {
+    match node.kind() {
+        SyntaxKind::IdentifierName | SyntaxKind::MemberAccessExpression | SyntaxKind::ElementAccessExpression
+        | SyntaxKind::TupleExpression | SyntaxKind::DeclarationExpression | SyntaxKind::ImplicitElementAccess
+        | SyntaxKind::ThisExpression => true,
+        SyntaxKind::ParenthesizedExpression => expressions(node).pop().is_some_and(|x| is_assignable(&x)),
+        _ => false,
+    }
+}
+
+// 'F(int)', 'F(long)' and 'F(string)'
+fn candidate_list<'a, I: Iterator<Item = &'a MemberSymbol>>(candidates: I) -> MessageArgument {
+    let names: Vec<String> = candidates.map(|x| format!("'{}'", signature_text(x))).collect();
+    and_list(names)
+}
+
+// 最后两项之间的 "and" 跟着消息翻译
+fn and_list(mut items: Vec<String>) -> MessageArgument {
+    let last = items.pop().unwrap_or_default();
+    if items.is_empty() {
+        return MessageArgument::from(last);
+    }
+    MessageArgument::phrase("and", &[&items.join(", "), &last])
+}
+
+fn function_kind(node: &SyntaxRef) -> MessageArgument {
+    let key = if node.kind() == SyntaxKind::LambdaExpression { "lambda-expression" } else { "anonymous-method" };
+    MessageArgument::phrase(key, &[])
+}
+
+// 方法的返回类型, 属性和索引器的类型
+fn declared_member_type(node: &SyntaxRef) -> Option<TypeSyntax> {
+    match node.kind() {
+        SyntaxKind::MethodDeclaration => MethodDeclaration::cast(node.clone())?.return_type(),
+        SyntaxKind::PropertyDeclaration | SyntaxKind::IndexerDeclaration =>
+            PropertyDeclaration::cast(node.clone())?.property_type(),
+        _ => None,
+    }
+}
+
+fn is_iterator(function: &SyntaxRef) -> bool {
+    function.traverse(true, false)
+        .filter(|x| x.kind() == SyntaxKind::YieldReturnStatement || x.kind() == SyntaxKind::YieldBreakStatement)
+        .any(|x| enclosing_function(&x).as_ref() == Some(function))
+}
+
+
+struct Checker<'a> {
+    declarations: &'a Declarations,
+    scopes: &'a ScopeTree,
+    // 检查过的局部变量的类型, var 是推断出来的类型
+    locals: Vec<(SyntaxRef, String)>,
+    functions: Vec<Function>,
+    // 重载决议给 lambda 实参定下的委托类型
+    targets: Vec<(SyntaxRef, String)>,
+    result: TypeCheck,
+}
+
+impl<'a> UserTypes for Checker<'a> {
+    fn is_user_value_type(&self, name: &str) -> bool {
+        self.declarations.is_user_value_type(name)
+    }
+}
+
+impl<'a> TypeContext for Checker<'a> {
+    fn declared_type(&self, member: &MemberSymbol, type_name: &str) -> Option<String> {
+        let containing = self.enclosing_type(&member.declaration).map_or(&[][..], |x| &x.type_parameters[..]);
+        if uses_type_parameters(type_name, &member.type_parameters) || uses_type_parameters(type_name, containing) {
+            return None;
+        }
+        Some(self.normalize(type_name, &member.declaration))
+    }
+
+    fn is_implicit(&self, value: Option<&SyntaxRef>, from: &str, to: &str) -> bool {
+        self.convertible(value, from, to) == Convertible::Implicit
+    }
+
+    // 用户声明的委托按声明的参数和返回类型
+    fn delegate_signature(&self, type_name: &str) -> Option<(Vec<String>, String)> {
+        if let Some(signature) = delegate_signature(type_name) {
+            return Some(signature);
+        }
+        let symbol = self.user_type(type_name).filter(|x| x.kind == SyntaxKind::DelegateDeclaration)?;
+        let declaration = &symbol.declarations[0];
+        let arguments: Vec<(String, String)> = symbol.type_parameters.iter().cloned().zip(type_arguments(type_name)).collect();
+        let resolve = |syntax: TypeSyntax| substitute_type(&self.syntax_type(&syntax), &arguments);
+        let returns = declaration.child_nodes().into_iter().find_map(TypeSyntax::cast).map(&resolve)?;
+        let parameters = declaration.child_of(SyntaxKind::ParameterList).map_or(Vec::new(), |x| x.children_of(SyntaxKind::Parameter));
+        let inputs = parameters.into_iter().map(|x| Parameter::cast(x).and_then(|x| x.parameter_type()).map(&resolve))
+            .collect::<Option<Vec<String>>>()?;
+        Some((inputs, returns))
+    }
+
+    // 在一个临时的检查器里检查 lambda 的函数体, 错误和类型都不留下
+    fn lambda_return(&self, lambda: &SyntaxRef, parameter_types: &[String]) -> Option<String> {
+        let mut checker = Checker {
+            declarations: self.declarations,
+            scopes: self.scopes,
+            locals: self.locals.clone(),
+            functions: Vec::new(),
+            targets: Vec::new(),
+            result: TypeCheck { types: Vec::new(), errors: Vec::new() },
+        };
+        checker.bind_lambda_parameters(lambda, parameter_types);
+        let returned = checker.lambda_body(lambda, Function { name: function_kind(lambda), returns: None, returned: Some(Vec::new()) });
+        checker.inferred_return(lambda, returned)
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, node: &SyntaxRef, code: &'static str, arguments: &[&str]) {
+        self.result.errors.push(Diagnostic::error(code, node.span(), arguments));
+    }
+
+    // 参数里有跟着消息翻译的词
+    fn error_with(&mut self, node: &SyntaxRef, code: &'static str, arguments: Vec<MessageArgument>) {
+        self.result.errors.push(Diagnostic::new(Severity::Error, code, node.span(), arguments));
+    }
+
+    // ------------------------------------ statements and declarations -----------------------
+
+    fn check_node(&mut self, node: &SyntaxRef) {
+        if let Some(declared) = declared_member_type(node) {
+            self.check_type_names(&declared);
+        }
+        match node.kind() {
+            kind if is_function(kind) => {
+                let function = self.function(node);
+                self.functions.push(function);
+                self.check_children(node);
+                self.functions.pop();
+            }
+            SyntaxKind::PropertyDeclaration | SyntaxKind::IndexerDeclaration
+                if node.child_of(SyntaxKind::ArrowExpressionClause).is_some() => {
+                let property = PropertyDeclaration::cast(node.clone()).unwrap();
+                let returns = property.property_type().map(|x| self.syntax_type(&x));
+                let name = property.name().unwrap_or_else(|| "this".to_string());
+                self.functions.push(Function { name: MessageArgument::from(name), returns, returned: None });
+                self.check_children(node);
+                self.functions.pop();
+            }
+            SyntaxKind::VariableDeclaration => self.check_variables(node),
+            SyntaxKind::Parameter => self.check_parameter(node),
+            SyntaxKind::ArrowExpressionClause => {
+                if let Some(value) = expressions(node).pop() {
+                    self.check_return_value(&value, false);
+                }
+            }
+            SyntaxKind::ReturnStatement => match expressions(node).pop() {
+                Some(value) => self.check_return_value(&value, true),
+                None => self.check_empty_return(node),
+            },
+            SyntaxKind::IfStatement | SyntaxKind::WhileStatement | SyntaxKind::DoStatement => {
+                for child in node.child_nodes() {
+                    if Expression::cast(child.clone()).is_some() {
+                        self.check_condition(&child);
+                    }
+                    else {
+                        self.check_node(&child);
+                    }
+                }
+            }
+            SyntaxKind::ForStatement => {
+                let mut semicolons = 0;
+                for child in node.children() {
+                    if child.is_token() {
+                        semicolons += (child.token_text() == ";") as usize;
+                    }
+                    else if semicolons == 1 && Expression::cast(child.clone()).is_some() {
+                        self.check_condition(&child);
+                    }
+                    else if Expression::cast(child.clone()).is_some() {
+                        self.expr(&child);
+                    }
+                    else {
+                        self.check_node(&child);
+                    }
+                }
+            }
+            SyntaxKind::ForEachStatement => self.check_foreach(node),
+            SyntaxKind::ExpressionStatement => {
+                if let Some(expression) = expressions(node).pop() {
+                    self.check_statement_expression(&expression);
+                }
+                self.check_children(node);
+            }
+            _ => self.check_children(node),
+        }
+    }
+
+    fn check_children(&mut self, node: &SyntaxRef) {
+        let value_names = has_value_names(node.kind());
+        for child in node.child_nodes() {
+            if Expression::cast(child.clone()).is_some() && (value_names || !is_name(child.kind())) {
+                self.expr(&child);
+            }
+            else if !is_name(child.kind()) {
+                self.check_node(&child);
+            }
+        }
+    }
+
+    fn function(&self, node: &SyntaxRef) -> Function {
+        let mut name = node.identifier().map(|x| x.token_text()).unwrap_or_default();
+        let returns = match node.kind() {
+            SyntaxKind::LambdaExpression | SyntaxKind::AnonymousMethodExpression => None,
+            SyntaxKind::ConstructorDeclaration | SyntaxKind::DestructorDeclaration => Some("void".to_string()),
+            SyntaxKind::AccessorDeclaration => {
+                let property = node.acestors().find_map(PropertyDeclaration::cast);
+                name = format!("{}.{}", property.and_then(|x| x.name()).unwrap_or_else(|| "this".to_string()), name);
+                match function_return_type(node) {
+                    Some(returns) => Some(self.syntax_type(&returns)),
+                    None => Some("void".to_string()),
+                }
+            }
+            _ => function_return_type(node).map(|x| self.syntax_type(&x)),
+        };
+        let is_checked = !is_async(node) && !is_iterator(node);
+        Function { name: MessageArgument::from(name), returns: returns.filter(|_| is_checked), returned: None }
+    }
+
+    fn check_variables(&mut self, node: &SyntaxRef) {
+        let declaration = VariableDeclaration::cast(node.clone()).unwrap();
+        let declared = declaration.variable_type();
+        let is_var = declared.as_ref().is_some_and(|x| x.text() == "var") && self.user_type("var").is_none();
+        if let Some(declared) = declared.as_ref().filter(|_| !is_var) {
+            self.check_type_names(declared);
+        }
+        let declared_type = declared.filter(|_| !is_var).map(|x| self.syntax_type(&x));
+        let declarators = declaration.declarators();
+        if is_var && declarators.len() > 1 {
+            self.error(node, "DY0507", &[]);
+        }
+        for declarator in declarators {
+            let node = declarator.syntax().clone();
+            let value = declarator.initializer().map(|x| x.syntax().clone());
+            let value_type = value.as_ref().and_then(|x| self.target_expr(x, declared_type.as_deref()));
+            let local_type = match (declared_type.clone(), value, value_type) {
+                (Some(declared), Some(value), Some(value_type)) => {
+                    self.check_conversion(&value, &value_type, &declared);
+                    Some(declared)
+                }
+                (Some(declared), _, _) => Some(declared),
+                (None, None, _) => {
+                    self.error(&node, "DY0508", &[]);
+                    None
+                }
+                (None, Some(ref value), _) if value.kind() == SyntaxKind::InitializerExpression => {
+                    self.error(value, "DY0509", &[]);
+                    None
+                }
+                (None, Some(ref value), None) if is_lambda(value) && !self.is_explicit_lambda(value) => {
+                    self.error_with(value, "DY0510", vec![function_kind(value)]);
+                    None
+                }
+                (None, Some(value), Some(ref value_type)) if value_type == "null" || value_type == "void" => {
+                    self.error(&value, "DY0511", &[if value_type == "null" { "<null>" } else { "void" }]);
+                    None
+                }
+                (None, _, value_type) => value_type,
+            };
+            if let Some(local_type) = local_type {
+                self.locals.push((node, local_type));
+            }
+        }
+    }
+
+    fn check_parameter(&mut self, node: &SyntaxRef) {
+        let parameter = Parameter::cast(node.clone()).unwrap();
+        if let Some(declared) = parameter.parameter_type() {
+            self.check_type_names(&declared);
+        }
+        let value = match parameter.default_value() {
+            Some(value) => value.syntax().clone(),
+            None => return,
+        };
+        let value_type = self.expr(&value);
+        if let (Some(declared), Some(value_type)) = (parameter.parameter_type(), value_type) {
+            let declared = self.syntax_type(&declared);
+            self.check_conversion(&value, &value_type, &declared);
+        }
+    }
+
+    // return e 和 => e; 表达式体的 void 方法相当于表达式语句
+    fn check_return_value(&mut self, value: &SyntaxRef, is_return: bool) {
+        let target = self.functions.last().and_then(|x| x.returns.clone()).filter(|x| x != "void");
+        let value_type = self.target_expr(value, target.as_deref());
+        // 推断返回类型的 lambda 只记下返回值的类型
+        if let Some(&mut Function { returned: Some(ref mut returned), .. }) = self.functions.last_mut() {
+            returned.push(value_type);
+            return;
+        }
+        let (name, returns) = match self.functions.last() {
+            Some(&Function { ref name, returns: Some(ref returns), .. }) => (name.clone(), returns.clone()),
+            _ => return,
+        };
+        if returns == "void" {
+            if is_return {
+                self.error_with(value, "DY0512", vec![name]);
+            }
+            else if let Some(expression) = expressions(&value.parent().unwrap()).pop() {
+                self.check_statement_expression(&expression);
+            }
+            return;
+        }
+        if let Some(value_type) = value_type {
+            self.check_conversion(value, &value_type, &returns);
+        }
+    }
+
+    fn check_empty_return(&mut self, node: &SyntaxRef) {
+        if let Some(&Function { returns: Some(ref returns), .. }) = self.functions.last() {
+            if returns != "void" {
+                let returns = returns.clone();
+                self.error(node, "DY0513", &[&returns]);
+            }
+        }
+    }
+
+    fn check_condition(&mut self, node: &SyntaxRef) {
+        if let Some(value_type) = self.expr(node) {
+            self.check_conversion(node, &value_type, "bool");
+        }
+    }
+
+    fn check_statement_expression(&mut self, node: &SyntaxRef) {
+        let is_statement = match node.kind() {
+            SyntaxKind::InvocationExpression | SyntaxKind::AssignmentExpression | SyntaxKind::AwaitExpression
+            | SyntaxKind::ObjectCreationExpression | SyntaxKind::ConditionalAccessExpression => true,
+            SyntaxKind::PrefixUnaryExpression | SyntaxKind::PostfixUnaryExpression => Expression::cast(node.clone()).and_then(|x| x.operator())
+                .is_some_and(|x| x == "++" || x == "--"),
+            _ => false,
+        };
+        if !is_statement {
+            self.error(node, "DY0514", &[]);
+        }
+    }
+
+    fn check_foreach(&mut self, node: &SyntaxRef) {
+        let mut declared = None;
+        let mut deconstruction = None;
+        let mut variable = None;
+        let mut collection = None;
+        let mut body = Vec::new();
+        let mut after_in = false;
+        for child in node.children() {
+            if child.is_token() {
+                after_in = after_in || child.token_text() == "in";
+                continue;
+            }
+            if !after_in {
+                if let Some(x) = TypeSyntax::cast(child.clone()) {
+                    declared = Some(x);
+                }
+                else if child.kind() == SyntaxKind::VariableDeclarator {
+                    variable = Some(child);
+                }
+                else if child.kind() == SyntaxKind::DeclarationExpression {
+                    deconstruction = Some(child);
+                }
+            }
+            else if collection.is_none() {
+                collection = Some(child);
+            }
+            else {
+                body.push(child);
+            }
+        }
+        let collection_type = collection.as_ref().and_then(|x| self.expr(x));
+        let element_type = match collection_type {
+            Some(ref x) => self.element_type(x),
+            None => None,
+        };
+        if let (Some(collection), Some(collection_type)) = (collection.as_ref(), collection_type.as_ref()) {
+            if element_type.is_none() && is_predefined(collection_type) {
+                self.error(collection, "DY0515", &[collection_type, collection_type]);
+            }
+        }
+        if let (Some(deconstruction), Some(element_type)) = (deconstruction, element_type.as_ref()) {
+            self.deconstruct(&deconstruction, element_type, false);
+        }
+        let variable_type = match declared {
+            Some(ref x) if x.text() == "var" => element_type,
+            Some(ref x) => Some(self.syntax_type(x)),
+            None => None,
+        };
+        if let (Some(variable), Some(variable_type)) = (variable, variable_type) {
+            self.locals.push((variable, variable_type));
+        }
+        for child in body {
+            self.check_node(&child);
+        }
+    }
+
+    fn element_type(&self, collection: &str) -> Option<String> {
+        if let Some(element) = collection.strip_suffix("[]") {
+            return Some(element.to_string());
+        }
+        if collection == "string" {
+            return Some("char".to_string());
+        }
+        let arguments = type_arguments(collection);
+        match generic_name(collection) {
+            "List" | "IList" | "IEnumerable" | "ICollection" | "IReadOnlyList" | "HashSet" | "Queue" | "Stack"
+                if arguments.len() == 1 => Some(arguments[0].clone()),
+            "Dictionary" | "IDictionary" if arguments.len() == 2 =>
+                Some(format!("KeyValuePair<{}, {}>", arguments[0], arguments[1])),
+            _ => None,
+        }
+    }
+
+    // ------------------------------------ types ---------------------------------------------
+
+    fn syntax_type(&self, syntax: &TypeSyntax) -> String {
+        self.normalize(&syntax.text(), syntax.syntax())
+    }
+
+    // 用户类型换成全名, 数组和可空类型按元素处理, 其他保持原样
+    fn normalize(&self, text: &str, at: &SyntaxRef) -> String {
+        let text = text.trim();
+        if let Some(element) = text.strip_suffix("[]") {
+            return format!("{}[]", self.normalize(element, at));
+        }
+        // Nullable<T> 统一写成 T?
+        let is_system_nullable = self.user_type("Nullable").is_none();
+        let inner = text.strip_suffix('?').or_else(|| underlying_type(text, &()).filter(|_| is_system_nullable));
+        if let Some(inner) = inner {
+            let inner = self.normalize(inner, at);
+            // 引用类型的 ? 只是可空注解 (dy_nullable), 类型还是 T
+            if !is_value_type(&inner, self.declarations) {
+                return inner;
+            }
+            return format!("{}?", inner);
+        }
+        if BUILTIN_TYPES.contains(&text) || text.contains('<') || text.starts_with('(') {
+            return text.to_string();
+        }
+        match self.declarations.resolve_type(text, &LookupContext::at(at)) {
+            Some(symbol) => symbol.full_name(),
+            None => text.to_string(),
+        }
+    }
+
+    fn user_type(&self, name: &str) -> Option<&'a TypeSymbol> {
+        self.declarations.find_type(generic_name(name))
+    }
+
+    fn is_known(&self, name: &str) -> bool {
+        if let Some(element) = name.strip_suffix("[]") {
+            return self.is_known(element);
+        }
+        let name = underlying_type(name, self.declarations).unwrap_or(name);
+        is_predefined(name) || self.user_type(name).is_some()
+    }
+
+    // 写出来的类型里的简单类型名, 找不到声明, 宿主也不提供时报错
+    fn check_type_names(&mut self, syntax: &TypeSyntax) {
+        if self.declarations.has_host_names() {
+            return;
+        }
+        for node in syntax.syntax().traverse(true, false) {
+            let is_outermost = node.parent().is_none_or(|x| !matches!(x.kind(),
+                SyntaxKind::QualifiedName | SyntaxKind::AliasQualifiedName));
+            if !matches!(node.kind(), SyntaxKind::IdentifierName | SyntaxKind::GenericName) || !is_outermost {
+                continue;
+            }
+            let name = node.identifier().map(|x| x.token_text()).unwrap_or_default();
+            let is_type_parameter = node.acestors().filter_map(|x| x.child_of(SyntaxKind::TypeParameterList))
+                .flat_map(|x| x.children_of(SyntaxKind::TypeParameter))
+                .any(|x| x.identifier().is_some_and(|x| x.token_text() == name));
+            if name.is_empty() || name == "dynamic" || HOST_TYPES.contains(&name.as_str()) || is_type_parameter {
+                continue;
+            }
+            let is_missing = matches!(self.declarations.resolve_name(&name, &LookupContext::at(&node)),
+                Err(NameError::NotFound(..)));
+            let is_missing = is_missing && self.declarations.resolve_type_at(&name, &node).is_none();
+            if is_missing && !self.in_unknown_base(&node) {
+                self.error(&node, "DY0429", &[&name]);
+            }
+        }
+    }
+
+    // 简单名字不是局部变量, 外层类型的成员, 类型, 命名空间或者 using static 导入的成员, 宿主也不提供
+    fn is_undeclared(&self, node: &SyntaxRef, name: &str) -> bool {
+        if name == "_" || self.declarations.has_host_names() || BUILTIN_FUNCTIONS.iter().any(|x| x.0 == name)
+            || !self.scopes.lookup(name, node.span().start).is_empty() || self.in_unknown_base(node) {
+            return false;
+        }
+        let mut outer = self.enclosing_type(node);
+        while let Some(symbol) = outer {
+            if !self.members(symbol, name).is_empty() {
+                return false;
+            }
+            outer = symbol.declarations.first().and_then(|x| self.enclosing_type(x));
+        }
+        let context = LookupContext::at(node);
+        matches!(self.declarations.resolve_name(name, &context), Err(NameError::NotFound(..)))
+            && self.declarations.resolve_type_at(name, node).is_none()
+            && self.declarations.lookup_imported(name, &context).is_ok_and(|x| x.is_empty())
+    }
+
+    // 外层类型有宿主提供的基类型, 名字可能来自那里
+    fn in_unknown_base(&self, node: &SyntaxRef) -> bool {
+        self.declarations.enclosing_types(node).into_iter().any(|x| self.has_unknown_base(x))
+    }
+
+    fn base_types(&self, symbol: &TypeSymbol) -> Vec<&'a TypeSymbol> {
+        let context = LookupContext::at(&symbol.declarations[0]);
+        symbol.base_types.iter().filter_map(|x| self.declarations.resolve_type(x, &context)).collect()
+    }
+
+    // 有基类型没有声明 (宿主类型), 成员可能来自那里
+    fn has_unknown_base(&self, symbol: &TypeSymbol) -> bool {
+        self.base_types(symbol).len() < symbol.base_types.len()
+            || self.base_types(symbol).iter().any(|x| self.has_unknown_base(x))
+    }
+
+    fn is_subtype(&self, symbol: &TypeSymbol, of: &TypeSymbol) -> bool {
+        let mut queue = vec![symbol.full_name()];
+        let mut i = 0;
+        while i < queue.len() {
+            if queue[i] == of.full_name() {
+                return true;
+            }
+            if let Some(current) = self.declarations.find_type(&queue[i]) {
+                for base in self.base_types(current) {
+                    if !queue.contains(&base.full_name()) {
+                        queue.push(base.full_name());
+                    }
+                }
+            }
+            i += 1;
+        }
+        false
+    }
+
+    fn enclosing_type(&self, node: &SyntaxRef) -> Option<&'a TypeSymbol> {
+        let declaration = node.acestors().skip(1).find(|x| TypeDeclaration::cast(x.clone()).is_some())?;
+        self.declarations.types.iter().find(|x| x.declarations.contains(&declaration))
+    }
+
+    // 类型和基类型里第一个声明了这个名字的类型的成员, 派生类的成员隐藏基类的
+    fn members(&self, symbol: &'a TypeSymbol, name: &str) -> Vec<&'a MemberSymbol> {
+        let mut queue = vec![symbol];
+        let mut i = 0;
+        while i < queue.len() {
+            let found = queue[i].members_named(name);
+            if !found.is_empty() {
+                return found;
+            }
+            for base in self.base_types(queue[i]) {
+                if !queue.iter().any(|x| x.full_name() == base.full_name()) {
+                    queue.push(base);
+                }
+            }
+            i += 1;
+        }
+        Vec::new()
+    }
+
+    // 在 node 处通过 receiver 类型的表达式访问 member, 不能访问时的错误
+    fn access_error(&self, node: &SyntaxRef, member: &MemberSymbol, receiver: Option<&TypeSymbol>)
+        -> Option<Diagnostic> {
+        let owner = self.enclosing_type(&member.declaration)?;
+        access_error(self.declarations, owner, Some(member), node, receiver)
+    }
+
+    fn check_access(&mut self, node: &SyntaxRef, member: &MemberSymbol, receiver: Option<&TypeSymbol>) {
+        if let Some(error) = self.access_error(node, member, receiver) {
+            self.result.errors.push(error);
+        }
+    }
+
+    // 成员的类型在成员声明的位置解析, 类型参数不知道具体类型
+    fn member_type(&self, member: &MemberSymbol) -> Value {
+        member.type_name.as_ref().and_then(|x| self.declared_type(member, x)).map_or(Value::Unknown, Value::Typed)
+    }
+
+    // 整数常量, 可以隐式转换成放得下它的更小的整数类型: byte b = 1 + 2
+    fn constant_integer(&self, node: &SyntaxRef) -> Option<i128> {
+        match evaluate(self.declarations, self.scopes, node)?.value {
+            ConstantValue::Integer(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    // 放得下的 int 常量换成另一边的整数类型
+    fn constant_operand(&self, node: &SyntaxRef, constant_type: &str, other: &str) -> String {
+        let fits_other = other != "char" && self.constant_integer(node).is_some_and(|x| fits(x, other));
+        if constant_type == "int" && fits_other {
+            return other.to_string();
+        }
+        constant_type.to_string()
+    }
+
+    fn convertible(&self, value: Option<&SyntaxRef>, from: &str, to: &str) -> Convertible {
+        if from == to || !self.is_known(from) || !self.is_known(to) {
+            return Convertible::Implicit;
+        }
+        let target = underlying_type(to, self.declarations).unwrap_or(to);
+        if let Some(constant) = value.and_then(|x| self.constant_integer(x)) {
+            if is_integral(from) && target != "char" && fits(constant, target) {
+                return Convertible::Implicit;
+            }
+        }
+        let (source, target) = (self.user_type(from), self.user_type(target));
+        let is_enum = |x: Option<&TypeSymbol>| x.is_some_and(|x| x.kind == SyntaxKind::EnumDeclaration);
+        if from == "null" {
+            let is_nullable = underlying_type(to, self.declarations).is_some();
+            let is_reference = !is_value_type(to, self.declarations);
+            return if is_nullable || is_reference { Convertible::Implicit } else { Convertible::No };
+        }
+        let conversion = classify_conversion(from, to, self.declarations);
+        if conversion.is_implicit() || to == "object" {
+            return Convertible::Implicit;
+        }
+        if let (Some(source), Some(target)) = (source, target) {
+            if self.is_subtype(source, target) {
+                return Convertible::Implicit;
+            }
+            let is_interface = |x: &TypeSymbol| x.kind == SyntaxKind::InterfaceDeclaration;
+            if self.is_subtype(target, source) || is_interface(source) || is_interface(target) {
+                return Convertible::Explicit;
+            }
+            return Convertible::No;
+        }
+        if is_enum(target) && is_integral(from) && value.and_then(|x| self.constant_integer(x)) == Some(0) {
+            return Convertible::Implicit;
+        }
+        let is_number = |x: &str| {
+            let x = underlying_type(x, self.declarations).unwrap_or(x);
+            is_numeric(x) || is_enum(self.user_type(x))
+        };
+        if (is_number(from) && is_number(to)) || from == "object" || conversion == Conversion::ExplicitNullable {
+            return Convertible::Explicit;
+        }
+        Convertible::No
+    }
+
+    fn check_conversion(&mut self, value: &SyntaxRef, from: &str, to: &str) {
+        match self.convertible(Some(value), from, to) {
+            Convertible::Implicit => {}
+            _ if from == "null" => self.error(value, "DY0503", &[to]),
+            Convertible::Explicit => {
+                // 加上强制转换就能编译, 不是简单名字的表达式要加括号
+                let text = value.text();
+                let text = text.trim();
+                let is_simple = text.chars().all(|x| x.is_alphanumeric() || x == '_' || x == '.');
+                let replacement = if is_simple { format!("({}){}", to, text) } else { format!("({})({})", to, text) };
+                let error = Diagnostic::error("DY0502", value.span(), &[from, to])
+                    .with_fix(Phrase::new("explicit-cast", &[]), value.span(), &replacement);
+                self.result.errors.push(error);
+            }
+            Convertible::No => self.error(value, "DY0501", &[from, to]),
+        }
+    }
+
+    // ------------------------------------ expressions ---------------------------------------
+
+    // 值表达式的类型; 类型名出现在这里是错误
+    fn expr(&mut self, node: &SyntaxRef) -> Option<String> {
+        match self.value(node) {
+            Value::Typed(x) => Some(x),
+            Value::Type(x) => {
+                self.error(node, "DY0516", &[&x]);
+                None
+            }
+            Value::Unknown => None,
+        }
+    }
+
+    fn value(&mut self, node: &SyntaxRef) -> Value {
+        let value = self.compute(node);
+        if let Value::Typed(ref x) = value {
+            self.result.types.push((node.clone(), x.clone()));
+        }
+        value
+    }
+
+    fn compute(&mut self, node: &SyntaxRef) -> Value {
+        let operands = expressions(node);
+        match node.kind() {
+            SyntaxKind::LiteralExpression => node.first_significant_token().and_then(|x| literal_type(&x))
+                .map_or(Value::Unknown, Value::Typed),
+            SyntaxKind::ParenthesizedExpression | SyntaxKind::CheckedExpression =>
+                operands.last().map_or(Value::Unknown, |x| self.value(x)),
+            kind if is_name(kind) => self.name_value(node),
+            SyntaxKind::ThisExpression => self.enclosing_type(node)
+                .map_or(Value::Unknown, |x| Value::Typed(x.full_name())),
+            SyntaxKind::BaseExpression => self.enclosing_type(node)
+                .and_then(|x| self.base_types(x).into_iter().find(|x| x.kind != SyntaxKind::InterfaceDeclaration))
+                .map_or(Value::Unknown, |x| Value::Typed(x.full_name())),
+            SyntaxKind::MemberAccessExpression => self.member_access(node),
+            SyntaxKind::InvocationExpression => self.invocation(node),
+            SyntaxKind::ElementAccessExpression => self.element_access(node),
+            SyntaxKind::ObjectCreationExpression => self.object_creation(node),
+            SyntaxKind::ArrayCreationExpression => self.array_creation(node),
+            SyntaxKind::ImplicitArrayCreationExpression => {
+                let elements = node.child_of(SyntaxKind::InitializerExpression).map_or(Vec::new(), |x| expressions(&x));
+                let types: Vec<Option<String>> = elements.iter().map(|x| self.expr(x)).collect();
+                match types.first() {
+                    Some(Some(first)) => Value::Typed(format!("{}[]", first)),
+                    _ => Value::Unknown,
+                }
+            }
+            SyntaxKind::CastExpression => self.cast(node),
+            SyntaxKind::BinaryExpression if operands.len() == 2 => {
+                let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+                let left = self.expr(&operands[0]);
+                let right = self.expr(&operands[1]);
+                match (left, right) {
+                    (Some(left), Some(right)) => {
+                        // 3u * 2 里的 2 按 uint 算
+                        let left_type = self.constant_operand(&operands[0], &left, &right);
+                        let right_type = self.constant_operand(&operands[1], &right, &left);
+                        self.binary(node, &op, &left_type, &right_type)
+                    }
+                    _ if COMPARISON_OPERATORS.contains(&op.as_str()) || op == "&&" || op == "||" =>
+                        Value::Typed("bool".to_string()),
+                    _ => Value::Unknown,
+                }
+            }
+            SyntaxKind::AssignmentExpression if operands.len() == 2 => self.assignment(node, &operands[0], &operands[1]),
+            SyntaxKind::PrefixUnaryExpression | SyntaxKind::PostfixUnaryExpression => self.unary(node),
+            SyntaxKind::ConditionalExpression if operands.len() == 3 => self.conditional(&operands),
+            SyntaxKind::IsExpression | SyntaxKind::IsPatternExpression => {
+                if let Some(operand) = operands.first() {
+                    self.expr(operand);
+                }
+                Value::Typed("bool".to_string())
+            }
+            SyntaxKind::AsExpression => {
+                if let Some(operand) = operands.first() {
+                    self.expr(operand);
+                }
+                let target = match node.child_nodes().into_iter().filter_map(TypeSyntax::cast).last() {
+                    Some(x) => self.syntax_type(&x),
+                    None => return Value::Unknown,
+                };
+                if is_value_type(&target, self.declarations) && underlying_type(&target, self.declarations).is_none() {
+                    self.error(node, "DY0517", &[&target]);
+                }
+                Value::Typed(target)
+            }
+            SyntaxKind::TypeOfExpression => Value::Typed("Type".to_string()),
+            SyntaxKind::SizeOfExpression => Value::Typed("int".to_string()),
+            SyntaxKind::DefaultExpression => node.child_nodes().into_iter().find_map(TypeSyntax::cast)
+                .map_or(Value::Unknown, |x| Value::Typed(self.syntax_type(&x))),
+            SyntaxKind::AwaitExpression => {
+                let task = operands.last().and_then(|x| self.expr(x));
+                match task {
+                    Some(ref x) if x == "Task" => Value::Typed("void".to_string()),
+                    Some(ref x) if generic_name(x) == "Task" => type_arguments(x).pop().map_or(Value::Unknown, Value::Typed),
+                    _ => Value::Unknown,
+                }
+            }
+            SyntaxKind::TupleExpression => {
+                // (X: 1, Y: "s") 的类型是 (int X, string Y)
+                let types: Vec<Option<String>> = node.child_nodes().into_iter()
+                    .map(|x| {
+                        let name = Argument::cast(x.clone()).and_then(|x| x.name());
+                        let element = expressions(&x).pop().and_then(|x| self.expr(&x));
+                        element.map(|x| name.map_or(x.clone(), |name| format!("{} {}", x, name)))
+                    })
+                    .collect();
+                if types.iter().all(|x| x.is_some()) {
+                    let types: Vec<String> = types.into_iter().map(|x| x.unwrap()).collect();
+                    return Value::Typed(format!("({})", types.join(", ")));
+                }
+                Value::Unknown
+            }
+            SyntaxKind::LambdaExpression | SyntaxKind::AnonymousMethodExpression => self.lambda(node, None),
+            _ => {
+                self.check_children(node);
+                Value::Unknown
+            }
+        }
+    }
+
+    fn name_value(&mut self, node: &SyntaxRef) -> Value {
+        if node.kind() == SyntaxKind::PredefinedType {
+            return Value::Type(node.token_text());
+        }
+        if node.kind() == SyntaxKind::IdentifierName {
+            let name = node.token_text();
+            let symbols = self.scopes.lookup(&name, node.span().start);
+            if let Some(symbol) = symbols.first() {
+                if symbol.is_local() {
+                    return self.local_type(symbol).map_or(Value::Unknown, Value::Typed);
+                }
+                if symbol.kind == SemanticFlags::EnumMemberDeclaration {
+                    let owner = self.enclosing_type(&symbol.node);
+                    return owner.map_or(Value::Unknown, |x| self.enum_member_type(node, x));
+                }
+            }
+            // 外层类型和基类型的成员
+            let mut outer = self.enclosing_type(node);
+            while let Some(symbol) = outer {
+                let members = self.members(symbol, &name);
+                if let Some(member) = members.iter().find(|x| x.kind != SyntaxKind::MethodDeclaration) {
+                    self.check_access(node, member, None);
+                    return self.member_type(member);
+                }
+                if !members.is_empty() {
+                    return Value::Unknown;
+                }
+                outer = symbol.declarations.first().and_then(|x| self.enclosing_type(x));
+            }
+        }
+        let context = LookupContext::at(node);
+        if let Ok(NameTarget::Type(_, symbol)) = self.declarations.resolve_name(&node.text(), &context) {
+            return Value::Type(symbol.full_name());
+        }
+        if let Ok(imported) = self.declarations.lookup_imported(&node.token_text(), &context) {
+            if let Some(&(_, member)) = imported.iter().find(|x| x.1.kind != SyntaxKind::MethodDeclaration) {
+                return self.member_type(member);
+            }
+        }
+        if node.kind() == SyntaxKind::IdentifierName && self.is_undeclared(node, &node.token_text()) {
+            self.error(node, "DY0553", &[&node.token_text()]);
+        }
+        Value::Unknown
+    }
+
+    // 枚举成员的初始值里, 同一个枚举的成员是基础类型的值: Y = X | 2
+    fn enum_member_type(&self, node: &SyntaxRef, owner: &TypeSymbol) -> Value {
+        let initializer = node.acestors().find(|x| x.kind() == SyntaxKind::EnumMemberDeclaration);
+        if initializer.is_some_and(|x| self.enclosing_type(&x).is_some_and(|x| ptr::eq(x, owner))) {
+            return Value::Typed(owner.enum_underlying_type());
+        }
+        Value::Typed(owner.full_name())
+    }
+
+    fn local_type(&self, symbol: &Symbol) -> Option<String> {
+        if let Some(x) = self.locals.iter().find(|x| x.0 == symbol.node) {
+            return Some(x.1.clone());
+        }
+        let node = &symbol.node;
+        let parent = node.parent()?;
+        let syntax = match symbol.kind {
+            SemanticFlags::FixedParameterDeclaration | SemanticFlags::ParameterArrayDeclaration
+            | SemanticFlags::ExplicitParameterDeclaration => Parameter::cast(node.clone())?.parameter_type(),
+            SemanticFlags::CatchExceptionParameterDeclaration => node.child_nodes().into_iter().find_map(TypeSyntax::cast),
+            _ if parent.kind() == SyntaxKind::DeclarationPattern => Pattern::cast(parent)?.pattern_type(),
+            _ if parent.kind() == SyntaxKind::DeclarationExpression =>
+                parent.child_nodes().into_iter().find_map(TypeSyntax::cast),
+            _ if parent.kind() == SyntaxKind::VariableDeclaration => VariableDeclaration::cast(parent)?.variable_type(),
+            _ => None,
+        }?;
+        // var 是上下文关键字, 有叫 var 的类型时才是类型名
+        if syntax.text() == "var" && self.user_type("var").is_none() {
+            return None;
+        }
+        Some(self.syntax_type(&syntax))
+    }
+
+    fn member_access(&mut self, node: &SyntaxRef) -> Value {
+        // A.B.C 可能是一个类型名
+        let context = LookupContext::at(node);
+        if let Ok(NameTarget::Type(_, symbol)) = self.declarations.resolve_name(&node.text(), &context) {
+            return Value::Type(symbol.full_name());
+        }
+        let children = node.child_nodes();
+        let (receiver, name) = match (children.first(), children.last()) {
+            (Some(receiver), Some(name)) if children.len() == 2 => (receiver.clone(), name.token_text()),
+            _ => return Value::Unknown,
+        };
+        let name = generic_name(&name).trim().to_string();
+        let (owner, found, qualifier) = match self.value(&receiver) {
+            Value::Type(x) => match self.user_type(&x) {
+                Some(symbol) => (symbol, self.static_member(node, symbol, &name), None),
+                None => return Value::Unknown,
+            },
+            Value::Typed(x) => match self.user_type(&x) {
+                Some(symbol) if !x.ends_with("[]") => {
+                    let qualifier = Some(symbol).filter(|_| receiver.kind() != SyntaxKind::BaseExpression);
+                    (symbol, self.instance_member(symbol, &name), qualifier)
+                }
+                _ if x.starts_with('(') => return self.tuple_member(node, &x, &name),
+                _ => {
+                    if self.is_primitive(&x) && !PRIMITIVE_MEMBERS.contains(&name.as_str()) {
+                        self.error(node, "DY0505", &[&x, &name]);
+                    }
+                    return self.builtin_member(&BUILTIN_PROPERTIES, &x, &name);
+                }
+            },
+            Value::Unknown => return Value::Unknown,
+        };
+        match found {
+            Some(value) => {
+                if let Some(member) = self.members(owner, &name).into_iter().find(|x| x.kind != SyntaxKind::MethodDeclaration) {
+                    self.check_access(node, member, qualifier);
+                }
+                value
+            }
+            None if self.has_unknown_base(owner) => Value::Unknown,
+            None => {
+                self.error(node, "DY0505", &[&owner.full_name(), &name]);
+                Value::Unknown
+            }
+        }
+    }
+
+    // None 表示确定没有这个成员
+    fn static_member(&self, node: &SyntaxRef, symbol: &'a TypeSymbol, name: &str) -> Option<Value> {
+        if symbol.kind == SyntaxKind::EnumDeclaration {
+            let is_member = TypeDeclaration::cast(symbol.declarations[0].clone()).is_some_and(|x| {
+                x.enum_members().iter().any(|x| x.identifier().is_some_and(|x| x.token_text() == name))
+            });
+            return if is_member { Some(self.enum_member_type(node, symbol)) } else { None };
+        }
+        let full_name = symbol.full_name();
+        if let Some(nested) = self.declarations.types.iter()
+            .find(|x| x.name == name && x.containing_type.as_ref() == Some(&full_name)) {
+            return Some(Value::Type(nested.full_name()));
+        }
+        self.instance_member(symbol, name)
+    }
+
+    fn instance_member(&self, symbol: &'a TypeSymbol, name: &str) -> Option<Value> {
+        let members = self.members(symbol, name);
+        match members.iter().find(|x| x.kind != SyntaxKind::MethodDeclaration) {
+            Some(member) => Some(self.member_type(member)),
+            None if members.is_empty() => None,
+            None => Some(Value::Unknown),
+        }
+    }
+
+    // 元组的元素按名字或者 Item1, Item2 访问
+    fn tuple_member(&mut self, node: &SyntaxRef, tuple: &str, name: &str) -> Value {
+        let elements = tuple_elements(tuple);
+        let position = name.strip_prefix("Item").and_then(|x| x.parse::<usize>().ok())
+            .filter(|&x| x > 0).map(|x| x - 1);
+        let element = elements.iter().find(|x| x.1.as_deref() == Some(name))
+            .or_else(|| position.and_then(|i| elements.get(i)));
+        match element {
+            Some(element) => Value::Typed(element.0.clone()),
+            None => {
+                if !PRIMITIVE_MEMBERS.contains(&name) {
+                    self.error(node, "DY0505", &[tuple, name]);
+                }
+                Value::Unknown
+            }
+        }
+    }
+
+    // var (a, b) = .., (var a, int b) = .. 和 foreach (var (a, b) in ..) 里 var 声明的变量按元组元素的类型
+    fn deconstruct(&mut self, target: &SyntaxRef, value_type: &str, is_var: bool) {
+        match target.kind() {
+            SyntaxKind::SingleVariableDesignation if is_var =>
+                self.locals.push((target.clone(), value_type.to_string())),
+            SyntaxKind::DeclarationExpression => {
+                let is_var = target.child_nodes().into_iter().find_map(TypeSyntax::cast)
+                    .is_some_and(|x| x.text() == "var" && self.user_type("var").is_none());
+                if let Some(designation) = target.child_nodes().pop() {
+                    self.deconstruct(&designation, value_type, is_var);
+                }
+            }
+            SyntaxKind::ParenthesizedVariableDesignation | SyntaxKind::TupleExpression => {
+                let elements = tuple_elements(value_type);
+                let parts: Vec<SyntaxRef> = target.child_nodes().into_iter()
+                    .filter_map(|x| if x.kind() == SyntaxKind::Argument { expressions(&x).pop() } else { Some(x) })
+                    .collect();
+                if parts.len() == elements.len() {
+                    for (part, element) in parts.iter().zip(elements) {
+                        self.deconstruct(part, &element.0, is_var);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 成员只有 PRIMITIVE_MEMBERS 的类型: 数值, bool 和 char
+    fn is_primitive(&self, type_name: &str) -> bool {
+        is_numeric(type_name) || type_name == "bool" || type_name == "char"
+    }
+
+    fn builtin_member(&self, table: &[(&str, &str, &str)], receiver: &str, name: &str) -> Value {
+        let matches = |x: &str| x.is_empty() || (x == "[]" && receiver.ends_with("[]")) || x == generic_name(receiver);
+        table.iter().find(|x| matches(x.0) && x.1 == name).map_or(Value::Unknown, |x| Value::Typed(x.2.to_string()))
+    }
+
+    fn invocation(&mut self, node: &SyntaxRef) -> Value {
+        let value = self.call(node);
+        self.check_lambda_arguments(node);
+        value
+    }
+
+    // lambda 实参按重载决议选中的形参类型检查, 没有选中的按没有目标类型检查
+    fn check_lambda_arguments(&mut self, node: &SyntaxRef) {
+        let arguments = node.child_of(SyntaxKind::ArgumentList).map_or(Vec::new(), |x| x.child_nodes());
+        for lambda in arguments.iter().filter_map(|x| expressions(x).pop()).filter(is_lambda) {
+            let target = self.targets.iter().position(|x| x.0 == lambda).map(|i| self.targets.remove(i).1);
+            let value = self.lambda(&lambda, target.as_deref());
+            if let Value::Typed(x) = value {
+                self.result.types.push((lambda, x));
+            }
+        }
+    }
+
+    fn call(&mut self, node: &SyntaxRef) -> Value {
+        let invocation = InvocationExpression::cast(node.clone()).unwrap();
+        let callee = match invocation.callee() {
+            Some(x) => x.syntax().clone(),
+            None => return Value::Unknown,
+        };
+        if callee.token_text() == "nameof" {
+            return Value::Typed("string".to_string());
+        }
+        let mut arguments = self.call_arguments(&invocation.arguments());
+        let count = arguments.len();
+        let context = LookupContext::at(node);
+        let mut qualifier = None;
+        let (name, candidates) = match callee.kind() {
+            SyntaxKind::IdentifierName | SyntaxKind::GenericName => {
+                let name = callee.identifier().map(|x| x.token_text()).unwrap_or_default();
+                if let Some(symbol) = self.scopes.lookup(&name, callee.span().start).first() {
+                    if symbol.is_local() {
+                        return self.local_type(symbol).map_or(Value::Unknown, |x| self.delegate_call(node, &x));
+                    }
+                }
+                let mut candidates: Vec<MemberSymbol> = Vec::new();
+                let mut outer = self.enclosing_type(node);
+                while let (Some(symbol), true) = (outer, candidates.is_empty()) {
+                    candidates = self.members(symbol, &name).into_iter()
+                        .filter(|x| x.kind == SyntaxKind::MethodDeclaration).cloned().collect();
+                    outer = symbol.declarations.first().and_then(|x| self.enclosing_type(x));
+                }
+                if candidates.is_empty() {
+                    candidates = self.declarations.lookup_imported(&name, &context).unwrap_or_default().into_iter()
+                        .map(|x| x.1.clone()).filter(|x| x.kind == SyntaxKind::MethodDeclaration).collect();
+                }
+                if candidates.is_empty() {
+                    if let Some(function) = BUILTIN_FUNCTIONS.iter().find(|x| x.0 == name) {
+                        return Value::Typed(function.1.to_string());
+                    }
+                    if self.is_undeclared(&callee, &name) {
+                        self.error(&callee, "DY0553", &[&name]);
+                    }
+                }
+                (name, candidates)
+            }
+            SyntaxKind::MemberAccessExpression => {
+                let children = callee.child_nodes();
+                let name = children.last().and_then(|x| x.identifier()).map(|x| x.token_text()).unwrap_or_default();
+                match self.value(&children[0]) {
+                    Value::Type(x) => match self.user_type(&x) {
+                        Some(symbol) => {
+                            let candidates: Vec<MemberSymbol> = self.members(symbol, &name).into_iter()
+                                .filter(|x| x.kind == SyntaxKind::MethodDeclaration).cloned().collect();
+                            if candidates.is_empty() && !self.has_unknown_base(symbol) {
+                                self.error(&callee, "DY0505", &[&x, &name]);
+                            }
+                            (name, candidates)
+                        }
+                        None => return Value::Unknown,
+                    },
+                    Value::Typed(x) => {
+                        let is_base = children[0].kind() == SyntaxKind::BaseExpression;
+                        qualifier = self.user_type(&x).filter(|_| !x.ends_with("[]") && !is_base);
+                        match self.declarations.lookup_method(&x, &name, count, &context) {
+                            Ok(MethodLookup::Instance(methods)) => (name, methods),
+                            Ok(MethodLookup::Extension(methods)) => {
+                                // 扩展方法的接收者是第一个实参
+                                arguments.insert(0, CallArgument::new(&children[0], Some(x.clone())));
+                                (name, methods.into_iter().map(|x| x.1).collect())
+                            }
+                            Err(_) => {
+                                let symbol = self.user_type(&x).filter(|_| !x.ends_with("[]"));
+                                let has_method = symbol.is_some_and(|symbol| {
+                                    self.members(symbol, &name).iter().any(|x| x.kind == SyntaxKind::MethodDeclaration)
+                                });
+                                if has_method {
+                                    let methods = self.members(symbol.unwrap(), &name).into_iter().cloned().collect();
+                                    (name, methods)
+                                }
+                                else {
+                                    // 宿主可能给数值和 bool 提供扩展方法
+                                    let is_primitive_missing = self.is_primitive(&x)
+                                        && !PRIMITIVE_MEMBERS.contains(&name.as_str())
+                                        && !self.declarations.has_host_names();
+                                    if symbol.is_some_and(|x| !self.has_unknown_base(x)) || is_primitive_missing {
+                                        self.error(&callee, "DY0506", &[&x, &name, &name, &x]);
+                                    }
+                                    return self.builtin_member(&BUILTIN_METHODS, &x, &name);
+                                }
+                            }
+                        }
+                    }
+                    Value::Unknown => return Value::Unknown,
+                }
+            }
+            _ => {
+                let callee_type = self.expr(&callee);
+                return callee_type.map_or(Value::Unknown, |x| self.delegate_call(node, &x));
+            }
+        };
+        if candidates.is_empty() {
+            return Value::Unknown;
+        }
+        let type_arguments = self.explicit_type_arguments(&callee);
+        let candidates: Vec<&MemberSymbol> = candidates.iter().collect();
+        if self.check_inaccessible(&callee, &candidates, &arguments, &type_arguments, qualifier) {
+            return Value::Unknown;
+        }
+        // 不能访问的重载不参加重载决议
+        let candidates: Vec<&MemberSymbol> = candidates.into_iter().filter(|x| self.access_error(&callee, x, qualifier).is_none()).collect();
+        let resolution = resolve(self, &candidates, &arguments, &type_arguments);
+        self.call_result(node, MessageArgument::phrase("method-named", &[&name]), resolution, &arguments, count)
+    }
+
+    fn call_arguments(&mut self, arguments: &[Argument]) -> Vec<CallArgument> {
+        let mut result = Vec::new();
+        for argument in arguments {
+            let value = match argument.expression() {
+                Some(x) => x.syntax().clone(),
+                None => continue,
+            };
+            // lambda 等重载决议定下委托类型之后再检查
+            let type_name = if is_lambda(&value) { None } else { self.expr(&value) };
+            result.push(CallArgument { name: argument.name(), ref_kind: argument.ref_kind().unwrap_or_default(),
+                ..CallArgument::new(&value, type_name) });
+        }
+        result
+    }
+
+    // F<int>(..) 的类型实参
+    fn explicit_type_arguments(&self, callee: &SyntaxRef) -> Vec<String> {
+        let name = match callee.kind() {
+            SyntaxKind::MemberAccessExpression => callee.child_nodes().pop(),
+            _ => Some(callee.clone()),
+        };
+        match name {
+            Some(ref name) if name.kind() == SyntaxKind::GenericName =>
+                type_arguments(&name.text()).iter().map(|x| self.normalize(x, name)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // 不能访问的重载不参加重载决议; 可以访问的都不可用而不能访问的可用, 或者全都不能访问时报错
+    fn check_inaccessible(&mut self, node: &SyntaxRef, candidates: &[&MemberSymbol], arguments: &[CallArgument],
+        type_arguments: &[String], receiver: Option<&TypeSymbol>) -> bool {
+        let (accessible, inaccessible): (Vec<&MemberSymbol>, Vec<&MemberSymbol>) = candidates.iter()
+            .partition(|x| self.access_error(node, x, receiver).is_none());
+        if inaccessible.is_empty() {
+            return false;
+        }
+        let is_applicable = |candidates: &[&MemberSymbol]| {
+            !matches!(resolve(self, candidates, arguments, type_arguments), Resolution::NoMatch(_))
+        };
+        if !accessible.is_empty() && is_applicable(&accessible) {
+            return false;
+        }
+        let member = match resolve(self, &inaccessible, arguments, type_arguments) {
+            Resolution::Resolved(x) => x.member,
+            _ if accessible.is_empty() => inaccessible[0],
+            _ => return false,
+        };
+        self.check_access(node, member, receiver);
+        true
+    }
+
+    // 重载决议的结果类型; 没有可用的或者有歧义时报错, 实参类型不全时不报歧义
+    fn call_result(&mut self, node: &SyntaxRef, target: MessageArgument, resolution: Resolution,
+        arguments: &[CallArgument], count: usize) -> Value {
+        let mismatches = match resolution {
+            Resolution::Resolved(x) => {
+                for (argument, parameter_type) in arguments.iter().zip(&x.parameter_types) {
+                    let (value, parameter_type) = match (argument.node.as_ref(), parameter_type.as_ref()) {
+                        (Some(value), Some(parameter_type)) => (value, parameter_type),
+                        _ => continue,
+                    };
+                    if is_lambda(value) {
+                        self.targets.push((value.clone(), parameter_type.clone()));
+                    }
+                    // out var x 的类型是形参的类型
+                    if value.kind() == SyntaxKind::DeclarationExpression {
+                        let is_var = value.child_nodes().into_iter().find_map(TypeSyntax::cast)
+                            .is_some_and(|x| x.text() == "var" && self.user_type("var").is_none());
+                        if let (true, Some(variable)) = (is_var, value.child_of(SyntaxKind::VariableDeclarator)) {
+                            self.locals.push((variable, parameter_type.clone()));
+                        }
+                    }
+                }
+                return x.return_type(self).map_or(Value::Unknown, Value::Typed);
+            }
+            Resolution::Ambiguous(candidates) => {
+                if arguments.iter().all(|x| x.type_name.is_some()) {
+                    self.error_with(node, "DY0518", vec![candidate_list(candidates.iter().map(|x| x.member))]);
+                    return Value::Unknown;
+                }
+                let results: Vec<Option<String>> = candidates.iter().map(|x| x.return_type(self)).collect();
+                return match results[0] {
+                    Some(ref x) if results.iter().all(|y| y.as_ref() == Some(x)) => Value::Typed(x.clone()),
+                    _ => Value::Unknown,
+                };
+            }
+            Resolution::NoMatch(mismatches) => mismatches,
+        };
+        let shaped: Vec<&(&MemberSymbol, Mismatch)> = mismatches.iter().filter(|x| x.1 != Mismatch::Count).collect();
+        if shaped.is_empty() {
+            self.error_with(node, "DY0519", vec![target, MessageArgument::from(count.to_string())]);
+        }
+        else if shaped.len() > 1 {
+            let types: Vec<&str> = arguments.iter().map(|x| x.type_name.as_ref().map_or("?", |x| x.as_str())).collect();
+            let candidates = candidate_list(shaped.iter().map(|x| x.0));
+            self.error_with(node, "DY0520", vec![target, MessageArgument::from(types.join(", ")), candidates]);
+        }
+        else {
+            let (member, ref mismatch) = *shaped[0];
+            let at = |i: usize| arguments[i].node.clone().unwrap_or_else(|| node.clone());
+            match *mismatch {
+                Mismatch::Conversion(i, ref from, ref to) =>
+                    self.error(&at(i), "DY0521", &[&(i + 1).to_string(), from, to]),
+                Mismatch::RefKind(i, ref expected) if expected.is_empty() =>
+                    self.error(&at(i), "DY0522", &[&(i + 1).to_string(), &arguments[i].ref_kind]),
+                Mismatch::RefKind(i, ref expected) => self.error(&at(i), "DY0523", &[&(i + 1).to_string(), expected]),
+                Mismatch::UnknownName(ref name) => self.error(node, "DY0524", &[&member.name, name]),
+                Mismatch::DuplicateName(ref name) => self.error(node, "DY0525", &[name]),
+                Mismatch::Missing(ref name) => self.error(node, "DY0554", &[name, &signature_text(member)]),
+                Mismatch::Inference(_) => self.error(node, "DY0526", &[&signature_text(member)]),
+                Mismatch::TypeArgumentCount(_) =>
+                    self.error(node, "DY0527", &[&signature_text(member), &member.type_parameters.len().to_string()]),
+                Mismatch::Count => {}
+            }
+        }
+        Value::Unknown
+    }
+
+    // Func<int, bool> 的调用结果是 bool, Action 是 void; lambda 实参的类型是委托的参数类型
+    fn delegate_call(&mut self, node: &SyntaxRef, delegate: &str) -> Value {
+        let (inputs, returns) = match self.delegate_signature(delegate) {
+            Some(x) => x,
+            None => return Value::Unknown,
+        };
+        let arguments = node.child_of(SyntaxKind::ArgumentList).map_or(Vec::new(), |x| x.child_nodes());
+        for (argument, input) in arguments.iter().zip(inputs) {
+            if let Some(lambda) = expressions(argument).pop().filter(is_lambda) {
+                self.targets.push((lambda, input));
+            }
+        }
+        Value::Typed(returns)
+    }
+
+    fn element_access(&mut self, node: &SyntaxRef) -> Value {
+        let receiver = match node.child_nodes().first() {
+            Some(x) => x.clone(),
+            None => return Value::Unknown,
+        };
+        let receiver_type = self.expr(&receiver);
+        let indexes: Vec<(SyntaxRef, Option<String>)> = node.child_of(SyntaxKind::BracketedArgumentList)
+            .map_or(Vec::new(), |x| x.child_nodes()).iter()
+            .filter_map(|x| expressions(x).pop())
+            .map(|x| {
+                let index_type = self.expr(&x);
+                (x, index_type)
+            })
+            .collect();
+        let receiver_type = match receiver_type {
+            Some(x) => x,
+            None => return Value::Unknown,
+        };
+        if let Some(element) = receiver_type.strip_suffix("[]") {
+            for (index, index_type) in &indexes {
+                if let Some(index_type) = index_type {
+                    if !["int", "uint", "long", "ulong"].iter().any(|x| self.convertible(Some(index), index_type, x) == Convertible::Implicit) {
+                        self.check_conversion(index, index_type, "int");
+                    }
+                }
+            }
+            return Value::Typed(element.to_string());
+        }
+        if receiver_type == "string" {
+            return Value::Typed("char".to_string());
+        }
+        let arguments = type_arguments(&receiver_type);
+        match generic_name(&receiver_type) {
+            "List" | "IList" | "IReadOnlyList" if arguments.len() == 1 => return Value::Typed(arguments[0].clone()),
+            "Dictionary" | "IDictionary" if arguments.len() == 2 => return Value::Typed(arguments[1].clone()),
+            _ => {}
+        }
+        match self.user_type(&receiver_type) {
+            Some(symbol) => match self.members(symbol, "this") {
+                ref indexers if !indexers.is_empty() => {
+                    let arguments: Vec<CallArgument> = indexes.iter().map(|x| CallArgument::new(&x.0, x.1.clone())).collect();
+                    let resolution = resolve(self, indexers, &arguments, &[]);
+                    let target = MessageArgument::phrase("indexer-of", &[&symbol.full_name()]);
+                    self.call_result(node, target, resolution, &arguments, arguments.len())
+                }
+                _ if self.has_unknown_base(symbol) => Value::Unknown,
+                _ => {
+                    self.error(node, "DY0528", &[&receiver_type]);
+                    Value::Unknown
+                }
+            },
+            None if is_predefined(&receiver_type) => {
+                self.error(node, "DY0528", &[&receiver_type]);
+                Value::Unknown
+            }
+            None => Value::Unknown,
+        }
+    }
+
+    fn object_creation(&mut self, node: &SyntaxRef) -> Value {
+        let created = node.child_nodes().into_iter().find_map(TypeSyntax::cast).map(|x| self.syntax_type(&x));
+        let arguments = node.child_of(SyntaxKind::ArgumentList)
+            .map_or(Vec::new(), |x| x.child_nodes().into_iter().filter_map(Argument::cast).collect::<Vec<_>>());
+        let arguments = self.call_arguments(&arguments);
+        if let Some(symbol) = created.as_ref().and_then(|x| self.user_type(x)) {
+            let constructors: Vec<&MemberSymbol> = symbol.members.iter()
+                .filter(|x| x.kind == SyntaxKind::ConstructorDeclaration && !x.is_static()).collect();
+            // 没有声明构造函数的类和所有结构体都有无参构造函数
+            let is_default = arguments.is_empty() && symbol.kind == SyntaxKind::StructDeclaration;
+            let target = MessageArgument::phrase("constructor-named", &[&symbol.full_name()]);
+            if !constructors.is_empty() && !is_default && !self.check_inaccessible(node, &constructors, &arguments, &[], None) {
+                let constructors: Vec<&MemberSymbol> = constructors.into_iter().filter(|x| self.access_error(node, x, None).is_none()).collect();
+                let resolution = resolve(self, &constructors, &arguments, &[]);
+                self.call_result(node, target, resolution, &arguments, arguments.len());
+            }
+            else if constructors.is_empty() && !arguments.is_empty() && symbol.kind != SyntaxKind::InterfaceDeclaration {
+                self.error_with(node, "DY0519", vec![target, MessageArgument::from(arguments.len().to_string())]);
+            }
+        }
+        // 初始化器里的成员名属于新建的对象, 只检查右边的值
+        for initializer in node.child_nodes().iter().filter(|x| TypeSyntax::cast((*x).clone()).is_none()
+            && x.kind() != SyntaxKind::ArgumentList) {
+            for element in expressions(initializer) {
+                match element.kind() {
+                    SyntaxKind::AssignmentExpression => {
+                        if let Some(value) = expressions(&element).pop() {
+                            self.expr(&value);
+                        }
+                    }
+                    _ => {
+                        self.expr(&element);
+                    }
+                }
+            }
+        }
+        self.check_lambda_arguments(node);
+        created.map_or(Value::Unknown, Value::Typed)
+    }
+
+    fn array_creation(&mut self, node: &SyntaxRef) -> Value {
+        let syntax = match node.child_nodes().into_iter().find_map(TypeSyntax::cast) {
+            Some(x) => x,
+            None => return Value::Unknown,
+        };
+        // new int[n] 的类型是 int[], 大小表达式要是整数
+        for size in syntax.syntax().traverse(true, false).filter(|x| x.kind() == SyntaxKind::ArrayRankSpecifier)
+            .flat_map(|x| expressions(&x)) {
+            self.check_condition_type(&size, "int");
+        }
+        let mut text = String::new();
+        let mut depth = 0;
+        for c in syntax.text().chars() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ if depth > 0 && c != ',' => continue,
+                _ => {}
+            }
+            text.push(c);
+        }
+        let array_type = self.normalize(&text, syntax.syntax());
+        let element = array_type.strip_suffix("[]").unwrap_or(&array_type).to_string();
+        if let Some(initializer) = node.child_of(SyntaxKind::InitializerExpression) {
+            for value in expressions(&initializer) {
+                if let Some(value_type) = self.expr(&value) {
+                    self.check_conversion(&value, &value_type, &element);
+                }
+            }
+        }
+        Value::Typed(array_type)
+    }
+
+    fn check_condition_type(&mut self, node: &SyntaxRef, expected: &str) {
+        if let Some(value_type) = self.expr(node) {
+            self.check_conversion(node, &value_type, expected);
+        }
+    }
+
+    fn cast(&mut self, node: &SyntaxRef) -> Value {
+        let children = node.child_nodes();
+        let (target, operand) = match (children.first().cloned().and_then(TypeSyntax::cast), children.last()) {
+            (Some(target), Some(operand)) if children.len() == 2 => (self.syntax_type(&target), operand.clone()),
+            _ => return Value::Unknown,
+        };
+        if let Some(operand_type) = self.expr(&operand) {
+            if self.convertible(Some(&operand), &operand_type, &target) == Convertible::No {
+                self.error(node, "DY0504", &[&operand_type, &target]);
+            }
+        }
+        Value::Typed(target)
+    }
+
+    fn binary(&mut self, node: &SyntaxRef, op: &str, left: &str, right: &str) -> Value {
+        let error = |checker: &mut Checker, node: &SyntaxRef| {
+            checker.error(node, "DY0529", &[op, left, right]);
+            Value::Unknown
+        };
+        if op == "??" {
+            if let Some(result) = coalesce_result(left, right, self.declarations) {
+                return Value::Typed(result);
+            }
+            if is_predefined(left) && is_predefined(right) {
+                return error(self, node);
+            }
+            return if self.convertible(None, right, left) == Convertible::Implicit { Value::Typed(left.to_string()) } else { Value::Unknown };
+        }
+        if op == "+" && (left == "string" || right == "string") && left != "void" && right != "void" {
+            return Value::Typed("string".to_string());
+        }
+        let is_reference = |x: &str| !is_value_type(x, self.declarations);
+        if (op == "==" || op == "!=") && is_reference(left) && is_reference(right) {
+            return Value::Typed("bool".to_string());
+        }
+        let is_enum = |x: &str| self.user_type(x).is_some_and(|x| x.kind == SyntaxKind::EnumDeclaration);
+        if is_enum(left) || is_enum(right) {
+            let result = match op {
+                _ if COMPARISON_OPERATORS.contains(&op) && left == right => Some("bool".to_string()),
+                "&" | "|" | "^" if left == right => Some(left.to_string()),
+                "-" if left == right => Some("int".to_string()),
+                "+" | "-" if is_enum(left) && is_integral(right) => Some(left.to_string()),
+                "+" if is_enum(right) && is_integral(left) => Some(right.to_string()),
+                _ => None,
+            };
+            return match result {
+                Some(x) => Value::Typed(x),
+                None => error(self, node),
+            };
+        }
+        if is_predefined(left) && is_predefined(right) {
+            return match lifted_binary(op, left, right, self.declarations) {
+                Some(x) => Value::Typed(x),
+                None => error(self, node),
+            };
+        }
+        // 用户定义的运算符
+        if let Some(result) = self.user_operator(node, op, &[left, right]) {
+            return result;
+        }
+        let is_user = |x: &str| self.user_type(x).is_some_and(|x| !self.has_unknown_base(x));
+        if (is_user(left) || is_predefined(left)) && (is_user(right) || is_predefined(right)) {
+            return error(self, node);
+        }
+        if COMPARISON_OPERATORS.contains(&op) {
+            return Value::Typed("bool".to_string());
+        }
+        Value::Unknown
+    }
+
+    // 用户定义的运算符, 操作数类型里声明的都是候选; None 表示没有可用的
+    fn user_operator(&mut self, node: &SyntaxRef, op: &str, operands: &[&str]) -> Option<Value> {
+        let operator = format!("operator {}", op);
+        let mut candidates: Vec<&MemberSymbol> = Vec::new();
+        for operand in operands {
+            let symbol = match self.user_type(underlying_type(operand, self.declarations).unwrap_or(operand)) {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+            for member in self.members(symbol, &operator) {
+                if !candidates.iter().any(|x| x.declaration == member.declaration) {
+                    candidates.push(member);
+                }
+            }
+        }
+        let arguments: Vec<CallArgument> = operands.iter()
+            .map(|x| CallArgument { node: None, name: None, ref_kind: String::new(), type_name: Some(x.to_string()) })
+            .collect();
+        match resolve(self, &candidates, &arguments, &[]) {
+            Resolution::Resolved(x) => Some(x.return_type(self).map_or(Value::Unknown, Value::Typed)),
+            Resolution::Ambiguous(_) => {
+                let types: Vec<String> = operands.iter().map(|x| format!("'{}'", x)).collect();
+                let code = if types.len() == 1 { "DY0530" } else { "DY0531" };
+                self.error_with(node, code, vec![MessageArgument::from(op), and_list(types)]);
+                Some(Value::Unknown)
+            }
+            Resolution::NoMatch(_) => None,
+        }
+    }
+
+    fn unary(&mut self, node: &SyntaxRef) -> Value {
+        let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+        let operand = match expressions(node).pop() {
+            Some(x) => x,
+            None => return Value::Unknown,
+        };
+        let operand_type = match self.expr(&operand) {
+            Some(x) => x,
+            None => return Value::Unknown,
+        };
+        if node.kind() == SyntaxKind::PostfixUnaryExpression && op == "!" {
+            return Value::Typed(operand_type);
+        }
+        let inner = underlying_type(&operand_type, self.declarations).unwrap_or(&operand_type).to_string();
+        let is_lifted = inner != operand_type;
+        if op == "++" || op == "--" {
+            if !is_assignable(&operand) {
+                self.error(&operand, "DY0532", &[]);
+            }
+            if is_numeric(&inner) || !is_predefined(&inner) {
+                return Value::Typed(operand_type);
+            }
+        }
+        else if let Some(result) = unary_result(&op, &inner) {
+            return Value::Typed(if is_lifted { make_nullable(result, self.declarations) } else { result.to_string() });
+        }
+        let is_enum = self.user_type(&inner).is_some_and(|x| x.kind == SyntaxKind::EnumDeclaration);
+        if is_enum && op == "~" {
+            return Value::Typed(operand_type);
+        }
+        if let Some(result) = self.user_operator(node, &op, &[&operand_type]) {
+            return result;
+        }
+        if is_predefined(&inner) || self.user_type(&inner).is_some_and(|x| !self.has_unknown_base(x)) {
+            self.error(node, "DY0533", &[&op, &operand_type]);
+        }
+        Value::Unknown
+    }
+
+    fn assignment(&mut self, node: &SyntaxRef, left: &SyntaxRef, right: &SyntaxRef) -> Value {
+        let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+        if !is_assignable(left) {
+            self.error(left, "DY0534", &[]);
+        }
+        let target = self.expr(left);
+        let value = match op.as_str() {
+            "=" => self.target_expr(right, target.as_deref()),
+            _ => self.expr(right),
+        };
+        if let (true, Some(value)) = (op == "=", value.as_ref()) {
+            self.deconstruct(left, value, false);
+        }
+        let (target, value) = match (target, value) {
+            (Some(target), Some(value)) => (target, value),
+            (target, _) => return target.map_or(Value::Unknown, Value::Typed),
+        };
+        if op == "=" {
+            self.check_conversion(right, &value, &target);
+        }
+        else if op != "??=" && self.is_known(&target) {
+            // x op= y 要求 x op y 能转换回 x, 数值运算允许显式转换, 比如 byte b += 1
+            let operator = op.trim_end_matches('=');
+            if let Value::Typed(result) = self.binary(node, operator, &target, &value) {
+                let back = self.convertible(None, &result, &target);
+                let is_explicit_ok = back == Convertible::Explicit
+                    && self.convertible(Some(right), &value, &target) == Convertible::Implicit;
+                if back != Convertible::Implicit && !is_explicit_ok {
+                    self.check_conversion(node, &result, &target);
+                }
+            }
+        }
+        Value::Typed(target)
+    }
+
+    // ------------------------------------ lambdas -------------------------------------------
+
+    // 有目标类型的表达式: lambda 的参数类型和返回类型从目标委托类型来
+    fn target_expr(&mut self, node: &SyntaxRef, target: Option<&str>) -> Option<String> {
+        if !is_lambda(node) || target.is_none() {
+            return self.expr(node);
+        }
+        match self.lambda(node, target) {
+            Value::Typed(x) => {
+                self.result.types.push((node.clone(), x.clone()));
+                Some(x)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_explicit_lambda(&self, lambda: &SyntaxRef) -> bool {
+        lambda_parameters(lambda).iter().all(|x| x.declaration() != Some(SemanticFlags::ImplicitParameterDeclaration))
+    }
+
+    fn bind_lambda_parameters(&mut self, lambda: &SyntaxRef, parameter_types: &[String]) {
+        for (parameter, type_name) in lambda_parameters(lambda).into_iter().zip(parameter_types) {
+            if parameter.declaration() == Some(SemanticFlags::ImplicitParameterDeclaration) {
+                self.locals.push((parameter, type_name.clone()));
+            }
+        }
+    }
+
+    fn lambda(&mut self, node: &SyntaxRef, target: Option<&str>) -> Value {
+        let unchecked = || Function { name: function_kind(node), returns: None, returned: None };
+        match target.map(|x| (x, self.delegate_signature(x))) {
+            Some((target, Some((inputs, output)))) => {
+                let parameters = lambda_parameters(node);
+                let has_list = node.kind() == SyntaxKind::LambdaExpression || node.child_of(SyntaxKind::ParameterList).is_some();
+                if has_list && parameters.len() != inputs.len() {
+                    self.error(node, "DY0535", &[target, &parameters.len().to_string()]);
+                    self.lambda_body(node, unchecked());
+                    return Value::Unknown;
+                }
+                self.bind_lambda_parameters(node, &inputs);
+                let returns = Some(output).filter(|_| !is_async(node));
+                self.lambda_body(node, Function { name: function_kind(node), returns, returned: None });
+                Value::Typed(target.to_string())
+            }
+            Some((target, None)) if is_predefined(target) && target != "object" => {
+                self.error_with(node, "DY0536", vec![function_kind(node), MessageArgument::from(target)]);
+                self.lambda_body(node, unchecked());
+                Value::Unknown
+            }
+            _ => {
+                // 没有目标类型: 参数都写了类型时推断成 Func 或 Action
+                let returned = self.lambda_body(node, Function { name: function_kind(node), returns: None, returned: Some(Vec::new()) });
+                if !self.is_explicit_lambda(node) {
+                    return Value::Unknown;
+                }
+                let inputs = lambda_parameters(node).into_iter()
+                    .map(|x| Parameter::cast(x).and_then(|x| x.parameter_type()).map(|x| self.syntax_type(&x)))
+                    .collect::<Option<Vec<String>>>();
+                match (inputs, self.inferred_return(node, returned)) {
+                    (Some(inputs), Some(ref returns)) if returns == "void" && inputs.is_empty() => Value::Typed("Action".to_string()),
+                    (Some(inputs), Some(ref returns)) if returns == "void" =>
+                        Value::Typed(format!("Action<{}>", inputs.join(", "))),
+                    (Some(mut inputs), Some(returns)) => {
+                        inputs.push(returns);
+                        Value::Typed(format!("Func<{}>", inputs.join(", ")))
+                    }
+                    _ => Value::Unknown,
+                }
+            }
+        }
+    }
+
+    // 检查 lambda 的参数和函数体, 返回记下的返回值类型
+    fn lambda_body(&mut self, node: &SyntaxRef, function: Function) -> Vec<Option<String>> {
+        self.functions.push(function);
+        for child in node.child_nodes() {
+            match child.kind() {
+                SyntaxKind::Block | SyntaxKind::Parameter | SyntaxKind::ParameterList => self.check_node(&child),
+                _ if Expression::cast(child.clone()).is_some() => self.check_return_value(&child, false),
+                _ => {}
+            }
+        }
+        self.functions.pop().and_then(|x| x.returned).unwrap_or_default()
+    }
+
+    // 表达式体的类型或者 return 值的公共类型, 没有返回值是 void; async 的包成 Task
+    fn inferred_return(&self, node: &SyntaxRef, returned: Vec<Option<String>>) -> Option<String> {
+        let returned = returned.into_iter().collect::<Option<Vec<String>>>()?;
+        let returns = match returned.first() {
+            Some(_) => {
+                let candidates: Vec<&String> = returned.iter().filter(|x| *x != "null").collect();
+                let best = candidates.iter().find(|x| candidates.iter().all(|y| self.is_implicit(None, y, x)))?;
+                best.to_string()
+            }
+            None => "void".to_string(),
+        };
+        if !is_async(node) {
+            return Some(returns);
+        }
+        Some(if returns == "void" { "Task".to_string() } else { format!("Task<{}>", returns) })
+    }
+
+    fn conditional(&mut self, operands: &[SyntaxRef]) -> Value {
+        self.check_condition(&operands[0]);
+        let (when_true, when_false) = (self.expr(&operands[1]), self.expr(&operands[2]));
+        let (a, b) = match (when_true, when_false) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Value::Unknown,
+        };
+        if a == b {
+            return Value::Typed(a);
+        }
+        let a_to_b = self.convertible(Some(&operands[1]), &a, &b) == Convertible::Implicit;
+        let b_to_a = self.convertible(Some(&operands[2]), &b, &a) == Convertible::Implicit;
+        match (a_to_b, b_to_a) {
+            (true, false) => Value::Typed(b),
+            (_, true) => Value::Typed(if a == "null" { b } else { a }),
+            (false, false) => {
+                let node = operands[0].parent().unwrap();
+                self.error(&node, "DY0537", &[&a, &b]);
+                Value::Unknown
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+
+    fn check(code: &str) -> (SyntaxRef, TypeCheck) {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        let result = check_types(&tree.root, &declarations);
+        (tree.root, result)
+    }
+
+    fn messages(result: &TypeCheck) -> Vec<String> {
+        result.errors.iter().map(|x| x.message.clone()).collect()
+    }
+
+    // text 最后一次出现处的表达式的类型
+    fn type_at(root: &SyntaxRef, result: &TypeCheck, code: &str, text: &str) -> String {
+        let start = code.rfind(text).unwrap();
+        root.traverse(true, false)
+            .filter(|x| x.span().start == start && x.span().end == start + text.len())
+            .find_map(|x| result.type_of(&x).map(|x| x.to_string()))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let (_, result) = check(include_str!("../../examples/hello_world.dy"));
+        assert_eq!(messages(&result), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_expression_types() {
+        let code = "namespace N { enum Color { Red, Green } class Point { public int X; public Point Next { get; set; } \
+            public static Point operator +(Point a, Point b) => a; }
+            class A { const byte Three = 1 + 2; double Half(int x) => x / 2.0; void F(int[] items, Point p, string s) {
+                const int two = 2; byte t = two * 100 + Three;
+                var a = 1 + 2L; var b = 3u * 2; var c = 1.5f + 1; var d = 'a' + 1; var e = 1m;
+                var f = items[0] > 1 && s.Length == 2; var g = p.Next.X; var h = p + p; var i = Half(1);
+                var j = (byte)1; var k = s ?? \"\"; var l = Color.Red; int? m = null; var n = m + 1;
+                foreach (var element in items) { var o = element; } var q = new Point[2]; var r = a < 1 ? 1 : 2.0; } } }";
+        let (root, result) = check(code);
+        assert_eq!(messages(&result), Vec::<String>::new());
+        let types: Vec<String> = ["1 + 2L", "3u * 2", "1.5f + 1", "'a' + 1", "1m", "items[0] > 1 && s.Length == 2",
+            "p.Next.X", "p + p", "Half(1)", "(byte)1", "s ?? \"\"", "Color.Red", "m + 1", "element", "new Point[2]",
+            "a < 1 ? 1 : 2.0"].iter().map(|x| type_at(&root, &result, code, x)).collect();
+        assert_eq!(types, ["long", "uint", "float", "int", "decimal", "bool", "int", "N.Point", "double", "byte",
+            "string", "N.Color", "int?", "int", "N.Point[]", "double"]);
+    }
+
+    #[test]
+    fn test_tuples() {
+        let code = "class A { void F((int, string)[] pairs) {
+                var (p, (q, r)) = (1, (\"a\", 2.5)); (var s, long t) = (1, 2); var tup = (X: 1, Y: \"s\");
+                int pi = q; int xs = tup.Y; var y = tup.Item1 + tup.X; var z = tup.Z; var u = r * t + p;
+                foreach (var (n, name) in pairs) { var m = name.Length + n; } } }";
+        let (root, result) = check(code);
+        assert_eq!(messages(&result), [
+            "cannot implicitly convert type 'string' to 'int'",
+            "cannot implicitly convert type 'string' to 'int'",
+            "'(int X, string Y)' does not contain a definition for 'Z'",
+        ]);
+        let types: Vec<String> = ["(X: 1, Y: \"s\")", "tup.Item1 + tup.X", "r * t + p", "name.Length + n"].iter()
+            .map(|x| type_at(&root, &result, code, x)).collect();
+        assert_eq!(types, ["(int X, string Y)", "int", "double", "int"]);
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let code = "class B { } class C : B { int V; } struct S { }
+            class A { int F(int x) { return \"s\"; } void G() { return 1; } int H() { return; }
+            void K(C c, B b, long l, S s) {
+                int a = l; int b2 = \"x\"; byte c2 = 300; byte d = 1; C e = b; B f = c; S g = null; var h = null;
+                bool i = 1 + true; int j = -\"s\"; if (a) { } var k = (string)a; F(\"s\"); F(1, 2); c.W = 1;
+                a + 1; 1 = a; int? m = a; short n = 1; n += 1; n = n + 1; var o = a == 1 ? \"s\" : 1; var p = a as int;
+                foreach (var q in a) { } } }";
+        let (_, result) = check(code);
+        assert_eq!(messages(&result), [
+            "cannot implicitly convert type 'string' to 'int'",
+            "since 'G' returns void, a return keyword must not be followed by an object expression",
+            "an object of a type convertible to 'int' is required",
+            "cannot implicitly convert type 'long' to 'int'. An explicit conversion exists (are you missing a cast?)",
+            "cannot implicitly convert type 'string' to 'int'",
+            "cannot implicitly convert type 'int' to 'byte'. An explicit conversion exists (are you missing a cast?)",
+            "cannot implicitly convert type 'B' to 'C'. An explicit conversion exists (are you missing a cast?)",
+            "cannot convert null to 'S' because it is a non-nullable value type",
+            "cannot assign <null> to an implicitly-typed variable",
+            "operator '+' cannot be applied to operands of type 'int' and 'bool'",
+            "operator '-' cannot be applied to operand of type 'string'",
+            "cannot implicitly convert type 'int' to 'bool'",
+            "cannot convert type 'int' to 'string'",
+            "argument 1: cannot convert from 'string' to 'int'",
+            "no overload for method 'F' takes 2 arguments",
+            "'C' does not contain a definition for 'W'",
+            "only assignment, call, increment, decrement, await, and new object expressions can be used as a statement",
+            "the left-hand side of an assignment must be a variable, property or indexer",
+            "cannot implicitly convert type 'int' to 'short'. An explicit conversion exists (are you missing a cast?)",
+            "type of conditional expression cannot be determined because there is no implicit conversion between \
+                'string' and 'int'",
+            "the as operator must be used with a reference type or nullable type ('int' is a non-nullable value type)",
+            "foreach statement cannot operate on variables of type 'int' because 'int' does not contain a public \
+                instance definition for 'GetEnumerator'",
+        ]);
+        // 用户结构和枚举的 T? 和 int? 一样写作 T?
+        let code = "struct S { } enum E { A } class A { void F(S s) { S? a = s; S? b = null; S c = a; S d = (S)b;
+            Nullable<S> e = a; E f = (E?)null; var g = e ?? s; } }";
+        let (root, result) = check(code);
+        assert_eq!(messages(&result), [
+            "cannot implicitly convert type 'S?' to 'S'. An explicit conversion exists (are you missing a cast?)",
+            "cannot implicitly convert type 'E?' to 'E'. An explicit conversion exists (are you missing a cast?)",
+        ]);
+        assert_eq!(type_at(&root, &result, code, "e ?? s"), "S");
+        // 枚举成员的初始值里, 同一个枚举的成员是基础类型
+        let code = "enum E { X = 1, Y = X | 2, Z = E.Y << 1, W = Z + X } enum B : byte { P = 1, Q = P * 2 }
+            class A { E e = E.X | E.Y; int i = E.Y & 4; }";
+        let (root, result) = check(code);
+        assert_eq!(messages(&result), ["operator '&' cannot be applied to operands of type 'E' and 'int'"]);
+        let types: Vec<String> = ["X | 2", "E.Y << 1", "Z + X", "P * 2", "E.X | E.Y"].iter()
+            .map(|x| type_at(&root, &result, code, x)).collect();
+        assert_eq!(types, ["int", "int", "int", "int", "E"]);
+    }
+
+    #[test]
+    fn test_member_lookup() {
+        let code = "interface I { void M(); int N(int x); } class A : I { public void M() { } public int N(int x) => x;
+                void F() { this.M(); } } class P { public virtual void M() { } }
+            class Q : P { public override void M() { } }
+            class R : Q { public override void M() { } void G() { base.M(); } } class S : A { public new void M() { } }
+            class Use { void F(A a, R r, S s, I i) { a.M(); var n = a.N(1); r.M(); s.M(); i.M(); } }";
+        let (root, result) = check(code);
+        assert_eq!(messages(&result), Vec::<String>::new());
+        assert_eq!(type_at(&root, &result, code, "a.N(1)"), "int");
+    }
+
+    #[test]
+    fn test_undeclared_names() {
+        let code = "class Hero { int hp; int Hp { get { return hp; } set { hp = value; } } class Item { } Item item;
+                void Run<T>(T t, Item[] items) { int a = undefinedVar + 1; Foo x = null; Heal(); hp.NoSuchMember = 1;
+                    int b = a.CompareTo(hp) + hp.GetHashCode() + a.Fill(); List<Bar> bars = null; T c = t;
+                    foreach (var i in items) { } if (t is Item found) { } println(a);
+                    _ = Hp; }
+                Hero Clone() => new Hero(); Missing Make(Other o) => null; }";
+        let (_, result) = check(code);
+        assert_eq!(messages(&result), [
+            "the name 'undefinedVar' does not exist in the current context",
+            "the type or namespace name 'Foo' could not be found",
+            "the name 'Heal' does not exist in the current context",
+            "'int' does not contain a definition for 'NoSuchMember'",
+            "'int' does not contain a definition for 'Fill' and no accessible extension method 'Fill' accepting a \
+                first argument of type 'int' could be found",
+            "the type or namespace name 'Bar' could not be found",
+            "the type or namespace name 'Missing' could not be found",
+            "the type or namespace name 'Other' could not be found",
+        ]);
+        // 宿主命名空间里的名字都可能存在
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        declarations.declare_namespace("System");
+        assert_eq!(messages(&check_types(&tree.root, &declarations)), ["'int' does not contain a definition for \
+            'NoSuchMember'"]);
+    }
+
+    #[test]
+    fn test_overloads() {
+        let code = "class V { public static V operator -(V a) => a; public static V operator -(V a, V b) => a; \
+                public static double operator *(V a, V b) => 0; public static V operator *(V a, double k) => a; }
+            class Grid { public Grid(int size) { } public Grid(int w, int h) { } public string this[int i] => \"\";
+                public int this[string key] => 0; }
+            class Host { string Log(string s) => s; int Log(string s, object o) => 0; long Log(object o) => 0;
+                T First<T>(T[] items) => items[0]; void Pair(int a, long b) { } void Pair(long a, int b) { }
+                bool Opt(int a, bool b = false, params string[] rest) => b;
+                void F(V v, Grid g, int[] xs, string[] names) {
+                    var a = Log(\"x\"); var b = Log(1); var c = Log(\"x\", 1); var d = -v; var e = v - v; var f = v * v;
+                    var h = v * 2; var i = new Grid(1, 2); var j = g[0]; var k = g[\"a\"]; var l = First(names);
+                    var m = First<int>(xs); var n = Opt(1, rest: \"x\"); var o = Opt(1, true, \"a\", \"b\");
+                    Pair(1, 2); Log(true, 1, 2); Opt(b: true); new Grid(); new Grid(\"s\"); g[true].ToString();
+                    Opt(1, c: 2); First(1); Opt(ref a); } }";
+        let (root, result) = check(code);
+        let types: Vec<String> = ["Log(\"x\")", "Log(1)", "Log(\"x\", 1)", "-v", "v - v", "v * v", "v * 2",
+            "new Grid(1, 2)", "g[0]", "g[\"a\"]", "First(names)", "First<int>(xs)", "Opt(1, rest: \"x\")",
+            "Opt(1, true, \"a\", \"b\")"].iter().map(|x| type_at(&root, &result, code, x)).collect();
+        assert_eq!(types, ["string", "long", "int", "V", "V", "double", "V", "Grid", "string", "int", "string", "int",
+            "bool", "bool"]);
+        assert_eq!(messages(&result), [
+            "the call is ambiguous between the following methods or properties: 'Pair(int, long)' and 'Pair(long, int)'",
+            "no overload for method 'Log' takes 3 arguments",
+            "there is no argument given that corresponds to the required parameter 'a' of \
+                'Opt(int, bool, params string[])'",
+            "no overload for constructor 'Grid' takes 0 arguments",
+            "argument 1: cannot convert from 'string' to 'int'",
+            "no overload for the indexer of 'Grid' matches the argument types (bool); candidates are: 'this(int)' and \
+                'this(string)'",
+            "the best overload for 'Opt' does not have a parameter named 'c'",
+            "the type arguments for method 'First<T>(T[])' cannot be inferred from the usage; try specifying the type \
+                arguments explicitly",
+            "argument 1 may not be passed with the 'ref' keyword",
+        ]);
+    }
+
+    #[test]
+    fn test_inference() {
+        let code = "delegate bool Check(string s);
+            class Host { R[] Map<T, R>(T[] items, Func<T, R> f) => null; T Pick<T>(T a, T b) => a;
+                void Each<T>(T[] items, Action<T> f) { } bool Parse(string s, out int value) { value = 0; return true; }
+                int Run(Func<int> f) => f(); string Run(Func<string> f) => f(); bool Test(Check c) => c(\"\");
+                void Use(params object[] all) { }
+                void F(int[] xs, string[] names) {
+                    var a1 = 1.5; var b1 = names; var c1 = Map(xs, x => x.ToString()); var d1 = Map(names, s => s.Length);
+                    var e1 = Pick(1, 2L); var f1 = (int x, int y) => x * y; var g1 = () => { };
+                    Func<int, bool> h1 = x => x > 0; var i1 = Run(() => 1); var j1 = Run(() => \"s\");
+                    var k1 = Test(s => s.Length > 0); Parse(\"1\", out var l1);
+                    Each(names, n => { var o1 = n.Length; Use(o1); });
+                    Func<string, int> p1 = s => { if (s == null) { return 0; } return s.Length; };
+                    var q1 = (string s) => { return s; };
+                    Use(a1, b1, c1, d1, e1, f1, g1, h1, i1, j1, k1, l1, q1);
+                    var r = x => x; var s1; var t = 1, u = 2; var v = { 1, 2 }; Func<int, int> w = (x, y) => x;
+                    Func<int, string> z = x => x; int bad = () => 1; Pick(1, \"s\"); Map(xs, 1);
+                    Each(xs, n => n.Length); } }";
+        let (root, result) = check(code);
+        let types: Vec<String> = ["a1", "b1", "c1", "d1", "e1", "f1", "g1", "h1", "i1", "j1", "k1", "l1", "o1", "q1"].iter()
+            .map(|x| type_at(&root, &result, code, x)).collect();
+        assert_eq!(types, ["double", "string[]", "string[]", "int[]", "long", "Func<int, int, int>", "Action",
+            "Func<int, bool>", "int", "string", "bool", "int", "int", "Func<string, string>"]);
+        assert_eq!(messages(&result), [
+            "cannot assign lambda expression to an implicitly-typed variable; the types of its parameters cannot be \
+                inferred",
+            "implicitly-typed variables must be initialized",
+            "implicitly-typed variables cannot have multiple declarators",
+            "cannot initialize an implicitly-typed variable with an array initializer",
+            "delegate 'Func<int, int>' does not take 2 arguments",
+            "cannot implicitly convert type 'int' to 'string'",
+            "cannot convert lambda expression to type 'int' because it is not a delegate type",
+            "the type arguments for method 'Pick<T>(T, T)' cannot be inferred from the usage; try specifying the type \
+                arguments explicitly",
+            "the type arguments for method 'Map<T, R>(T[], Func<T, R>)' cannot be inferred from the usage; try \
+                specifying the type arguments explicitly",
+            "'int' does not contain a definition for 'Length'",
+            "only assignment, call, increment, decrement, await, and new object expressions can be used as a statement",
+        ]);
+    }
+
+    #[test]
+    fn test_accessibility() {
+        let code = "class Unit { int hp; protected int Armor; protected void Heal(int n) { } private Unit(string s) { }
+                public Unit() { } public static int Count() => 0; private static int Seed() => 0; }
+            class Hero : Unit { int Get(Hero h, Unit u) { Heal(1); h.Heal(1); u.Heal(1); base.Heal(2); return hp + Armor + u.Armor; } }
+            class Game { void Run(Unit u) { var a = u.Armor; u.Heal(1); new Unit(); new Unit(\"x\"); Unit.Count(); Unit.Seed(); } }";
+        let (_, result) = check(code);
+        assert_eq!(messages(&result), [
+            "cannot access protected member 'Unit.Heal(int)' via a qualifier of type 'Unit'; the qualifier must be of \
+                type 'Hero' (or derived from it)",
+            "'Unit.hp' is inaccessible due to its protection level",
+            "cannot access protected member 'Unit.Armor' via a qualifier of type 'Unit'; the qualifier must be of \
+                type 'Hero' (or derived from it)",
+            "'Unit.Armor' is inaccessible due to its protection level",
+            "'Unit.Heal(int)' is inaccessible due to its protection level",
+            "'Unit.Unit(string)' is inaccessible due to its protection level",
+            "'Unit.Seed()' is inaccessible due to its protection level",
+        ]);
+    }
+}
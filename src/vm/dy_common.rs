@@ -3,6 +3,7 @@ use std::cell::{RefCell, Ref, RefMut};
 use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::default::Default;
+use std::collections::VecDeque;
 
 // copy from: https://github.com/RazrFalcon/rctree/
 // DyRef 最多有一个parent节点
@@ -85,13 +86,13 @@ impl<T> DyRef<T> {
 //    }
 
 
-    pub fn borrow(&self) -> Ref<T> {
+    pub fn borrow(&self) -> Ref<'_, T> {
         // 不导入 Borrow 的时候是 RefCell 的，导入 Borrow 就是 Rc as Borrow
         // self.0.borrow()
         Ref::map(self.0.borrow(), |v| &v.data)
     }
 
-    pub fn borrow_mut(&self) -> RefMut<T> {
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
         RefMut::map(self.0.borrow_mut(), |v| &mut v.data)
     }
 
@@ -224,32 +225,41 @@ impl<T> DyRef<T> {
         assert!(*self != *sibling, "can't insert to self");
         sibling.detach();
         let mut self_node = self.0.borrow_mut();
-        let mut parent_rc_op = self_node.parent.upgrade();
-        if let Some(ref mut parent_rc) = parent_rc_op {
-            let mut parent_mut = parent_rc.borrow_mut();
-            if Rc::ptr_eq(&parent_mut.last_child.upgrade().unwrap(), &self.0) {
-                parent_mut.last_child = Rc::downgrade(&sibling.0);
+        let mut sibling_node = sibling.0.borrow_mut();
+        if let Some(ref parent_rc) = self_node.parent.upgrade() {
+            if self_node.next_sibling.is_none() {
+                parent_rc.borrow_mut().last_child = Rc::downgrade(&sibling.0);
             }
-            sibling.0.borrow_mut().parent = Rc::downgrade(parent_rc);
+            sibling_node.parent = Rc::downgrade(parent_rc);
+        }
+        // 原来的 next_sibling 接到新节点后面
+        if let Some(next_sibling_rc) = self_node.next_sibling.take() {
+            next_sibling_rc.borrow_mut().pre_sibling = Rc::downgrade(&sibling.0);
+            sibling_node.next_sibling = Some(next_sibling_rc);
         }
         self_node.next_sibling = Some(Rc::clone(&sibling.0));
-        sibling.0.borrow_mut().pre_sibling = Rc::downgrade(&self.0)
+        sibling_node.pre_sibling = Rc::downgrade(&self.0);
     }
 
     pub fn insert_before(&self, sibling: &DyRef<T>) {
         assert!(*self != *sibling, "can't insert to self");
         sibling.detach();
         let mut self_node = self.0.borrow_mut();
-        let mut parent_rc_op = self_node.parent.upgrade();
-        if let Some(ref mut parent_rc) = parent_rc_op {
-            let mut parent_mut = parent_rc.borrow_mut();
-            if Rc::ptr_eq(parent_mut.first_child.as_mut().unwrap(), &self.0) {
-                parent_mut.first_child = Some(Rc::clone(&sibling.0));
+        let mut sibling_node = sibling.0.borrow_mut();
+        let pre_sibling_rc_op = self_node.pre_sibling.upgrade();
+        if let Some(ref parent_rc) = self_node.parent.upgrade() {
+            if pre_sibling_rc_op.is_none() {
+                parent_rc.borrow_mut().first_child = Some(Rc::clone(&sibling.0));
             }
-            sibling.0.borrow_mut().parent = Rc::downgrade(parent_rc);
+            sibling_node.parent = Rc::downgrade(parent_rc);
+        }
+        // 原来的 pre_sibling 接到新节点前面
+        if let Some(ref pre_sibling_rc) = pre_sibling_rc_op {
+            pre_sibling_rc.borrow_mut().next_sibling = Some(Rc::clone(&sibling.0));
+            sibling_node.pre_sibling = Rc::downgrade(pre_sibling_rc);
         }
         self_node.pre_sibling = Rc::downgrade(&sibling.0);
-        sibling.0.borrow_mut().next_sibling = Some(Rc::clone(&self.0));
+        sibling_node.next_sibling = Some(Rc::clone(&self.0));
     }
 
     // remove from parent and reconnect sibling
@@ -257,7 +267,7 @@ impl<T> DyRef<T> {
         let mut self_node = self.0.borrow_mut();
         let mut parent_rc_op = self_node.parent.upgrade();
         self_node.parent = Weak::new();
-        let mut pre_sibling_rc_op = &mut self_node.pre_sibling.upgrade();
+        let pre_sibling_rc_op = &mut self_node.pre_sibling.upgrade();
 
         // remove frome parent
         if let Some(ref mut parent_rc) = parent_rc_op {
@@ -336,8 +346,11 @@ impl<T> DyRef<T> {
     }
 
 
-    pub fn traverse(&self, is_depth_first: bool, is_reverse: bool) {
-        Traverse::new(self.clone(), is_depth_first, is_reverse);
+    /// Returns an iterator over this node and all of its descendants.
+    ///
+    /// Depth first visits in pre-order, otherwise level by level.
+    pub fn traverse(&self, is_depth_first: bool, is_reverse: bool) -> Traverse<T> {
+        Traverse::new(self.clone(), is_depth_first, is_reverse)
     }
 
 }
@@ -399,6 +412,7 @@ pub struct Traverse<T> {
     pub is_depth_first: bool,
     root: DyRef<T>,
     cur: Option<DyRef<T>>,
+    queue: VecDeque<DyRef<T>>,
 }
 
 impl<T> Traverse<T> {
@@ -410,65 +424,49 @@ impl<T> Traverse<T> {
             is_reverse,
             root,
             cur,
+            queue: VecDeque::new(),
         };
         return traverse;
     }
 
     fn get_next(&mut self, cur_node: DyRef<T>) -> Option<DyRef<T>> {
+        if !self.is_depth_first {
+            if self.is_reverse {
+                self.queue.extend(cur_node.reverse_children());
+            }
+            else {
+                self.queue.extend(cur_node.children());
+            }
+            return self.queue.pop_front();
+        }
+        let first_child = if self.is_reverse {
+            cur_node.last_child()
+        }
+        else {
+            cur_node.first_child()
+        };
+        if first_child.is_some() {
+            return first_child;
+        }
         let mut parent = cur_node;
         loop {
-            let mut first_child: Option<DyRef<T>>;
-            if self.is_depth_first && self.is_reverse {
-                first_child = parent.last_child();
-            }
-            else if self.is_depth_first && !self.is_reverse {
-                first_child = parent.first_child();
+            if parent == self.root {
+                return None;
             }
-            else if self.is_reverse {
-                if parent == self.root {
-                    return None;
-                }
-                first_child = parent.pre_sibling();
+            let next_sibling = if self.is_reverse {
+                parent.pre_sibling()
             }
             else {
-                if parent == self.root {
-                    return None;
-                }
-                first_child = parent.next_sibling();
+                parent.next_sibling()
+            };
+            if next_sibling.is_some() {
+                return next_sibling;
             }
-            if first_child.is_none() {
-                let mut next_sibling: Option<DyRef<T>>;
-                if self.is_depth_first && self.is_reverse {
-                    if parent == self.root {
-                        return None;
-                    }
-                    next_sibling = parent.pre_sibling();
-                }
-                else if self.is_depth_first && !self.is_reverse {
-                    if parent == self.root {
-                        return None;
-                    }
-                    next_sibling = parent.next_sibling();
-                }
-                else if self.is_reverse {
-                    next_sibling = parent.last_child();
-                }
-                else {
-                    next_sibling = parent.first_child();
-                }
-                if next_sibling.is_none() {
-                    parent = parent.parent().unwrap();
-                    if parent == self.root {
-                        return None;
-                    }
-                } else {
-                    return next_sibling;
-                }
-            } else {
-                return first_child;
+            match parent.parent() {
+                Some(x) => parent = x,
+                None => return None,
             }
         }
-        return None;
     }
 }
 
@@ -494,7 +492,7 @@ mod test {
     use super::*;
     #[test]
     fn test_parent() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         assert_eq!(child, parent.first_child().unwrap());
@@ -504,7 +502,7 @@ mod test {
 
     #[test]
     fn test_root() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         assert_eq!(child.root(), parent);
@@ -513,7 +511,7 @@ mod test {
 
     #[test]
     fn test_first_child() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         assert_eq!(child, parent.first_child().unwrap());
@@ -523,7 +521,7 @@ mod test {
 
     #[test]
     fn test_last_child() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         assert_eq!(child, parent.last_child().unwrap());
@@ -533,7 +531,7 @@ mod test {
 
     #[test]
     fn test_next_sibling() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.insert_after(&child);
         assert_eq!(child, parent.next_sibling().unwrap());
@@ -545,7 +543,7 @@ mod test {
 
     #[test]
     fn test_pre_sibling() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.insert_before(&child);
         assert_eq!(child, parent.pre_sibling().unwrap());
@@ -557,7 +555,7 @@ mod test {
 
     #[test]
     fn test_detach() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         let pre = DyRef::new(3);
@@ -565,18 +563,18 @@ mod test {
         child.insert_before(&pre);
         child.insert_after(&next);
         child.detach();
-        assert_eq!(child.parent().is_none(), true);
+        assert!(child.parent().is_none());
         assert_eq!(pre.next_sibling().unwrap(), next);
         assert_eq!(next.pre_sibling().unwrap(), pre);
     }
 
     #[test]
     fn test_copy() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         let copy_parent = parent.deep_copy();
-        assert_eq!(copy_parent.has_children(), true);
+        assert!(copy_parent.has_children());
         assert_ne!(copy_parent.first_child().unwrap(), child);
         let copy_child = copy_parent.first_child().unwrap();
         assert_eq!(*child.borrow_mut(), *copy_child.borrow_mut());
@@ -584,7 +582,7 @@ mod test {
 
     #[test]
     fn test_clone() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let clone_parent = parent.clone();
         let child = DyRef::new(2);
         parent.append(&child);
@@ -594,7 +592,7 @@ mod test {
 
     #[test]
     fn test_iter() {
-        let mut parent = DyRef::new(1);
+        let parent = DyRef::new(1);
         let child = DyRef::new(2);
         parent.append(&child);
         parent.append(&DyRef::new(3));
@@ -604,12 +602,29 @@ mod test {
         next_child.insert_before(&DyRef::new(6));
         next_child.insert_after(&DyRef::new(7));
 
-        let children = parent.children();
+        let mut children = parent.children();
         assert_eq!(children.next().unwrap(), child);
-        assert_eq!(children.next().unwrap().borrow(), &6);
-        assert_eq!(children.next().unwrap().borrow(), &3);
-        assert_eq!(children.next().unwrap().borrow(), &7);
+        assert_eq!(*children.next().unwrap().borrow(), 6);
+        assert_eq!(*children.next().unwrap().borrow(), 3);
+        assert_eq!(*children.next().unwrap().borrow(), 7);
 
     }
+
+    #[test]
+    fn test_traverse() {
+        let parent = DyRef::new(1);
+        let child = DyRef::new(2);
+        parent.append(&child);
+        parent.append(&DyRef::new(3));
+        child.append(&DyRef::new(4));
+        let depth_first: Vec<i32> = parent.traverse(true, false).map(|x| *x.borrow()).collect();
+        assert_eq!(depth_first, vec![1, 2, 4, 3]);
+        let reverse: Vec<i32> = parent.traverse(true, true).map(|x| *x.borrow()).collect();
+        assert_eq!(reverse, vec![1, 3, 2, 4]);
+        let breadth_first: Vec<i32> = parent.traverse(false, false).map(|x| *x.borrow()).collect();
+        assert_eq!(breadth_first, vec![1, 2, 3, 4]);
+        let sub_tree: Vec<i32> = child.traverse(true, false).map(|x| *x.borrow()).collect();
+        assert_eq!(sub_tree, vec![2, 4]);
+    }
 }
 
@@ -0,0 +1,937 @@
+use vm::dy_binder::ScopeTree;
+use vm::dy_check::{literal_type, integer_value, unary_result};
+use vm::dy_diagnostic::{Diagnostic, Phrase};
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_parser::{TokenKind, BUILTIN_TYPES};
+use vm::dy_symbols::{Declarations, TypeSymbol, LookupContext};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, Expression, TypeSyntax, TypeDeclaration, VariableDeclaration,
+    Parameter};
+use vm::dy_types::{binary_result, classify_conversion, is_integral, is_numeric};
+
+// ------------------------------------ constants ----------------------------------------------
+// 编译期常量: const 局部变量和字段, 枚举成员, case 标签, 参数默认值和特性实参的值.
+// 常量表达式默认是 checked 的, 溢出是错误, unchecked(..) 里按目标类型截断.
+// 类型不匹配由类型检查报告, 这里只在能确定是常量的时候给出值
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    Bool(bool),
+    Char(char),
+    Integer(i128),
+    /// float, double and decimal.
+    Real(f64),
+    String(String),
+    Null,
+}
+
+/// A constant and its type; enum constants have the full name of the enum as type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub value: ConstantValue,
+    pub type_name: String,
+}
+
+impl Constant {
+    fn new(value: ConstantValue, type_name: &str) -> Constant {
+        Constant { value, type_name: type_name.to_string() }
+    }
+
+    /// The value as written in diagnostics: `300`, `"a"`, `'c'`, `true` or `null`.
+    pub fn text(&self) -> String {
+        match self.value {
+            ConstantValue::Bool(x) => x.to_string(),
+            ConstantValue::Char(x) => format!("'{}'", x),
+            ConstantValue::Integer(x) => x.to_string(),
+            ConstantValue::Real(x) => x.to_string(),
+            ConstantValue::String(ref x) => format!("\"{}\"", x),
+            ConstantValue::Null => "null".to_string(),
+        }
+    }
+
+    // 字符串拼接时的文本
+    fn concat_text(&self) -> String {
+        match self.value {
+            ConstantValue::Bool(true) => "True".to_string(),
+            ConstantValue::Bool(false) => "False".to_string(),
+            ConstantValue::Char(x) => x.to_string(),
+            ConstantValue::String(ref x) => x.clone(),
+            ConstantValue::Null => String::new(),
+            _ => self.text(),
+        }
+    }
+}
+
+/// Values and errors found by `evaluate_constants`.
+pub struct ConstantEvaluation {
+    /// Values of const declarators, enum members, case labels, parameters with a default value and attribute
+    /// arguments, keyed by those nodes.
+    pub values: Vec<(SyntaxRef, Constant)>,
+    pub errors: Vec<Diagnostic>,
+}
+
+impl ConstantEvaluation {
+    pub fn value_of(&self, node: &SyntaxRef) -> Option<&Constant> {
+        self.values.iter().find(|x| x.0 == *node).map(|x| &x.1)
+    }
+}
+
+/// Evaluates the constants of a compilation unit; constants of other units are evaluated when referenced.
+pub fn evaluate_constants(root: &SyntaxRef, declarations: &Declarations) -> ConstantEvaluation {
+    let scopes = ScopeTree::bind(root);
+    let mut evaluator = Evaluator::new(declarations, &scopes, true);
+    let mut values = Vec::new();
+    for node in root.traverse(true, false) {
+        let value = match node.kind() {
+            SyntaxKind::VariableDeclarator if node.declaration() == Some(SemanticFlags::ConstantDeclarator) =>
+                evaluator.declared(&node).ok(),
+            SyntaxKind::EnumMemberDeclaration => evaluator.declared(&node).ok(),
+            SyntaxKind::SwitchStatement => {
+                values.extend(evaluator.case_labels(&node));
+                continue;
+            }
+            SyntaxKind::Parameter => evaluator.default_value(&node),
+            SyntaxKind::AttributeArgument => evaluator.attribute_argument(&node),
+            _ => continue,
+        };
+        if let Some(value) = value {
+            values.push((node, value));
+        }
+    }
+    ConstantEvaluation { values, errors: evaluator.errors }
+}
+
+/// The value of `node` if it is a constant expression, without reporting errors.
+pub fn evaluate(declarations: &Declarations, scopes: &ScopeTree, node: &SyntaxRef) -> Option<Constant> {
+    Evaluator::new(declarations, scopes, false).expr(node, true).ok()
+}
+
+const SIZES: [(&'static str, i128); 12] = [("sbyte", 1), ("byte", 1), ("bool", 1), ("short", 2), ("ushort", 2),
+    ("char", 2), ("int", 4), ("uint", 4), ("float", 4), ("long", 8), ("ulong", 8), ("double", 8)];
+
+fn range(type_name: &str) -> Option<(i128, i128)> {
+    let range = match type_name {
+        "sbyte" => (i8::MIN as i128, i8::MAX as i128),
+        "byte" => (0, u8::MAX as i128),
+        "short" => (i16::MIN as i128, i16::MAX as i128),
+        "ushort" | "char" => (0, u16::MAX as i128),
+        "int" => (i32::MIN as i128, i32::MAX as i128),
+        "uint" => (0, u32::MAX as i128),
+        "long" => (i64::MIN as i128, i64::MAX as i128),
+        "ulong" => (0, u64::MAX as i128),
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// Whether an integer fits in the integral type or `char` `type_name`.
+pub fn fits(value: i128, type_name: &str) -> bool {
+    range(type_name).is_some_and(|(min, max)| value >= min && value <= max)
+}
+
+// unchecked 的截断: 按类型的位数取模
+fn wrap(value: i128, type_name: &str) -> i128 {
+    let (min, max) = match range(type_name) {
+        Some(range) => range,
+        None => return value,
+    };
+    let modulus = max - min + 1;
+    let wrapped = value.rem_euclid(modulus);
+    if min < 0 && wrapped > max { wrapped - modulus } else { wrapped }
+}
+
+fn as_integer(value: &ConstantValue) -> Option<i128> {
+    match *value {
+        ConstantValue::Integer(x) => Some(x),
+        ConstantValue::Char(x) => Some(x as i128),
+        _ => None,
+    }
+}
+
+fn as_real(value: &ConstantValue) -> Option<f64> {
+    match *value {
+        ConstantValue::Real(x) => Some(x),
+        _ => as_integer(value).map(|x| x as f64),
+    }
+}
+
+fn real(value: f64, type_name: &str) -> ConstantValue {
+    ConstantValue::Real(if type_name == "float" { value as f32 as f64 } else { value })
+}
+
+/// Converts a constant to the predefined type `to` as a cast does. None when that is not a constant conversion,
+/// `Some(Err(()))` when the value does not fit and `checked` is set.
+fn convert_value(value: &ConstantValue, to: &str, checked: bool) -> Option<Result<ConstantValue, ()>> {
+    if let Some((min, max)) = range(to) {
+        let integer = match *value {
+            ConstantValue::Real(x) if x.is_finite() => x.trunc() as i128,
+            ConstantValue::Real(_) if checked => return Some(Err(())),
+            ConstantValue::Real(_) => 0,
+            _ => as_integer(value)?,
+        };
+        let integer = match (integer >= min && integer <= max, checked) {
+            (true, _) => integer,
+            (false, true) => return Some(Err(())),
+            (false, false) => wrap(integer, to),
+        };
+        if to == "char" {
+            return Some(Ok(ConstantValue::Char(std::char::from_u32(integer as u32).unwrap_or('\u{FFFD}'))));
+        }
+        return Some(Ok(ConstantValue::Integer(integer)));
+    }
+    let converted = match (to, value) {
+        ("float", _) | ("double", _) | ("decimal", _) => real(as_real(value)?, to),
+        ("bool", &ConstantValue::Bool(x)) => ConstantValue::Bool(x),
+        ("string", ConstantValue::String(x)) => ConstantValue::String(x.clone()),
+        ("string", &ConstantValue::Null) | ("object", &ConstantValue::Null) => ConstantValue::Null,
+        _ => return None,
+    };
+    Some(Ok(converted))
+}
+
+fn zero(type_name: &str) -> Option<ConstantValue> {
+    let value = match type_name {
+        "bool" => ConstantValue::Bool(false),
+        "char" => ConstantValue::Char('\0'),
+        "float" | "double" | "decimal" => ConstantValue::Real(0.0),
+        "string" | "object" => ConstantValue::Null,
+        _ if is_integral(type_name) => ConstantValue::Integer(0),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('a') => '\u{7}',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{C}',
+            Some('v') => '\u{B}',
+            Some(kind @ 'u') | Some(kind @ 'U') | Some(kind @ 'x') => {
+                let count = match kind { 'u' => 4, 'U' => 8, _ => 4 };
+                let mut digits = String::new();
+                while digits.len() < count && chars.peek().is_some_and(|x| x.is_ascii_hexdigit()) {
+                    digits.push(chars.next().unwrap());
+                }
+                u32::from_str_radix(&digits, 16).ok().and_then(std::char::from_u32).unwrap_or('\u{FFFD}')
+            }
+            Some(x) => x,
+            None => break,
+        };
+        result.push(escaped);
+    }
+    result
+}
+
+fn literal(node: &SyntaxRef) -> Option<Constant> {
+    let token = node.first_significant_token()?;
+    let text = token.token_text();
+    let type_name = literal_type(&token)?;
+    let value = match token.token_kind()? {
+        TokenKind::BoolLiteral => ConstantValue::Bool(text == "true"),
+        TokenKind::NullLiteral => ConstantValue::Null,
+        TokenKind::CharLiteral => ConstantValue::Char(unescape(&text[1..text.len() - 1]).chars().next()?),
+        // 插值字符串不是常量
+        TokenKind::StringLiteral if text.starts_with('$') => return None,
+        TokenKind::StringLiteral if text.starts_with('@') => ConstantValue::String(text[2..text.len() - 1].replace("\"\"", "\"")),
+        TokenKind::StringLiteral => ConstantValue::String(unescape(&text[1..text.len() - 1])),
+        TokenKind::IntegerLiteral => ConstantValue::Integer(integer_value(&text)? as i128),
+        TokenKind::RealLiteral => {
+            let digits: String = text.trim_end_matches(['f', 'F', 'd', 'D', 'm', 'M']).chars().filter(|x| *x != '_').collect();
+            real(digits.parse().ok()?, &type_name)
+        }
+        _ => return None,
+    };
+    Some(Constant::new(value, &type_name))
+}
+
+fn expressions(node: &SyntaxRef) -> Vec<SyntaxRef> {
+    node.child_nodes().into_iter().filter(|x| Expression::cast(x.clone()).is_some()).collect()
+}
+
+// 不是常量表达式, 或者是常量但是出了错 (溢出, 循环定义等), 后者已经报过错
+enum Failure {
+    NotConstant,
+    Reported,
+}
+
+type Evaluated = Result<Constant, Failure>;
+
+enum State {
+    Evaluating,
+    Done(Option<Constant>),
+}
+
+struct Evaluator<'a> {
+    declarations: &'a Declarations,
+    scopes: &'a ScopeTree,
+    unit: SyntaxRef,
+    report: bool,
+    // const 声明和枚举成员的值, 用来发现循环定义
+    states: Vec<(SyntaxRef, State)>,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(declarations: &'a Declarations, scopes: &'a ScopeTree, report: bool) -> Evaluator<'a> {
+        let unit = scopes.root.borrow().node.clone();
+        Evaluator { declarations, scopes, unit, report, states: Vec::new(), errors: Vec::new() }
+    }
+
+    fn in_unit(&self, node: &SyntaxRef) -> bool {
+        node.acestors().last().as_ref() == Some(&self.unit)
+    }
+
+    // 别的编译单元里的错误在那个单元里报
+    fn error(&mut self, node: &SyntaxRef, code: &'static str, arguments: &[&str]) -> Failure {
+        self.report(node, Diagnostic::error(code, node.span(), arguments))
+    }
+
+    fn report(&mut self, node: &SyntaxRef, error: Diagnostic) -> Failure {
+        if self.report && self.in_unit(node) {
+            self.errors.push(error);
+        }
+        Failure::Reported
+    }
+
+    fn normalize(&self, text: &str, at: &SyntaxRef) -> String {
+        if BUILTIN_TYPES.contains(&text) {
+            return text.to_string();
+        }
+        self.declarations.resolve_type_at(text, at).map_or(text.to_string(), |x| x.full_name())
+    }
+
+    fn enum_type(&self, type_name: &str) -> Option<&'a TypeSymbol> {
+        self.declarations.find_type(type_name).filter(|x| x.kind == SyntaxKind::EnumDeclaration)
+    }
+
+    // ------------------------------------ declarations --------------------------------------
+
+    fn constant_name(&self, node: &SyntaxRef) -> String {
+        let name = node.identifier().map(|x| x.token_text()).unwrap_or_default();
+        let is_local = node.acestors().any(|x| x.kind() == SyntaxKind::LocalDeclarationStatement);
+        match self.declarations.enclosing_types(node).first() {
+            Some(symbol) if !is_local => format!("{}.{}", symbol.full_name(), name),
+            _ => name,
+        }
+    }
+
+    // const 声明或者枚举成员的值, 每个只算一次
+    fn declared(&mut self, node: &SyntaxRef) -> Evaluated {
+        if let Some(i) = self.states.iter().position(|x| x.0 == *node) {
+            match self.states[i].1 {
+                State::Done(ref value) => return value.clone().ok_or(Failure::Reported),
+                State::Evaluating => {
+                    self.states[i].1 = State::Done(None);
+                    let name = node.identifier().unwrap_or_else(|| node.clone());
+                    let constant_name = self.constant_name(node);
+                    return Err(self.error(&name, "DY0541", &[&constant_name]));
+                }
+            }
+        }
+        self.states.push((node.clone(), State::Evaluating));
+        let value = if node.kind() == SyntaxKind::EnumMemberDeclaration { self.enum_member(node) } else { self.constant(node) };
+        let i = self.states.iter().position(|x| x.0 == *node).unwrap();
+        if let State::Evaluating = self.states[i].1 {
+            self.states[i].1 = State::Done(value.as_ref().ok().cloned());
+        }
+        value.map_err(|_| Failure::Reported)
+    }
+
+    fn constant(&mut self, node: &SyntaxRef) -> Evaluated {
+        let declaration = node.parent().and_then(VariableDeclaration::cast).ok_or(Failure::Reported)?;
+        let declared = declaration.variable_type().map(|x| self.normalize(&x.text(), node)).ok_or(Failure::Reported)?;
+        let value = expressions(node).pop().ok_or(Failure::Reported)?;
+        match self.expr(&value, true) {
+            Ok(constant) => self.implicit(&value, constant, &declared),
+            Err(Failure::NotConstant) => {
+                let constant_name = self.constant_name(node);
+                Err(self.error(&value, "DY0542", &[&constant_name]))
+            }
+            Err(failure) => Err(failure),
+        }
+    }
+
+    // 没有初始值的枚举成员是前一个成员加一, 第一个是 0
+    fn enum_member(&mut self, node: &SyntaxRef) -> Evaluated {
+        let declaration = node.acestors().skip(1).find(|x| x.kind() == SyntaxKind::EnumDeclaration).ok_or(Failure::Reported)?;
+        let symbol = self.declarations.types.iter().find(|x| x.declarations.contains(&declaration)).ok_or(Failure::Reported)?;
+        let underlying = symbol.enum_underlying_type();
+        let name = node.identifier().unwrap_or_else(|| node.clone());
+        let value = match expressions(node).pop() {
+            Some(value) => {
+                let constant = match self.expr(&value, true) {
+                    Ok(constant) => constant,
+                    Err(Failure::NotConstant) => {
+                        let constant_name = self.constant_name(node);
+                        return Err(self.error(&value, "DY0542", &[&constant_name]));
+                    }
+                    Err(failure) => return Err(failure),
+                };
+                match convert_value(&constant.value, &underlying, true) {
+                    Some(Ok(ConstantValue::Integer(x))) if constant.type_name != "char" => x,
+                    Some(Err(())) => return Err(self.error(&value, "DY0543", &[&constant.text(), &underlying])),
+                    _ => return Err(self.error(&value, "DY0501", &[&constant.type_name, &underlying])),
+                }
+            }
+            None => {
+                let members = TypeDeclaration::cast(declaration.clone()).map_or(Vec::new(), |x| x.enum_members());
+                let previous = members.iter().position(|x| x == node).and_then(|i| i.checked_sub(1)).map(|i| members[i].clone());
+                let previous = match previous {
+                    Some(previous) => self.declared(&previous).map_err(|_| Failure::Reported)?,
+                    None => Constant::new(ConstantValue::Integer(-1), &underlying),
+                };
+                let next = as_integer(&previous.value).unwrap_or(0) + 1;
+                if !fits(next, &underlying) {
+                    return Err(self.error(&name, "DY0545", &[]));
+                }
+                next
+            }
+        };
+        Ok(Constant::new(ConstantValue::Integer(value), &symbol.full_name()))
+    }
+
+    // 常量隐式转换成声明的类型; 不能转换的由类型检查报告
+    fn implicit(&mut self, node: &SyntaxRef, constant: Constant, to: &str) -> Evaluated {
+        if constant.type_name == to {
+            return Ok(constant);
+        }
+        if constant.type_name == "default" {
+            if let Some(symbol) = self.enum_type(to) {
+                return Ok(Constant::new(ConstantValue::Integer(0), &symbol.full_name()));
+            }
+            return zero(to).map(|x| Constant::new(x, to)).ok_or(Failure::NotConstant);
+        }
+        if constant.value == ConstantValue::Null && !is_numeric(to) && to != "bool" && self.enum_type(to).is_none() {
+            return Ok(Constant::new(ConstantValue::Null, to));
+        }
+        // int 常量放得下时可以隐式转换成更小的整数类型
+        let is_constant_conversion = constant.type_name == "int" && is_integral(to) && to != "char"
+            && as_integer(&constant.value).is_some_and(|x| fits(x, to));
+        if !is_constant_conversion && !classify_conversion(&constant.type_name, to, self.declarations).is_implicit() {
+            return Err(Failure::Reported);
+        }
+        match convert_value(&constant.value, to, true) {
+            Some(Ok(value)) => Ok(Constant::new(value, to)),
+            Some(Err(())) => Err(self.error(node, "DY0543", &[&constant.text(), to])),
+            None if to == "object" => Ok(constant),
+            None => Err(Failure::Reported),
+        }
+    }
+
+    // switch 语句的 case 标签, 同一个 switch 里不能有相同的值
+    fn case_labels(&mut self, node: &SyntaxRef) -> Vec<(SyntaxRef, Constant)> {
+        let mut values: Vec<(SyntaxRef, Constant)> = Vec::new();
+        let labels = node.children_of(SyntaxKind::SwitchSection).iter().flat_map(|x| x.children_of(SyntaxKind::CaseSwitchLabel)).collect::<Vec<_>>();
+        for label in labels {
+            let value = match expressions(&label).pop() {
+                Some(value) => value,
+                None => continue,
+            };
+            match self.expr(&value, true) {
+                Ok(constant) => {
+                    if values.iter().any(|x| x.1.value == constant.value) {
+                        self.error(&label, "DY0546", &[&constant.text()]);
+                    }
+                    values.push((label, constant));
+                }
+                // case int: 这样的类型模式
+                Err(Failure::NotConstant) if self.is_type_name(&value) => {}
+                Err(Failure::NotConstant) => {
+                    self.error(&value, "DY0547", &[]);
+                }
+                Err(Failure::Reported) => {}
+            }
+        }
+        values
+    }
+
+    fn is_type_name(&self, node: &SyntaxRef) -> bool {
+        node.kind() == SyntaxKind::PredefinedType || self.declarations.resolve_type_at(&node.text(), node).is_some()
+    }
+
+    // 默认值还可以是 default 和没有实参的 new S()
+    fn default_value(&mut self, node: &SyntaxRef) -> Option<Constant> {
+        let parameter = Parameter::cast(node.clone())?;
+        let value = parameter.default_value()?.syntax().clone();
+        let declared = parameter.parameter_type().map(|x| self.normalize(&x.text(), node));
+        match self.expr(&value, true) {
+            Ok(constant) => match declared {
+                Some(declared) => self.implicit(&value, constant, &declared).ok(),
+                None => Some(constant),
+            },
+            Err(Failure::NotConstant) => {
+                let is_default = value.kind() == SyntaxKind::DefaultExpression || (value.kind() == SyntaxKind::ObjectCreationExpression
+                    && value.child_of(SyntaxKind::ArgumentList).is_none_or(|x| expressions(&x).is_empty()
+                        && x.child_nodes().is_empty()));
+                if !is_default {
+                    let name = parameter.name().unwrap_or_default();
+                    self.error(&value, "DY0548", &[&name]);
+                }
+                None
+            }
+            Err(Failure::Reported) => None,
+        }
+    }
+
+    fn attribute_argument(&mut self, node: &SyntaxRef) -> Option<Constant> {
+        let value = expressions(node).pop()?;
+        match self.expr(&value, true) {
+            Ok(constant) => Some(constant),
+            Err(Failure::NotConstant) => {
+                let is_allowed = [SyntaxKind::TypeOfExpression, SyntaxKind::ArrayCreationExpression,
+                    SyntaxKind::ImplicitArrayCreationExpression].contains(&value.kind());
+                if !is_allowed {
+                    self.error(&value, "DY0549", &[]);
+                }
+                None
+            }
+            Err(Failure::Reported) => None,
+        }
+    }
+
+    // ------------------------------------ expressions ---------------------------------------
+
+    fn expr(&mut self, node: &SyntaxRef, checked: bool) -> Evaluated {
+        let operands = expressions(node);
+        let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+        match node.kind() {
+            SyntaxKind::LiteralExpression if op == "default" => Ok(Constant::new(ConstantValue::Null, "default")),
+            SyntaxKind::LiteralExpression => literal(node).ok_or(Failure::NotConstant),
+            SyntaxKind::ParenthesizedExpression if operands.len() == 1 => self.expr(&operands[0], checked),
+            SyntaxKind::CheckedExpression if operands.len() == 1 => self.expr(&operands[0], op == "checked"),
+            SyntaxKind::PrefixUnaryExpression if operands.len() == 1 => self.unary(node, &op, &operands[0], checked),
+            SyntaxKind::BinaryExpression if operands.len() == 2 => self.binary(node, &op, &operands, checked),
+            SyntaxKind::ConditionalExpression if operands.len() == 3 => self.conditional(&operands, checked),
+            SyntaxKind::CastExpression => self.cast(node, checked),
+            SyntaxKind::SizeOfExpression => {
+                let type_name = node.child_nodes().into_iter().find_map(TypeSyntax::cast).map(|x| x.text()).unwrap_or_default();
+                let size = SIZES.iter().find(|x| x.0 == type_name).map(|x| x.1).or(if type_name == "decimal" { Some(16) } else { None });
+                size.map(|x| Constant::new(ConstantValue::Integer(x), "int")).ok_or(Failure::NotConstant)
+            }
+            SyntaxKind::DefaultExpression => {
+                let type_name = match node.child_nodes().into_iter().find_map(TypeSyntax::cast) {
+                    Some(syntax) => self.normalize(&syntax.text(), node),
+                    None => return Ok(Constant::new(ConstantValue::Null, "default")),
+                };
+                if let Some(symbol) = self.enum_type(&type_name) {
+                    return Ok(Constant::new(ConstantValue::Integer(0), &symbol.full_name()));
+                }
+                zero(&type_name).map(|x| Constant::new(x, &type_name)).ok_or(Failure::NotConstant)
+            }
+            SyntaxKind::InvocationExpression => self.nameof(node),
+            SyntaxKind::IdentifierName => self.name(node),
+            SyntaxKind::MemberAccessExpression => self.member_access(node),
+            _ => Err(Failure::NotConstant),
+        }
+    }
+
+    fn overflow(&mut self, node: &SyntaxRef) -> Failure {
+        self.report(node, Diagnostic::error("DY0550", node.span(), &[]).with_note(Phrase::new("unchecked", &[])))
+    }
+
+    // 整数运算的结果放进类型里: checked 时溢出报错, unchecked 时截断
+    fn integer(&mut self, node: &SyntaxRef, value: i128, type_name: &str, checked: bool) -> Evaluated {
+        if fits(value, type_name) {
+            return Ok(Constant::new(ConstantValue::Integer(value), type_name));
+        }
+        if checked {
+            return Err(self.overflow(node));
+        }
+        Ok(Constant::new(ConstantValue::Integer(wrap(value, type_name)), type_name))
+    }
+
+    fn unary(&mut self, node: &SyntaxRef, op: &str, operand: &SyntaxRef, checked: bool) -> Evaluated {
+        // -2147483648 和 -9223372036854775808 的字面量本身超出了 int 和 long
+        if op == "-" && operand.kind() == SyntaxKind::LiteralExpression {
+            if let Some(Constant { value: ConstantValue::Integer(x), .. }) = literal(operand) {
+                if x == i32::MAX as i128 + 1 || x == i64::MAX as i128 + 1 {
+                    return Ok(Constant::new(ConstantValue::Integer(-x), if x == i32::MAX as i128 + 1 { "int" } else { "long" }));
+                }
+            }
+        }
+        let constant = self.expr(operand, checked)?;
+        let underlying = self.enum_type(&constant.type_name).map(|x| x.enum_underlying_type());
+        let operand_type = underlying.clone().unwrap_or_else(|| constant.type_name.clone());
+        if op == "~" && underlying.is_some() {
+            let value = as_integer(&constant.value).unwrap_or(0);
+            let flipped = self.integer(node, !value, &operand_type, false)?;
+            return Ok(Constant::new(flipped.value, &constant.type_name));
+        }
+        let result = unary_result(op, &operand_type).ok_or(Failure::Reported)?;
+        match (op, &constant.value) {
+            ("!", &ConstantValue::Bool(x)) => Ok(Constant::new(ConstantValue::Bool(!x), result)),
+            (_, &ConstantValue::Real(x)) => Ok(Constant::new(real(if op == "-" { -x } else { x }, result), result)),
+            ("+", value) => self.integer(node, as_integer(value).unwrap_or(0), result, checked),
+            ("-", value) => self.integer(node, -as_integer(value).unwrap_or(0), result, checked),
+            ("~", value) => self.integer(node, !as_integer(value).unwrap_or(0), result, false),
+            _ => Err(Failure::NotConstant),
+        }
+    }
+
+    fn binary(&mut self, node: &SyntaxRef, op: &str, operands: &[SyntaxRef], checked: bool) -> Evaluated {
+        let left = self.expr(&operands[0], checked)?;
+        let right = self.expr(&operands[1], checked)?;
+        if op == "??" {
+            return Ok(if left.value == ConstantValue::Null { right } else { left });
+        }
+        let is_string = |x: &Constant| x.type_name == "string";
+        if op == "+" && (is_string(&left) || is_string(&right)) {
+            return Ok(Constant::new(ConstantValue::String(left.concat_text() + &right.concat_text()), "string"));
+        }
+        if (op == "==" || op == "!=") && (is_string(&left) || is_string(&right)) {
+            return Ok(Constant::new(ConstantValue::Bool((left.value == right.value) == (op == "==")), "bool"));
+        }
+        if let (&ConstantValue::Bool(x), &ConstantValue::Bool(y)) = (&left.value, &right.value) {
+            let value = match op {
+                "&" | "&&" => x && y,
+                "|" | "||" => x || y,
+                "^" | "!=" => x != y,
+                "==" => x == y,
+                _ => return Err(Failure::Reported),
+            };
+            return Ok(Constant::new(ConstantValue::Bool(value), "bool"));
+        }
+        // 枚举按基础类型运算: E | E 和 E + int 还是 E, E - E 是基础类型
+        let (left_enum, right_enum) = (self.enum_type(&left.type_name), self.enum_type(&right.type_name));
+        let left_type = left_enum.map_or(left.type_name.clone(), |x| x.enum_underlying_type());
+        let right_type = right_enum.map_or(right.type_name.clone(), |x| x.enum_underlying_type());
+        let enum_result = match (left_enum, right_enum) {
+            (Some(x), Some(_)) if ["&", "|", "^"].contains(&op) => Some(x.full_name()),
+            (Some(x), None) if op == "+" || op == "-" => Some(x.full_name()),
+            (None, Some(x)) if op == "+" => Some(x.full_name()),
+            _ => None,
+        };
+        let result = self.numeric(node, op, &left, &left_type, &right, &right_type, checked)?;
+        match enum_result {
+            Some(enum_type) => Ok(Constant::new(result.value, &enum_type)),
+            None => Ok(result),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn numeric(&mut self, node: &SyntaxRef, op: &str, left: &Constant, left_type: &str, right: &Constant, right_type: &str,
+        checked: bool) -> Evaluated {
+        let result = binary_result(op, left_type, right_type).ok_or(Failure::Reported)?;
+        if result == "bool" {
+            let ordering = match (as_integer(&left.value), as_integer(&right.value)) {
+                (Some(x), Some(y)) => x.partial_cmp(&y),
+                _ => as_real(&left.value).and_then(|x| as_real(&right.value).and_then(|y| x.partial_cmp(&y))),
+            };
+            let value = match op {
+                "==" => ordering == Some(std::cmp::Ordering::Equal),
+                "!=" => ordering != Some(std::cmp::Ordering::Equal),
+                "<" => ordering == Some(std::cmp::Ordering::Less),
+                ">" => ordering == Some(std::cmp::Ordering::Greater),
+                "<=" => ordering.is_some_and(|x| x != std::cmp::Ordering::Greater),
+                ">=" => ordering.is_some_and(|x| x != std::cmp::Ordering::Less),
+                _ => return Err(Failure::Reported),
+            };
+            return Ok(Constant::new(ConstantValue::Bool(value), "bool"));
+        }
+        if !is_integral(&result) {
+            let (x, y) = (as_real(&left.value).ok_or(Failure::Reported)?, as_real(&right.value).ok_or(Failure::Reported)?);
+            if result == "decimal" && (op == "/" || op == "%") && y == 0.0 {
+                return Err(self.error(node, "DY0551", &[]));
+            }
+            let value = match op {
+                "+" => x + y,
+                "-" => x - y,
+                "*" => x * y,
+                "/" => x / y,
+                "%" => x % y,
+                _ => return Err(Failure::Reported),
+            };
+            return Ok(Constant::new(real(value, &result), &result));
+        }
+        let (x, y) = (as_integer(&left.value).ok_or(Failure::Reported)?, as_integer(&right.value).ok_or(Failure::Reported)?);
+        let bits = if result == "long" || result == "ulong" { 63 } else { 31 };
+        let value = match op {
+            "+" => x + y,
+            "-" => x - y,
+            "*" => x * y,
+            "/" | "%" if y == 0 => return Err(self.error(node, "DY0551", &[])),
+            // C# 的整数除法向零取整
+            "/" => x / y,
+            "%" => x % y,
+            "<<" => return self.integer(node, wrap(x << (y & bits), &result), &result, false),
+            ">>" => return self.integer(node, x >> (y & bits), &result, false),
+            "&" => x & y,
+            "|" => x | y,
+            "^" => x ^ y,
+            _ => return Err(Failure::Reported),
+        };
+        self.integer(node, value, &result, checked)
+    }
+
+    fn conditional(&mut self, operands: &[SyntaxRef], checked: bool) -> Evaluated {
+        let condition = self.expr(&operands[0], checked)?;
+        let when_true = self.expr(&operands[1], checked)?;
+        let when_false = self.expr(&operands[2], checked)?;
+        let chosen = match condition.value {
+            ConstantValue::Bool(true) => when_true.clone(),
+            ConstantValue::Bool(false) => when_false.clone(),
+            _ => return Err(Failure::Reported),
+        };
+        // 两边类型不同时是能隐式转换到的那一边
+        let conversion = classify_conversion(&when_true.type_name, &when_false.type_name, self.declarations);
+        let type_name = if conversion.is_implicit() {
+            when_false.type_name
+        } else {
+            when_true.type_name
+        };
+        if chosen.type_name == type_name {
+            return Ok(chosen);
+        }
+        match convert_value(&chosen.value, &type_name, true) {
+            Some(Ok(value)) => Ok(Constant::new(value, &type_name)),
+            _ => Err(Failure::Reported),
+        }
+    }
+
+    fn cast(&mut self, node: &SyntaxRef, checked: bool) -> Evaluated {
+        let children = node.child_nodes();
+        let syntax = match (children.first().cloned().and_then(TypeSyntax::cast), children.len()) {
+            (Some(syntax), 2) => syntax,
+            _ => return Err(Failure::NotConstant),
+        };
+        let constant = self.expr(&children[1], checked)?;
+        let target = self.normalize(&syntax.text(), node);
+        let enum_target = self.enum_type(&target);
+        let converted_type = enum_target.map_or(target.clone(), |x| x.enum_underlying_type());
+        if constant.type_name == target {
+            return Ok(constant);
+        }
+        match convert_value(&constant.value, &converted_type, checked) {
+            Some(Ok(value)) => Ok(Constant::new(value, &target)),
+            Some(Err(())) => Err(self.error(node, "DY0544", &[&constant.text(), &target])),
+            None => Err(Failure::NotConstant),
+        }
+    }
+
+    fn nameof(&mut self, node: &SyntaxRef) -> Evaluated {
+        let callee = node.child_nodes().into_iter().next().ok_or(Failure::NotConstant)?;
+        let is_nameof = callee.kind() == SyntaxKind::IdentifierName && callee.token_text() == "nameof"
+            && self.local_symbol(&callee).is_none();
+        if !is_nameof {
+            return Err(Failure::NotConstant);
+        }
+        let argument = node.child_of(SyntaxKind::ArgumentList).and_then(|x| x.child_nodes().pop())
+            .and_then(|x| expressions(&x).pop()).ok_or(Failure::NotConstant)?;
+        let name = match argument.kind() {
+            SyntaxKind::MemberAccessExpression => argument.child_nodes().pop().and_then(|x| x.identifier()),
+            _ => argument.identifier(),
+        };
+        let name = name.ok_or_else(|| self.error(&argument, "DY0552", &[]))?;
+        Ok(Constant::new(ConstantValue::String(name.token_text()), "string"))
+    }
+
+    // ------------------------------------ names ---------------------------------------------
+
+    fn local_symbol(&self, node: &SyntaxRef) -> Option<SyntaxRef> {
+        if !self.in_unit(node) {
+            return None;
+        }
+        let symbols = self.scopes.lookup(&node.token_text(), node.span().start);
+        symbols.into_iter().find(|x| x.is_local()).map(|x| x.node)
+    }
+
+    // 枚举成员的初始值里, 同一个枚举的其他成员按基础类型算
+    fn enum_value(&mut self, member: &SyntaxRef, from: &SyntaxRef, symbol: &TypeSymbol) -> Evaluated {
+        let constant = self.declared(member)?;
+        let is_inside = symbol.declarations.iter().any(|x| from.acestors().any(|y| y == *x));
+        if is_inside {
+            return Ok(Constant::new(constant.value, &symbol.enum_underlying_type()));
+        }
+        Ok(constant)
+    }
+
+    // 类型和基类型里名叫 name 的常量
+    fn member_constant(&mut self, symbol: &'a TypeSymbol, name: &str, from: &SyntaxRef) -> Option<Evaluated> {
+        if symbol.kind == SyntaxKind::EnumDeclaration {
+            let members = TypeDeclaration::cast(symbol.declarations[0].clone()).map_or(Vec::new(), |x| x.enum_members());
+            let member = members.into_iter().find(|x| x.identifier().is_some_and(|x| x.token_text() == name))?;
+            return Some(self.enum_value(&member, from, symbol));
+        }
+        for owner in self.declarations.with_bases(symbol) {
+            if let Some(member) = owner.members_named(name).into_iter().next() {
+                if !member.has_modifier("const") {
+                    return Some(Err(Failure::NotConstant));
+                }
+                let declaration = member.declaration.clone();
+                return Some(self.declared(&declaration));
+            }
+        }
+        None
+    }
+
+    fn name(&mut self, node: &SyntaxRef) -> Evaluated {
+        if let Some(local) = self.local_symbol(node) {
+            if local.declaration() == Some(SemanticFlags::ConstantDeclarator) {
+                return self.declared(&local);
+            }
+            return Err(Failure::NotConstant);
+        }
+        let name = node.token_text();
+        for symbol in self.declarations.enclosing_types(node) {
+            if let Some(value) = self.member_constant(symbol, &name, node) {
+                return value;
+            }
+        }
+        // using static 导入的常量
+        let context = LookupContext::at(node);
+        let imported = self.declarations.lookup_imported(&name, &context).unwrap_or_default();
+        if let Some(&(_, member)) = imported.iter().find(|x| x.1.has_modifier("const")) {
+            let declaration = member.declaration.clone();
+            return self.declared(&declaration);
+        }
+        Err(Failure::NotConstant)
+    }
+
+    fn member_access(&mut self, node: &SyntaxRef) -> Evaluated {
+        let children = node.child_nodes();
+        if children.len() != 2 {
+            return Err(Failure::NotConstant);
+        }
+        let name = children[1].token_text();
+        if children[0].kind() == SyntaxKind::PredefinedType {
+            let type_name = children[0].token_text();
+            let (min, max) = range(&type_name).ok_or(Failure::NotConstant)?;
+            let value = match name.as_str() {
+                "MinValue" => min,
+                "MaxValue" => max,
+                _ => return Err(Failure::NotConstant),
+            };
+            let value = if type_name == "char" { ConstantValue::Char(std::char::from_u32(value as u32).unwrap_or('\u{FFFD}')) } else { ConstantValue::Integer(value) };
+            return Ok(Constant::new(value, &type_name));
+        }
+        let symbol = self.declarations.resolve_type_at(&children[0].text(), node).ok_or(Failure::NotConstant)?;
+        self.member_constant(symbol, &name, node).unwrap_or(Err(Failure::NotConstant))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+    use vm::dy_syntax::SyntaxTree;
+
+    fn evaluate_code(code: &str) -> (SyntaxTree, ConstantEvaluation) {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        let result = evaluate_constants(&tree.root, &declarations);
+        (tree, result)
+    }
+
+    // 名字叫 name 的声明的值
+    fn value_of(tree: &SyntaxTree, result: &ConstantEvaluation, name: &str) -> String {
+        tree.root.traverse(true, false)
+            .filter(|x| x.identifier().is_some_and(|x| x.token_text() == name))
+            .find_map(|x| result.value_of(&x).map(|x| format!("{}: {}", x.text(), x.type_name)))
+            .unwrap_or_default()
+    }
+
+    fn messages(result: &ConstantEvaluation) -> Vec<String> {
+        result.errors.iter().map(|x| x.message.clone()).collect()
+    }
+
+    #[test]
+    fn test_constants() {
+        let code = "using static Limits; static class Limits { public const int Max = 100; }
+            class A { const int X = Y * 2 + 1, Y = Max / 3; const long L = (long)X << 40; const double D = X / 2.0;
+                const string S = \"x=\" + X + ',' + true + null; const string N = nameof(A.X) + nameof(Limits);
+                const int Size = sizeof(long) + sizeof(char); const byte B = 200; const char C = (char)('a' + 1);
+                const bool T = X > 10 && S != \"\"; const int M = -2147483648; const uint U = unchecked((uint)-1);
+                const float F = 1 / 3f; const int Z = default; const string V = @\"a\"\"b\" + \"\\t\";
+                void F(int a = X, string s = null) { const int local = X % 7; } }";
+        let (tree, result) = evaluate_code(code);
+        assert_eq!(messages(&result), Vec::<String>::new());
+        assert_eq!(value_of(&tree, &result, "Max"), "100: int");
+        assert_eq!(value_of(&tree, &result, "X"), "67: int");
+        assert_eq!(value_of(&tree, &result, "L"), (67i64 << 40).to_string() + ": long");
+        assert_eq!(value_of(&tree, &result, "D"), "33.5: double");
+        assert_eq!(value_of(&tree, &result, "S"), "\"x=67,True\": string");
+        assert_eq!(value_of(&tree, &result, "N"), "\"XLimits\": string");
+        assert_eq!(value_of(&tree, &result, "Size"), "10: int");
+        assert_eq!(value_of(&tree, &result, "B"), "200: byte");
+        assert_eq!(value_of(&tree, &result, "C"), "'b': char");
+        assert_eq!(value_of(&tree, &result, "T"), "true: bool");
+        assert_eq!(value_of(&tree, &result, "M"), "-2147483648: int");
+        assert_eq!(value_of(&tree, &result, "U"), "4294967295: uint");
+        assert_eq!(value_of(&tree, &result, "F"), format!("{}: float", (1.0f32 / 3.0) as f64));
+        assert_eq!(value_of(&tree, &result, "Z"), "0: int");
+        assert_eq!(value_of(&tree, &result, "V"), "\"a\"b\t\": string");
+        assert_eq!(value_of(&tree, &result, "a"), "67: int");
+        assert_eq!(value_of(&tree, &result, "s"), "null: string");
+        assert_eq!(value_of(&tree, &result, "local"), "4: int");
+    }
+
+    #[test]
+    fn test_enums_and_labels() {
+        let code = "enum Color : byte { Red, Green = Red + 5, Blue, All = Red | Green | Blue }
+            enum Big : byte { A = 255, B }
+            enum Flags { None = 0, Read = 1 << 0, Write = 1 << 1, Both = Read | Write }
+            [Flag(Flags.Both, Name = \"rw\", Type = typeof(Flags))]
+            class C { const Flags Mask = Flags.Read | Flags.Write; const int Count = (int)Color.Blue - (int)Color.Green;
+                void Run(int x, Color c) {
+                    switch (x) { case Count: case 1 + 1: break; case Count - 1: break; case 2: break; case Count + 2: break; }
+                    switch (c) { case Color.Red: break; case x: break; } } }";
+        let (tree, result) = evaluate_code(code);
+        assert_eq!(messages(&result), [
+            "the enumerator value is too large to fit in its type",
+            "the switch statement contains multiple cases with the label value 2",
+            "a constant value is expected",
+        ]);
+        assert_eq!(value_of(&tree, &result, "Green"), "5: Color");
+        assert_eq!(value_of(&tree, &result, "Blue"), "6: Color");
+        assert_eq!(value_of(&tree, &result, "All"), "7: Color");
+        assert_eq!(value_of(&tree, &result, "Both"), "3: Flags");
+        assert_eq!(value_of(&tree, &result, "Mask"), "3: Flags");
+        assert_eq!(value_of(&tree, &result, "Count"), "1: int");
+        let arguments: Vec<String> = tree.root.traverse(true, false).filter(|x| x.kind() == SyntaxKind::AttributeArgument)
+            .filter_map(|x| result.value_of(&x).map(|x| x.text())).collect();
+        assert_eq!(arguments, ["3", "\"rw\""]);
+        let labels: Vec<String> = tree.root.traverse(true, false).filter(|x| x.kind() == SyntaxKind::CaseSwitchLabel)
+            .filter_map(|x| result.value_of(&x).map(|x| x.text())).collect();
+        assert_eq!(labels, ["1", "2", "0", "2", "3", "0"]);
+    }
+
+    #[test]
+    fn test_constant_errors() {
+        let code = "class A { const int X = Y + 1; const int Y = X; const int Self = Self;
+            const int Big = int.MaxValue + 1; const int Wrapped = unchecked(int.MaxValue + 1); const int Div = 1 / (X - X);
+            const byte Cast = (byte)300; const byte Raw = unchecked((byte)300); static int f; const int NotConst = f;
+            const long Shift = 1L << 65;
+            [Range(f)] void F(int a = f, A b = null, int c = default) { const string s = \"a\" + c; } }";
+        let (tree, result) = evaluate_code(code);
+        assert_eq!(messages(&result), [
+            "the evaluation of the constant value for 'A.X' involves a circular definition",
+            "the evaluation of the constant value for 'A.Self' involves a circular definition",
+            "the operation overflows at compile time in checked mode",
+            "constant value '300' cannot be converted to a 'byte' (use 'unchecked' syntax to override)",
+            "the expression being assigned to 'A.NotConst' must be constant",
+            "an attribute argument must be a constant expression, typeof expression or array creation expression",
+            "default parameter value for 'a' must be a compile-time constant",
+            "the expression being assigned to 's' must be constant",
+        ]);
+        assert_eq!(value_of(&tree, &result, "Wrapped"), "-2147483648: int");
+        assert_eq!(value_of(&tree, &result, "Raw"), "44: byte");
+        assert_eq!(value_of(&tree, &result, "Shift"), "2: long");
+        assert_eq!(value_of(&tree, &result, "b"), "null: A");
+        assert_eq!(value_of(&tree, &result, "c"), "0: int");
+    }
+}
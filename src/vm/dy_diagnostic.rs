@@ -0,0 +1,881 @@
+use std::slice;
+
+use vm::dy_access::check_accessibility;
+use vm::dy_binder::ScopeTree;
+use vm::dy_check::check_types;
+use vm::dy_const::evaluate_constants;
+use vm::dy_export::span_members;
+use vm::dy_flow::analyze_flow;
+use vm::dy_inherit::check_inheritance;
+use vm::dy_json::Json;
+use vm::dy_locale::{self, text, Locale, EN_US_TEXTS};
+use vm::dy_lower::{check_async, check_iterators, check_using};
+use vm::dy_nullable::analyze_nullable;
+use vm::dy_parser::{Span, TokenKind};
+use vm::dy_symbols::Declarations;
+use vm::dy_syntax::{SyntaxRef, SyntaxTree};
+
+// ------------------------------------ diagnostics --------------------------------------------
+// 统一的诊断信息: 代码, 严重程度, 主要和次要的标注位置, 附注和修改建议.
+// 各个阶段报告时就给出稳定的代码 (DY0101) 和模板里 {} 的值, 消息在输出时按语言用模板生成.
+// 代码按阶段分段: 01 词法, 02 预处理, 03 语法, 04 绑定和声明, 05 类型检查, 06 继承, 07 控制流, 08 降级, 09 可空, 10 运行时
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+/// Message templates by code; `{}` are the arguments.
+pub const MESSAGES: &'static [(&'static str, &'static str)] = &[
+    ("DY0101", "unexpected character '{}'"),
+    ("DY0102", "newline in constant"),
+    ("DY0103", "too many characters in character literal"),
+    ("DY0104", "empty character literal"),
+    ("DY0105", "end-of-file found, '*/' expected"),
+    ("DY0201", "preprocessor directive expected"),
+    ("DY0202", "unexpected preprocessor directive"),
+    ("DY0203", "single-line comment or end-of-line expected"),
+    ("DY0204", "#endif directive expected"),
+    ("DY0205", "#endregion directive expected"),
+    ("DY0301", "expected '{}', found '{}'"),
+    ("DY0302", "expected '{}' at end of file"),
+    ("DY0303", "unexpected '{}'"),
+    ("DY0304", "formatting would change the meaning of the code"),
+    ("DY0401", "the label '{}' is a duplicate"),
+    ("DY0402", "a local variable or parameter named '{}' is already defined in this scope"),
+    ("DY0403", "'{}' is already defined in this scope"),
+    ("DY0404", "a local or parameter named '{}' cannot be declared in this scope because that name is used in an enclosing \
+        local scope to define a local or parameter"),
+    ("DY0405", "type '{}' already defines a member called '{}' with the same parameter types"),
+    ("DY0406", "'{}' already contains a definition for '{}'"),
+    ("DY0407", "a partial method may not have multiple defining declarations"),
+    ("DY0408", "a partial method may not have multiple implementing declarations"),
+    ("DY0409", "both partial method declarations must be static or neither may be static"),
+    ("DY0410", "a partial method must be declared within a partial class or partial struct"),
+    ("DY0411", "partial methods must have a void return type"),
+    ("DY0412", "partial methods cannot have out parameters"),
+    ("DY0413", "a partial method cannot have access modifiers or the virtual, abstract, override, new, sealed, or extern \
+        modifiers"),
+    ("DY0414", "no defining declaration found for implementing declaration of partial method '{}'"),
+    ("DY0415", "missing partial modifier on declaration of type '{}'; another partial declaration of this type exists"),
+    ("DY0416", "partial declarations of '{}' must be all classes, all structs or all interfaces"),
+    ("DY0417", "partial declarations of '{}' have conflicting accessibility modifiers"),
+    ("DY0418", "partial declarations of '{}' must have the same type parameter names in the same order"),
+    ("DY0419", "partial declarations of '{}' must not specify different base classes"),
+    ("DY0420", "the 'this' modifier can only be applied to the first parameter"),
+    ("DY0421", "extension methods must be defined in a non-generic static class"),
+    ("DY0422", "extension methods must be defined in a top level static class"),
+    ("DY0423", "extension methods must be static"),
+    ("DY0424", "the type or namespace name '{}' does not exist in the global namespace"),
+    ("DY0425", "the type or namespace name '{}' does not exist in the namespace '{}'"),
+    ("DY0426", "the extern alias '{}' was not specified in a reference"),
+    ("DY0427", "the alias '{}' could not be found"),
+    ("DY0428", "'{}' is an ambiguous reference between '{}' and '{}'"),
+    ("DY0429", "the type or namespace name '{}' could not be found"),
+    ("DY0430", "the using alias '{}' appeared previously in this namespace"),
+    ("DY0431", "a 'using namespace' directive can only be applied to namespaces; '{}' is a type not a namespace, consider a \
+        'using static' directive instead"),
+    ("DY0432", "a 'using static' directive can only be applied to types; '{}' is a namespace not a type"),
+    ("DY0501", "cannot implicitly convert type '{}' to '{}'"),
+    ("DY0502", "cannot implicitly convert type '{}' to '{}'. An explicit conversion exists (are you missing a cast?)"),
+    ("DY0503", "cannot convert null to '{}' because it is a non-nullable value type"),
+    ("DY0504", "cannot convert type '{}' to '{}'"),
+    ("DY0505", "'{}' does not contain a definition for '{}'"),
+    ("DY0506", "'{}' does not contain a definition for '{}' and no accessible extension method '{}' accepting a first \
+        argument of type '{}' could be found"),
+    ("DY0507", "implicitly-typed variables cannot have multiple declarators"),
+    ("DY0508", "implicitly-typed variables must be initialized"),
+    ("DY0509", "cannot initialize an implicitly-typed variable with an array initializer"),
+    ("DY0510", "cannot assign {} to an implicitly-typed variable; the types of its parameters cannot be inferred"),
+    ("DY0511", "cannot assign {} to an implicitly-typed variable"),
+    ("DY0512", "since '{}' returns void, a return keyword must not be followed by an object expression"),
+    ("DY0513", "an object of a type convertible to '{}' is required"),
+    ("DY0514", "only assignment, call, increment, decrement, await, and new object expressions can be used as a statement"),
+    ("DY0515", "foreach statement cannot operate on variables of type '{}' because '{}' does not contain a public instance \
+        definition for 'GetEnumerator'"),
+    ("DY0516", "'{}' is a type, which is not valid in the given context"),
+    ("DY0517", "the as operator must be used with a reference type or nullable type ('{}' is a non-nullable value type)"),
+    ("DY0518", "the call is ambiguous between the following methods or properties: {}"),
+    ("DY0519", "no overload for {} takes {} arguments"),
+    ("DY0520", "no overload for {} matches the argument types ({}); candidates are: {}"),
+    ("DY0521", "argument {}: cannot convert from '{}' to '{}'"),
+    ("DY0522", "argument {} may not be passed with the '{}' keyword"),
+    ("DY0523", "argument {} must be passed with the '{}' keyword"),
+    ("DY0524", "the best overload for '{}' does not have a parameter named '{}'"),
+    ("DY0525", "named argument '{}' specifies a parameter for which a positional argument has already been given"),
+    ("DY0526", "the type arguments for method '{}' cannot be inferred from the usage; try specifying the type arguments \
+        explicitly"),
+    ("DY0527", "the generic method '{}' requires {} type arguments"),
+    ("DY0528", "cannot apply indexing with [] to an expression of type '{}'"),
+    ("DY0529", "operator '{}' cannot be applied to operands of type '{}' and '{}'"),
+    ("DY0530", "operator '{}' is ambiguous on an operand of type {}"),
+    ("DY0531", "operator '{}' is ambiguous on operands of type {}"),
+    ("DY0532", "the operand of an increment or decrement operator must be a variable, property or indexer"),
+    ("DY0533", "operator '{}' cannot be applied to operand of type '{}'"),
+    ("DY0534", "the left-hand side of an assignment must be a variable, property or indexer"),
+    ("DY0535", "delegate '{}' does not take {} arguments"),
+    ("DY0536", "cannot convert {} to type '{}' because it is not a delegate type"),
+    ("DY0537", "type of conditional expression cannot be determined because there is no implicit conversion between '{}' \
+        and '{}'"),
+    ("DY0538", "'{}' is inaccessible due to its protection level"),
+    ("DY0539", "cannot access protected member '{}' via a qualifier of type '{}'; the qualifier must be of type '{}' (or \
+        derived from it)"),
+    ("DY0540", "Inconsistent accessibility: {} '{}' is less accessible than {}"),
+    ("DY0541", "the evaluation of the constant value for '{}' involves a circular definition"),
+    ("DY0542", "the expression being assigned to '{}' must be constant"),
+    ("DY0543", "constant value '{}' cannot be converted to a '{}'"),
+    ("DY0544", "constant value '{}' cannot be converted to a '{}' (use 'unchecked' syntax to override)"),
+    ("DY0545", "the enumerator value is too large to fit in its type"),
+    ("DY0546", "the switch statement contains multiple cases with the label value {}"),
+    ("DY0547", "a constant value is expected"),
+    ("DY0548", "default parameter value for '{}' must be a compile-time constant"),
+    ("DY0549", "an attribute argument must be a constant expression, typeof expression or array creation expression"),
+    ("DY0550", "the operation overflows at compile time in checked mode"),
+    ("DY0551", "division by constant zero"),
+    ("DY0552", "expression does not have a name"),
+    ("DY0553", "the name '{}' does not exist in the current context"),
+    ("DY0554", "there is no argument given that corresponds to the required parameter '{}' of '{}'"),
+    ("DY0601", "type '{}' in interface list is not an interface"),
+    ("DY0602", "'{}': cannot derive from sealed type '{}'"),
+    ("DY0603", "'{}': cannot derive from static class '{}'"),
+    ("DY0604", "class '{}' cannot have multiple base classes: '{}' and '{}'"),
+    ("DY0605", "base class '{}' must come before any interfaces"),
+    ("DY0606", "circular base type dependency involving '{}' and '{}'"),
+    ("DY0607", "'{}' is abstract but it is contained in non-abstract type '{}'"),
+    ("DY0608", "'{}' cannot declare a body because it is marked abstract"),
+    ("DY0609", "'{}': virtual or abstract members cannot be private"),
+    ("DY0610", "'{}' cannot be sealed because it is not an override"),
+    ("DY0611", "'{}' hides inherited member '{}'. To make the current member override that implementation, add the \
+        override keyword. Otherwise add the new keyword."),
+    ("DY0612", "'{}' hides inherited member '{}'. Use the new keyword if hiding was intended."),
+    ("DY0613", "the member '{}' does not hide an accessible member. The new keyword is not required."),
+    ("DY0614", "'{}': no suitable {} found to override"),
+    ("DY0615", "'{}': cannot override inherited member '{}' because it is not marked virtual, abstract, or override"),
+    ("DY0616", "'{}': cannot override inherited member '{}' because it is sealed"),
+    ("DY0617", "'{}': {} must be '{}' to match overridden member '{}'"),
+    ("DY0618", "'{}': cannot change access modifiers when overriding '{}' inherited member '{}'"),
+    ("DY0619", "'{}' does not implement inherited abstract member '{}'"),
+    ("DY0620", "'{}': containing type does not implement interface '{}'"),
+    ("DY0621", "'{}' does not implement interface member '{}'. '{}' cannot implement an interface member because it is not \
+        public."),
+    ("DY0622", "'{}' does not implement interface member '{}'. '{}' cannot implement '{}' because it does not have the \
+        matching return type of '{}'."),
+    ("DY0623", "'{}' does not implement interface member '{}'"),
+    ("DY0624", "keyword 'base' is not available in a static method"),
+    ("DY0625", "cannot call an abstract base member: '{}'"),
+    ("DY0701", "use of unassigned local variable '{}'"),
+    ("DY0702", "use of unassigned out parameter '{}'"),
+    ("DY0703", "the out parameter '{}' must be assigned to before control leaves the current method"),
+    ("DY0704", "'{}': not all code paths return a value"),
+    ("DY0705", "unreachable code detected"),
+    ("DY0706", "control cannot fall out of switch from final case label ('{}')"),
+    ("DY0707", "control cannot fall through from one case label ('{}') to another"),
+    ("DY0801", "'await' can only be used in an async method"),
+    ("DY0802", "cannot await in the body of a lock statement"),
+    ("DY0803", "cannot await in a catch filter"),
+    ("DY0804", "the 'async' modifier is only valid on methods, lambdas and anonymous methods"),
+    ("DY0805", "async methods cannot have ref or out parameters"),
+    ("DY0806", "async method must return void, Task or Task<T>"),
+    ("DY0807", "an async method returning void or Task cannot return a value"),
+    ("DY0808", "an async method returning Task<T> must return a value"),
+    ("DY0809", "yield cannot be used in a lambda or anonymous method"),
+    ("DY0810", "cannot yield in the body of a finally clause"),
+    ("DY0811", "cannot yield a value in the body of a catch clause"),
+    ("DY0812", "cannot yield a value in the body of a try block with a catch clause"),
+    ("DY0813", "'{}' is not an iterator interface type"),
+    ("DY0814", "only methods, operators and get accessors can be iterators"),
+    ("DY0815", "iterators cannot have ref or out parameters"),
+    ("DY0816", "cannot return a value from an iterator, use 'yield return' or 'yield break'"),
+    ("DY0817", "a using variable cannot be used directly within a switch section"),
+    ("DY0818", "a using declaration cannot be an embedded statement"),
+    ("DY0819", "a using declaration requires an initializer"),
+    ("DY0820", "cannot assign to '{}' because it is a using variable"),
+    ("DY0821", "invalid anonymous type member declarator"),
+    ("DY0822", "anonymous type cannot have multiple members named '{}'"),
+    ("DY0823", "invalid initializer member declarator"),
+    ("DY0824", "member '{}' is initialized more than once"),
+    ("DY0825", "invalid deconstruction target"),
+    ("DY0826", "deconstruction target must be a variable"),
+    ("DY0827", "cannot deconstruct a tuple of {} elements into {} variables"),
+    ("DY0828", "the left-hand side of '??=' must be a variable, property or indexer"),
+    ("DY0829", "a {} node cannot be lowered"),
+    ("DY0901", "dereference of a possibly null reference"),
+    ("DY0902", "converting null literal or possible null value to non-nullable type"),
+    ("DY0903", "cannot convert null literal to non-nullable reference type"),
+    ("DY0904", "possible null reference assignment"),
+    ("DY0905", "possible null reference return"),
+    ("DY1001", "cannot access a disposed object"),
+];
+
+/// The message template of a code.
+pub fn template(code: &str) -> Option<&'static str> {
+    MESSAGES.iter().find(|x| x.0 == code).map(|x| x.1)
+}
+
+/// Fills the `{}` of a template in order; `{0}`, `{1}` pick an argument, for translations that reorder them.
+pub fn format_template(template: &str, arguments: &[String]) -> String {
+    let mut text = String::new();
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(x) => start + x,
+            None => break,
+        };
+        let index = match rest[start + 1..end].parse() {
+            Ok(x) => x,
+            Err(_) if start + 1 == end => {
+                next += 1;
+                next - 1
+            }
+            Err(_) => {
+                text.push_str(&rest[..end + 1]);
+                rest = &rest[end + 1..];
+                continue;
+            }
+        };
+        text.push_str(&rest[..start]);
+        text.push_str(arguments.get(index).map_or("", |x| x.as_str()));
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    text
+}
+
+/// A text of the catalogs (`dy_locale::text`) with the values of its `{}`, rendered in the language of the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phrase {
+    pub key: &'static str,
+    pub arguments: Vec<String>,
+}
+
+impl Phrase {
+    pub fn new(key: &'static str, arguments: &[&str]) -> Phrase {
+        assert!(EN_US_TEXTS.iter().any(|x| x.0 == key), "unknown text '{}'", key);
+        Phrase { key, arguments: arguments.iter().map(|x| x.to_string()).collect() }
+    }
+
+    pub fn text(&self, locale: Locale) -> String {
+        format_template(text(self.key, locale), &self.arguments)
+    }
+}
+
+/// The value of a `{}` in a message template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    /// Source text such as a name or a type, the same in every language.
+    Name(String),
+    /// Words of the message itself, e.g. "return type", translated with it.
+    Phrase(Phrase),
+}
+
+impl Argument {
+    pub fn phrase(key: &'static str, arguments: &[&str]) -> Argument {
+        Argument::Phrase(Phrase::new(key, arguments))
+    }
+
+    pub fn text(&self, locale: Locale) -> String {
+        match *self {
+            Argument::Name(ref x) => x.clone(),
+            Argument::Phrase(ref x) => x.text(locale),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Argument {
+    fn from(name: &'a str) -> Argument {
+        Argument::Name(name.to_string())
+    }
+}
+
+impl From<String> for Argument {
+    fn from(name: String) -> Argument {
+        Argument::Name(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: Phrase,
+}
+
+/// A suggested edit: replace `span` with `replacement`; an empty span inserts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub message: Phrase,
+    pub span: Span,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// The message in English.
+    pub message: String,
+    /// Values of the `{}` in the template of `code`.
+    pub arguments: Vec<Argument>,
+    pub span: Span,
+    /// Related places, e.g. the previous definition of a duplicate.
+    pub labels: Vec<Label>,
+    pub notes: Vec<Phrase>,
+    pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+    /// `code` must be in `MESSAGES` and `arguments` fill its template.
+    pub fn new(severity: Severity, code: &'static str, span: Span, arguments: Vec<Argument>) -> Diagnostic {
+        let template = template(code).unwrap_or_else(|| panic!("unknown diagnostic code {}", code));
+        assert_eq!(template.matches("{}").count(), arguments.len(), "arguments of {}", code);
+        let english: Vec<String> = arguments.iter().map(|x| x.text(Locale::EnUs)).collect();
+        Diagnostic {
+            code,
+            severity,
+            message: format_template(template, &english),
+            arguments,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn error(code: &'static str, span: Span, arguments: &[&str]) -> Diagnostic {
+        Diagnostic::new(Severity::Error, code, span, arguments.iter().map(|x| Argument::from(*x)).collect())
+    }
+
+    pub fn warning(code: &'static str, span: Span, arguments: &[&str]) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, code, span, arguments.iter().map(|x| Argument::from(*x)).collect())
+    }
+
+    pub fn with_label(mut self, span: Span, message: Phrase) -> Diagnostic {
+        self.labels.push(Label { span, message });
+        self
+    }
+
+    pub fn with_note(mut self, note: Phrase) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_fix(mut self, message: Phrase, span: Span, replacement: &str) -> Diagnostic {
+        self.fixes.push(Fix { message, span, replacement: replacement.to_string() });
+        self
+    }
+
+    /// The message from the catalog of `locale`.
+    pub fn message_in(&self, locale: Locale) -> String {
+        let arguments: Vec<String> = self.arguments.iter().map(|x| x.text(locale)).collect();
+        format_template(dy_locale::message(self.code, locale).unwrap_or_default(), &arguments)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn to_json(&self, source: &SourceFile, locale: Locale) -> Json {
+        let label = |label: &Label| {
+            let mut members = vec![("message".to_string(), Json::from(label.message.text(locale).as_str()))];
+            span_members(&mut members, label.span, source.line_column(label.span.start), source.line_column(label.span.end));
+            Json::Object(members)
+        };
+        let fixes = self.fixes.iter().map(|fix| {
+            let mut members = vec![("message".to_string(), Json::from(fix.message.text(locale).as_str())),
+                ("replacement".to_string(), Json::from(fix.replacement.as_str()))];
+            span_members(&mut members, fix.span, source.line_column(fix.span.start), source.line_column(fix.span.end));
+            Json::Object(members)
+        });
+        let arguments = self.arguments.iter().map(|x| Json::from(x.text(locale).as_str())).collect();
+        let mut members = vec![
+            ("code".to_string(), Json::from(self.code)),
+            ("severity".to_string(), Json::from(self.severity.name())),
+            ("message".to_string(), Json::from(self.message_in(locale).as_str())),
+            ("arguments".to_string(), Json::Array(arguments)),
+        ];
+        span_members(&mut members, self.span, source.line_column(self.span.start), source.line_column(self.span.end));
+        members.push(("labels".to_string(), Json::Array(self.labels.iter().map(label).collect())));
+        let notes = self.notes.iter().map(|x| Json::from(x.text(locale).as_str())).collect();
+        members.push(("notes".to_string(), Json::Array(notes)));
+        members.push(("fixes".to_string(), Json::Array(fixes.collect())));
+        Json::Object(members)
+    }
+}
+
+/// `{"file": ..., "locale": ..., "diagnostics": [...]}`, messages in `locale`.
+pub fn diagnostics_to_json(diagnostics: &[Diagnostic], source: &SourceFile, locale: Locale) -> Json {
+    let diagnostics = diagnostics.iter().map(|x| x.to_json(source, locale)).collect();
+    Json::Object(vec![("file".to_string(), Json::from(source.name.as_str())),
+        ("locale".to_string(), Json::from(locale.tag())),
+        ("diagnostics".to_string(), Json::Array(diagnostics))])
+}
+
+// ------------------------------------ rendering ----------------------------------------------
+
+/// Source text for rendering; offsets are in characters like the spans.
+pub struct SourceFile {
+    pub name: String,
+    chars: Vec<char>,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> SourceFile {
+        let chars: Vec<char> = text.chars().collect();
+        let mut line_starts = vec![0];
+        line_starts.extend(chars.iter().enumerate().filter(|x| *x.1 == '\n').map(|x| x.0 + 1));
+        SourceFile { name: name.to_string(), chars, line_starts }
+    }
+
+    /// Zero based line and column.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(x) => x,
+            Err(x) => x - 1,
+        };
+        (line, offset - self.line_starts[line])
+    }
+
+    fn line(&self, line: usize) -> &[char] {
+        let start = self.line_starts[line];
+        let mut end = self.line_starts.get(line + 1).map_or(self.chars.len(), |x| x - 1);
+        if end > start && self.chars[end - 1] == '\r' {
+            end -= 1;
+        }
+        &self.chars[start..end.max(start)]
+    }
+
+    fn text(&self, span: Span) -> String {
+        self.chars[span.start.min(self.chars.len())..span.end.min(self.chars.len())].iter().collect()
+    }
+}
+
+const RESET: &'static str = "\x1b[0m";
+const BOLD: &'static str = "\x1b[1m";
+const BLUE: &'static str = "\x1b[1;34m";
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "\x1b[1;31m",
+        Severity::Warning => "\x1b[1;33m",
+        Severity::Info => "\x1b[1;36m",
+    }
+}
+
+// 终端里东亚宽字符占两列
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Renders a diagnostic with the source lines of its labels, carets under the primary span and dashes under the
+/// secondary ones, then the notes and fixes; `color` adds ANSI colors.
+pub fn render(diagnostic: &Diagnostic, source: &SourceFile, color: bool, locale: Locale) -> String {
+    let paint = |style: &str, text: &str| if color { format!("{}{}{}", style, text, RESET) } else { text.to_string() };
+    let severity = diagnostic.severity;
+    let mut out = String::new();
+    out.push_str(&paint(severity_color(severity), &format!("{}[{}]", text(severity.name(), locale), diagnostic.code)));
+    out.push_str(&paint(BOLD, &format!(": {}", diagnostic.message_in(locale))));
+    out.push('\n');
+    let mut labels: Vec<(Span, String, bool)> = vec![(diagnostic.span, String::new(), true)];
+    labels.extend(diagnostic.labels.iter().map(|x| (x.span, x.message.text(locale), false)));
+    labels.sort_by_key(|x| x.0.start);
+    let last_line = labels.iter().map(|x| source.line_column(x.0.start).0).max().unwrap_or(0);
+    let width = (last_line + 1).to_string().len();
+    let gutter = " ".repeat(width);
+    let (line, column) = source.line_column(diagnostic.span.start);
+    out.push_str(&format!("{}{} {}:{}:{}\n", gutter, paint(BLUE, "-->"), source.name, line + 1, column + 1));
+    out.push_str(&format!("{} {}\n", gutter, paint(BLUE, "|")));
+    let mut previous: Option<usize> = None;
+    for (span, message, is_primary) in labels {
+        let (line, column) = source.line_column(span.start);
+        let text = source.line(line);
+        if previous.is_some_and(|x| line > x + 1) {
+            out.push_str(&paint(BLUE, "...\n"));
+        }
+        if previous != Some(line) {
+            let number = format!("{:>width$} |", line + 1, width = width);
+            out.push_str(&format!("{} {}\n", paint(BLUE, &number), text.iter().collect::<String>()));
+        }
+        previous = Some(line);
+        // 跨行的范围只标到行尾, 制表符原样保留让标记对齐
+        let end = if source.line_column(span.end).0 == line { source.line_column(span.end).1 } else { text.len() };
+        let padding: String = text.iter().take(column)
+            .map(|x| if *x == '\t' { "\t".to_string() } else { " ".repeat(char_width(*x)) }).collect();
+        let marked: usize = text.iter().take(end).skip(column).map(|x| char_width(*x)).sum();
+        let marker = if is_primary { "^" } else { "-" }.repeat(marked.max(1));
+        let style = if is_primary { severity_color(severity) } else { BLUE };
+        let message = if message.is_empty() { message } else { format!(" {}", message) };
+        out.push_str(&format!("{} {}{}\n", paint(BLUE, &format!("{} |", gutter)), padding, paint(style, &(marker + &message))));
+    }
+    for note in &diagnostic.notes {
+        out.push_str(&format!("{} {} {}: {}\n", gutter, paint(BLUE, "="), text("note", locale), note.text(locale)));
+    }
+    for fix in &diagnostic.fixes {
+        let action = if fix.span.start == fix.span.end {
+            format_template(text("insert", locale), slice::from_ref(&fix.replacement))
+        }
+        else if fix.replacement.is_empty() {
+            format_template(text("remove", locale), &[source.text(fix.span)])
+        }
+        else {
+            format_template(text("replace", locale), &[source.text(fix.span), fix.replacement.clone()])
+        };
+        let message = fix.message.text(locale);
+        out.push_str(&format!("{} {} {}: {}: {}\n", gutter, paint(BLUE, "="), text("help", locale), message, action));
+    }
+    out
+}
+
+// ------------------------------------ pipeline -----------------------------------------------
+
+/// Options of `diagnose`.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticOptions {
+    /// Nullable reference warnings without `#nullable enable`.
+    pub nullable: bool,
+    /// Namespaces the host provides, e.g. `System`.
+    pub namespaces: Vec<String>,
+    /// Assemblies only reachable through `extern alias`, by alias.
+    pub references: Vec<(String, Declarations)>,
+}
+
+// 词法和预处理: lexer 把坏的 token 留在树里, 这里报告
+fn token_diagnostics(root: &SyntaxRef, diagnostics: &mut Vec<Diagnostic>) {
+    let mut comment: Option<Span> = None;
+    let mut groups: Vec<(String, Span)> = Vec::new();
+    let mut end = 0;
+    for token in root.tokens() {
+        let text = token.token_text();
+        let span = token.span();
+        end = end.max(span.end);
+        let kind = match token.token_kind() {
+            Some(x) => x,
+            None => continue,
+        };
+        let diagnostic = match kind {
+            TokenKind::Unknown => Diagnostic::error("DY0101", span, &[&text]),
+            TokenKind::StringLiteral if !text.starts_with('@') && !text.starts_with("$@") && !is_closed(&text, '"') =>
+                Diagnostic::error("DY0102", span, &[]),
+            TokenKind::CharLiteral if !is_closed(&text, '\'') => Diagnostic::error("DY0102", span, &[]),
+            TokenKind::CharLiteral if text == "''" => Diagnostic::error("DY0104", span, &[]),
+            TokenKind::CharLiteral if !is_single_character(&text[1..text.len() - 1]) =>
+                Diagnostic::error("DY0103", span, &[]),
+            TokenKind::Comment => {
+                if comment.is_none() && text.starts_with("/*") && !(text.len() >= 4 && text.ends_with("*/")) {
+                    comment = Some(span);
+                }
+                else if comment.is_some() && text.ends_with("*/") {
+                    comment = None;
+                }
+                continue;
+            }
+            TokenKind::PreprocessorDirectiveExpected => Diagnostic::error("DY0201", span, &[]),
+            TokenKind::PreprocessorUnexpectedDirective => Diagnostic::error("DY0202", span, &[]),
+            TokenKind::PreprocessorCommentExpected => Diagnostic::error("DY0203", span, &[]),
+            TokenKind::Preprocessor => {
+                let directive = text.trim_start_matches('#').trim().to_string();
+                match directive.as_str() {
+                    "if" | "region" => groups.push((directive, span)),
+                    "endif" | "endregion" => {
+                        groups.pop();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        diagnostics.push(diagnostic);
+    }
+    if let Some(span) = comment {
+        diagnostics.push(Diagnostic::error("DY0105", span, &[]));
+    }
+    for (directive, span) in groups {
+        let code = if directive == "if" { "DY0204" } else { "DY0205" };
+        diagnostics.push(Diagnostic::error(code, Span::new(end, end), &[])
+            .with_label(span, Phrase::new("opened-here", &[&directive])));
+    }
+}
+
+// 结束的引号不能是转义的
+fn is_closed(text: &str, quote: char) -> bool {
+    let body = text.trim_start_matches('$');
+    if body.chars().count() < 2 || !body.ends_with(quote) {
+        return false;
+    }
+    let escapes = body[..body.len() - 1].chars().rev().take_while(|x| *x == '\\').count();
+    escapes % 2 == 0
+}
+
+fn is_single_character(text: &str) -> bool {
+    let count = text.chars().count();
+    match text.strip_prefix('\\') {
+        Some(escape) if escape.starts_with('u') => count == 6,
+        Some(escape) if escape.starts_with('U') => count == 10,
+        Some(escape) if escape.starts_with('x') => (3..=6).contains(&count),
+        Some(_) => count == 2,
+        None => count == 1,
+    }
+}
+
+/// Runs every phase on the compilation units of a program, which share their declarations: a partial type or a
+/// partial method can be split across files. Diagnostics are returned per unit, in the order of `units`. Semantic
+/// checks only run when no unit has lexical or syntax errors, so that they do not report the consequences of those.
+pub fn diagnose(units: &[(&SyntaxTree, &SourceFile)], options: &DiagnosticOptions) -> Vec<Vec<Diagnostic>> {
+    let mut results: Vec<Vec<Diagnostic>> = units.iter().map(|&(tree, source)| {
+        let mut diagnostics = Vec::new();
+        token_diagnostics(&tree.root, &mut diagnostics);
+        // 坏 token 所在行的语法错误都是它引起的
+        let lexical: Vec<usize> = diagnostics.iter().map(|x| source.line_column(x.span.start).0).collect();
+        let is_lexical = |x: &&Diagnostic| lexical.contains(&source.line_column(x.span.start).0);
+        diagnostics.extend(tree.errors.iter().filter(|x| !is_lexical(x)).cloned());
+        diagnostics
+    }).collect();
+    if results.iter().all(|x| x.is_empty()) {
+        let mut declarations = Declarations::new();
+        for namespace in &options.namespaces {
+            declarations.declare_namespace(namespace);
+        }
+        for (alias, reference) in &options.references {
+            declarations.add_extern(alias, reference.clone());
+        }
+        // 先收集所有单元的声明, 再逐个单元检查
+        let declared: Vec<Vec<Diagnostic>> = units.iter().map(|x| declarations.add(&x.0.compilation_unit())).collect();
+        for ((&(tree, _), mut all), diagnostics) in units.iter().zip(declared).zip(&mut results) {
+            let root = &tree.root;
+            let unit = tree.compilation_unit();
+            all.extend(declarations.check_partial_methods(root));
+            all.extend(declarations.check_extension_methods(root));
+            all.extend(declarations.check_usings(&unit));
+            all.extend(declarations.check_names(&unit));
+            // 成员, 类型和 using 别名重名已经由声明收集报过了, 作用域只补充它没报的
+            let scope_errors: Vec<Diagnostic> = ScopeTree::bind(root).errors.into_iter()
+                .filter(|x| !all.iter().any(|y| y.span.start <= x.span.start && x.span.end <= y.span.end))
+                .collect();
+            all.extend(scope_errors);
+            all.extend(check_types(root, &declarations).errors);
+            all.extend(evaluate_constants(root, &declarations).errors);
+            all.extend(check_accessibility(root, &declarations));
+            let inheritance = check_inheritance(root, &declarations);
+            all.extend(inheritance.errors);
+            all.extend(inheritance.warnings);
+            let flow = analyze_flow(root);
+            all.extend(flow.errors);
+            all.extend(flow.warnings);
+            all.extend(check_async(root));
+            all.extend(check_iterators(root));
+            all.extend(check_using(root));
+            all.extend(analyze_nullable(root, &declarations, options.nullable).warnings);
+            // 不同阶段可能报告同一个错误
+            for diagnostic in all {
+                let is_same = |x: &Diagnostic| x.span == diagnostic.span && x.message == diagnostic.message;
+                if !diagnostics.iter().any(is_same) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+    }
+    for diagnostics in &mut results {
+        diagnostics.sort_by_key(|x| x.span.start);
+    }
+    results
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+
+    fn diagnose_code(code: &str, options: &DiagnosticOptions) -> (SourceFile, Vec<Diagnostic>) {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        let source = SourceFile::new("test.dy", code);
+        let diagnostics = diagnose(&[(&tree, &source)], options).remove(0);
+        (source, diagnostics)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(|x| format!("{} {}: {}", x.severity.name(), x.code, x.message)).collect()
+    }
+
+    #[test]
+    fn test_codes() {
+        let error = Diagnostic::error("DY0502", Span::new(0, 1), &["long", "int"]);
+        assert_eq!(error.message, "cannot implicitly convert type 'long' to 'int'. An explicit conversion exists \
+            (are you missing a cast?)");
+        assert_eq!(error.message_in(Locale::ZhCn), "无法将类型 'long' 隐式转换为 'int'. 存在一个显式转换 (是否缺少强制转换?)");
+        // 参数里的词跟着消息翻译, 名字不变
+        let arguments = vec![Argument::phrase("return-type", &[]), Argument::from("A"),
+            Argument::phrase("method-named", &["C.F()"])];
+        let error = Diagnostic::new(Severity::Error, "DY0540", Span::new(0, 1), arguments);
+        assert_eq!(error.message, "Inconsistent accessibility: return type 'A' is less accessible than method 'C.F()'");
+        let arguments = ["A".to_string(), "B".to_string()];
+        assert_eq!(format_template("{1} in {0}, {}", &arguments), "B in A, A");
+        let codes: Vec<&str> = MESSAGES.iter().map(|x| x.0).collect();
+        assert!(codes.windows(2).all(|x| x[0] < x[1]));
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let code = "#region a\nclass A {\n    char c = 'ab';\n    string s = \"x\n    int § = 1;\n}\n#bogus\n/* open";
+        let (_, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(codes(&diagnostics), [
+            "error DY0103: too many characters in character literal",
+            "error DY0102: newline in constant",
+            "error DY0101: unexpected character '§'",
+            "error DY0202: unexpected preprocessor directive",
+            "error DY0105: end-of-file found, '*/' expected",
+            "error DY0205: #endregion directive expected",
+        ]);
+        let code = "class A {\n    int F(long l) {\n        int a = l\n        int b;\n        return a + b;\n        return 0;\n    }\n}\n";
+        let (_, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(codes(&diagnostics), [
+            "error DY0502: cannot implicitly convert type 'long' to 'int'. An explicit conversion exists (are you missing a cast?)",
+            "error DY0701: use of unassigned local variable 'b'",
+            "warning DY0705: unreachable code detected",
+        ]);
+        assert_eq!(diagnostics[0].fixes, [Fix { message: Phrase::new("explicit-cast", &[]), span: Span::new(46, 47),
+            replacement: "(int)l".to_string() }]);
+        assert_eq!(diagnostics[1].labels[0].message.text(Locale::EnUs), "'b' declared here without a value");
+        let code = "class A { void F(string? s) { var n = s.Length; } }";
+        let options = DiagnosticOptions { nullable: true, ..DiagnosticOptions::default() };
+        let (_, diagnostics) = diagnose_code(code, &options);
+        assert_eq!(codes(&diagnostics), ["warning DY0901: dereference of a possibly null reference"]);
+        let code = "class A { string? Find() => null; int F() { string? s = Find(); if (s != null) { return s.Length; } \
+            return s == \"x\" ? 1 : 0; } }";
+        for nullable in [false, true] {
+            let (_, diagnostics) = diagnose_code(code, &DiagnosticOptions { nullable, ..DiagnosticOptions::default() });
+            assert_eq!(codes(&diagnostics), Vec::<String>::new());
+        }
+        // 同一个重名只报一次
+        let code = "using X = N1.Inner; using X = N2; namespace N1 { class Inner { } } \
+            namespace N2 { class B { ~B() { } ~B() { } } }";
+        let (_, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(codes(&diagnostics), [
+            "error DY0430: the using alias 'X' appeared previously in this namespace",
+            "error DY0405: type 'B' already defines a member called '~B' with the same parameter types",
+        ]);
+        // 宿主的命名空间和 extern alias 引用由调用方提供
+        let code = "extern alias Legacy; using System; class A { global::Nope.T t; Legacy::Old.V v; }";
+        let (_, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(codes(&diagnostics), [
+            "error DY0426: the extern alias 'Legacy' was not specified in a reference",
+            "error DY0429: the type or namespace name 'System' could not be found",
+            "error DY0424: the type or namespace name 'Nope' does not exist in the global namespace",
+            "error DY0426: the extern alias 'Legacy' was not specified in a reference",
+        ]);
+        let mut legacy = Declarations::new();
+        let mut dy_parser = DyParser::new("namespace Old { public class V { } }".to_string());
+        dy_parser.lexer();
+        legacy.add(&dy_parser.parse().compilation_unit());
+        let options = DiagnosticOptions { namespaces: vec!["System".to_string()],
+            references: vec![("Legacy".to_string(), legacy)], ..DiagnosticOptions::default() };
+        let (_, diagnostics) = diagnose_code(code, &options);
+        assert_eq!(codes(&diagnostics),
+            ["error DY0424: the type or namespace name 'Nope' does not exist in the global namespace"]);
+    }
+
+    #[test]
+    fn test_diagnose_units() {
+        // 部分方法的定义和实现在不同的文件里, 错误报在它所在的文件
+        let parse = |code: &str| {
+            let mut dy_parser = DyParser::new(code.to_string());
+            dy_parser.lexer();
+            dy_parser.parse()
+        };
+        let first = "namespace G { partial class P { partial void OnInit(); } }";
+        let second = "namespace G { partial class P { partial void OnInit() { } int F() { return \"x\"; } } }";
+        let (first_tree, second_tree) = (parse(first), parse(second));
+        let (first_source, second_source) = (SourceFile::new("p1.dy", first), SourceFile::new("p2.dy", second));
+        let units = [(&first_tree, &first_source), (&second_tree, &second_source)];
+        let diagnostics = diagnose(&units, &DiagnosticOptions::default());
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(codes(&diagnostics[0]), Vec::<String>::new());
+        assert_eq!(codes(&diagnostics[1]), ["error DY0501: cannot implicitly convert type 'string' to 'int'"]);
+        let diagnostics = diagnose(&units[1..], &DiagnosticOptions::default());
+        assert_eq!(diagnostics[0][0].code, "DY0414");
+        // 一个文件有语法错误时不做语义检查
+        let broken = parse("class Q {");
+        let broken_source = SourceFile::new("q.dy", "class Q {");
+        let diagnostics = diagnose(&[units[1], (&broken, &broken_source)], &DiagnosticOptions::default());
+        assert!(diagnostics[0].is_empty());
+        assert_eq!(diagnostics[1][0].code, "DY0302");
+    }
+
+    #[test]
+    fn test_render() {
+        let code = "class A {\n    void F() {\n        int x = 2 int y = 3;\n    }\n}\n";
+        let (source, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(render(&diagnostics[0], &source, false, Locale::EnUs), "\
+error[DY0301]: expected ';', found 'int'
+ --> test.dy:3:19
+  |
+3 |         int x = 2 int y = 3;
+  |                   ^^^
+  = help: insert ';': insert `;`
+");
+        assert_eq!(diagnostics[0].fixes[0].span, Span::new(42, 42));
+        let colored = render(&diagnostics[0], &source, true, Locale::EnUs);
+        assert!(colored.starts_with("\x1b[1;31merror[DY0301]\x1b[0m\x1b[1m: expected"));
+        let code = "class A {\n    void F() {\n        int x = 1;\n\n\n        int x = 2;\n    }\n}\n";
+        let (source, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(render(&diagnostics[0], &source, false, Locale::EnUs), "\
+error[DY0402]: a local variable or parameter named 'x' is already defined in this scope
+ --> test.dy:6:13
+  |
+3 |         int x = 1;
+  |             - previous definition of 'x' here
+...
+6 |         int x = 2;
+  |             ^
+");
+        assert_eq!(render(&diagnostics[0], &source, false, Locale::ZhCn), "\
+错误[DY0402]: 已在此范围内定义了名为 'x' 的局部变量或参数
+ --> test.dy:6:13
+  |
+3 |         int x = 1;
+  |             - 'x' 之前在这里定义
+...
+6 |         int x = 2;
+  |             ^
+");
+        let json = diagnostics_to_json(&diagnostics, &source, Locale::EnUs);
+        let diagnostic = &json.get("diagnostics").and_then(|x| x.as_array()).unwrap()[0];
+        assert_eq!(diagnostic.get("code").and_then(|x| x.as_str()), Some("DY0402"));
+        assert_eq!(diagnostic.get("arguments").unwrap().to_string(), "[\"x\"]");
+        assert_eq!(diagnostic.get("start").unwrap().to_string(), "{\"line\":6,\"column\":13}");
+        assert_eq!(diagnostic.get("labels").unwrap().to_string(), "[{\"message\":\"previous definition of 'x' here\",\
+            \"span\":[37,38],\"start\":{\"line\":3,\"column\":13},\"end\":{\"line\":3,\"column\":14}}]");
+        // 宽字符占两列, 标记按显示宽度对齐
+        let code = "class A { void F() { string s = \"中文\"; int y = s; } }";
+        let (source, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert_eq!(render(&diagnostics[0], &source, false, Locale::EnUs), "\
+error[DY0501]: cannot implicitly convert type 'string' to 'int'
+ --> test.dy:1:47
+  |
+1 | class A { void F() { string s = \"中文\"; int y = s; } }
+  |                                                 ^
+");
+        let code = "class A { int F() { return \"中文\"; } }";
+        let (source, diagnostics) = diagnose_code(code, &DiagnosticOptions::default());
+        assert!(render(&diagnostics[0], &source, false, Locale::EnUs).ends_with("\n  |                            ^^^^^^\n"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use vm::dy_locale::error_message;
+
+// ------------------------------------ disposal -----------------------------------------------
+// using 结束时 (包括异常退出) 调用 Dispose. 宿主把引擎资源包成 NativeHandle 交给脚本,
+// 释放函数只会执行一次, 释放以后再访问报 ObjectDisposedException
+
+pub trait Disposable {
+    fn dispose(&mut self);
+
+    fn is_disposed(&self) -> bool;
+}
+
+pub type DisposableRef = Rc<RefCell<dyn Disposable>>;
+
+/// A native resource marked disposable by the host.
+pub struct NativeHandle<T> {
+    value: Option<T>,
+    release: Option<Box<dyn FnOnce(T)>>,
+}
+
+impl<T> NativeHandle<T> {
+    /// `release` runs on Dispose, or when the handle is dropped without being disposed.
+    pub fn new<F: FnOnce(T) + 'static>(value: T, release: F) -> NativeHandle<T> {
+        NativeHandle { value: Some(value), release: Some(Box::new(release)) }
+    }
+
+    pub fn get(&self) -> Result<&T, String> {
+        self.value.as_ref().ok_or_else(|| error_message("DY1001", &[]))
+    }
+
+    pub fn get_mut(&mut self) -> Result<&mut T, String> {
+        self.value.as_mut().ok_or_else(|| error_message("DY1001", &[]))
+    }
+}
+
+impl<T> Disposable for NativeHandle<T> {
+    fn dispose(&mut self) {
+        if let (Some(value), Some(release)) = (self.value.take(), self.release.take()) {
+            release(value);
+        }
+    }
+
+    fn is_disposed(&self) -> bool {
+        self.value.is_none()
+    }
+}
+
+impl<T> Drop for NativeHandle<T> {
+    fn drop(&mut self) {
+        self.dispose();
+    }
+}
+
+/// Resources of the using blocks being executed; leaving a block disposes them in reverse order,
+/// dropping the scope (an error or panic unwinding through it) disposes the rest.
+pub struct DisposeScope {
+    resources: Vec<DisposableRef>,
+    // 每个 using 块开始时 resources 的长度
+    marks: Vec<usize>,
+}
+
+impl DisposeScope {
+    pub fn new() -> DisposeScope {
+        DisposeScope { resources: Vec::new(), marks: Vec::new() }
+    }
+
+    /// Enters a using block.
+    pub fn enter(&mut self) {
+        self.marks.push(self.resources.len());
+    }
+
+    /// Registers a resource of the innermost block; null resources are simply not tracked.
+    pub fn track(&mut self, resource: DisposableRef) {
+        self.resources.push(resource);
+    }
+
+    /// Leaves the innermost block, disposing its resources last-declared first.
+    pub fn exit(&mut self) {
+        let mark = self.marks.pop().unwrap_or(0);
+        while self.resources.len() > mark {
+            let resource = self.resources.pop().unwrap();
+            resource.borrow_mut().dispose();
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.marks.len()
+    }
+}
+
+impl Default for DisposeScope {
+    fn default() -> Self {
+        DisposeScope::new()
+    }
+}
+
+impl Drop for DisposeScope {
+    fn drop(&mut self) {
+        while !self.marks.is_empty() {
+            self.exit();
+        }
+        self.exit();
+    }
+}
+
+/// `using (resource) { body }` for host code: disposes after `body`, whether it returns Ok, Err or panics.
+pub fn using<R, F: FnOnce() -> R>(resource: DisposableRef, body: F) -> R {
+    let mut scope = DisposeScope::new();
+    scope.enter();
+    scope.track(resource);
+    body()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::panic;
+    use vm::dy_locale::{set_locale, Locale};
+
+    fn handle(name: &'static str, log: &Rc<RefCell<Vec<&'static str>>>) -> Rc<RefCell<NativeHandle<&'static str>>> {
+        let log = log.clone();
+        Rc::new(RefCell::new(NativeHandle::new(name, move |x| log.borrow_mut().push(x))))
+    }
+
+    #[test]
+    fn test_dispose_scope() {
+        set_locale(Some(Locale::EnUs));
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let (a, b, c) = (handle("a", &log), handle("b", &log), handle("c", &log));
+        let mut scope = DisposeScope::new();
+        scope.enter();
+        scope.track(a.clone());
+        scope.enter();
+        scope.track(b.clone());
+        scope.track(c.clone());
+        scope.exit();
+        assert_eq!(*log.borrow(), ["c", "b"]);
+        assert!(b.borrow().is_disposed());
+        assert_eq!(b.borrow().get().unwrap_err(), "cannot access a disposed object");
+        assert_eq!(*a.borrow().get().unwrap(), "a");
+
+        // 重复 Dispose 不会再释放
+        b.borrow_mut().dispose();
+        assert_eq!(scope.depth(), 1);
+        drop(scope);
+        assert_eq!(*log.borrow(), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_using() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let result: Result<i32, String> = using(handle("file", &log), || Err("read failed".to_string()));
+        assert!(result.is_err());
+        assert_eq!(*log.borrow(), ["file"]);
+
+        let resource = handle("socket", &log);
+        let caught = panic::catch_unwind(panic::AssertUnwindSafe(|| using(resource.clone(), || panic!("script error"))));
+        assert!(caught.is_err());
+        assert!(resource.borrow().is_disposed());
+        assert_eq!(*log.borrow(), ["file", "socket"]);
+
+        drop(handle("leaked", &log));
+        assert_eq!(log.borrow().last(), Some(&"leaked"));
+    }
+}
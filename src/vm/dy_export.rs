@@ -0,0 +1,192 @@
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_json::Json;
+use vm::dy_parser::{DyParser, Span, TokenKind};
+use vm::dy_syntax::{SyntaxKind, SyntaxNode, SyntaxRef, SyntaxTree};
+
+// ------------------------------------ export -------------------------------------------------
+// 语法树导出成 JSON 和 S 表达式, 给 Python/TypeScript 等外部工具使用
+// 偏移按字符 (Unicode scalar) 计算, 从 0 开始; 行列从 1 开始, 结束位置不包含
+
+fn position(line_column: (usize, usize)) -> Json {
+    Json::Object(vec![("line".to_string(), Json::from(line_column.0 + 1)),
+        ("column".to_string(), Json::from(line_column.1 + 1))])
+}
+
+pub fn span_members(members: &mut Vec<(String, Json)>, span: Span, start: (usize, usize), end: (usize, usize)) {
+    members.push(("span".to_string(), Json::Array(vec![Json::from(span.start), Json::from(span.end)])));
+    members.push(("start".to_string(), position(start)));
+    members.push(("end".to_string(), position(end)));
+}
+
+/// Lexer tokens, trivia included, as a JSON array.
+pub fn tokens_to_json(dy_parser: &DyParser) -> Json {
+    let tokens = dy_parser.tokens().iter().map(|token| {
+        let span = token.span();
+        let mut members = vec![("kind".to_string(), Json::from(format!("{:?}", token.kind))),
+            ("text".to_string(), Json::from(dy_parser.token_text(token)))];
+        span_members(&mut members, span, dy_parser.line_column(span.start), dy_parser.line_column(span.end));
+        Json::Object(members)
+    });
+    Json::Array(tokens.collect())
+}
+
+/// Syntax errors and the tree, `{"errors": [...], "root": {...}}`.
+pub fn tree_to_json(tree: &SyntaxTree, trivia: bool) -> Json {
+    let errors = tree.errors.iter().map(|error| {
+        let mut members = vec![("message".to_string(), Json::from(error.message.as_str()))];
+        span_members(&mut members, error.span, tree.line_column(error.span.start), tree.line_column(error.span.end));
+        Json::Object(members)
+    });
+    Json::Object(vec![("errors".to_string(), Json::Array(errors.collect())),
+        ("root".to_string(), node_to_json(tree, &tree.root, trivia))])
+}
+
+pub fn node_to_json(tree: &SyntaxTree, node: &SyntaxRef, trivia: bool) -> Json {
+    let span = node.span();
+    let mut members = vec![("kind".to_string(), Json::from(format!("{:?}", node.kind())))];
+    if let Some(kind) = node.token_kind() {
+        members.push(("token".to_string(), Json::from(format!("{:?}", kind))));
+        members.push(("text".to_string(), Json::from(node.text())));
+    }
+    span_members(&mut members, span, tree.line_column(span.start), tree.line_column(span.end));
+    let flags = node.flags();
+    if flags != 0 {
+        members.push(("flags".to_string(), Json::from(flags)));
+        if let Some(x) = SemanticFlags::declaration(flags) {
+            members.push(("declaration".to_string(), Json::from(format!("{:?}", x))));
+        }
+        if let Some(x) = SemanticFlags::scope(flags) {
+            members.push(("scope".to_string(), Json::from(format!("{:?}", x))));
+        }
+    }
+    if !node.is_token() {
+        let children = node.children().filter(|x| trivia || !x.is_trivia()).map(|x| node_to_json(tree, &x, trivia));
+        members.push(("children".to_string(), Json::Array(children.collect())));
+    }
+    Json::Object(members)
+}
+
+/// Rebuilds a tree from `tree_to_json` or `node_to_json` output.
+pub fn tree_from_json(json: &Json) -> Result<SyntaxRef, String> {
+    match json.get("root") {
+        Some(root) => node_from_json(root),
+        None => node_from_json(json),
+    }
+}
+
+fn node_from_json(json: &Json) -> Result<SyntaxRef, String> {
+    let name = json.get("kind").and_then(|x| x.as_str()).ok_or("node without kind")?;
+    let kind = SyntaxKind::from_name(name).ok_or_else(|| format!("unknown node kind '{}'", name))?;
+    let span = match json.get("span").and_then(|x| x.as_array()) {
+        Some(x) if x.len() == 2 => {
+            let start = x[0].as_usize().ok_or("invalid span")?;
+            let end = x[1].as_usize().ok_or("invalid span")?;
+            Span::new(start, end)
+        }
+        Some(_) => return Err("invalid span".to_string()),
+        None => Span::default(),
+    };
+    if kind == SyntaxKind::Token {
+        let name = json.get("token").and_then(|x| x.as_str()).ok_or("token without token kind")?;
+        let token_kind = TokenKind::from_name(name).ok_or_else(|| format!("unknown token kind '{}'", name))?;
+        let text = json.get("text").and_then(|x| x.as_str()).ok_or("token without text")?;
+        return Ok(SyntaxRef::new(SyntaxNode::token(token_kind, text.to_string(), span)));
+    }
+    let flags = json.get("flags").and_then(|x| x.as_f64()).unwrap_or(0.0) as i32;
+    let mut data = SyntaxNode::new(kind, flags);
+    data.span = span;
+    let node = SyntaxRef::new(data);
+    if let Some(children) = json.get("children").and_then(|x| x.as_array()) {
+        for child in children {
+            node.append(&node_from_json(child)?);
+        }
+    }
+    Ok(node)
+}
+
+/// Indented S-expression, one node per line:
+/// `(Kind start..end line:column-line:column :declaration X :scope Y children...)`.
+pub fn tree_to_sexpr(tree: &SyntaxTree, trivia: bool) -> String {
+    let mut out = String::new();
+    write_sexpr(&mut out, tree, &tree.root, trivia, 0);
+    out.push('\n');
+    out
+}
+
+fn write_sexpr(out: &mut String, tree: &SyntaxTree, node: &SyntaxRef, trivia: bool, depth: usize) {
+    let span = node.span();
+    let start = tree.line_column(span.start);
+    let end = tree.line_column(span.end);
+    out.push_str(&"  ".repeat(depth));
+    out.push('(');
+    match node.token_kind() {
+        Some(kind) => out.push_str(&format!("{:?} {:?}", kind, node.text())),
+        None => out.push_str(&format!("{:?}", node.kind())),
+    }
+    out.push_str(&format!(" {}..{} {}:{}-{}:{}", span.start, span.end, start.0 + 1, start.1 + 1,
+        end.0 + 1, end.1 + 1));
+    if let Some(x) = SemanticFlags::declaration(node.flags()) {
+        out.push_str(&format!(" :declaration {:?}", x));
+    }
+    if let Some(x) = SemanticFlags::scope(node.flags()) {
+        out.push_str(&format!(" :scope {:?}", x));
+    }
+    for child in node.children().filter(|x| trivia || !x.is_trivia()) {
+        out.push('\n');
+        write_sexpr(out, tree, &child, trivia, depth + 1);
+    }
+    out.push(')');
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(code: &str) -> (DyParser, SyntaxTree) {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        (dy_parser, tree)
+    }
+
+    #[test]
+    fn test_json() {
+        let (dy_parser, tree) = parse("class A\n{\n\tint x = 1;\n}");
+        let tokens = tokens_to_json(&dy_parser);
+        let x = &tokens.as_array().unwrap()[9];
+        assert_eq!(x.get("text").unwrap().as_str(), Some("x"));
+        assert_eq!(x.get("start").unwrap().get("line").unwrap().as_usize(), Some(3));
+        assert_eq!(x.get("start").unwrap().get("column").unwrap().as_usize(), Some(6));
+
+        let json = tree_to_json(&tree, true);
+        let class = &json.get("root").unwrap().get("children").unwrap().as_array().unwrap()[0];
+        assert_eq!(class.get("kind").unwrap().as_str(), Some("ClassDeclaration"));
+        assert_eq!(class.get("scope").unwrap().as_str(), Some("TypeDeclarationScope"));
+
+        // 导出再导入, 结构和文本不变
+        let node = tree_from_json(&Json::parse(&json.to_string()).unwrap()).unwrap();
+        assert_eq!(node.text(), tree.text());
+        let copy = SyntaxTree::new(node, Vec::new(), tree.line_offsets().clone());
+        assert_eq!(tree_to_json(&copy, true), json);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        assert!(tree_from_json(&Json::parse(r#"{"kind": "Nope"}"#).unwrap()).is_err());
+        assert!(tree_from_json(&Json::parse(r#"{"kind": "Token", "token": "Identifier"}"#).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_sexpr() {
+        let (_, tree) = parse("using A;");
+        let expected = "(CompilationUnit 0..8 1:1-1:9 :scope CompilationUnitScope
+  (UsingDirective 0..8 1:1-1:9 :declaration UsingNamespace
+    (Keyword \"using\" 0..5 1:1-1:6)
+    (IdentifierName 6..7 1:7-1:8
+      (Identifier \"A\" 6..7 1:7-1:8))
+    (PunctuatorOrOperator \";\" 7..8 1:8-1:9)))
+";
+        assert_eq!(tree_to_sexpr(&tree, false), expected);
+    }
+}
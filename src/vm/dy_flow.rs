@@ -0,0 +1,1059 @@
+use vm::dy_binder::ScopeTree;
+use vm::dy_diagnostic::{Diagnostic, Phrase};
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_lower::{is_function, is_async, enclosing_function, function_return_type};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, Expression, Statement, Parameter, PropertyDeclaration,
+    VariableDeclarator, Argument};
+
+// ------------------------------------ control flow -------------------------------------------
+// 函数体的控制流图: 基本块里按求值顺序记录语句的开始和局部变量的读写, 块之间是可能的跳转
+// 图上做可达性和明确赋值分析, 报告未赋值的读取, 没有赋值的 out 参数, 缺少的返回值, 不可达的代码和 case 贯穿
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Start of a statement, for reachability.
+    Statement(SyntaxRef),
+    /// Read of a tracked variable.
+    Read(usize, SyntaxRef),
+    /// Write of a tracked variable, with the assigned value when there is one.
+    Write(usize, Option<SyntaxRef>),
+    /// Member or element access on the value of an expression, after the expression is evaluated.
+    Dereference(SyntaxRef),
+    /// Start of a block where the condition is known to be true or false.
+    Assume(SyntaxRef, bool),
+    /// A value stored in a declaration or returned, after it is evaluated.
+    Store(SyntaxRef),
+    /// Write of a name or `this.F` that is not a tracked variable, with the assignment when there is one.
+    Assign(SyntaxRef, Option<SyntaxRef>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock {
+    pub actions: Vec<Action>,
+    pub successors: Vec<usize>,
+}
+
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    /// Locals and parameters of the function, by declaring node; parameters other than `out` are assigned on entry.
+    pub variables: Vec<SyntaxRef>,
+    /// The block where control falls off the end of the body.
+    pub end: Option<usize>,
+    /// Switch sections whose end is in the given block.
+    pub section_ends: Vec<(SyntaxRef, usize)>,
+}
+
+pub const ENTRY: usize = 0;
+pub const EXIT: usize = 1;
+
+struct Jump {
+    is_loop: bool,
+    break_to: usize,
+    continue_to: usize,
+    /// Switch sections with their first block, for `goto case`.
+    sections: Vec<(SyntaxRef, usize)>,
+}
+
+struct Builder<'a> {
+    scopes: &'a ScopeTree,
+    function: SyntaxRef,
+    graph: ControlFlowGraph,
+    current: Option<usize>,
+    jumps: Vec<Jump>,
+    labels: Vec<(String, usize)>,
+}
+
+fn bool_constant(node: &SyntaxRef) -> Option<bool> {
+    match node.kind() {
+        SyntaxKind::LiteralExpression => match node.token_text().as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        },
+        SyntaxKind::ParenthesizedExpression => node.child_nodes().pop().and_then(|x| bool_constant(&x)),
+        _ => None,
+    }
+}
+
+fn expressions(node: &SyntaxRef) -> Vec<SyntaxRef> {
+    node.child_nodes().into_iter().filter(|x| Expression::cast(x.clone()).is_some()).collect()
+}
+
+fn statements(node: &SyntaxRef) -> Vec<SyntaxRef> {
+    node.child_nodes().into_iter().filter(|x| Statement::cast(x.clone()).is_some()).collect()
+}
+
+fn is_out_parameter(node: &SyntaxRef) -> bool {
+    Parameter::cast(node.clone()).is_some_and(|x| x.modifiers().iter().any(|x| x == "out"))
+}
+
+// case 1: 这样的标签文本
+fn label_text(label: &SyntaxRef) -> String {
+    let tokens: Vec<String> = label.significant_tokens().iter().map(|x| x.token_text()).collect();
+    tokens.join(" ").replace(" :", ":")
+}
+
+impl<'a> Builder<'a> {
+    fn new_block(&mut self) -> usize {
+        self.graph.blocks.push(BasicBlock::default());
+        self.graph.blocks.len() - 1
+    }
+
+    fn link(&mut self, from: usize, to: usize) {
+        if !self.graph.blocks[from].successors.contains(&to) {
+            self.graph.blocks[from].successors.push(to);
+        }
+    }
+
+    // 当前块跳到 target, 当前位置之后不可达
+    fn jump(&mut self, target: usize) {
+        if let Some(current) = self.current.take() {
+            self.link(current, target);
+        }
+    }
+
+    // 当前块流入 target 并从 target 继续
+    fn enter(&mut self, target: usize) {
+        self.jump(target);
+        self.current = Some(target);
+    }
+
+    // 跳转之后的代码放在一个没有前驱的新块里
+    fn block(&mut self) -> usize {
+        match self.current {
+            Some(x) => x,
+            None => {
+                let block = self.new_block();
+                self.current = Some(block);
+                block
+            }
+        }
+    }
+
+    fn act(&mut self, action: Action) {
+        let block = self.block();
+        self.graph.blocks[block].actions.push(action);
+    }
+
+    fn variable(&mut self, declaration: &SyntaxRef) -> Option<usize> {
+        if let Some(i) = self.graph.variables.iter().position(|x| x == declaration) {
+            return Some(i);
+        }
+        let is_local = declaration.declaration() == Some(SemanticFlags::LocalVariableDeclarator);
+        if !is_local || enclosing_function(declaration).as_ref() != Some(&self.function) {
+            return None;
+        }
+        self.graph.variables.push(declaration.clone());
+        Some(self.graph.variables.len() - 1)
+    }
+
+    fn write(&mut self, declaration: &SyntaxRef, value: Option<&SyntaxRef>) {
+        if let Some(i) = self.variable(declaration) {
+            self.act(Action::Write(i, value.cloned()));
+        }
+    }
+
+    // 名字引用的跟踪变量
+    fn referenced(&mut self, name: &SyntaxRef) -> Option<usize> {
+        let symbol = self.scopes.lookup(&name.token_text(), name.span().start).into_iter().next()?;
+        if !symbol.is_local() {
+            return None;
+        }
+        self.variable(&symbol.node)
+    }
+
+    // ------------------------------------ statements ----------------------------------------
+
+    fn statement(&mut self, node: &SyntaxRef) {
+        // 标签语句从 goto 能到达的块开始
+        if node.kind() == SyntaxKind::LabeledStatement {
+            let name = node.identifier().map(|x| x.token_text()).unwrap_or_default();
+            let label = self.label(&name);
+            self.enter(label);
+        }
+        if node.kind() != SyntaxKind::Block {
+            self.act(Action::Statement(node.clone()));
+        }
+        match node.kind() {
+            SyntaxKind::Block => {
+                for child in statements(node) {
+                    self.statement(&child);
+                }
+            }
+            SyntaxKind::IfStatement => self.if_statement(node),
+            SyntaxKind::WhileStatement => {
+                let header = self.new_block();
+                self.enter(header);
+                let branches = self.condition(expressions(node).pop().as_ref());
+                self.loop_body(node, header, header, branches, |_| {});
+            }
+            SyntaxKind::DoStatement => self.do_statement(node),
+            SyntaxKind::ForStatement => self.for_statement(node),
+            SyntaxKind::ForEachStatement => {
+                if let Some(collection) = expressions(node).pop() {
+                    self.expression(&collection);
+                    self.act(Action::Dereference(collection));
+                }
+                let header = self.new_block();
+                self.enter(header);
+                let branches = self.fork();
+                self.loop_body(node, header, header, branches, |_| {});
+            }
+            SyntaxKind::SwitchStatement => self.switch_statement(node),
+            SyntaxKind::BreakStatement => {
+                if let Some(target) = self.jumps.last().map(|x| x.break_to) {
+                    self.jump(target);
+                }
+            }
+            SyntaxKind::ContinueStatement => {
+                if let Some(target) = self.jumps.iter().rev().find(|x| x.is_loop).map(|x| x.continue_to) {
+                    self.jump(target);
+                }
+            }
+            SyntaxKind::ReturnStatement | SyntaxKind::YieldBreakStatement => {
+                for child in expressions(node) {
+                    self.expression(&child);
+                    self.act(Action::Store(child));
+                }
+                self.jump(EXIT);
+            }
+            SyntaxKind::ThrowStatement => {
+                for child in expressions(node) {
+                    self.expression(&child);
+                }
+                self.current = None;
+            }
+            SyntaxKind::GotoStatement => self.goto_statement(node),
+            SyntaxKind::LabeledStatement => {
+                for child in statements(node) {
+                    self.statement(&child);
+                }
+            }
+            SyntaxKind::TryStatement => self.try_statement(node),
+            _ => {
+                // 表达式语句, 声明, using, lock, checked, yield return: 先算表达式再执行里面的语句
+                for child in node.child_nodes() {
+                    if Statement::cast(child.clone()).is_some() {
+                        self.statement(&child);
+                    }
+                    else {
+                        self.expression(&child);
+                    }
+                }
+            }
+        }
+    }
+
+    fn if_statement(&mut self, node: &SyntaxRef) {
+        let (when_true, when_false) = self.condition(expressions(node).pop().as_ref());
+        let join = self.new_block();
+        self.current = Some(when_true);
+        for child in statements(node) {
+            self.statement(&child);
+        }
+        self.jump(join);
+        self.current = Some(when_false);
+        if let Some(clause) = node.child_of(SyntaxKind::ElseClause) {
+            for child in statements(&clause) {
+                self.statement(&child);
+            }
+        }
+        self.jump(join);
+        self.current = Some(join);
+    }
+
+    // 分成两条都可能走的路
+    fn fork(&mut self) -> (usize, usize) {
+        let start = self.block();
+        let when_true = self.new_block();
+        let when_false = self.new_block();
+        self.link(start, when_true);
+        self.link(start, when_false);
+        self.current = None;
+        (when_true, when_false)
+    }
+
+    // 条件为真和为假时的块; && 和 || 的右边只在一边赋值, 常量 true 和 false 剪掉另一边
+    fn condition(&mut self, node: Option<&SyntaxRef>) -> (usize, usize) {
+        let node = match node {
+            Some(x) => x,
+            None => {
+                let start = self.block();
+                let when_true = self.new_block();
+                self.link(start, when_true);
+                self.current = None;
+                return (when_true, self.new_block());
+            }
+        };
+        let operands = expressions(node);
+        let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+        match node.kind() {
+            SyntaxKind::ParenthesizedExpression if operands.len() == 1 => self.condition(operands.first()),
+            SyntaxKind::PrefixUnaryExpression if op == "!" && operands.len() == 1 => {
+                let (when_true, when_false) = self.condition(operands.first());
+                (when_false, when_true)
+            }
+            SyntaxKind::BinaryExpression if (op == "&&" || op == "||") && operands.len() == 2 => {
+                let (left_true, left_false) = self.condition(operands.first());
+                let join = self.new_block();
+                if op == "&&" {
+                    self.current = Some(left_true);
+                    let (when_true, when_false) = self.condition(operands.last());
+                    self.link(left_false, join);
+                    self.link(when_false, join);
+                    (when_true, join)
+                }
+                else {
+                    self.current = Some(left_false);
+                    let (when_true, when_false) = self.condition(operands.last());
+                    self.link(left_true, join);
+                    self.link(when_true, join);
+                    (join, when_false)
+                }
+            }
+            _ => {
+                let constant = bool_constant(node);
+                self.expression(node);
+                let start = self.block();
+                let when_true = self.new_block();
+                let when_false = self.new_block();
+                self.graph.blocks[when_true].actions.push(Action::Assume(node.clone(), true));
+                self.graph.blocks[when_false].actions.push(Action::Assume(node.clone(), false));
+                if constant != Some(false) {
+                    self.link(start, when_true);
+                }
+                if constant != Some(true) {
+                    self.link(start, when_false);
+                }
+                self.current = None;
+                (when_true, when_false)
+            }
+        }
+    }
+
+    // 循环体和出口, header 是条件所在的块; continue 跳到 next, 默认就是 header
+    fn loop_body<F: FnOnce(&mut Builder<'a>)>(&mut self, node: &SyntaxRef, header: usize, next: usize,
+        (when_true, when_false): (usize, usize), after_body: F) {
+        self.current = Some(when_true);
+        if node.kind() == SyntaxKind::ForEachStatement {
+            if let Some(variable) = node.child_of(SyntaxKind::VariableDeclarator) {
+                self.write(&variable, None);
+            }
+        }
+        self.jumps.push(Jump { is_loop: true, break_to: when_false, continue_to: next, sections: Vec::new() });
+        for child in statements(node) {
+            self.statement(&child);
+        }
+        self.jumps.pop();
+        if next != header {
+            self.enter(next);
+            after_body(self);
+        }
+        self.jump(header);
+        self.current = Some(when_false);
+    }
+
+    fn do_statement(&mut self, node: &SyntaxRef) {
+        let body = self.new_block();
+        let next = self.new_block();
+        let exit = self.new_block();
+        self.enter(body);
+        self.jumps.push(Jump { is_loop: true, break_to: exit, continue_to: next, sections: Vec::new() });
+        for child in statements(node) {
+            self.statement(&child);
+        }
+        self.jumps.pop();
+        self.enter(next);
+        let (when_true, when_false) = self.condition(expressions(node).pop().as_ref());
+        self.current = Some(when_true);
+        self.jump(body);
+        self.current = Some(when_false);
+        self.enter(exit);
+    }
+
+    // for (初始化; 条件; 迭代) 按分号分成三段
+    fn for_statement(&mut self, node: &SyntaxRef) {
+        let mut parts: Vec<Vec<SyntaxRef>> = vec![Vec::new(), Vec::new(), Vec::new()];
+        let mut part = 0;
+        for child in node.children() {
+            if child.kind() == SyntaxKind::Token {
+                part += (child.token_text() == ";") as usize;
+            }
+            else if Statement::cast(child.clone()).is_none() && part < 3 {
+                parts[part].push(child);
+            }
+        }
+        for child in &parts[0] {
+            self.expression(child);
+        }
+        let header = self.new_block();
+        self.enter(header);
+        let branches = self.condition(parts[1].first());
+        let next = self.new_block();
+        let iterators = parts[2].clone();
+        self.loop_body(node, header, next, branches, move |builder| {
+            for child in &iterators {
+                builder.expression(child);
+            }
+        });
+    }
+
+    fn switch_statement(&mut self, node: &SyntaxRef) {
+        for child in expressions(node) {
+            self.expression(&child);
+        }
+        let switch = self.block();
+        let exit = self.new_block();
+        let sections: Vec<(SyntaxRef, usize)> = node.children_of(SyntaxKind::SwitchSection).into_iter()
+            .map(|x| (x, self.new_block()))
+            .collect();
+        for &(_, block) in &sections {
+            self.link(switch, block);
+        }
+        let has_default = sections.iter().any(|x| x.0.child_of(SyntaxKind::DefaultSwitchLabel).is_some());
+        if !has_default {
+            self.link(switch, exit);
+        }
+        self.jumps.push(Jump { is_loop: false, break_to: exit, continue_to: exit, sections: sections.clone() });
+        for (section, block) in sections {
+            self.current = Some(block);
+            for child in section.child_nodes() {
+                match Statement::cast(child.clone()) {
+                    Some(_) => self.statement(&child),
+                    None => self.expression(&child),
+                }
+            }
+            if let Some(end) = self.current.take() {
+                self.graph.section_ends.push((section, end));
+            }
+        }
+        self.jumps.pop();
+        self.current = Some(exit);
+    }
+
+    fn goto_statement(&mut self, node: &SyntaxRef) {
+        let target = if node.has_child_token("case") || node.has_child_token("default") {
+            // goto case 1 找值写法一样的 case 标签
+            let value = expressions(node).pop().map(|x| x.token_text());
+            let sections = self.jumps.iter().rev().find(|x| !x.is_loop).map(|x| x.sections.clone()).unwrap_or_default();
+            sections.into_iter().find(|x| x.0.child_nodes().iter().any(|label| match value {
+                Some(ref value) => label.kind() == SyntaxKind::CaseSwitchLabel
+                    && expressions(label).first().map(|x| x.token_text()).as_ref() == Some(value),
+                None => label.kind() == SyntaxKind::DefaultSwitchLabel,
+            })).map(|x| x.1)
+        }
+        else {
+            let name = node.identifier().map(|x| x.token_text()).unwrap_or_default();
+            Some(self.label(&name))
+        };
+        match target {
+            Some(target) => self.jump(target),
+            None => self.current = None,
+        }
+    }
+
+    fn label(&mut self, name: &str) -> usize {
+        if let Some(x) = self.labels.iter().find(|x| x.0 == name) {
+            return x.1;
+        }
+        let block = self.new_block();
+        self.labels.push((name.to_string(), block));
+        block
+    }
+
+    // catch 从 try 开始时的状态进入, try 里任何地方都可能抛出; finally 只按正常完成的路径连
+    fn try_statement(&mut self, node: &SyntaxRef) {
+        let start = self.block();
+        let join = self.new_block();
+        let mut ends = Vec::new();
+        for child in node.child_nodes() {
+            match child.kind() {
+                SyntaxKind::Block => {
+                    let body = self.new_block();
+                    self.enter(body);
+                    self.statement(&child);
+                    ends.extend(self.current.take());
+                }
+                SyntaxKind::CatchClause => {
+                    let catch = self.new_block();
+                    self.link(start, catch);
+                    self.current = Some(catch);
+                    for part in child.child_nodes() {
+                        match part.kind() {
+                            SyntaxKind::Block => self.statement(&part),
+                            _ => self.expression(&part),
+                        }
+                    }
+                    ends.extend(self.current.take());
+                }
+                SyntaxKind::FinallyClause => {
+                    let finally = self.new_block();
+                    for &end in &ends {
+                        self.link(end, finally);
+                    }
+                    ends.clear();
+                    self.current = Some(finally);
+                    for part in statements(&child) {
+                        self.statement(&part);
+                    }
+                    ends.extend(self.current.take());
+                }
+                _ => {}
+            }
+        }
+        for end in ends {
+            self.link(end, join);
+        }
+        self.current = Some(join);
+    }
+
+    // ------------------------------------ expressions ---------------------------------------
+
+    fn expression(&mut self, node: &SyntaxRef) {
+        let operands = expressions(node);
+        match node.kind() {
+            // lambda 和匿名方法的函数体单独分析
+            SyntaxKind::LambdaExpression | SyntaxKind::AnonymousMethodExpression => {}
+            SyntaxKind::IdentifierName => {
+                if let Some(i) = self.referenced(node) {
+                    self.act(Action::Read(i, node.clone()));
+                }
+            }
+            SyntaxKind::MemberAccessExpression => {
+                if let Some(receiver) = node.child_nodes().first() {
+                    self.expression(receiver);
+                    self.act(Action::Dereference(receiver.clone()));
+                }
+            }
+            SyntaxKind::ElementAccessExpression => {
+                let children = node.child_nodes();
+                if let Some((receiver, rest)) = children.split_first() {
+                    self.expression(receiver);
+                    self.act(Action::Dereference(receiver.clone()));
+                    for child in rest {
+                        self.expression(child);
+                    }
+                }
+            }
+            SyntaxKind::VariableDeclarator => {
+                let declarator = VariableDeclarator::cast(node.clone()).unwrap();
+                let in_declaration = node.parent().is_some_and(|x| x.kind() == SyntaxKind::VariableDeclaration);
+                match declarator.initializer() {
+                    Some(value) => {
+                        self.expression(value.syntax());
+                        self.act(Action::Store(value.syntax().clone()));
+                        self.write(node, Some(value.syntax()));
+                    }
+                    // out var x
+                    None if !in_declaration => self.write(node, None),
+                    None => {}
+                }
+            }
+            SyntaxKind::SingleVariableDesignation => self.write(node, None),
+            SyntaxKind::InvocationExpression => {
+                if node.child_nodes().first().is_some_and(|x| x.token_text() == "nameof") {
+                    return;
+                }
+                for child in node.child_nodes() {
+                    if child.kind() != SyntaxKind::ArgumentList {
+                        self.expression(&child);
+                    }
+                }
+                self.arguments(node.child_of(SyntaxKind::ArgumentList));
+            }
+            SyntaxKind::ObjectCreationExpression => {
+                self.arguments(node.child_of(SyntaxKind::ArgumentList));
+                for child in node.child_nodes().iter().filter(|x| x.kind() != SyntaxKind::ArgumentList) {
+                    self.initializer(child);
+                }
+            }
+            SyntaxKind::AssignmentExpression if operands.len() == 2 => {
+                let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+                let (left, right) = (&operands[0], &operands[1]);
+                if op == "=" {
+                    self.target(left);
+                    self.expression(right);
+                    self.act(Action::Store(right.clone()));
+                }
+                else if op == "??=" {
+                    self.expression(left);
+                    self.branch(|builder| builder.expression(right));
+                }
+                else {
+                    self.expression(left);
+                    self.expression(right);
+                }
+                self.assign(left, Some(node));
+            }
+            SyntaxKind::PrefixUnaryExpression | SyntaxKind::PostfixUnaryExpression => {
+                for operand in &operands {
+                    self.expression(operand);
+                }
+                let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+                if op == "++" || op == "--" {
+                    for operand in &operands {
+                        self.assign(operand, None);
+                    }
+                }
+            }
+            SyntaxKind::BinaryExpression if operands.len() == 2 => {
+                let op = Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default();
+                // 值里的 && 和 || 也按条件分两路, 右边知道左边的结果
+                if op == "&&" || op == "||" {
+                    let (when_true, when_false) = self.condition(Some(node));
+                    let join = self.new_block();
+                    self.link(when_true, join);
+                    self.link(when_false, join);
+                    self.current = Some(join);
+                    return;
+                }
+                self.expression(&operands[0]);
+                if op == "??" {
+                    self.branch(|builder| builder.expression(&operands[1]));
+                }
+                else {
+                    self.expression(&operands[1]);
+                }
+            }
+            SyntaxKind::ConditionalExpression if operands.len() == 3 => {
+                let (when_true, when_false) = self.condition(operands.first());
+                let join = self.new_block();
+                for (block, operand) in [when_true, when_false].iter().zip(&operands[1..]) {
+                    self.current = Some(*block);
+                    self.expression(operand);
+                    self.jump(join);
+                }
+                self.current = Some(join);
+            }
+            SyntaxKind::ConditionalAccessExpression => {
+                let children = node.child_nodes();
+                if let Some((receiver, rest)) = children.split_first() {
+                    self.expression(receiver);
+                    self.branch(|builder| {
+                        for child in rest {
+                            builder.expression(child);
+                        }
+                    });
+                }
+            }
+            _ => {
+                for child in node.child_nodes() {
+                    self.expression(&child);
+                }
+            }
+        }
+    }
+
+    // 可能执行也可能跳过的部分: && 和 || 的右边, ??, ?.
+    fn branch<F: FnOnce(&mut Builder<'a>)>(&mut self, conditional: F) {
+        let start = self.block();
+        let join = self.new_block();
+        let block = self.new_block();
+        self.link(start, join);
+        self.link(start, block);
+        self.current = Some(block);
+        conditional(self);
+        self.jump(join);
+        self.current = Some(join);
+    }
+
+    // out 实参在调用之后赋值, ref 实参要先赋值
+    fn arguments(&mut self, list: Option<SyntaxRef>) {
+        let arguments: Vec<Argument> = list.map_or(Vec::new(), |x| x.child_nodes().into_iter().filter_map(Argument::cast).collect());
+        let mut outs = Vec::new();
+        for argument in arguments {
+            let value = match argument.expression() {
+                Some(x) => x.syntax().clone(),
+                None => continue,
+            };
+            if argument.ref_kind().as_deref() == Some("out") {
+                self.target(&value);
+                outs.push(value);
+            }
+            else {
+                self.expression(&value);
+            }
+        }
+        for value in outs {
+            self.assign(&value, None);
+        }
+    }
+
+    // 对象初始化器里 X = 1 的 X 是新对象的成员
+    fn initializer(&mut self, node: &SyntaxRef) {
+        match node.kind() {
+            SyntaxKind::InitializerExpression | SyntaxKind::ObjectInitializerExpression
+            | SyntaxKind::CollectionInitializerExpression => {
+                for child in expressions(node) {
+                    match child.kind() {
+                        SyntaxKind::AssignmentExpression => {
+                            let operands = expressions(&child);
+                            if let Some(value) = operands.last() {
+                                self.initializer(value);
+                            }
+                        }
+                        _ => self.initializer(&child),
+                    }
+                }
+            }
+            kind if is_function(kind) => {}
+            _ => self.expression(node),
+        }
+    }
+
+    // 赋值目标里要先求值的部分: a[i] = 的 a 和 i, p.x = 的 p (局部结构体可以逐个字段赋值)
+    fn target(&mut self, node: &SyntaxRef) {
+        match node.kind() {
+            SyntaxKind::IdentifierName | SyntaxKind::DeclarationExpression => {}
+            SyntaxKind::ParenthesizedExpression | SyntaxKind::TupleExpression | SyntaxKind::Argument => {
+                for child in node.child_nodes() {
+                    self.target(&child);
+                }
+            }
+            SyntaxKind::MemberAccessExpression => {
+                if let Some(receiver) = node.child_nodes().first() {
+                    if receiver.kind() != SyntaxKind::IdentifierName {
+                        self.expression(receiver);
+                    }
+                    self.act(Action::Dereference(receiver.clone()));
+                }
+            }
+            _ => self.expression(node),
+        }
+    }
+
+    // value 是整个赋值表达式, 复合赋值, ++ 和 out 实参没有
+    fn assign(&mut self, node: &SyntaxRef, value: Option<&SyntaxRef>) {
+        match node.kind() {
+            SyntaxKind::IdentifierName => match self.referenced(node) {
+                Some(i) => self.act(Action::Write(i, value.cloned())),
+                None => self.act(Action::Assign(node.clone(), value.cloned())),
+            },
+            SyntaxKind::MemberAccessExpression => self.act(Action::Assign(node.clone(), value.cloned())),
+            SyntaxKind::ParenthesizedExpression | SyntaxKind::TupleExpression | SyntaxKind::Argument => {
+                for child in node.child_nodes() {
+                    self.assign(&child, None);
+                }
+            }
+            SyntaxKind::DeclarationExpression => {
+                for designation in node.traverse(true, false) {
+                    if designation.kind() == SyntaxKind::SingleVariableDesignation
+                        || designation.kind() == SyntaxKind::VariableDeclarator {
+                        self.write(&designation, None);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ControlFlowGraph {
+    /// Builds the graph of a function body; `out` parameters are tracked from the start.
+    pub fn build(function: &SyntaxRef, scopes: &ScopeTree) -> ControlFlowGraph {
+        let graph = ControlFlowGraph { blocks: Vec::new(), variables: Vec::new(), end: None, section_ends: Vec::new() };
+        let mut builder = Builder { scopes, function: function.clone(), graph, current: None, jumps: Vec::new(),
+            labels: Vec::new() };
+        builder.new_block();
+        builder.new_block();
+        builder.current = Some(ENTRY);
+        let parameters = function.traverse(true, false)
+            .filter(|x| x.kind() == SyntaxKind::Parameter && enclosing_function(x).as_ref() == Some(function));
+        builder.graph.variables.extend(parameters);
+        for child in function.child_nodes() {
+            match child.kind() {
+                SyntaxKind::Block => builder.statement(&child),
+                SyntaxKind::ArrowExpressionClause => {
+                    builder.expression(&child);
+                    if let Some(value) = expressions(&child).pop() {
+                        builder.act(Action::Store(value));
+                    }
+                    builder.jump(EXIT);
+                }
+                SyntaxKind::ConstructorInitializer => builder.expression(&child),
+                _ if Expression::cast(child.clone()).is_some() && function.kind() == SyntaxKind::LambdaExpression => {
+                    builder.expression(&child);
+                    builder.act(Action::Store(child.clone()));
+                    builder.jump(EXIT);
+                }
+                _ => {}
+            }
+        }
+        builder.graph.end = builder.current;
+        builder.jump(EXIT);
+        builder.graph
+    }
+
+    pub fn predecessors(&self, block: usize) -> Vec<usize> {
+        (0..self.blocks.len()).filter(|&x| self.blocks[x].successors.contains(&block)).collect()
+    }
+
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![ENTRY];
+        while let Some(block) = stack.pop() {
+            if reachable[block] {
+                continue;
+            }
+            reachable[block] = true;
+            stack.extend(self.blocks[block].successors.iter().cloned());
+        }
+        reachable
+    }
+
+    /// Definitely assigned variables at the start of each block; unreachable blocks have everything assigned.
+    pub fn assigned(&self) -> Vec<Vec<bool>> {
+        let reachable = self.reachable();
+        let predecessors: Vec<Vec<usize>> = (0..self.blocks.len())
+            .map(|x| self.predecessors(x).into_iter().filter(|&p| reachable[p]).collect())
+            .collect();
+        let count = self.variables.len();
+        let mut states = vec![vec![true; count]; self.blocks.len()];
+        states[ENTRY] = self.variables.iter().map(|x| x.kind() == SyntaxKind::Parameter && !is_out_parameter(x)).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 0..self.blocks.len() {
+                if block == ENTRY || !reachable[block] {
+                    continue;
+                }
+                let mut state = vec![true; count];
+                for &p in &predecessors[block] {
+                    let out = self.transfer(p, &states[p]);
+                    for i in 0..count {
+                        state[i] = state[i] && out[i];
+                    }
+                }
+                if state != states[block] {
+                    states[block] = state;
+                    changed = true;
+                }
+            }
+        }
+        states
+    }
+
+    fn transfer(&self, block: usize, state: &[bool]) -> Vec<bool> {
+        let mut state = state.to_vec();
+        for action in &self.blocks[block].actions {
+            if let Action::Write(i, _) = *action {
+                state[i] = true;
+            }
+        }
+        state
+    }
+}
+
+// ------------------------------------ diagnostics --------------------------------------------
+
+/// Flow errors and warnings (unreachable code) of a compilation unit.
+pub struct FlowAnalysis {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+fn function_name(function: &SyntaxRef) -> String {
+    let name = function.identifier().map(|x| x.token_text()).unwrap_or_default();
+    if function.kind() == SyntaxKind::AccessorDeclaration {
+        let property = function.acestors().find_map(PropertyDeclaration::cast).and_then(|x| x.name());
+        let keyword = function.significant_tokens().iter().map(|x| x.token_text())
+            .find(|x| ["get", "set", "init", "add", "remove"].contains(&x.as_str())).unwrap_or_default();
+        return format!("{}.{}", property.unwrap_or_else(|| "this".to_string()), keyword);
+    }
+    name
+}
+
+// 必须在每条路径上返回值的函数: 返回类型不是 void, 不是迭代器, 也不是不带结果的 async Task
+fn returns_value(function: &SyntaxRef) -> bool {
+    if function.child_of(SyntaxKind::Block).is_none() {
+        return false;
+    }
+    let return_type = match function_return_type(function) {
+        Some(x) => x.text(),
+        None => return false,
+    };
+    let is_iterator = function.traverse(true, false)
+        .any(|x| (x.kind() == SyntaxKind::YieldReturnStatement || x.kind() == SyntaxKind::YieldBreakStatement)
+            && enclosing_function(&x).as_ref() == Some(function));
+    let is_task = is_async(function) && (return_type == "Task" || return_type == "ValueTask");
+    return_type != "void" && !is_iterator && !is_task
+}
+
+fn analyze_function(function: &SyntaxRef, scopes: &ScopeTree, result: &mut FlowAnalysis) {
+    let graph = ControlFlowGraph::build(function, scopes);
+    let reachable = graph.reachable();
+    let assigned = graph.assigned();
+    let at = |node: &SyntaxRef| node.identifier().map_or(node.span(), |x| x.span());
+    for (block, actions) in graph.blocks.iter().enumerate().filter(|x| reachable[x.0]) {
+        let mut state = assigned[block].clone();
+        for action in &actions.actions {
+            match *action {
+                Action::Write(i, _) => state[i] = true,
+                Action::Read(i, ref node) if !state[i] => {
+                    let variable = &graph.variables[i];
+                    let code = if is_out_parameter(variable) { "DY0702" } else { "DY0701" };
+                    let name = variable.identifier().map(|x| x.token_text()).unwrap_or_default();
+                    let error = Diagnostic::error(code, node.span(), &[&name])
+                        .with_label(at(variable), Phrase::new("declared-unassigned", &[&name]));
+                    result.errors.push(error);
+                    // 报一次就够了
+                    state[i] = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    if reachable[EXIT] {
+        for (i, variable) in graph.variables.iter().enumerate().filter(|x| is_out_parameter(x.1)) {
+            if !assigned[EXIT][i] {
+                let name = variable.identifier().map(|x| x.token_text()).unwrap_or_default();
+                result.errors.push(Diagnostic::error("DY0703", at(function), &[&name]));
+            }
+        }
+    }
+    if graph.end.is_some_and(|x| reachable[x]) && returns_value(function) {
+        result.errors.push(Diagnostic::error("DY0704", at(function), &[&function_name(function)]));
+    }
+    // 一串不可达的语句只报第一句, 外层语句已经不可达的不报
+    let mut unreachable: Vec<SyntaxRef> = Vec::new();
+    for (_, actions) in graph.blocks.iter().enumerate().filter(|x| !reachable[x.0]) {
+        for action in &actions.actions {
+            if let Action::Statement(ref node) = *action {
+                unreachable.push(node.clone());
+            }
+        }
+    }
+    for node in &unreachable {
+        let is_inner = node.acestors().skip(1).any(|x| unreachable.contains(&x));
+        let previous = node.preceding_siblings().skip(1).find(|x| Statement::cast(x.clone()).is_some());
+        let is_following = previous.is_some_and(|x| unreachable.contains(&x));
+        if !is_inner && !is_following {
+            result.warnings.push(Diagnostic::warning("DY0705", node.span(), &[]));
+        }
+    }
+    for &(ref section, end) in &graph.section_ends {
+        if !reachable[end] {
+            continue;
+        }
+        let label = section.child_nodes().into_iter()
+            .find(|x| x.kind() == SyntaxKind::CaseSwitchLabel || x.kind() == SyntaxKind::CasePatternSwitchLabel
+                || x.kind() == SyntaxKind::DefaultSwitchLabel)
+            .map(|x| label_text(&x))
+            .unwrap_or_default();
+        let is_last = section.parent()
+            .is_some_and(|x| x.children_of(SyntaxKind::SwitchSection).last() == Some(section));
+        let code = if is_last { "DY0706" } else { "DY0707" };
+        result.errors.push(Diagnostic::error(code, section.child_nodes()[0].span(), &[&label]));
+    }
+}
+
+/// Definite assignment, return and reachability checks of every function in a compilation unit.
+pub fn analyze_flow(root: &SyntaxRef) -> FlowAnalysis {
+    let scopes = ScopeTree::bind(root);
+    let mut result = FlowAnalysis { errors: Vec::new(), warnings: Vec::new() };
+    for node in root.traverse(true, false) {
+        if is_function(node.kind()) {
+            analyze_function(&node, &scopes, &mut result);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+
+    fn analyze(code: &str) -> FlowAnalysis {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        analyze_flow(&tree.root)
+    }
+
+    fn messages(errors: &[Diagnostic]) -> Vec<String> {
+        errors.iter().map(|x| x.message.clone()).collect()
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let result = analyze(include_str!("../../examples/hello_world.dy"));
+        assert_eq!(messages(&result.errors), Vec::<String>::new());
+        assert_eq!(messages(&result.warnings), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_definite_assignment() {
+        let code = "class A {
+            bool Try(out int r) { r = 1; return true; }
+            void F(bool c, int[] items) {
+                int a; int b = a;
+                int x; if (c) { x = 1; } else { x = 2; } b = x;
+                int y; if (c) { y = 1; } b = y;
+                int z; while (c) { z = 1; } b = z;
+                int w; do { w = 1; } while (c); b = w;
+                int v; if (c && Try(out v)) { b = v; } b = v;
+                Try(out var t); b = t;
+                int u; u = u + 1;
+                int s; b = c ? (s = 1) : 2; b = s;
+                int q; foreach (var i in items) { q = i; } b = q;
+                int k; for (k = 0; k < 3; k++) { } b = k;
+                int m; try { m = 1; } catch { m = 2; } b = m;
+                int n; try { n = 1; } catch { } b = n;
+            }
+            void G(out int r, bool c) { if (c) { r = 1; } }
+            void H(out int r, bool c) { if (c) { throw new Exception(); } r = r + 1; }
+        }";
+        let result = analyze(code);
+        assert_eq!(messages(&result.errors), vec![
+            "use of unassigned local variable 'a'",
+            "use of unassigned local variable 'y'",
+            "use of unassigned local variable 'z'",
+            "use of unassigned local variable 'v'",
+            "use of unassigned local variable 'u'",
+            "use of unassigned local variable 's'",
+            "use of unassigned local variable 'q'",
+            "use of unassigned local variable 'n'",
+            "the out parameter 'r' must be assigned to before control leaves the current method",
+            "use of unassigned out parameter 'r'",
+        ]);
+    }
+
+    #[test]
+    fn test_reachability() {
+        let code = "class A {
+            int F(bool c) { if (c) { return 1; } }
+            int G(bool c) { if (c) { return 1; } else { return 2; } }
+            int H() { while (true) { } }
+            int I(int x) { switch (x) { case 1: return 1; default: throw new Exception(); } }
+            IEnumerable<int> J() { yield return 1; }
+            int P { get { } }
+            void K(int x) {
+                return;
+                x = 1;
+                x = 2;
+            }
+            void L(int x) {
+                while (x > 0) { break; x--; }
+                switch (x) {
+                    case 1: x = 2;
+                    case 2: x = 3; break;
+                    case 3: goto case 1;
+                    default: x = 4;
+                }
+            }
+            int M(int x) { goto end; end: return x; }
+        }";
+        let result = analyze(code);
+        assert_eq!(messages(&result.errors), vec![
+            "'F': not all code paths return a value",
+            "'P.get': not all code paths return a value",
+            "control cannot fall through from one case label ('case 1:') to another",
+            "control cannot fall out of switch from final case label ('default:')",
+        ]);
+        let spans: Vec<&str> = result.warnings.iter().map(|x| &code[x.span.start..x.span.end]).collect();
+        assert_eq!(spans, vec!["x = 1;", "x--;"]);
+        assert_eq!(messages(&result.warnings), vec!["unreachable code detected", "unreachable code detected"]);
+    }
+}
@@ -0,0 +1,635 @@
+use vm::dy_diagnostic::Diagnostic;
+use vm::dy_parser::{DyParser, Span, TokenKind};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, SyntaxTree};
+
+// ------------------------------------ formatter ----------------------------------------------
+// 在无损语法树上重新生成空白: 缩进, 空格, 大括号位置, 空行和折行
+// 注释, #region 和非活动代码原样保留
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BraceStyle {
+    /// `{` on its own line, as in hello_world.dy.
+    Allman,
+    /// `{` at the end of the line that opens the block.
+    KAndR,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    pub indent: IndentStyle,
+    pub brace_style: BraceStyle,
+    /// Blank lines kept between statements and members, extra ones are removed.
+    pub max_blank_lines: usize,
+    /// Lines longer than this are wrapped after `,` and binary operators.
+    pub max_width: usize,
+    /// Width of a tab when measuring lines.
+    pub tab_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent: IndentStyle::Spaces(4),
+            brace_style: BraceStyle::Allman,
+            max_blank_lines: 1,
+            max_width: 120,
+            tab_width: 4,
+        }
+    }
+}
+
+/// Formats `source`. Code with syntax errors is not formatted and the errors are returned.
+pub fn format(source: &str, options: &FormatOptions) -> Result<String, Vec<Diagnostic>> {
+    let mut dy_parser = DyParser::new(source.to_string());
+    dy_parser.lexer();
+    let tree = dy_parser.parse();
+    if !tree.errors.is_empty() {
+        return Err(tree.errors);
+    }
+    let formatted = format_tree(&tree, options);
+    // 格式化只能改变空白
+    if significant_tokens(source) != significant_tokens(&formatted) {
+        return Err(vec![Diagnostic::error("DY0304", Span::new(0, 0), &[])]);
+    }
+    Ok(formatted)
+}
+
+pub fn format_tree(tree: &SyntaxTree, options: &FormatOptions) -> String {
+    let mut formatter = Formatter::new(tree, options);
+    formatter.layout();
+    formatter.render()
+}
+
+fn significant_tokens(source: &str) -> Vec<(TokenKind, String)> {
+    let mut dy_parser = DyParser::new(source.to_string());
+    dy_parser.lexer();
+    dy_parser.tokens().iter()
+        .filter(|x| x.kind != TokenKind::Whitespace && x.kind != TokenKind::EndOfLine)
+        .map(|x| (x.kind, dy_parser.token_text(x).trim().to_string()))
+        .collect()
+}
+
+
+struct Item {
+    text: String,
+    kind: TokenKind,
+    leaf: SyntaxRef,
+    newlines_before: usize,
+    // 非活动代码和多行注释的后续行, 原样输出
+    raw: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Separator {
+    None,
+    Space,
+    Newline,
+}
+
+struct Piece {
+    text: String,
+    space_before: bool,
+    break_after: bool,
+}
+
+struct Line {
+    indent: usize,
+    raw: bool,
+    pieces: Vec<Piece>,
+}
+
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    items: Vec<Item>,
+    lines: Vec<Line>,
+    newline: &'static str,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(tree: &SyntaxTree, options: &'a FormatOptions) -> Formatter<'a> {
+        let text = tree.text();
+        let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        Formatter { options, items: collect_items(tree), lines: Vec::new(), newline }
+    }
+
+    fn layout(&mut self) {
+        for i in 0..self.items.len() {
+            let separator = if i == 0 { Separator::Newline } else { self.separator(i) };
+            let item = &self.items[i];
+            if separator == Separator::Newline {
+                if i > 0 {
+                    let blank = if self.is_block_brace(&self.items[i-1].leaf, "{") || self.is_block_brace(&item.leaf, "}") {
+                        0
+                    }
+                    else {
+                        item.newlines_before.saturating_sub(1).min(self.options.max_blank_lines)
+                    };
+                    for _ in 0..blank {
+                        self.lines.push(Line { indent: 0, raw: true, pieces: Vec::new() });
+                    }
+                }
+                let indent = if item.kind == TokenKind::Preprocessor && !item.text.starts_with("#region")
+                    && !item.text.starts_with("#endregion") {
+                    0
+                }
+                else {
+                    self.indent_level(&item.leaf)
+                };
+                self.lines.push(Line { indent, raw: item.raw, pieces: Vec::new() });
+            }
+            let break_after = is_break_candidate(&item.leaf, &item.text);
+            let piece = Piece { text: item.text.clone(), space_before: separator == Separator::Space, break_after };
+            self.lines.last_mut().unwrap().pieces.push(piece);
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for line in &self.lines {
+            if line.raw {
+                let text: String = line.pieces.iter().map(|x| x.text.as_str()).collect();
+                out.push_str(text.trim_end());
+                out.push_str(self.newline);
+                continue;
+            }
+            for (indent, pieces) in self.wrap(line) {
+                out.push_str(&self.indent_text(indent));
+                for (i, piece) in pieces.iter().enumerate() {
+                    if i > 0 && piece.space_before {
+                        out.push(' ');
+                    }
+                    out.push_str(&piece.text);
+                }
+                out.push_str(self.newline);
+            }
+        }
+        out
+    }
+
+    fn indent_text(&self, level: usize) -> String {
+        match self.options.indent {
+            IndentStyle::Tabs => "\t".repeat(level),
+            IndentStyle::Spaces(n) => " ".repeat(n * level),
+        }
+    }
+
+    fn indent_width(&self, level: usize) -> usize {
+        match self.options.indent {
+            IndentStyle::Tabs => self.options.tab_width * level,
+            IndentStyle::Spaces(n) => n * level,
+        }
+    }
+
+    // 贪心折行, 续行多缩进一级
+    fn wrap<'b>(&self, line: &'b Line) -> Vec<(usize, &'b [Piece])> {
+        let pieces = &line.pieces;
+        let mut result = Vec::new();
+        let mut start = 0;
+        while start < pieces.len() {
+            let indent = if start == 0 { line.indent } else { line.indent + 1 };
+            let mut column = self.indent_width(indent);
+            let mut end = pieces.len();
+            let mut candidate = None;
+            for i in start..pieces.len() {
+                if i > start && pieces[i].space_before {
+                    column += 1;
+                }
+                column += pieces[i].text.chars().count();
+                if column > self.options.max_width {
+                    if let Some(x) = candidate {
+                        end = x + 1;
+                        break;
+                    }
+                }
+                if i + 1 < pieces.len() && pieces[i].break_after && pieces[i+1].space_before {
+                    candidate = Some(i);
+                }
+            }
+            result.push((indent, &pieces[start..end]));
+            start = end;
+        }
+        result
+    }
+
+    fn separator(&self, i: usize) -> Separator {
+        let (prev, next) = (&self.items[i-1], &self.items[i]);
+        if prev.raw || next.raw || prev.kind == TokenKind::Preprocessor || next.kind == TokenKind::Preprocessor {
+            return Separator::Newline;
+        }
+        if prev.kind == TokenKind::Comment && prev.text.starts_with("//") {
+            return Separator::Newline;
+        }
+        if next.kind == TokenKind::Comment || prev.kind == TokenKind::Comment {
+            if next.newlines_before > 0 {
+                return Separator::Newline;
+            }
+            // 块注释后面还是按注释前的 token 换行: { /* c */ int x;
+            let significant = self.items[..i].iter().rev().find(|x| x.kind != TokenKind::Comment);
+            if let (TokenKind::Comment, false, Some(last)) = (prev.kind, next.kind == TokenKind::Comment, significant) {
+                if !last.raw && (self.ends_line(&last.leaf) || self.begins_line(&next.leaf, &last.text)) {
+                    return Separator::Newline;
+                }
+            }
+            return Separator::Space;
+        }
+        if self.options.brace_style == BraceStyle::KAndR && prev.text == "}" && is_continuation(&next.leaf) {
+            return Separator::Space;
+        }
+        if self.ends_line(&prev.leaf) || self.begins_line(&next.leaf, &prev.text) {
+            return Separator::Newline;
+        }
+        if has_space(&prev.leaf, &prev.text, &next.leaf, &next.text) {
+            return Separator::Space;
+        }
+        Separator::None
+    }
+
+    // 有换行的大括号: 代码块, 类型和命名空间体, switch, 带访问器体的属性
+    fn is_block_brace(&self, leaf: &SyntaxRef, brace: &str) -> bool {
+        if leaf.token_text() != brace || leaf.is_trivia() {
+            return false;
+        }
+        match leaf.parent() {
+            Some(parent) => is_block_container(&parent),
+            None => false,
+        }
+    }
+
+    fn ends_line(&self, leaf: &SyntaxRef) -> bool {
+        if self.is_block_brace(leaf, "{") {
+            return true;
+        }
+        let parent = match leaf.parent() {
+            Some(x) => x,
+            None => return false,
+        };
+        let text = leaf.token_text();
+        match (parent.kind(), text.as_str()) {
+            (SyntaxKind::CaseSwitchLabel, ":") | (SyntaxKind::CasePatternSwitchLabel, ":")
+            | (SyntaxKind::DefaultSwitchLabel, ":") | (SyntaxKind::LabeledStatement, ":") | (SyntaxKind::EnumBody, ",")
+            | (SyntaxKind::SwitchExpression, ",") => return true,
+            (SyntaxKind::AttributeList, "]") => {
+                return parent.parent().is_some_and(|x| x.kind() != SyntaxKind::Parameter
+                    && x.kind() != SyntaxKind::TypeParameter);
+            }
+            _ => {}
+        }
+        for node in parent.acestors() {
+            if node.last_significant_token().as_ref() != Some(leaf) {
+                break;
+            }
+            if is_line_node(&node) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn begins_line(&self, leaf: &SyntaxRef, prev_text: &str) -> bool {
+        if self.is_block_brace(leaf, "}") {
+            return true;
+        }
+        if self.is_block_brace(leaf, "{") {
+            if self.options.brace_style == BraceStyle::Allman {
+                return true;
+            }
+            // 单独的代码块
+            let parent = leaf.parent().unwrap();
+            return parent.kind() == SyntaxKind::Block && parent.parent().is_some_and(|x| is_statement_container(&x));
+        }
+        if is_continuation(leaf) {
+            return self.options.brace_style == BraceStyle::Allman || prev_text != "}";
+        }
+        let parent = match leaf.parent() {
+            Some(x) => x,
+            None => return false,
+        };
+        for node in parent.acestors() {
+            if node.first_significant_token().as_ref() != Some(leaf) {
+                break;
+            }
+            if is_embedded_statement(&node) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn indent_level(&self, leaf: &SyntaxRef) -> usize {
+        let offset = leaf.span().start;
+        let mut level = 0;
+        let mut child = leaf.clone();
+        let mut ancestor = leaf.parent();
+        while let Some(node) = ancestor {
+            if is_block_container(&node) {
+                let open = node.child_token("{").map(|x| x.span().end);
+                let close = node.reverse_children().find(|x| x.token_text() == "}" && !x.is_trivia())
+                    .map(|x| x.span().start);
+                let after_open = open.is_some_and(|x| offset >= x);
+                let before_close = close.is_none_or(|x| offset < x);
+                if after_open && before_close {
+                    level += 1;
+                }
+            }
+            else if (is_embedded_statement(&node) && !is_stacked_using(&node))
+                || (node.kind() == SyntaxKind::SwitchSection && !is_switch_label(&child)) {
+                level += 1;
+            }
+            child = node.clone();
+            ancestor = node.parent();
+        }
+        level
+    }
+}
+
+fn collect_items(tree: &SyntaxTree) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut newlines = 0;
+    let mut in_directive = false;
+    for leaf in tree.root.tokens() {
+        let kind = leaf.token_kind().unwrap();
+        let text = leaf.token_text();
+        match kind {
+            TokenKind::EndOfLine => {
+                newlines += 1;
+                in_directive = false;
+                continue;
+            }
+            TokenKind::Missing => continue,
+            _ => {}
+        }
+        // 预处理指令整行作为一项
+        if in_directive {
+            items.last_mut().unwrap().text.push_str(&text);
+            continue;
+        }
+        if kind == TokenKind::Whitespace {
+            continue;
+        }
+        if kind == TokenKind::Preprocessor {
+            in_directive = true;
+        }
+        let raw = kind == TokenKind::InactiveCode
+            || (kind == TokenKind::Comment && !text.starts_with("//") && !text.starts_with("/*"));
+        items.push(Item { text, kind, leaf, newlines_before: newlines, raw });
+        newlines = 0;
+    }
+    for item in items.iter_mut() {
+        if item.kind == TokenKind::Preprocessor {
+            item.text = item.text.trim_end().to_string();
+        }
+    }
+    items
+}
+
+fn is_block_container(node: &SyntaxRef) -> bool {
+    match node.kind() {
+        SyntaxKind::Block | SyntaxKind::TypeBody | SyntaxKind::NamespaceBody | SyntaxKind::EnumBody
+        | SyntaxKind::SwitchStatement | SyntaxKind::SwitchExpression => true,
+        // 只有 get; set; 的属性写在一行
+        SyntaxKind::AccessorList => node.children_of(SyntaxKind::AccessorDeclaration).iter()
+            .any(|x| x.child_of(SyntaxKind::Block).is_some() || x.child_of(SyntaxKind::ArrowExpressionClause).is_some()),
+        _ => false,
+    }
+}
+
+fn is_switch_label(node: &SyntaxRef) -> bool {
+    matches!(node.kind(),
+        SyntaxKind::CaseSwitchLabel | SyntaxKind::CasePatternSwitchLabel | SyntaxKind::DefaultSwitchLabel)
+}
+
+fn is_statement_container(node: &SyntaxRef) -> bool {
+    node.kind() == SyntaxKind::Block || node.kind() == SyntaxKind::SwitchSection
+        || node.kind() == SyntaxKind::LabeledStatement
+}
+
+fn is_member(kind: SyntaxKind) -> bool {
+    matches!(kind,
+        SyntaxKind::ClassDeclaration | SyntaxKind::StructDeclaration | SyntaxKind::InterfaceDeclaration
+        | SyntaxKind::EnumDeclaration | SyntaxKind::DelegateDeclaration | SyntaxKind::FieldDeclaration
+        | SyntaxKind::EventFieldDeclaration | SyntaxKind::MethodDeclaration | SyntaxKind::ConstructorDeclaration
+        | SyntaxKind::DestructorDeclaration | SyntaxKind::PropertyDeclaration | SyntaxKind::IndexerDeclaration
+        | SyntaxKind::EventDeclaration | SyntaxKind::OperatorDeclaration
+        | SyntaxKind::ConversionOperatorDeclaration | SyntaxKind::NamespaceDeclaration
+        | SyntaxKind::UsingDirective | SyntaxKind::ExternAliasDirective)
+}
+
+// 结束后要换行的节点
+fn is_line_node(node: &SyntaxRef) -> bool {
+    let kind = node.kind();
+    if is_member(kind) {
+        return true;
+    }
+    if kind == SyntaxKind::AccessorDeclaration {
+        return node.parent().is_some_and(|x| is_block_container(&x));
+    }
+    if kind == SyntaxKind::Block {
+        // lambda 和匿名方法的代码块后面还有表达式
+        return node.parent().is_some_and(|x| x.kind() != SyntaxKind::LambdaExpression
+            && x.kind() != SyntaxKind::AnonymousMethodExpression);
+    }
+    kind.is_statement()
+}
+
+// if, while 等不带大括号的子语句
+fn is_embedded_statement(node: &SyntaxRef) -> bool {
+    let kind = node.kind();
+    if !kind.is_statement() || kind == SyntaxKind::Block {
+        return false;
+    }
+    let parent = match node.parent() {
+        Some(x) => x,
+        None => return false,
+    };
+    match parent.kind() {
+        SyntaxKind::ElseClause => kind != SyntaxKind::IfStatement,
+        SyntaxKind::IfStatement | SyntaxKind::WhileStatement | SyntaxKind::DoStatement | SyntaxKind::ForStatement
+        | SyntaxKind::ForEachStatement | SyntaxKind::UsingStatement | SyntaxKind::LockStatement => true,
+        _ => false,
+    }
+}
+
+// 连续的 using 叠在一起, 不缩进
+fn is_stacked_using(node: &SyntaxRef) -> bool {
+    node.kind() == SyntaxKind::UsingStatement && parent_kind(node) == Some(SyntaxKind::UsingStatement)
+}
+
+// else, catch, finally 和 do 的 while 接在上一个代码块后面
+fn is_continuation(leaf: &SyntaxRef) -> bool {
+    matches!((parent_kind(leaf), leaf.token_text().as_str()),
+        (Some(SyntaxKind::ElseClause), "else") | (Some(SyntaxKind::CatchClause), "catch")
+        | (Some(SyntaxKind::FinallyClause), "finally") | (Some(SyntaxKind::DoStatement), "while"))
+}
+
+fn is_break_candidate(leaf: &SyntaxRef, text: &str) -> bool {
+    if leaf.is_trivia() {
+        return false;
+    }
+    if text == "," {
+        return true;
+    }
+    match leaf.parent().map(|x| x.kind()) {
+        Some(SyntaxKind::BinaryExpression) | Some(SyntaxKind::AssignmentExpression)
+        | Some(SyntaxKind::ConditionalExpression) => leaf.token_kind() == Some(TokenKind::PunctuatorOrOperator),
+        Some(SyntaxKind::VariableDeclarator) | Some(SyntaxKind::LambdaExpression)
+        | Some(SyntaxKind::ArrowExpressionClause) => text == "=" || text == "=>",
+        _ => false,
+    }
+}
+
+fn parent_kind(leaf: &SyntaxRef) -> Option<SyntaxKind> {
+    leaf.parent().map(|x| x.kind())
+}
+
+// 同一行里两个 token 之间是否有空格
+fn has_space(prev: &SyntaxRef, prev_text: &str, next: &SyntaxRef, next_text: &str) -> bool {
+    let prev_parent = parent_kind(prev);
+    let next_parent = parent_kind(next);
+    match next_text {
+        "," | ";" | ")" | "]" | "." | "::" => return false,
+        _ => {}
+    }
+    match prev_text {
+        "(" | "[" | "." | "::" => return false,
+        _ => {}
+    }
+    // - -x 和 + +x 不能连在一起
+    if (prev_text == "-" || prev_text == "+") && next_text.starts_with(prev_text) {
+        return true;
+    }
+    match (next_parent, next_text) {
+        (Some(SyntaxKind::NullableType), "?") | (Some(SyntaxKind::ConditionalAccessExpression), "?")
+        | (Some(SyntaxKind::PostfixUnaryExpression), "++") | (Some(SyntaxKind::PostfixUnaryExpression), "--")
+        | (Some(SyntaxKind::PostfixUnaryExpression), "!")
+        | (Some(SyntaxKind::TypeArgumentList), "<") | (Some(SyntaxKind::TypeArgumentList), ">")
+        | (Some(SyntaxKind::TypeParameterList), "<") | (Some(SyntaxKind::TypeParameterList), ">")
+        | (Some(SyntaxKind::CaseSwitchLabel), ":") | (Some(SyntaxKind::CasePatternSwitchLabel), ":")
+        | (Some(SyntaxKind::DefaultSwitchLabel), ":") | (Some(SyntaxKind::LabeledStatement), ":")
+        | (Some(SyntaxKind::Argument), ":") | (Some(SyntaxKind::Subpattern), ":")
+        | (Some(SyntaxKind::AttributeArgument), ":") | (Some(SyntaxKind::AttributeList), ":") => return false,
+        (Some(SyntaxKind::BinaryExpression), ">") if prev_text == ">" => return false,
+        (Some(SyntaxKind::AssignmentExpression), ">=") if prev_text == ">" => return false,
+        _ => {}
+    }
+    match (prev_parent, prev_text) {
+        (Some(SyntaxKind::ConditionalAccessExpression), "?") | (Some(SyntaxKind::TypeArgumentList), "<")
+        | (Some(SyntaxKind::TypeParameterList), "<") | (Some(SyntaxKind::CastExpression), ")") => return false,
+        (Some(SyntaxKind::PrefixUnaryExpression), _) => {
+            let operator = prev.parent().unwrap().first_significant_token();
+            if operator.as_ref() == Some(prev) {
+                return false;
+            }
+        }
+        _ => {}
+    }
+    if next_text == "[" {
+        // 对象初始化里的 ["k"] = v
+        let is_index_initializer = next_parent == Some(SyntaxKind::BracketedArgumentList)
+            && next.parent().unwrap().parent().map(|x| x.kind()) == Some(SyntaxKind::ImplicitElementAccess);
+        return next_parent == Some(SyntaxKind::AttributeList) || is_index_initializer;
+    }
+    if next_text == "(" {
+        if prev_text == "new" {
+            return false;
+        }
+        return match next_parent {
+            Some(SyntaxKind::ArgumentList) | Some(SyntaxKind::AttributeArgumentList)
+            | Some(SyntaxKind::TypeOfExpression) | Some(SyntaxKind::SizeOfExpression)
+            | Some(SyntaxKind::DefaultExpression) | Some(SyntaxKind::CheckedExpression) => false,
+            // Point(1, 2) 和 is (1, 2)
+            Some(SyntaxKind::PositionalPatternClause) => {
+                let pattern = next.parent().unwrap().parent().unwrap();
+                pattern.first_significant_token().as_ref() == Some(next)
+            }
+            Some(SyntaxKind::ParameterList) => {
+                let owner = next.parent().unwrap().parent().map(|x| x.kind());
+                owner == Some(SyntaxKind::LambdaExpression) || owner == Some(SyntaxKind::AnonymousMethodExpression)
+            }
+            _ => true,
+        };
+    }
+    true
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn format_with(code: &str, options: &FormatOptions) -> String {
+        let formatted = format(code, options).unwrap();
+        // 再格式化一次结果不变
+        assert_eq!(format(&formatted, options).unwrap(), formatted);
+        formatted
+    }
+
+    #[test]
+    fn test_allman() {
+        let code = "namespace A {\nclass B{\n\n\n  int  x=1 ;\n  void F( int a,int b ){ if(a>b) return  ;else{ F(b ,a) } }\n}}";
+        let expected = "namespace A\n{\n    class B\n    {\n        int x = 1;\n        void F(int a, int b)\n        {\n            if (a > b)\n                return;\n            else\n            {\n                F(b, a)\n            }\n        }\n    }\n}\n";
+        assert_eq!(format_with(code, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_k_and_r() {
+        let options = FormatOptions { indent: IndentStyle::Tabs, brace_style: BraceStyle::KAndR, ..FormatOptions::default() };
+        let code = "class B\n{\n    void F()\n    {\n        try\n        {\n            G(-x, !y, i++, (int)z, a?.b, list[0])\n        }\n        catch (E e)\n        {\n        }\n    }\n}";
+        let expected = "class B {\n\tvoid F() {\n\t\ttry {\n\t\t\tG(-x, !y, i++, (int)z, a?.b, list[0])\n\t\t} catch (E e) {\n\t\t}\n\t}\n}\n";
+        assert_eq!(format_with(code, &options), expected);
+    }
+
+    #[test]
+    fn test_comments() {
+        let code = "class B\n{\n  #region fields   \n    // leading\n  int x; // trailing\n  /* a\n     b */\n  int y;\n#if DEBUG\n  int z;\n#endif\n  #endregion\n}\n";
+        let expected = "class B\n{\n    #region fields\n    // leading\n    int x; // trailing\n    /* a\n     b */\n    int y;\n#if DEBUG\n  int z;\n#endif\n    #endregion\n}\n";
+        assert_eq!(format_with(code, &FormatOptions::default()), expected);
+        let code = "class A { /* c */ int x; void F() { /* d */ return; } }";
+        let expected = "class A\n{ /* c */\n    int x;\n    void F()\n    { /* d */\n        return;\n    }\n}\n";
+        assert_eq!(format_with(code, &FormatOptions::default()), expected);
+        let options = FormatOptions { brace_style: BraceStyle::KAndR, ..FormatOptions::default() };
+        let code = "namespace N { /* block */ class A { int x; } }";
+        let expected = "namespace N { /* block */\n    class A {\n        int x;\n    }\n}\n";
+        assert_eq!(format_with(code, &options), expected);
+    }
+
+    #[test]
+    fn test_wrap() {
+        let options = FormatOptions { max_width: 30, ..FormatOptions::default() };
+        let code = "class B\n{\n    int F() => Add(first, second, third + fourth);\n}\n";
+        let expected = "class B\n{\n    int F() => Add(first,\n        second, third +\n        fourth);\n}\n";
+        assert_eq!(format_with(code, &options), expected);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let code = "class A{int F(object x){if(x is Point( 0,var y ){Hp:>0} p)return 1;switch(x){case Enemy e when e.Hp>0:break;}\nreturn x switch{int n=>n,_=>0};}}";
+        let expected = "class A\n{\n    int F(object x)\n    {\n        if (x is Point(0, var y) { Hp: > 0 } p)\n            return 1;\n        switch (x)\n        {\n            case Enemy e when e.Hp > 0:\n                break;\n        }\n        return x switch\n        {\n            int n => n,\n            _ => 0\n        };\n    }\n}\n";
+        assert_eq!(format_with(code, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_using() {
+        let code = "class A{void F(){using(var a=A())using(var b=B()){b.Read();}\nusing var c=C();}}";
+        let expected = "class A\n{\n    void F()\n    {\n        using (var a = A())\n        using (var b = B())\n        {\n            b.Read();\n        }\n        using var c = C();\n    }\n}\n";
+        assert_eq!(format_with(code, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let code = include_str!("../../examples/hello_world.dy");
+        let formatted = format_with(code, &FormatOptions::default());
+        assert!(formatted.contains("    public class Test\n    {\n        public static int Add(int a, int b)\n"));
+        assert!(formatted.contains("            println(Add(1, 2))\n"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(format("class A {", &FormatOptions::default()).is_err());
+    }
+}
@@ -1,4 +1,10 @@
-enum SemanticFlags {
+use vm::dy_diagnostic::{Argument, Diagnostic, Phrase, Severity};
+use vm::dy_parser::{DyParser, SyntaxToken, TokenKind, BlockState, Span, BUILTIN_TYPES};
+use vm::dy_syntax::{SyntaxKind, SyntaxNode, SyntaxRef, SyntaxTree, MODIFIERS};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(i32)]
+pub enum SemanticFlags {
 	None = 0,
 
 	SymbolDeclarationsMask = (1 << 8) - 1,
@@ -97,3 +103,2725 @@ enum SemanticFlags {
 
 	ScopesEnd,
 }
+
+const DECLARATIONS: [SemanticFlags; 46] = [SemanticFlags::NamespaceDeclaration, SemanticFlags::UsingNamespace,
+	SemanticFlags::UsingAlias, SemanticFlags::ExternAlias, SemanticFlags::ClassDeclaration,
+	SemanticFlags::TypeParameterDeclaration, SemanticFlags::BaseListDeclaration, SemanticFlags::ConstructorDeclarator,
+	SemanticFlags::DestructorDeclarator, SemanticFlags::ConstantDeclarator, SemanticFlags::MethodDeclarator,
+	SemanticFlags::LocalVariableDeclarator, SemanticFlags::ForEachVariableDeclaration,
+	SemanticFlags::FromClauseVariableDeclaration, SemanticFlags::LabeledStatement,
+	SemanticFlags::CatchExceptionParameterDeclaration, SemanticFlags::FixedParameterDeclaration,
+	SemanticFlags::ParameterArrayDeclaration, SemanticFlags::ImplicitParameterDeclaration,
+	SemanticFlags::ExplicitParameterDeclaration, SemanticFlags::PropertyDeclaration, SemanticFlags::IndexerDeclaration,
+	SemanticFlags::GetAccessorDeclaration, SemanticFlags::SetAccessorDeclaration, SemanticFlags::EventDeclarator,
+	SemanticFlags::EventWithAccessorsDeclaration, SemanticFlags::AddAccessorDeclaration,
+	SemanticFlags::RemoveAccessorDeclaration, SemanticFlags::VariableDeclarator, SemanticFlags::OperatorDeclarator,
+	SemanticFlags::ConversionOperatorDeclarator, SemanticFlags::StructDeclaration, SemanticFlags::InterfaceDeclaration,
+	SemanticFlags::InterfacePropertyDeclaration, SemanticFlags::InterfaceMethodDeclaration,
+	SemanticFlags::InterfaceEventDeclaration, SemanticFlags::InterfaceIndexerDeclaration,
+	SemanticFlags::InterfaceGetAccessorDeclaration, SemanticFlags::InterfaceSetAccessorDeclaration,
+	SemanticFlags::EnumDeclaration, SemanticFlags::EnumMemberDeclaration, SemanticFlags::DelegateDeclaration,
+	SemanticFlags::AnonymousObjectCreation, SemanticFlags::MemberDeclarator, SemanticFlags::LambdaExpressionDeclaration,
+	SemanticFlags::AnonymousMethodDeclaration];
+
+const SCOPES: [SemanticFlags; 35] = [SemanticFlags::CompilationUnitScope, SemanticFlags::NamespaceBodyScope,
+	SemanticFlags::ClassBaseScope, SemanticFlags::TypeParameterConstraintsScope, SemanticFlags::ClassBodyScope,
+	SemanticFlags::StructInterfacesScope, SemanticFlags::StructBodyScope, SemanticFlags::InterfaceBaseScope,
+	SemanticFlags::InterfaceBodyScope, SemanticFlags::FormalParameterListScope, SemanticFlags::EnumBodyScope,
+	SemanticFlags::MethodBodyScope, SemanticFlags::ConstructorInitializerScope, SemanticFlags::LambdaExpressionScope,
+	SemanticFlags::LambdaExpressionBodyScope, SemanticFlags::AnonymousMethodScope,
+	SemanticFlags::AnonymousMethodBodyScope, SemanticFlags::CodeBlockScope, SemanticFlags::SwitchBlockScope,
+	SemanticFlags::ForStatementScope, SemanticFlags::EmbeddedStatementScope, SemanticFlags::UsingStatementScope,
+	SemanticFlags::LocalVariableInitializerScope, SemanticFlags::SpecificCatchScope, SemanticFlags::ArgumentListScope,
+	SemanticFlags::AttributeArgumentsScope, SemanticFlags::MemberInitializerScope, SemanticFlags::TypeDeclarationScope,
+	SemanticFlags::MethodDeclarationScope, SemanticFlags::AttributesScope, SemanticFlags::AccessorBodyScope,
+	SemanticFlags::AccessorsListScope, SemanticFlags::QueryExpressionScope, SemanticFlags::QueryBodyScope,
+	SemanticFlags::MemberDeclarationScope];
+
+impl SemanticFlags {
+	pub fn bits(self) -> i32 {
+		self as i32
+	}
+
+	// 声明和作用域组合, 比如 ClassDeclaration | TypeDeclarationScope
+	pub fn with(self, other: SemanticFlags) -> i32 {
+		self as i32 | other as i32
+	}
+
+	pub fn declaration(flags: i32) -> Option<SemanticFlags> {
+		let index = flags & SemanticFlags::SymbolDeclarationsMask as i32;
+		if index > SemanticFlags::SymbolDeclarationsBegin as i32 && index < SemanticFlags::SymbolDeclarationsEnd as i32 {
+			return Some(DECLARATIONS[(index - 2) as usize]);
+		}
+		None
+	}
+
+	pub fn scope(flags: i32) -> Option<SemanticFlags> {
+		let index = (flags & SemanticFlags::ScopesMask as i32) >> 8;
+		if index > 0 && index <= SCOPES.len() as i32 {
+			return Some(SCOPES[(index - 1) as usize]);
+		}
+		None
+	}
+}
+
+
+// ------------------------------------ grammar ------------------------------------------------
+// 递归下降, 直接生成 DyRef<SyntaxNode> 具体语法树
+// 语句末尾的分号可以省略 (换行或者 '}' 结束), 见 hello_world.dy
+
+const ASSIGNMENT_OPERATORS: [&'static str; 11] = ["=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", "??="];
+
+const PREFIX_OPERATORS: [&'static str; 6] = ["+", "-", "!", "~", "++", "--"];
+
+const PARAMETER_MODIFIERS: [&'static str; 5] = ["ref", "out", "in", "params", "this"];
+
+const OVERLOADABLE_OPERATORS: [&'static str; 20] = ["+", "-", "!", "~", "++", "--", "*", "/", "%", "&", "|", "^", "<<",
+	"==", "!=", ">", "<", ">=", "<=", "true"];
+
+// 泛型参数列表后面允许出现的 token, 用来区分 a < b > c 和 F<T>(x)
+const TYPE_ARGUMENT_FOLLOWS: [&'static str; 17] = ["(", ")", "]", "}", ":", ";", ",", ".", "?", "==", "!=", "|", "^",
+	"&&", "||", "&", "["];
+
+// 模式后面可以出现的 token, 'case Foo:' 和 'x is int)' 里的类型到这里结束
+const PATTERN_FOLLOWS: [&'static str; 12] = [")", "]", "}", ",", ":", ";", "=>", "?", "&&", "||", "==", "!="];
+
+const PATTERN_COMBINATORS: [&'static str; 3] = ["and", "or", "when"];
+
+const RELATIONAL_OPERATORS: [&'static str; 4] = ["<", "<=", ">", ">="];
+
+pub struct Grammar<'a> {
+	dy_parser: &'a DyParser,
+	tokens: Vec<SyntaxToken>,
+	texts: Vec<String>,
+	// 非空白 token 在 tokens 里的下标
+	significant: Vec<usize>,
+	cur: usize,
+	// 还没放进树里的第一个 token
+	emitted: usize,
+	stack: Vec<SyntaxRef>,
+	errors: Vec<Diagnostic>,
+	type_names: Vec<String>,
+}
+
+impl<'a> Grammar<'a> {
+	pub fn new(dy_parser: &'a DyParser) -> Grammar<'a> {
+		let source = dy_parser.tokens();
+		let mut tokens: Vec<SyntaxToken> = Vec::new();
+		let mut i = 0;
+		while i < source.len() {
+			let mut token = source[i].clone();
+			i += 1;
+			// 跨行的 verbatim 字符串合并成一个 token
+			let mut open = token.kind == TokenKind::StringLiteral && token.block_state == BlockState::String;
+			while open && i < source.len() {
+				let next = &source[i];
+				if next.kind == TokenKind::EndOfLine {
+					token.end_at = next.end_at;
+				}
+				else if next.kind == TokenKind::StringLiteral {
+					token.end_at = next.end_at;
+					open = next.block_state == BlockState::String;
+				}
+				else {
+					break;
+				}
+				i += 1;
+			}
+			token.block_state = BlockState::None;
+			// '>>' 和 '>>=' 拆开, 泛型参数列表才能正确关闭, 移位运算再由相邻的 '>' 组合
+			let text = dy_parser.token_text(&token);
+			if token.kind == TokenKind::PunctuatorOrOperator && (text == ">>" || text == ">>=") {
+				let mut first = token.clone();
+				first.end_at = first.begin_at;
+				let mut second = token.clone();
+				second.begin_at = first.end_at + 1;
+				tokens.push(first);
+				tokens.push(second);
+				continue;
+			}
+			tokens.push(token);
+		}
+		let texts: Vec<String> = tokens.iter().map(|x| dy_parser.token_text(x)).collect();
+		let significant = (0..tokens.len()).filter(|x| !tokens[*x].kind.is_trivia()).collect();
+		Grammar {
+			dy_parser,
+			tokens,
+			texts,
+			significant,
+			cur: 0,
+			emitted: 0,
+			stack: Vec::new(),
+			errors: Vec::new(),
+			type_names: Vec::new(),
+		}
+	}
+
+	pub fn parse(mut self) -> SyntaxTree {
+		let root = self.start(SyntaxKind::CompilationUnit, SemanticFlags::CompilationUnitScope.bits());
+		self.parse_compilation_unit();
+		let end = self.tokens.len();
+		self.emit_trivia(end);
+		self.finish();
+		SyntaxTree::new(root, self.errors, self.dy_parser.line_offsets().clone())
+	}
+
+	// -------------------------------- token access -------------------------------------------
+
+	fn is_eof(&self) -> bool {
+		self.cur >= self.significant.len()
+	}
+
+	fn text_at(&self, i: usize) -> &str {
+		if i < self.significant.len() {
+			return &self.texts[self.significant[i]];
+		}
+		""
+	}
+
+	fn kind_at(&self, i: usize) -> TokenKind {
+		if i < self.significant.len() {
+			return self.tokens[self.significant[i]].kind;
+		}
+		TokenKind::EOF
+	}
+
+	fn text(&self) -> &str {
+		self.text_at(self.cur)
+	}
+
+	fn nth(&self, n: usize) -> &str {
+		self.text_at(self.cur + n)
+	}
+
+	fn at(&self, text: &str) -> bool {
+		self.text() == text && !self.kind_at(self.cur).is_literal()
+	}
+
+	fn is_identifier(&self, i: usize) -> bool {
+		self.kind_at(i) == TokenKind::Identifier
+	}
+
+	fn is_predefined_type(&self, i: usize) -> bool {
+		self.kind_at(i) == TokenKind::Keyword && BUILTIN_TYPES.contains(&self.text_at(i))
+	}
+
+	// 两个 token 之间没有空白
+	fn is_adjacent(&self, i: usize) -> bool {
+		if i + 1 >= self.significant.len() {
+			return false;
+		}
+		self.tokens[self.significant[i]].end_at + 1 == self.tokens[self.significant[i+1]].begin_at
+	}
+
+	// 当前 token 和前一个 token 不在同一行
+	fn newline_before(&self) -> bool {
+		if self.cur == 0 || self.is_eof() {
+			return false;
+		}
+		let previous = &self.tokens[self.significant[self.cur-1]];
+		let current = &self.tokens[self.significant[self.cur]];
+		self.dy_parser.line_column(previous.end_at).0 < self.dy_parser.line_column(current.begin_at).0
+	}
+
+	fn offset(&self) -> usize {
+		if self.is_eof() {
+			return self.dy_parser.source().len();
+		}
+		self.tokens[self.significant[self.cur]].begin_at
+	}
+
+	fn previous_end(&self) -> usize {
+		if self.cur == 0 {
+			return 0;
+		}
+		self.tokens[self.significant[self.cur-1]].end_at + 1
+	}
+
+	// 把当前 token 拆成两个, 比如 '?.' 拆成 '?' 和 '.'
+	fn split_current(&mut self, first_len: usize) {
+		let index = self.significant[self.cur];
+		let mut first = self.tokens[index].clone();
+		first.end_at = first.begin_at + first_len - 1;
+		let mut second = self.tokens[index].clone();
+		second.begin_at = first.end_at + 1;
+		let first_text = self.dy_parser.token_text(&first);
+		let second_text = self.dy_parser.token_text(&second);
+		self.tokens[index] = first;
+		self.texts[index] = first_text;
+		self.tokens.insert(index + 1, second);
+		self.texts.insert(index + 1, second_text);
+		for x in self.significant.iter_mut().skip(self.cur + 1) {
+			*x += 1;
+		}
+		self.significant.insert(self.cur + 1, index + 1);
+	}
+
+	// -------------------------------- tree building ------------------------------------------
+
+	fn top(&self) -> &SyntaxRef {
+		self.stack.last().unwrap()
+	}
+
+	fn start(&mut self, kind: SyntaxKind, flags: i32) -> SyntaxRef {
+		let node = SyntaxRef::new(SyntaxNode::new(kind, flags));
+		if let Some(parent) = self.stack.last() {
+			parent.append(&node);
+		}
+		self.stack.push(node.clone());
+		node
+	}
+
+	// 把已经完成的 node 包进一个新的节点, 用于二元表达式等左递归的结构
+	fn precede(&mut self, node: &SyntaxRef, kind: SyntaxKind, flags: i32) -> SyntaxRef {
+		let wrapper = SyntaxRef::new(SyntaxNode::new(kind, flags));
+		node.insert_before(&wrapper);
+		wrapper.append(node);
+		self.stack.push(wrapper.clone());
+		wrapper
+	}
+
+	fn finish(&mut self) -> SyntaxRef {
+		let node = self.stack.pop().unwrap();
+		let first = node.children().find(|x| !x.is_trivia()).map(|x| x.span().start);
+		let last = node.reverse_children().find(|x| !x.is_trivia()).map(|x| x.span().end);
+		let span = match (first, last) {
+			(Some(start), Some(end)) => Span::new(start, end),
+			_ => Span::new(self.previous_end(), self.previous_end()),
+		};
+		node.borrow_mut().span = span;
+		node
+	}
+
+	fn set_kind(&self, node: &SyntaxRef, kind: SyntaxKind, flags: i32) {
+		let mut data = node.borrow_mut();
+		data.kind = kind;
+		data.flags = flags;
+	}
+
+	// 去掉一层节点, 子节点留在原来的位置
+	fn dissolve(&self, node: &SyntaxRef) {
+		let children: Vec<SyntaxRef> = node.children().collect();
+		for child in children.iter() {
+			node.insert_before(child);
+		}
+		node.detach();
+	}
+
+	fn push_leaf(&mut self, index: usize) {
+		let token = &self.tokens[index];
+		let leaf = SyntaxRef::new(SyntaxNode::token(token.kind, self.texts[index].clone(), token.span()));
+		self.top().append(&leaf);
+	}
+
+	fn emit_trivia(&mut self, until: usize) {
+		while self.emitted < until {
+			let index = self.emitted;
+			self.push_leaf(index);
+			self.emitted += 1;
+		}
+	}
+
+	fn bump(&mut self) {
+		if self.is_eof() {
+			return;
+		}
+		let index = self.significant[self.cur];
+		self.emit_trivia(index);
+		self.push_leaf(index);
+		self.emitted = index + 1;
+		self.cur += 1;
+	}
+
+	fn error(&mut self, code: &'static str, arguments: Vec<Argument>) -> Diagnostic {
+		let span = if self.is_eof() {
+			Span::new(self.offset(), self.offset())
+		}
+		else {
+			self.tokens[self.significant[self.cur]].span()
+		};
+		Diagnostic::new(Severity::Error, code, span, arguments)
+	}
+
+	fn missing(&mut self, expected: &str) {
+		let mut error = self.expected(Argument::from(expected));
+		// 缺少的标点补在上一个 token 后面
+		if [";", ")", "]", "}", ",", ":"].contains(&expected) {
+			let end = self.previous_end();
+			error = error.with_fix(Phrase::new("insert-token", &[expected]), Span::new(end, end), expected);
+		}
+		self.errors.push(error);
+		self.missing_leaf();
+	}
+
+	// 缺少的是一类语法成分, 比如 'identifier', 'type'
+	fn missing_kind(&mut self, key: &'static str) {
+		let error = self.expected(Argument::phrase(key, &[]));
+		self.errors.push(error);
+		self.missing_leaf();
+	}
+
+	fn expected(&mut self, expected: Argument) -> Diagnostic {
+		if self.is_eof() {
+			return self.error("DY0302", vec![expected]);
+		}
+		let found = Argument::from(self.text());
+		self.error("DY0301", vec![expected, found])
+	}
+
+	fn missing_leaf(&mut self) {
+		let end = self.previous_end();
+		let leaf = SyntaxRef::new(SyntaxNode::token(TokenKind::Missing, String::new(), Span::new(end, end)));
+		self.top().append(&leaf);
+	}
+
+	fn expect(&mut self, text: &str) -> bool {
+		if self.at(text) {
+			self.bump();
+			return true;
+		}
+		self.missing(text);
+		false
+	}
+
+	fn expect_identifier(&mut self) -> bool {
+		if self.is_identifier(self.cur) {
+			self.bump();
+			return true;
+		}
+		self.missing_kind("identifier");
+		false
+	}
+
+	fn at_statement_end(&self) -> bool {
+		self.is_eof() || self.at(";") || self.at("}") || self.newline_before()
+	}
+
+	// ';' 可以省略
+	fn expect_end(&mut self) {
+		if self.at(";") {
+			self.bump();
+		}
+		else if !self.at_statement_end() {
+			self.missing(";");
+		}
+	}
+
+	fn error_token(&mut self) {
+		let error = self.error("DY0303", vec![Argument::from(self.text())]);
+		self.errors.push(error);
+		self.start(SyntaxKind::Error, 0);
+		self.bump();
+		self.finish();
+	}
+
+	// -------------------------------- look ahead ---------------------------------------------
+
+	fn scan_type(&self, mut i: usize) -> Option<usize> {
+		if self.is_predefined_type(i) {
+			i += 1;
+		}
+		else if self.text_at(i) == "(" {
+			i = self.scan_tuple_type(i)?;
+		}
+		else if self.is_identifier(i) {
+			i = self.scan_name(i)?;
+		}
+		else {
+			return None;
+		}
+		loop {
+			let text = self.text_at(i);
+			if text == "?" && self.kind_at(i) == TokenKind::PunctuatorOrOperator {
+				i += 1;
+			}
+			else if (text == "?[" || text == "[") && (self.text_at(i+1) == "]" || self.text_at(i+1) == ",") {
+				i = self.scan_rank(i + 1)?;
+			}
+			else {
+				break;
+			}
+		}
+		Some(i)
+	}
+
+	// (int, string), (int x, int y), 至少两个元素
+	fn scan_tuple_type(&self, mut i: usize) -> Option<usize> {
+		let mut count = 0;
+		loop {
+			i = self.scan_type(i + 1)?;
+			if self.is_identifier(i) {
+				i += 1;
+			}
+			count += 1;
+			match self.text_at(i) {
+				"," => {}
+				")" if count > 1 => return Some(i + 1),
+				_ => return None,
+			}
+		}
+	}
+
+	fn scan_rank(&self, mut i: usize) -> Option<usize> {
+		while self.text_at(i) == "," {
+			i += 1;
+		}
+		if self.text_at(i) == "]" {
+			return Some(i + 1);
+		}
+		None
+	}
+
+	fn scan_name(&self, mut i: usize) -> Option<usize> {
+		if self.is_identifier(i) && self.text_at(i+1) == "::" {
+			i += 2;
+		}
+		loop {
+			if !self.is_identifier(i) {
+				return None;
+			}
+			i += 1;
+			if self.text_at(i) == "<" {
+				i = self.scan_type_arguments(i)?;
+			}
+			if self.text_at(i) == "." && self.is_identifier(i+1) {
+				i += 1;
+				continue;
+			}
+			return Some(i);
+		}
+	}
+
+	fn scan_type_arguments(&self, mut i: usize) -> Option<usize> {
+		i += 1;
+		// typeof(List<>), typeof(Dictionary<,>)
+		if self.text_at(i) == ">" || self.text_at(i) == "," {
+			while self.text_at(i) == "," {
+				i += 1;
+			}
+			if self.text_at(i) == ">" {
+				return Some(i + 1);
+			}
+			return None;
+		}
+		loop {
+			i = self.scan_type(i)?;
+			match self.text_at(i) {
+				"," => i += 1,
+				">" => return Some(i + 1),
+				_ => return None,
+			}
+		}
+	}
+
+	// 表达式里 F<T> 后面只能跟特定的 token
+	fn is_generic_name_ahead(&self, i: usize) -> bool {
+		if !self.is_identifier(i) || self.text_at(i+1) != "<" {
+			return false;
+		}
+		match self.scan_type_arguments(i + 1) {
+			Some(j) => j >= self.significant.len() || TYPE_ARGUMENT_FOLLOWS.contains(&self.text_at(j)),
+			None => false,
+		}
+	}
+
+	fn matching_paren(&self, mut i: usize) -> Option<usize> {
+		let mut depth = 0;
+		while i < self.significant.len() {
+			match self.text_at(i) {
+				"(" | "[" | "{" | "?[" => depth += 1,
+				")" | "]" | "}" => {
+					depth -= 1;
+					if depth == 0 {
+						return Some(i);
+					}
+				}
+				_ => {}
+			}
+			i += 1;
+		}
+		None
+	}
+
+	fn is_lambda_ahead(&self) -> bool {
+		let mut i = self.cur;
+		if self.text_at(i) == "async" && (self.is_identifier(i+1) || self.text_at(i+1) == "(") {
+			i += 1;
+		}
+		if self.is_identifier(i) && self.text_at(i+1) == "=>" {
+			return true;
+		}
+		if self.text_at(i) == "(" {
+			if let Some(j) = self.matching_paren(i) {
+				return self.text_at(j+1) == "=>";
+			}
+		}
+		false
+	}
+
+	fn is_cast_ahead(&self) -> bool {
+		let j = match self.scan_type(self.cur + 1) {
+			Some(j) => j,
+			None => return false,
+		};
+		if self.text_at(j) != ")" {
+			return false;
+		}
+		if self.is_predefined_type(self.cur + 1) {
+			return true;
+		}
+		let next = j + 1;
+		match self.kind_at(next) {
+			TokenKind::Identifier | TokenKind::BoolLiteral | TokenKind::NullLiteral | TokenKind::CharLiteral
+			| TokenKind::StringLiteral | TokenKind::IntegerLiteral | TokenKind::RealLiteral => true,
+			TokenKind::Keyword => ["this", "base", "new", "typeof", "default", "sizeof", "checked", "unchecked",
+				"delegate"].contains(&self.text_at(next)) || self.is_predefined_type(next),
+			_ => self.text_at(next) == "(" || self.text_at(next) == "!" || self.text_at(next) == "~",
+		}
+	}
+
+	// await 是上下文关键字, 后面跟着表达式时才是 await 运算
+	fn is_await_operand(&self, i: usize) -> bool {
+		match self.kind_at(i) {
+			TokenKind::Identifier | TokenKind::BoolLiteral | TokenKind::NullLiteral | TokenKind::CharLiteral
+			| TokenKind::StringLiteral | TokenKind::IntegerLiteral | TokenKind::RealLiteral => true,
+			TokenKind::Keyword => ["this", "base", "new", "typeof", "default", "checked", "unchecked"]
+				.contains(&self.text_at(i)) || self.is_predefined_type(i),
+			_ => self.text_at(i) == "(",
+		}
+	}
+
+	// Type name = ...;  Type name;  Type name, ...
+	fn is_local_declaration_ahead(&self) -> bool {
+		if self.text() == "await" && self.is_identifier(self.cur + 1) {
+			return false;
+		}
+		match self.scan_type(self.cur) {
+			Some(j) => {
+				if !self.is_identifier(j) {
+					return false;
+				}
+				let next = self.text_at(j + 1);
+				next == "=" || next == ";" || next == "," || next == "}" || j + 1 >= self.significant.len()
+					|| self.tokens[self.significant[j+1]].line_index > self.tokens[self.significant[j]].line_index
+			}
+			None => false,
+		}
+	}
+
+	fn same_line(&self, i: usize) -> bool {
+		i > 0 && i < self.significant.len()
+			&& self.tokens[self.significant[i]].line_index == self.tokens[self.significant[i-1]].line_index
+	}
+
+	// 模式里的类型不带 '?', 'x is int ? a : b' 是条件表达式
+	fn scan_pattern_type(&self, i: usize) -> Option<usize> {
+		let mut j = if self.is_predefined_type(i) { i + 1 } else { self.scan_name(i)? };
+		while self.text_at(j) == "[" && (self.text_at(j+1) == "]" || self.text_at(j+1) == ",") {
+			j = self.scan_rank(j + 1)?;
+		}
+		Some(j)
+	}
+
+	fn is_pattern_end(&self, i: usize) -> bool {
+		i >= self.significant.len() || PATTERN_FOLLOWS.contains(&self.text_at(i))
+			|| (self.is_identifier(i) && PATTERN_COMBINATORS.contains(&self.text_at(i)))
+	}
+
+	// Enemy e 里的 e, 必须和类型在同一行
+	fn is_designation_at(&self, i: usize) -> bool {
+		self.is_identifier(i) && !PATTERN_COMBINATORS.contains(&self.text_at(i)) && self.same_line(i)
+	}
+
+	// x is T 是类型判断; x is null, x is > 0, x is T t, x is not T 都是模式
+	fn is_pattern_ahead(&self, i: usize) -> bool {
+		if (self.text_at(i) == "not" || self.text_at(i) == "var") && self.is_identifier(i) && !self.is_pattern_end(i + 1) {
+			return true;
+		}
+		match self.scan_pattern_type(i) {
+			Some(j) => self.is_designation_at(j) || ((self.text_at(j) == "(" || self.text_at(j) == "{") && self.same_line(j))
+				|| (self.is_identifier(j) && (self.text_at(j) == "and" || self.text_at(j) == "or")),
+			None => true,
+		}
+	}
+
+	// (p) 是括号模式, (a, b), (x: p), (a) { .. } 和 (a) name 是位置模式
+	fn is_parenthesized_pattern(&self) -> bool {
+		let close = match self.matching_paren(self.cur) {
+			Some(x) => x,
+			None => return true,
+		};
+		if close == self.cur + 1 || (self.is_identifier(self.cur + 1) && self.nth(2) == ":") || self.has_top_level_comma(close) {
+			return false;
+		}
+		self.text_at(close + 1) != "{" && !self.is_designation_at(close + 1)
+	}
+
+	// 当前 '(' 和 close 之间最外层有没有逗号
+	fn has_top_level_comma(&self, close: usize) -> bool {
+		let mut depth = 0;
+		for i in self.cur + 1..close {
+			match self.text_at(i) {
+				"(" | "[" | "{" | "?[" => depth += 1,
+				")" | "]" | "}" => depth -= 1,
+				"," if depth == 0 => return true,
+				_ => {}
+			}
+		}
+		false
+	}
+
+	// (1, 2), (x: 1, y: 2), (int a, var b)
+	fn is_tuple_ahead(&self) -> bool {
+		match self.matching_paren(self.cur) {
+			Some(close) => (self.is_identifier(self.cur + 1) && self.nth(2) == ":") || self.has_top_level_comma(close),
+			None => false,
+		}
+	}
+
+	// var (x, y) = .. 和 foreach (var (k, v) in ..)
+	fn is_deconstruction_ahead(&self) -> bool {
+		if !self.at("var") || self.nth(1) != "(" {
+			return false;
+		}
+		match self.matching_paren(self.cur + 1) {
+			Some(close) => self.text_at(close + 1) == "=" || self.text_at(close + 1) == "in",
+			None => false,
+		}
+	}
+
+	// -------------------------------- declarations -------------------------------------------
+
+	fn parse_compilation_unit(&mut self) {
+		self.parse_directives();
+		while !self.is_eof() {
+			let before = self.cur;
+			self.parse_namespace_member();
+			if self.cur == before {
+				self.error_token();
+			}
+		}
+	}
+
+	fn parse_directives(&mut self) {
+		loop {
+			if self.at("extern") && self.nth(1) == "alias" {
+				self.start(SyntaxKind::ExternAliasDirective, SemanticFlags::ExternAlias.bits());
+				self.bump();
+				self.bump();
+				self.expect_identifier();
+				self.expect_end();
+				self.finish();
+			}
+			else if self.at("using") && self.nth(1) != "(" {
+				self.parse_using_directive();
+			}
+			else {
+				break;
+			}
+		}
+	}
+
+	fn parse_using_directive(&mut self) {
+		let node = self.start(SyntaxKind::UsingDirective, SemanticFlags::UsingNamespace.bits());
+		self.bump();
+		if self.at("static") {
+			self.bump();
+		}
+		else if self.is_identifier(self.cur) && self.nth(1) == "=" {
+			self.set_kind(&node, SyntaxKind::UsingDirective, SemanticFlags::UsingAlias.bits());
+			self.bump();
+			self.bump();
+		}
+		self.parse_name();
+		self.expect_end();
+		self.finish();
+	}
+
+	fn parse_namespace_member(&mut self) {
+		if self.at("namespace") {
+			self.start(SyntaxKind::NamespaceDeclaration, SemanticFlags::NamespaceDeclaration.bits());
+			self.bump();
+			self.parse_name();
+			self.start(SyntaxKind::NamespaceBody, SemanticFlags::NamespaceBodyScope.bits());
+			self.expect("{");
+			self.parse_directives();
+			while !self.is_eof() && !self.at("}") {
+				let before = self.cur;
+				self.parse_namespace_member();
+				if self.cur == before {
+					self.error_token();
+				}
+			}
+			self.expect("}");
+			self.finish();
+			if self.at(";") {
+				self.bump();
+			}
+			self.finish();
+		}
+		else if self.at("using") {
+			self.parse_using_directive();
+		}
+		else {
+			self.parse_member(SyntaxKind::CompilationUnit);
+		}
+	}
+
+	fn parse_attribute_lists(&mut self) {
+		while self.at("[") {
+			self.start(SyntaxKind::AttributeList, SemanticFlags::AttributesScope.bits());
+			self.bump();
+			// [assembly: ...], [return: ...]
+			if (self.is_identifier(self.cur) || self.kind_at(self.cur) == TokenKind::Keyword) && self.nth(1) == ":" {
+				self.bump();
+				self.bump();
+			}
+			loop {
+				self.start(SyntaxKind::Attribute, 0);
+				self.parse_name();
+				if self.at("(") {
+					self.start(SyntaxKind::AttributeArgumentList, SemanticFlags::AttributeArgumentsScope.bits());
+					self.bump();
+					while !self.is_eof() && !self.at(")") {
+						self.start(SyntaxKind::AttributeArgument, 0);
+						if self.is_identifier(self.cur) && (self.nth(1) == "=" || self.nth(1) == ":") {
+							self.bump();
+							self.bump();
+						}
+						self.parse_expression();
+						self.finish();
+						if !self.at(",") {
+							break;
+						}
+						self.bump();
+					}
+					self.expect(")");
+					self.finish();
+				}
+				self.finish();
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+				if self.at("]") {
+					break;
+				}
+			}
+			self.expect("]");
+			self.finish();
+		}
+	}
+
+	fn is_modifier(&self) -> bool {
+		let text = self.text();
+		match text {
+			"async" | "partial" => {
+				// 上下文关键字, 后面必须还是声明
+				let next = self.nth(1);
+				self.is_identifier(self.cur + 1) || self.kind_at(self.cur + 1) == TokenKind::Keyword
+					&& !["operator", "this"].contains(&next)
+			}
+			"new" => self.kind_at(self.cur + 1) == TokenKind::Keyword || self.is_identifier(self.cur + 1),
+			_ => self.kind_at(self.cur) == TokenKind::Keyword && MODIFIERS.contains(&text),
+		}
+	}
+
+	fn parse_modifiers(&mut self) -> Vec<String> {
+		let mut modifiers = Vec::new();
+		while self.is_modifier() {
+			modifiers.push(self.text().to_string());
+			self.bump();
+		}
+		modifiers
+	}
+
+	// 类型成员, 也用于 namespace 里的类型声明
+	fn parse_member(&mut self, container: SyntaxKind) {
+		let node = self.start(SyntaxKind::Error, 0);
+		self.parse_attribute_lists();
+		let modifiers = self.parse_modifiers();
+		let is_interface = container == SyntaxKind::InterfaceDeclaration;
+		match self.text() {
+			"class" | "struct" | "interface" => self.parse_type_declaration(&node),
+			"enum" => self.parse_enum_declaration(&node),
+			"delegate" => self.parse_delegate_declaration(&node),
+			"event" => self.parse_event_declaration(&node, is_interface),
+			"~" => {
+				self.set_kind(&node, SyntaxKind::DestructorDeclaration,
+					SemanticFlags::DestructorDeclarator.with(SemanticFlags::MethodDeclarationScope));
+				self.bump();
+				self.expect_identifier();
+				self.parse_parameter_list();
+				self.parse_method_body();
+			}
+			"implicit" | "explicit" => {
+				self.set_kind(&node, SyntaxKind::ConversionOperatorDeclaration,
+					SemanticFlags::ConversionOperatorDeclarator.with(SemanticFlags::MethodDeclarationScope));
+				self.bump();
+				self.expect("operator");
+				self.parse_type();
+				self.parse_parameter_list();
+				self.parse_method_body();
+			}
+			_ => {
+				let is_constructor = self.is_identifier(self.cur) && self.nth(1) == "("
+					&& self.type_names.last().is_some_and(|x| x == self.text());
+				if is_constructor {
+					self.set_kind(&node, SyntaxKind::ConstructorDeclaration,
+						SemanticFlags::ConstructorDeclarator.with(SemanticFlags::MethodDeclarationScope));
+					self.bump();
+					self.parse_parameter_list();
+					if self.at(":") {
+						self.start(SyntaxKind::ConstructorInitializer, SemanticFlags::ConstructorInitializerScope.bits());
+						self.bump();
+						if self.at("base") || self.at("this") {
+							self.bump();
+						}
+						else {
+							self.missing("base");
+						}
+						self.parse_argument_list();
+						self.finish();
+					}
+					self.parse_method_body();
+				}
+				else if self.scan_type(self.cur).is_some() {
+					self.parse_typed_member(&node, &modifiers, is_interface);
+				}
+				else if node.has_children() {
+					self.missing_kind("member-declaration");
+				}
+			}
+		}
+		self.finish();
+	}
+
+	// Type 开头的成员: 字段, 方法, 属性, 索引器, 运算符
+	fn parse_typed_member(&mut self, node: &SyntaxRef, modifiers: &[String], is_interface: bool) {
+		let return_type = self.parse_type();
+		if self.at("operator") {
+			self.set_kind(node, SyntaxKind::OperatorDeclaration,
+				SemanticFlags::OperatorDeclarator.with(SemanticFlags::MethodDeclarationScope));
+			self.bump();
+			if self.at(">") && self.nth(1) == ">" && self.is_adjacent(self.cur) {
+				self.bump();
+				self.bump();
+			}
+			else if OVERLOADABLE_OPERATORS.contains(&self.text()) || self.at("false") {
+				self.bump();
+			}
+			else {
+				self.missing_kind("overloadable-operator");
+			}
+			self.parse_parameter_list();
+			self.parse_method_body();
+			return;
+		}
+		if self.at("this") {
+			let flags = if is_interface { SemanticFlags::InterfaceIndexerDeclaration } else { SemanticFlags::IndexerDeclaration };
+			self.set_kind(node, SyntaxKind::IndexerDeclaration, flags.with(SemanticFlags::MemberDeclarationScope));
+			self.bump();
+			self.start(SyntaxKind::BracketedParameterList, SemanticFlags::FormalParameterListScope.bits());
+			self.parse_parameters("[", "]");
+			self.finish();
+			self.parse_property_body(is_interface);
+			return;
+		}
+		let is_field = self.is_identifier(self.cur) && {
+			let next = self.nth(1);
+			next == "=" || next == ";" || next == "," || next == "}" || self.cur + 1 >= self.significant.len()
+				|| self.tokens[self.significant[self.cur+1]].line_index > self.tokens[self.significant[self.cur]].line_index
+		};
+		if is_field {
+			self.set_kind(node, SyntaxKind::FieldDeclaration, SemanticFlags::MemberDeclarationScope.bits());
+			let declarator = if modifiers.iter().any(|x| x == "const") {
+				SemanticFlags::ConstantDeclarator
+			}
+			else {
+				SemanticFlags::VariableDeclarator
+			};
+			self.precede(&return_type, SyntaxKind::VariableDeclaration, 0);
+			self.parse_variable_declarators(declarator);
+			self.finish();
+			self.expect_end();
+			return;
+		}
+		self.parse_member_name();
+		if self.at("(") || self.at("<") {
+			let flags = if is_interface { SemanticFlags::InterfaceMethodDeclaration } else { SemanticFlags::MethodDeclarator };
+			self.set_kind(node, SyntaxKind::MethodDeclaration, flags.with(SemanticFlags::MethodDeclarationScope));
+			if self.at("<") {
+				self.parse_type_parameter_list();
+			}
+			self.parse_parameter_list();
+			self.parse_constraint_clauses();
+			self.parse_method_body();
+		}
+		else if self.at("{") || self.at("=>") {
+			let flags = if is_interface { SemanticFlags::InterfacePropertyDeclaration } else { SemanticFlags::PropertyDeclaration };
+			self.set_kind(node, SyntaxKind::PropertyDeclaration, flags.with(SemanticFlags::MemberDeclarationScope));
+			self.parse_property_body(is_interface);
+		}
+		else {
+			self.set_kind(node, SyntaxKind::MethodDeclaration, SemanticFlags::MethodDeclarator.bits());
+			self.missing("(");
+		}
+	}
+
+	// IFoo<T>.Bar 显式接口实现的名字
+	fn parse_member_name(&mut self) {
+		let mut qualifiers = 0;
+		let mut i = self.cur;
+		loop {
+			if !self.is_identifier(i) {
+				break;
+			}
+			let mut j = i + 1;
+			if self.text_at(j) == "<" {
+				match self.scan_type_arguments(j) {
+					Some(x) => j = x,
+					None => break,
+				}
+			}
+			if self.text_at(j) == "." && self.is_identifier(j + 1) {
+				qualifiers += 1;
+				i = j + 1;
+			}
+			else {
+				break;
+			}
+		}
+		if qualifiers > 0 {
+			self.start(SyntaxKind::ExplicitInterfaceSpecifier, 0);
+			let mut name = self.parse_simple_name(false);
+			for _ in 1..qualifiers {
+				self.precede(&name, SyntaxKind::QualifiedName, 0);
+				self.bump();
+				self.parse_simple_name(false);
+				name = self.finish();
+			}
+			self.expect(".");
+			self.finish();
+		}
+		self.expect_identifier();
+	}
+
+	fn parse_type_declaration(&mut self, node: &SyntaxRef) {
+		let (kind, declaration, base_scope, body_scope) = match self.text() {
+			"class" => (SyntaxKind::ClassDeclaration, SemanticFlags::ClassDeclaration, SemanticFlags::ClassBaseScope,
+				SemanticFlags::ClassBodyScope),
+			"struct" => (SyntaxKind::StructDeclaration, SemanticFlags::StructDeclaration,
+				SemanticFlags::StructInterfacesScope, SemanticFlags::StructBodyScope),
+			_ => (SyntaxKind::InterfaceDeclaration, SemanticFlags::InterfaceDeclaration,
+				SemanticFlags::InterfaceBaseScope, SemanticFlags::InterfaceBodyScope),
+		};
+		self.set_kind(node, kind, declaration.with(SemanticFlags::TypeDeclarationScope));
+		self.bump();
+		let name = self.text().to_string();
+		self.expect_identifier();
+		if self.at("<") {
+			self.parse_type_parameter_list();
+		}
+		if self.at(":") {
+			self.start(SyntaxKind::BaseList, SemanticFlags::BaseListDeclaration.with(base_scope));
+			self.bump();
+			loop {
+				self.parse_type();
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+			self.finish();
+		}
+		self.parse_constraint_clauses();
+		self.type_names.push(name);
+		self.start(SyntaxKind::TypeBody, body_scope.bits());
+		self.expect("{");
+		while !self.is_eof() && !self.at("}") {
+			let before = self.cur;
+			self.parse_member(kind);
+			if self.cur == before {
+				self.error_token();
+			}
+		}
+		self.expect("}");
+		self.finish();
+		self.type_names.pop();
+		if self.at(";") {
+			self.bump();
+		}
+	}
+
+	fn parse_enum_declaration(&mut self, node: &SyntaxRef) {
+		self.set_kind(node, SyntaxKind::EnumDeclaration,
+			SemanticFlags::EnumDeclaration.with(SemanticFlags::TypeDeclarationScope));
+		self.bump();
+		self.expect_identifier();
+		if self.at(":") {
+			self.start(SyntaxKind::BaseList, SemanticFlags::BaseListDeclaration.bits());
+			self.bump();
+			self.parse_type();
+			self.finish();
+		}
+		self.start(SyntaxKind::EnumBody, SemanticFlags::EnumBodyScope.bits());
+		self.expect("{");
+		while !self.is_eof() && !self.at("}") {
+			self.start(SyntaxKind::EnumMemberDeclaration, SemanticFlags::EnumMemberDeclaration.bits());
+			self.parse_attribute_lists();
+			self.expect_identifier();
+			if self.at("=") {
+				self.bump();
+				self.parse_expression();
+			}
+			self.finish();
+			if self.at(",") {
+				self.bump();
+			}
+			else if !self.at("}") {
+				if self.newline_before() && self.is_identifier(self.cur) {
+					continue;
+				}
+				self.missing(",");
+				break;
+			}
+		}
+		self.expect("}");
+		self.finish();
+		if self.at(";") {
+			self.bump();
+		}
+	}
+
+	fn parse_delegate_declaration(&mut self, node: &SyntaxRef) {
+		self.set_kind(node, SyntaxKind::DelegateDeclaration,
+			SemanticFlags::DelegateDeclaration.with(SemanticFlags::TypeDeclarationScope));
+		self.bump();
+		self.parse_type();
+		self.expect_identifier();
+		if self.at("<") {
+			self.parse_type_parameter_list();
+		}
+		self.parse_parameter_list();
+		self.parse_constraint_clauses();
+		self.expect_end();
+	}
+
+	fn parse_event_declaration(&mut self, node: &SyntaxRef, is_interface: bool) {
+		self.bump();
+		let event_type = self.parse_type();
+		let with_accessors = self.is_identifier(self.cur) && (self.nth(1) == "{" || self.nth(1) == ".");
+		if with_accessors {
+			let flags = if is_interface { SemanticFlags::InterfaceEventDeclaration } else { SemanticFlags::EventWithAccessorsDeclaration };
+			self.set_kind(node, SyntaxKind::EventDeclaration, flags.with(SemanticFlags::MemberDeclarationScope));
+			self.parse_member_name();
+			self.parse_accessor_list(is_interface);
+		}
+		else {
+			self.set_kind(node, SyntaxKind::EventFieldDeclaration, SemanticFlags::MemberDeclarationScope.bits());
+			self.precede(&event_type, SyntaxKind::VariableDeclaration, 0);
+			let flags = if is_interface { SemanticFlags::InterfaceEventDeclaration } else { SemanticFlags::EventDeclarator };
+			self.parse_variable_declarators(flags);
+			self.finish();
+			self.expect_end();
+		}
+	}
+
+	fn parse_type_parameter_list(&mut self) {
+		self.start(SyntaxKind::TypeParameterList, 0);
+		self.bump();
+		loop {
+			self.start(SyntaxKind::TypeParameter, SemanticFlags::TypeParameterDeclaration.bits());
+			self.parse_attribute_lists();
+			if self.at("in") || self.at("out") {
+				self.bump();
+			}
+			self.expect_identifier();
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect(">");
+		self.finish();
+	}
+
+	fn parse_constraint_clauses(&mut self) {
+		while self.at("where") && self.is_identifier(self.cur + 1) && self.nth(2) == ":" {
+			self.start(SyntaxKind::TypeParameterConstraintClause, SemanticFlags::TypeParameterConstraintsScope.bits());
+			self.bump();
+			self.bump();
+			self.bump();
+			loop {
+				if self.at("class") || self.at("struct") {
+					self.bump();
+				}
+				else if self.at("new") {
+					self.bump();
+					self.expect("(");
+					self.expect(")");
+				}
+				else {
+					self.parse_type();
+				}
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+			self.finish();
+		}
+	}
+
+	fn parse_parameter_list(&mut self) {
+		self.start(SyntaxKind::ParameterList, SemanticFlags::FormalParameterListScope.bits());
+		self.parse_parameters("(", ")");
+		self.finish();
+	}
+
+	fn parse_parameters(&mut self, open: &str, close: &str) {
+		if !self.expect(open) {
+			return;
+		}
+		while !self.is_eof() && !self.at(close) {
+			let node = self.start(SyntaxKind::Parameter, SemanticFlags::FixedParameterDeclaration.bits());
+			self.parse_attribute_lists();
+			while PARAMETER_MODIFIERS.contains(&self.text()) && self.kind_at(self.cur) == TokenKind::Keyword {
+				if self.at("params") {
+					self.set_kind(&node, SyntaxKind::Parameter, SemanticFlags::ParameterArrayDeclaration.bits());
+				}
+				self.bump();
+			}
+			self.parse_type();
+			self.expect_identifier();
+			if self.at("=") {
+				self.bump();
+				self.parse_expression();
+			}
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect(close);
+	}
+
+	fn parse_method_body(&mut self) {
+		if self.at("{") {
+			self.parse_block(SemanticFlags::MethodBodyScope);
+		}
+		else if self.at("=>") {
+			self.parse_arrow_expression_clause();
+			self.expect_end();
+		}
+		else {
+			self.expect_end();
+		}
+	}
+
+	fn parse_arrow_expression_clause(&mut self) {
+		self.start(SyntaxKind::ArrowExpressionClause, 0);
+		self.bump();
+		self.parse_expression();
+		self.finish();
+	}
+
+	fn parse_property_body(&mut self, is_interface: bool) {
+		if self.at("=>") {
+			self.parse_arrow_expression_clause();
+			self.expect_end();
+			return;
+		}
+		self.parse_accessor_list(is_interface);
+		if self.at("=") {
+			self.bump();
+			self.parse_variable_initializer();
+			self.expect_end();
+		}
+	}
+
+	fn parse_accessor_list(&mut self, is_interface: bool) {
+		self.start(SyntaxKind::AccessorList, SemanticFlags::AccessorsListScope.bits());
+		self.expect("{");
+		while !self.is_eof() && !self.at("}") {
+			let node = self.start(SyntaxKind::AccessorDeclaration, 0);
+			self.parse_attribute_lists();
+			self.parse_modifiers();
+			let flags = match (self.text(), is_interface) {
+				("get", false) => SemanticFlags::GetAccessorDeclaration,
+				("set", false) => SemanticFlags::SetAccessorDeclaration,
+				("get", true) => SemanticFlags::InterfaceGetAccessorDeclaration,
+				("set", true) => SemanticFlags::InterfaceSetAccessorDeclaration,
+				("add", _) => SemanticFlags::AddAccessorDeclaration,
+				("remove", _) => SemanticFlags::RemoveAccessorDeclaration,
+				_ => SemanticFlags::None,
+			};
+			if flags == SemanticFlags::None {
+				self.missing("get");
+				self.finish();
+				break;
+			}
+			self.set_kind(&node, SyntaxKind::AccessorDeclaration, flags.bits());
+			self.bump();
+			if self.at("{") {
+				self.parse_block(SemanticFlags::AccessorBodyScope);
+			}
+			else if self.at("=>") {
+				self.parse_arrow_expression_clause();
+				self.expect_end();
+			}
+			else {
+				self.expect_end();
+			}
+			self.finish();
+		}
+		self.expect("}");
+		self.finish();
+	}
+
+	fn parse_variable_declarators(&mut self, flags: SemanticFlags) {
+		loop {
+			self.start(SyntaxKind::VariableDeclarator, flags.bits());
+			self.expect_identifier();
+			if self.at("=") {
+				self.bump();
+				self.parse_variable_initializer();
+			}
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+	}
+
+	fn parse_variable_initializer(&mut self) {
+		if self.at("{") {
+			self.parse_initializer();
+		}
+		else {
+			self.parse_expression();
+		}
+	}
+
+	// -------------------------------- types --------------------------------------------------
+
+	fn parse_type(&mut self) -> SyntaxRef {
+		self.parse_type_with(false)
+	}
+
+	// in_expression: 'x is int ? a : b' 里的 '?' 不是可空类型
+	fn parse_type_with(&mut self, in_expression: bool) -> SyntaxRef {
+		let mut node = self.parse_non_array_type();
+		loop {
+			if self.at("?") && (!in_expression || self.is_nullable_suffix()) {
+				self.precede(&node, SyntaxKind::NullableType, 0);
+				self.bump();
+				node = self.finish();
+			}
+			else if self.at("?[") && (self.nth(1) == "]" || self.nth(1) == ",") {
+				self.split_current(1);
+				self.precede(&node, SyntaxKind::NullableType, 0);
+				self.bump();
+				node = self.finish();
+			}
+			else if self.at("[") && (self.nth(1) == "]" || self.nth(1) == ",") {
+				self.precede(&node, SyntaxKind::ArrayType, 0);
+				while self.at("[") && (self.nth(1) == "]" || self.nth(1) == ",") {
+					self.start(SyntaxKind::ArrayRankSpecifier, 0);
+					self.bump();
+					while self.at(",") {
+						self.bump();
+					}
+					self.expect("]");
+					self.finish();
+				}
+				node = self.finish();
+			}
+			else {
+				return node;
+			}
+		}
+	}
+
+	fn is_nullable_suffix(&self) -> bool {
+		let next = self.nth(1);
+		self.cur + 1 >= self.significant.len()
+			|| [")", ",", ";", "]", "}", "=", "??", "&&", "||", "==", "!="].contains(&next)
+	}
+
+	fn parse_non_array_type(&mut self) -> SyntaxRef {
+		if self.is_predefined_type(self.cur) {
+			self.start(SyntaxKind::PredefinedType, 0);
+			self.bump();
+			return self.finish();
+		}
+		if self.at("(") {
+			self.start(SyntaxKind::TupleType, 0);
+			self.bump();
+			while !self.is_eof() && !self.at(")") {
+				self.start(SyntaxKind::TupleElement, 0);
+				self.parse_type();
+				if self.is_identifier(self.cur) {
+					self.bump();
+				}
+				self.finish();
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+			self.expect(")");
+			return self.finish();
+		}
+		if self.is_identifier(self.cur) {
+			return self.parse_name();
+		}
+		self.start(SyntaxKind::IdentifierName, 0);
+		self.missing_kind("type");
+		self.finish()
+	}
+
+	fn parse_name(&mut self) -> SyntaxRef {
+		let mut node = if self.is_identifier(self.cur) && self.nth(1) == "::" {
+			self.start(SyntaxKind::AliasQualifiedName, 0);
+			self.bump();
+			self.bump();
+			self.parse_simple_name(false);
+			self.finish()
+		}
+		else {
+			self.parse_simple_name(false)
+		};
+		while self.at(".") && self.is_identifier(self.cur + 1) {
+			self.precede(&node, SyntaxKind::QualifiedName, 0);
+			self.bump();
+			self.parse_simple_name(false);
+			node = self.finish();
+		}
+		node
+	}
+
+	fn parse_simple_name(&mut self, in_expression: bool) -> SyntaxRef {
+		let is_generic = if in_expression {
+			self.is_generic_name_ahead(self.cur)
+		}
+		else {
+			self.is_identifier(self.cur) && self.nth(1) == "<" && self.scan_type_arguments(self.cur + 1).is_some()
+		};
+		if is_generic {
+			self.start(SyntaxKind::GenericName, 0);
+			self.bump();
+			self.parse_type_argument_list();
+			return self.finish();
+		}
+		self.start(SyntaxKind::IdentifierName, 0);
+		self.expect_identifier();
+		self.finish()
+	}
+
+	fn parse_type_argument_list(&mut self) {
+		self.start(SyntaxKind::TypeArgumentList, 0);
+		self.bump();
+		if self.at(">") || self.at(",") {
+			while self.at(",") {
+				self.bump();
+			}
+		}
+		else {
+			loop {
+				self.parse_type();
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+		}
+		self.expect(">");
+		self.finish();
+	}
+
+	// -------------------------------- statements ---------------------------------------------
+
+	fn parse_block(&mut self, scope: SemanticFlags) -> SyntaxRef {
+		self.start(SyntaxKind::Block, scope.bits());
+		self.expect("{");
+		while !self.is_eof() && !self.at("}") {
+			let before = self.cur;
+			self.parse_statement();
+			if self.cur == before {
+				self.error_token();
+			}
+		}
+		self.expect("}");
+		self.finish()
+	}
+
+	fn parse_statement(&mut self) -> SyntaxRef {
+		match self.text() {
+			"{" if self.kind_at(self.cur) == TokenKind::PunctuatorOrOperator => {
+				return self.parse_block(SemanticFlags::CodeBlockScope);
+			}
+			";" => {
+				self.start(SyntaxKind::EmptyStatement, 0);
+				self.bump();
+				return self.finish();
+			}
+			"if" => return self.parse_if_statement(),
+			"while" => {
+				self.start(SyntaxKind::WhileStatement, 0);
+				self.bump();
+				self.parse_parenthesized_condition();
+				self.parse_statement();
+				return self.finish();
+			}
+			"do" => {
+				self.start(SyntaxKind::DoStatement, 0);
+				self.bump();
+				self.parse_statement();
+				self.expect("while");
+				self.parse_parenthesized_condition();
+				self.expect_end();
+				return self.finish();
+			}
+			"for" => return self.parse_for_statement(),
+			"foreach" => return self.parse_foreach_statement(),
+			"switch" => return self.parse_switch_statement(),
+			"break" | "continue" => {
+				let kind = if self.at("break") { SyntaxKind::BreakStatement } else { SyntaxKind::ContinueStatement };
+				self.start(kind, 0);
+				self.bump();
+				self.expect_end();
+				return self.finish();
+			}
+			"return" | "throw" => {
+				let kind = if self.at("return") { SyntaxKind::ReturnStatement } else { SyntaxKind::ThrowStatement };
+				self.start(kind, 0);
+				self.bump();
+				if !self.at_statement_end() {
+					self.parse_expression();
+				}
+				self.expect_end();
+				return self.finish();
+			}
+			"try" => return self.parse_try_statement(),
+			"using" if self.nth(1) == "(" => {
+				self.start(SyntaxKind::UsingStatement, SemanticFlags::UsingStatementScope.bits());
+				self.bump();
+				self.bump();
+				if self.is_local_declaration_ahead() {
+					self.parse_variable_declaration(SemanticFlags::LocalVariableDeclarator);
+				}
+				else {
+					self.parse_expression();
+				}
+				self.expect(")");
+				self.parse_statement();
+				return self.finish();
+			}
+			// using var r = Open();  作用域到所在块的结尾
+			"using" => {
+				self.start(SyntaxKind::LocalDeclarationStatement, 0);
+				self.bump();
+				self.parse_variable_declaration(SemanticFlags::LocalVariableDeclarator);
+				self.expect_end();
+				return self.finish();
+			}
+			"lock" => {
+				self.start(SyntaxKind::LockStatement, 0);
+				self.bump();
+				self.parse_parenthesized_condition();
+				self.parse_statement();
+				return self.finish();
+			}
+			"goto" => {
+				self.start(SyntaxKind::GotoStatement, 0);
+				self.bump();
+				if self.at("case") {
+					self.bump();
+					self.parse_expression();
+				}
+				else if self.at("default") {
+					self.bump();
+				}
+				else {
+					self.expect_identifier();
+				}
+				self.expect_end();
+				return self.finish();
+			}
+			"checked" | "unchecked" if self.nth(1) == "{" => {
+				self.start(SyntaxKind::CheckedStatement, 0);
+				self.bump();
+				self.parse_block(SemanticFlags::CodeBlockScope);
+				return self.finish();
+			}
+			"yield" if self.nth(1) == "return" || self.nth(1) == "break" => {
+				let kind = if self.nth(1) == "return" { SyntaxKind::YieldReturnStatement } else { SyntaxKind::YieldBreakStatement };
+				self.start(kind, 0);
+				self.bump();
+				self.bump();
+				if kind == SyntaxKind::YieldReturnStatement {
+					self.parse_expression();
+				}
+				self.expect_end();
+				return self.finish();
+			}
+			"const" => {
+				self.start(SyntaxKind::LocalDeclarationStatement, 0);
+				self.bump();
+				self.parse_variable_declaration(SemanticFlags::ConstantDeclarator);
+				self.expect_end();
+				return self.finish();
+			}
+			_ => {}
+		}
+		if self.is_identifier(self.cur) && self.nth(1) == ":" {
+			self.start(SyntaxKind::LabeledStatement, SemanticFlags::LabeledStatement.bits());
+			self.bump();
+			self.bump();
+			if !self.at("}") {
+				self.parse_statement();
+			}
+			return self.finish();
+		}
+		if self.is_local_declaration_ahead() {
+			self.start(SyntaxKind::LocalDeclarationStatement, 0);
+			self.parse_variable_declaration(SemanticFlags::LocalVariableDeclarator);
+			self.expect_end();
+			return self.finish();
+		}
+		self.start(SyntaxKind::ExpressionStatement, 0);
+		self.parse_expression();
+		self.expect_end();
+		self.finish()
+	}
+
+	fn parse_variable_declaration(&mut self, flags: SemanticFlags) -> SyntaxRef {
+		self.start(SyntaxKind::VariableDeclaration, 0);
+		self.parse_type();
+		self.parse_variable_declarators(flags);
+		self.finish()
+	}
+
+	fn parse_parenthesized_condition(&mut self) {
+		self.expect("(");
+		self.parse_expression();
+		self.expect(")");
+	}
+
+	fn parse_if_statement(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::IfStatement, 0);
+		self.bump();
+		self.parse_parenthesized_condition();
+		self.parse_statement();
+		if self.at("else") {
+			self.start(SyntaxKind::ElseClause, 0);
+			self.bump();
+			self.parse_statement();
+			self.finish();
+		}
+		self.finish()
+	}
+
+	fn parse_for_statement(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::ForStatement, SemanticFlags::ForStatementScope.bits());
+		self.bump();
+		self.expect("(");
+		if self.is_local_declaration_ahead() {
+			self.parse_variable_declaration(SemanticFlags::LocalVariableDeclarator);
+		}
+		else {
+			self.parse_expression_list(";");
+		}
+		self.expect(";");
+		if !self.at(";") {
+			self.parse_expression();
+		}
+		self.expect(";");
+		self.parse_expression_list(")");
+		self.expect(")");
+		self.parse_statement();
+		self.finish()
+	}
+
+	fn parse_expression_list(&mut self, close: &str) {
+		while !self.is_eof() && !self.at(close) {
+			self.parse_expression();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+	}
+
+	fn parse_foreach_statement(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::ForEachStatement, SemanticFlags::ForStatementScope.bits());
+		self.bump();
+		self.expect("(");
+		if self.is_deconstruction_ahead() {
+			self.parse_deconstruction_declaration(SemanticFlags::ForEachVariableDeclaration);
+		}
+		else if self.at("(") && !self.scan_type(self.cur).is_some_and(|j| self.is_identifier(j)) {
+			// foreach ((int k, var v) in ..)
+			self.parse_tuple_expression();
+		}
+		else {
+			self.parse_type();
+			self.start(SyntaxKind::VariableDeclarator, SemanticFlags::ForEachVariableDeclaration.bits());
+			self.expect_identifier();
+			self.finish();
+		}
+		self.expect("in");
+		self.parse_expression();
+		self.expect(")");
+		self.parse_statement();
+		self.finish()
+	}
+
+	fn parse_switch_statement(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::SwitchStatement, SemanticFlags::SwitchBlockScope.bits());
+		self.bump();
+		self.parse_parenthesized_condition();
+		self.expect("{");
+		while !self.is_eof() && !self.at("}") {
+			if !self.at("case") && !self.at("default") {
+				self.missing("case");
+				break;
+			}
+			self.start(SyntaxKind::SwitchSection, 0);
+			while self.at("case") || (self.at("default") && self.nth(1) == ":") {
+				if self.at("case") {
+					let label = self.start(SyntaxKind::CaseSwitchLabel, 0);
+					self.bump();
+					let pattern = self.parse_pattern();
+					// case 1: 还是常量标签
+					if pattern.kind() == SyntaxKind::ConstantPattern && !self.at("when") {
+						self.dissolve(&pattern);
+					}
+					else {
+						self.set_kind(&label, SyntaxKind::CasePatternSwitchLabel, 0);
+						self.parse_when_clause();
+					}
+				}
+				else {
+					self.start(SyntaxKind::DefaultSwitchLabel, 0);
+					self.bump();
+				}
+				self.expect(":");
+				self.finish();
+			}
+			while !self.is_eof() && !self.at("}") && !self.at("case") && (!self.at("default") || self.nth(1) != ":") {
+				let before = self.cur;
+				self.parse_statement();
+				if self.cur == before {
+					self.error_token();
+				}
+			}
+			self.finish();
+		}
+		self.expect("}");
+		self.finish()
+	}
+
+	fn parse_try_statement(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::TryStatement, 0);
+		self.bump();
+		self.parse_block(SemanticFlags::CodeBlockScope);
+		while self.at("catch") {
+			self.start(SyntaxKind::CatchClause, SemanticFlags::SpecificCatchScope.bits());
+			self.bump();
+			if self.at("(") {
+				self.start(SyntaxKind::CatchDeclaration, SemanticFlags::CatchExceptionParameterDeclaration.bits());
+				self.bump();
+				self.parse_type();
+				if self.is_identifier(self.cur) {
+					self.bump();
+				}
+				self.expect(")");
+				self.finish();
+			}
+			if self.at("when") {
+				self.start(SyntaxKind::CatchFilterClause, 0);
+				self.bump();
+				self.parse_parenthesized_condition();
+				self.finish();
+			}
+			self.parse_block(SemanticFlags::CodeBlockScope);
+			self.finish();
+		}
+		if self.at("finally") {
+			self.start(SyntaxKind::FinallyClause, 0);
+			self.bump();
+			self.parse_block(SemanticFlags::CodeBlockScope);
+			self.finish();
+		}
+		self.finish()
+	}
+
+	// -------------------------------- expressions --------------------------------------------
+
+	fn parse_expression(&mut self) -> SyntaxRef {
+		if self.is_lambda_ahead() {
+			return self.parse_lambda();
+		}
+		let lhs = self.parse_conditional();
+		if let Some(count) = self.assignment_operator() {
+			self.precede(&lhs, SyntaxKind::AssignmentExpression, 0);
+			for _ in 0..count {
+				self.bump();
+			}
+			self.parse_expression();
+			return self.finish();
+		}
+		lhs
+	}
+
+	// 返回组成运算符的 token 个数, '>' '>=' 组成 '>>='
+	fn assignment_operator(&self) -> Option<usize> {
+		if self.at(">") && self.nth(1) == ">=" && self.is_adjacent(self.cur) {
+			return Some(2);
+		}
+		if ASSIGNMENT_OPERATORS.contains(&self.text()) && self.kind_at(self.cur) == TokenKind::PunctuatorOrOperator {
+			return Some(1);
+		}
+		None
+	}
+
+	fn parse_conditional(&mut self) -> SyntaxRef {
+		let condition = self.parse_binary(1);
+		if self.at("?") {
+			self.precede(&condition, SyntaxKind::ConditionalExpression, 0);
+			self.bump();
+			self.parse_expression();
+			self.expect(":");
+			self.parse_expression();
+			return self.finish();
+		}
+		condition
+	}
+
+	// (优先级, token 个数, 右结合)
+	fn binary_operator(&self) -> Option<(u8, usize, bool)> {
+		if self.kind_at(self.cur) != TokenKind::PunctuatorOrOperator && !self.at("is") && !self.at("as") {
+			return None;
+		}
+		let operator = match self.text() {
+			"??" => (1, 1, true),
+			"||" => (2, 1, false),
+			"&&" => (3, 1, false),
+			"|" => (4, 1, false),
+			"^" => (5, 1, false),
+			"&" => (6, 1, false),
+			"==" | "!=" => (7, 1, false),
+			"<" | "<=" | ">=" | "is" | "as" => (8, 1, false),
+			">" => {
+				if self.nth(1) == ">" && self.is_adjacent(self.cur) {
+					(9, 2, false)
+				}
+				else if self.nth(1) == ">=" && self.is_adjacent(self.cur) {
+					return None;
+				}
+				else {
+					(8, 1, false)
+				}
+			}
+			"<<" => (9, 1, false),
+			"+" | "-" => (10, 1, false),
+			"*" | "/" | "%" => (11, 1, false),
+			_ => return None,
+		};
+		Some(operator)
+	}
+
+	fn parse_binary(&mut self, min_precedence: u8) -> SyntaxRef {
+		let mut lhs = self.parse_unary();
+		// x switch { .. } 比二元运算优先级高
+		while self.at("switch") && self.nth(1) == "{" {
+			lhs = self.parse_switch_expression(&lhs);
+		}
+		loop {
+			let (precedence, count, right) = match self.binary_operator() {
+				Some(x) => x,
+				None => return lhs,
+			};
+			if precedence < min_precedence {
+				return lhs;
+			}
+			if self.at("is") && self.is_pattern_ahead(self.cur + 1) {
+				self.precede(&lhs, SyntaxKind::IsPatternExpression, 0);
+				self.bump();
+				self.parse_pattern();
+				lhs = self.finish();
+				continue;
+			}
+			if self.at("is") || self.at("as") {
+				let kind = if self.at("is") { SyntaxKind::IsExpression } else { SyntaxKind::AsExpression };
+				self.precede(&lhs, kind, 0);
+				self.bump();
+				self.parse_type_with(true);
+				lhs = self.finish();
+				continue;
+			}
+			self.precede(&lhs, SyntaxKind::BinaryExpression, 0);
+			for _ in 0..count {
+				self.bump();
+			}
+			self.parse_binary(if right { precedence } else { precedence + 1 });
+			lhs = self.finish();
+		}
+	}
+
+	fn parse_unary(&mut self) -> SyntaxRef {
+		if PREFIX_OPERATORS.contains(&self.text()) && self.kind_at(self.cur) == TokenKind::PunctuatorOrOperator {
+			self.start(SyntaxKind::PrefixUnaryExpression, 0);
+			self.bump();
+			self.parse_unary();
+			return self.finish();
+		}
+		if self.at("await") && self.is_await_operand(self.cur + 1) {
+			self.start(SyntaxKind::AwaitExpression, 0);
+			self.bump();
+			self.parse_unary();
+			return self.finish();
+		}
+		if self.at("(") && self.is_cast_ahead() {
+			self.start(SyntaxKind::CastExpression, 0);
+			self.bump();
+			self.parse_type();
+			self.expect(")");
+			self.parse_unary();
+			return self.finish();
+		}
+		let primary = self.parse_primary();
+		self.parse_postfix(primary)
+	}
+
+	fn parse_postfix(&mut self, mut node: SyntaxRef) -> SyntaxRef {
+		loop {
+			let text = self.text().to_string();
+			match text.as_str() {
+				"." if self.kind_at(self.cur) == TokenKind::PunctuatorOrOperator => {
+					self.precede(&node, SyntaxKind::MemberAccessExpression, 0);
+					self.bump();
+					self.parse_simple_name(true);
+					node = self.finish();
+				}
+				"::" => {
+					self.precede(&node, SyntaxKind::AliasQualifiedName, 0);
+					self.bump();
+					self.parse_simple_name(true);
+					node = self.finish();
+				}
+				"?." | "?[" => {
+					self.precede(&node, SyntaxKind::ConditionalAccessExpression, 0);
+					self.split_current(1);
+					self.bump();
+					let binding = if self.at(".") {
+						self.start(SyntaxKind::MemberBindingExpression, 0);
+						self.bump();
+						self.parse_simple_name(true);
+						self.finish()
+					}
+					else {
+						self.start(SyntaxKind::ElementBindingExpression, 0);
+						self.parse_bracketed_argument_list();
+						self.finish()
+					};
+					self.parse_postfix(binding);
+					return self.finish();
+				}
+				"(" if !self.newline_before() => {
+					self.precede(&node, SyntaxKind::InvocationExpression, 0);
+					self.parse_argument_list();
+					node = self.finish();
+				}
+				"[" if !self.newline_before() => {
+					self.precede(&node, SyntaxKind::ElementAccessExpression, 0);
+					self.parse_bracketed_argument_list();
+					node = self.finish();
+				}
+				"++" | "--" if !self.newline_before() => {
+					self.precede(&node, SyntaxKind::PostfixUnaryExpression, 0);
+					self.bump();
+					node = self.finish();
+				}
+				// x! 是 null-forgiving, 只影响可空分析
+				"!" => {
+					self.precede(&node, SyntaxKind::PostfixUnaryExpression, 0);
+					self.bump();
+					node = self.finish();
+				}
+				_ => return node,
+			}
+		}
+	}
+
+	fn parse_argument_list(&mut self) {
+		self.start(SyntaxKind::ArgumentList, SemanticFlags::ArgumentListScope.bits());
+		self.parse_arguments("(", ")");
+		self.finish();
+	}
+
+	fn parse_bracketed_argument_list(&mut self) {
+		self.start(SyntaxKind::BracketedArgumentList, SemanticFlags::ArgumentListScope.bits());
+		self.parse_arguments("[", "]");
+		self.finish();
+	}
+
+	fn parse_arguments(&mut self, open: &str, close: &str) {
+		if !self.expect(open) {
+			return;
+		}
+		while !self.is_eof() && !self.at(close) {
+			self.start(SyntaxKind::Argument, 0);
+			if self.is_identifier(self.cur) && self.nth(1) == ":" {
+				self.bump();
+				self.bump();
+			}
+			let is_out = self.at("out");
+			if self.at("ref") || self.at("out") || self.at("in") {
+				self.bump();
+			}
+			// out var x, out int x
+			let is_declaration = is_out && match self.scan_type(self.cur) {
+				Some(j) => self.is_identifier(j) && (self.text_at(j+1) == ")" || self.text_at(j+1) == ","),
+				None => false,
+			};
+			if is_declaration {
+				self.start(SyntaxKind::DeclarationExpression, 0);
+				self.parse_type();
+				self.start(SyntaxKind::VariableDeclarator, SemanticFlags::LocalVariableDeclarator.bits());
+				self.bump();
+				self.finish();
+				self.finish();
+			}
+			else {
+				self.parse_expression();
+			}
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect(close);
+	}
+
+	fn parse_primary(&mut self) -> SyntaxRef {
+		let kind = self.kind_at(self.cur);
+		if kind.is_literal() {
+			self.start(SyntaxKind::LiteralExpression, 0);
+			self.bump();
+			return self.finish();
+		}
+		if self.is_deconstruction_ahead() {
+			return self.parse_deconstruction_declaration(SemanticFlags::LocalVariableDeclarator);
+		}
+		if self.at("async") && self.nth(1) == "delegate" {
+			return self.parse_anonymous_method();
+		}
+		if kind == TokenKind::Identifier {
+			let node = if self.nth(1) == "::" {
+				self.start(SyntaxKind::AliasQualifiedName, 0);
+				self.bump();
+				self.bump();
+				self.parse_simple_name(true);
+				self.finish()
+			}
+			else {
+				self.parse_simple_name(true)
+			};
+			return node;
+		}
+		if self.is_predefined_type(self.cur) {
+			self.start(SyntaxKind::PredefinedType, 0);
+			self.bump();
+			return self.finish();
+		}
+		match self.text() {
+			"this" => {
+				self.start(SyntaxKind::ThisExpression, 0);
+				self.bump();
+				self.finish()
+			}
+			"base" => {
+				self.start(SyntaxKind::BaseExpression, 0);
+				self.bump();
+				self.finish()
+			}
+			"(" if self.is_tuple_ahead() => self.parse_tuple_expression(),
+			"(" => {
+				self.start(SyntaxKind::ParenthesizedExpression, 0);
+				self.bump();
+				self.parse_expression();
+				self.expect(")");
+				self.finish()
+			}
+			"new" => self.parse_new(),
+			"typeof" | "sizeof" => {
+				let kind = if self.at("typeof") { SyntaxKind::TypeOfExpression } else { SyntaxKind::SizeOfExpression };
+				self.start(kind, 0);
+				self.bump();
+				self.expect("(");
+				self.parse_type();
+				self.expect(")");
+				self.finish()
+			}
+			"default" => {
+				if self.nth(1) == "(" {
+					self.start(SyntaxKind::DefaultExpression, 0);
+					self.bump();
+					self.bump();
+					self.parse_type();
+					self.expect(")");
+				}
+				else {
+					self.start(SyntaxKind::LiteralExpression, 0);
+					self.bump();
+				}
+				self.finish()
+			}
+			"checked" | "unchecked" => {
+				self.start(SyntaxKind::CheckedExpression, 0);
+				self.bump();
+				self.expect("(");
+				self.parse_expression();
+				self.expect(")");
+				self.finish()
+			}
+			"delegate" => self.parse_anonymous_method(),
+			_ => {
+				self.start(SyntaxKind::Error, 0);
+				self.missing_kind("expression");
+				self.finish()
+			}
+		}
+	}
+
+	// [async] delegate [(parameters)] { .. }
+	fn parse_anonymous_method(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::AnonymousMethodExpression,
+			SemanticFlags::AnonymousMethodDeclaration.with(SemanticFlags::AnonymousMethodScope));
+		if self.at("async") {
+			self.bump();
+		}
+		self.bump();
+		if self.at("(") {
+			self.parse_parameter_list();
+		}
+		self.parse_block(SemanticFlags::AnonymousMethodBodyScope);
+		self.finish()
+	}
+
+	fn parse_tuple_expression(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::TupleExpression, 0);
+		self.bump();
+		while !self.is_eof() && !self.at(")") {
+			self.start(SyntaxKind::Argument, 0);
+			if self.is_identifier(self.cur) && self.nth(1) == ":" {
+				self.bump();
+				self.bump();
+			}
+			// (int x, var y) = ..
+			let is_declaration = match self.scan_type(self.cur) {
+				Some(j) => self.is_identifier(j) && (self.text_at(j+1) == "," || self.text_at(j+1) == ")"),
+				None => false,
+			};
+			if is_declaration {
+				self.start(SyntaxKind::DeclarationExpression, 0);
+				self.parse_type();
+				self.parse_designation(SemanticFlags::LocalVariableDeclarator);
+				self.finish();
+			}
+			else {
+				self.parse_expression();
+			}
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect(")");
+		self.finish()
+	}
+
+	fn parse_deconstruction_declaration(&mut self, flags: SemanticFlags) -> SyntaxRef {
+		self.start(SyntaxKind::DeclarationExpression, 0);
+		self.start(SyntaxKind::IdentifierName, 0);
+		self.bump();
+		self.finish();
+		self.parse_designation(flags);
+		self.finish()
+	}
+
+	fn parse_new(&mut self) -> SyntaxRef {
+		let node = self.start(SyntaxKind::ObjectCreationExpression, 0);
+		self.bump();
+		if self.at("[") {
+			// new[] { 1, 2 }
+			self.set_kind(&node, SyntaxKind::ImplicitArrayCreationExpression, 0);
+			self.bump();
+			while self.at(",") {
+				self.bump();
+			}
+			self.expect("]");
+			self.parse_initializer();
+			return self.finish();
+		}
+		if self.at("{") {
+			// new { X = 1, p.Name }
+			self.set_kind(&node, SyntaxKind::AnonymousObjectCreationExpression, SemanticFlags::AnonymousObjectCreation.bits());
+			self.bump();
+			while !self.is_eof() && !self.at("}") {
+				self.start(SyntaxKind::AnonymousObjectMemberDeclarator, SemanticFlags::MemberDeclarator.bits());
+				if self.is_identifier(self.cur) && self.nth(1) == "=" {
+					self.bump();
+					self.bump();
+				}
+				self.parse_expression();
+				self.finish();
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+			self.expect("}");
+			return self.finish();
+		}
+		let created = self.parse_non_array_type();
+		let created = if self.at("?") {
+			self.precede(&created, SyntaxKind::NullableType, 0);
+			self.bump();
+			self.finish()
+		}
+		else {
+			created
+		};
+		if self.at("[") {
+			self.set_kind(&node, SyntaxKind::ArrayCreationExpression, 0);
+			self.precede(&created, SyntaxKind::ArrayType, 0);
+			while self.at("[") {
+				self.start(SyntaxKind::ArrayRankSpecifier, 0);
+				self.bump();
+				while !self.is_eof() && !self.at("]") {
+					if self.at(",") {
+						self.bump();
+						continue;
+					}
+					let before = self.cur;
+					self.parse_expression();
+					if self.cur == before {
+						break;
+					}
+				}
+				self.expect("]");
+				self.finish();
+			}
+			self.finish();
+			if self.at("{") {
+				self.parse_initializer();
+			}
+			return self.finish();
+		}
+		if self.at("(") {
+			self.parse_argument_list();
+		}
+		else if !self.at("{") {
+			self.missing("(");
+		}
+		if self.at("{") {
+			self.parse_object_or_collection_initializer();
+		}
+		self.finish()
+	}
+
+	// 第一个元素是 'Name =' 或 '[..] =' 的是对象初始化, 否则是集合初始化
+	fn parse_object_or_collection_initializer(&mut self) -> SyntaxRef {
+		let is_object = self.nth(1) == "}" || self.nth(1) == "["
+			|| (self.is_identifier(self.cur + 1) && self.nth(2) == "=");
+		if is_object {
+			self.start(SyntaxKind::ObjectInitializerExpression, SemanticFlags::MemberInitializerScope.bits());
+		}
+		else {
+			self.start(SyntaxKind::CollectionInitializerExpression, 0);
+		}
+		self.bump();
+		while !self.is_eof() && !self.at("}") {
+			if is_object {
+				self.parse_member_initializer();
+			}
+			else if self.at("{") {
+				// 字典的 { "k", v }
+				self.start(SyntaxKind::ComplexElementInitializerExpression, 0);
+				self.bump();
+				self.parse_expression_list("}");
+				self.expect("}");
+				self.finish();
+			}
+			else {
+				self.parse_expression();
+			}
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect("}");
+		self.finish()
+	}
+
+	// Name = value, ["k"] = value, Pos = { X = 1 }, Items = { 1, 2 }
+	fn parse_member_initializer(&mut self) {
+		let is_index = self.at("[");
+		let is_name = self.is_identifier(self.cur) && self.nth(1) == "=";
+		if !is_index && !is_name {
+			self.parse_expression();
+			return;
+		}
+		self.start(SyntaxKind::AssignmentExpression, 0);
+		if is_index {
+			self.start(SyntaxKind::ImplicitElementAccess, 0);
+			self.parse_bracketed_argument_list();
+			self.finish();
+		}
+		else {
+			self.start(SyntaxKind::IdentifierName, 0);
+			self.bump();
+			self.finish();
+		}
+		self.expect("=");
+		if self.at("{") {
+			self.parse_object_or_collection_initializer();
+		}
+		else {
+			self.parse_expression();
+		}
+		self.finish();
+	}
+
+	fn parse_initializer(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::InitializerExpression, 0);
+		self.expect("{");
+		while !self.is_eof() && !self.at("}") {
+			self.parse_variable_initializer();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect("}");
+		self.finish()
+	}
+
+	fn parse_lambda(&mut self) -> SyntaxRef {
+		self.start(SyntaxKind::LambdaExpression,
+			SemanticFlags::LambdaExpressionDeclaration.with(SemanticFlags::LambdaExpressionScope));
+		if self.at("async") {
+			self.bump();
+		}
+		if self.is_identifier(self.cur) {
+			self.start(SyntaxKind::Parameter, SemanticFlags::ImplicitParameterDeclaration.bits());
+			self.bump();
+			self.finish();
+		}
+		else {
+			self.start(SyntaxKind::ParameterList, SemanticFlags::FormalParameterListScope.bits());
+			self.bump();
+			while !self.is_eof() && !self.at(")") {
+				if self.is_identifier(self.cur) && (self.nth(1) == "," || self.nth(1) == ")") {
+					self.start(SyntaxKind::Parameter, SemanticFlags::ImplicitParameterDeclaration.bits());
+					self.bump();
+				}
+				else {
+					self.start(SyntaxKind::Parameter, SemanticFlags::ExplicitParameterDeclaration.bits());
+					while PARAMETER_MODIFIERS.contains(&self.text()) && self.kind_at(self.cur) == TokenKind::Keyword {
+						self.bump();
+					}
+					self.parse_type();
+					self.expect_identifier();
+				}
+				self.finish();
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+			self.expect(")");
+			self.finish();
+		}
+		self.expect("=>");
+		if self.at("{") {
+			self.parse_block(SemanticFlags::LambdaExpressionBodyScope);
+		}
+		else {
+			self.parse_expression();
+		}
+		self.finish()
+	}
+
+	fn parse_switch_expression(&mut self, governing: &SyntaxRef) -> SyntaxRef {
+		self.precede(governing, SyntaxKind::SwitchExpression, 0);
+		self.bump();
+		self.bump();
+		while !self.is_eof() && !self.at("}") {
+			self.start(SyntaxKind::SwitchExpressionArm, SemanticFlags::CodeBlockScope.bits());
+			self.parse_pattern();
+			self.parse_when_clause();
+			self.expect("=>");
+			self.parse_expression();
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect("}");
+		self.finish()
+	}
+
+	fn parse_when_clause(&mut self) {
+		if self.at("when") {
+			self.start(SyntaxKind::WhenClause, 0);
+			self.bump();
+			// 'when ok => 1' 里的 ok 不是 lambda 参数
+			self.parse_conditional();
+			self.finish();
+		}
+	}
+
+	// -------------------------------- patterns -----------------------------------------------
+	// 优先级 or < and < not < 基本模式
+	// 单独的名字 (case Color.Red:) 当成常量模式, 是不是类型留给语义分析
+
+	fn parse_pattern(&mut self) -> SyntaxRef {
+		let mut lhs = self.parse_conjunctive_pattern();
+		while self.at("or") && self.is_identifier(self.cur) {
+			self.precede(&lhs, SyntaxKind::BinaryPattern, 0);
+			self.bump();
+			self.parse_conjunctive_pattern();
+			lhs = self.finish();
+		}
+		lhs
+	}
+
+	fn parse_conjunctive_pattern(&mut self) -> SyntaxRef {
+		let mut lhs = self.parse_negated_pattern();
+		while self.at("and") && self.is_identifier(self.cur) {
+			self.precede(&lhs, SyntaxKind::BinaryPattern, 0);
+			self.bump();
+			self.parse_negated_pattern();
+			lhs = self.finish();
+		}
+		lhs
+	}
+
+	fn parse_negated_pattern(&mut self) -> SyntaxRef {
+		if self.at("not") && self.is_identifier(self.cur) && !self.is_pattern_end(self.cur + 1) {
+			self.start(SyntaxKind::UnaryPattern, 0);
+			self.bump();
+			self.parse_negated_pattern();
+			return self.finish();
+		}
+		self.parse_primary_pattern()
+	}
+
+	fn parse_primary_pattern(&mut self) -> SyntaxRef {
+		if RELATIONAL_OPERATORS.contains(&self.text()) && self.kind_at(self.cur) == TokenKind::PunctuatorOrOperator {
+			self.start(SyntaxKind::RelationalPattern, 0);
+			self.bump();
+			self.parse_binary(9);
+			return self.finish();
+		}
+		if self.at("var") && (self.is_identifier(self.cur + 1) || self.nth(1) == "(") {
+			self.start(SyntaxKind::VarPattern, 0);
+			self.bump();
+			self.parse_designation(SemanticFlags::LocalVariableDeclarator);
+			return self.finish();
+		}
+		if self.at("_") && self.is_pattern_end(self.cur + 1) {
+			self.start(SyntaxKind::DiscardPattern, 0);
+			self.bump();
+			return self.finish();
+		}
+		if self.at("(") && self.is_parenthesized_pattern() {
+			self.start(SyntaxKind::ParenthesizedPattern, 0);
+			self.bump();
+			self.parse_pattern();
+			self.expect(")");
+			return self.finish();
+		}
+		if self.at("(") || self.at("{") {
+			self.start(SyntaxKind::RecursivePattern, 0);
+			self.parse_pattern_clauses();
+			return self.finish();
+		}
+		if let Some(j) = self.scan_pattern_type(self.cur) {
+			if self.is_designation_at(j) {
+				self.start(SyntaxKind::DeclarationPattern, 0);
+				self.parse_type_with(true);
+				self.parse_designation(SemanticFlags::LocalVariableDeclarator);
+				return self.finish();
+			}
+			if self.text_at(j) == "(" || self.text_at(j) == "{" {
+				self.start(SyntaxKind::RecursivePattern, 0);
+				self.parse_type_with(true);
+				self.parse_pattern_clauses();
+				return self.finish();
+			}
+			let is_name = (self.cur..j).all(|i| self.is_identifier(i) || self.text_at(i) == "." || self.text_at(i) == "::");
+			if !is_name && self.is_pattern_end(j) {
+				self.start(SyntaxKind::TypePattern, 0);
+				self.parse_type_with(true);
+				return self.finish();
+			}
+		}
+		self.start(SyntaxKind::ConstantPattern, 0);
+		self.parse_binary(9);
+		self.finish()
+	}
+
+	// Point(..) { .. } p, 类型已经解析过了
+	fn parse_pattern_clauses(&mut self) {
+		if self.at("(") {
+			self.start(SyntaxKind::PositionalPatternClause, 0);
+			self.parse_subpatterns(")");
+			self.finish();
+		}
+		if self.at("{") {
+			self.start(SyntaxKind::PropertyPatternClause, 0);
+			self.parse_subpatterns("}");
+			self.finish();
+		}
+		if self.is_designation_at(self.cur) {
+			self.parse_designation(SemanticFlags::LocalVariableDeclarator);
+		}
+	}
+
+	fn parse_subpatterns(&mut self, close: &str) {
+		self.bump();
+		while !self.is_eof() && !self.at(close) {
+			self.start(SyntaxKind::Subpattern, 0);
+			// Hp: > 0, Target.Hp: > 0
+			let mut j = self.cur;
+			while self.is_identifier(j) && self.text_at(j+1) == "." {
+				j += 2;
+			}
+			if self.is_identifier(j) && self.text_at(j+1) == ":" {
+				while self.cur <= j + 1 {
+					self.bump();
+				}
+			}
+			self.parse_pattern();
+			self.finish();
+			if !self.at(",") {
+				break;
+			}
+			self.bump();
+		}
+		self.expect(close);
+	}
+
+	fn parse_designation(&mut self, flags: SemanticFlags) {
+		if self.at("(") {
+			self.start(SyntaxKind::ParenthesizedVariableDesignation, 0);
+			self.bump();
+			while !self.is_eof() && !self.at(")") {
+				self.parse_designation(flags);
+				if !self.at(",") {
+					break;
+				}
+				self.bump();
+			}
+			self.expect(")");
+		}
+		else if self.at("_") {
+			self.start(SyntaxKind::DiscardDesignation, 0);
+			self.bump();
+		}
+		else {
+			self.start(SyntaxKind::SingleVariableDesignation, flags.bits());
+			self.expect_identifier();
+		}
+		self.finish();
+	}
+}
+
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use vm::dy_syntax::MethodDeclaration;
+
+	fn parse(code: &str) -> SyntaxTree {
+		let mut dy_parser = DyParser::new(code.to_string());
+		dy_parser.lexer();
+		dy_parser.parse()
+	}
+
+	fn kinds(tree: &SyntaxTree) -> Vec<SyntaxKind> {
+		tree.root.traverse(true, false).filter(|x| !x.is_token()).map(|x| x.kind()).collect()
+	}
+
+	const SAMPLE: &'static str = r#"using System;
+using IO = System.IO;
+
+namespace A.B
+{
+	// comment
+	[Serializable]
+	public class Foo<T> : Bar, IBaz where T : class, new()
+	{
+		private const int Max = 10, Min = 0;
+		public int Count { get; private set; } = 1;
+		public T this[int i] => items[i];
+		public event Action Changed;
+
+		public Foo(int x) : base(x) { }
+
+		#region methods
+		public static Foo<T> operator +(Foo<T> a, Foo<T> b) => a;
+
+		public async Task<int> Run(ref int a, params object[] rest)
+		{
+			var list = new List<int> { 1, 2 };
+			int[] array = new int[3];
+			Dictionary<string, List<int>> map = null;
+			for (int i = 0; i < Max; i++)
+			{
+				if (a is int && a >> 1 > 0) continue;
+				else break;
+			}
+			foreach (var x in list) a += x;
+			switch (a) { case 1: case 2: return 1; default: break; }
+			try { Use(out var y); } catch (Exception e) when (e != null) { throw; } finally { }
+			Func<int, int> f = n => n * 2;
+			a >>= 1;
+			int? maybe = a > 0 ? (int?)a : null;
+			var z = obj?.Name?[0] ?? "";
+			return await Task.FromResult(F<int>(a) + (int)a);
+		}
+		#endregion
+	}
+
+	enum Color { Red = 1, Green, Blue }
+}
+"#;
+
+	#[test]
+	fn test_lossless() {
+		let tree = parse(SAMPLE);
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		assert_eq!(tree.text(), SAMPLE);
+	}
+
+	#[test]
+	fn test_optional_semicolon() {
+		let tree = parse("class A\n{\n\tvoid F()\n\t{\n\t\tint a = 1\n\t\tprintln(a)\n\t\treturn\n\t}\n}");
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		let statements = kinds(&tree).into_iter().filter(|x| x.is_statement()).count();
+		// Block + 3 statements
+		assert_eq!(statements, 4);
+
+		let tree = parse("class A { void F() { int a = 1 println(a) } }");
+		assert_eq!(tree.errors.len(), 1);
+		assert_eq!(tree.text(), "class A { void F() { int a = 1 println(a) } }");
+	}
+
+	#[test]
+	fn test_expression() {
+		let tree = parse("class A { int a = 1 + 2 * 3 >> 1 ?? 4; bool b = x < y && F<int>(z); }");
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		let declarator = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::VariableDeclarator).unwrap();
+		let value = declarator.child_nodes().into_iter().last().unwrap();
+		// ((1 + (2 * 3)) >> 1) ?? 4
+		assert_eq!(value.kind(), SyntaxKind::BinaryExpression);
+		assert!(value.has_child_token("??"));
+		let shift = value.child_nodes()[0].clone();
+		assert_eq!(shift.text().trim(), "1 + 2 * 3 >> 1");
+		assert!(kinds(&tree).contains(&SyntaxKind::GenericName));
+	}
+
+	#[test]
+	fn test_patterns() {
+		let code = "class A { int F(object x) {
+			if (x is Enemy { Hp: > 0 and < 100, Target.Name: \"a\" } e || x is not null) return 1
+			var b = x is int
+			switch (x) { case 1: case Point(0, var y) when y > 0: break; case int or long: break; }
+			return x switch { > 0 => 1, (int a, _) => a, _ => 0, };
+		} }";
+		let tree = parse(code);
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		assert_eq!(tree.text(), code);
+		let all = kinds(&tree);
+		for kind in [SyntaxKind::IsPatternExpression, SyntaxKind::IsExpression, SyntaxKind::RecursivePattern,
+			SyntaxKind::UnaryPattern, SyntaxKind::BinaryPattern, SyntaxKind::CaseSwitchLabel,
+			SyntaxKind::CasePatternSwitchLabel, SyntaxKind::WhenClause, SyntaxKind::SwitchExpression,
+			SyntaxKind::DiscardPattern, SyntaxKind::TypePattern].iter() {
+			assert!(all.contains(kind), "{:?}", kind);
+		}
+		// case 1: 不包 ConstantPattern
+		let label = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::CaseSwitchLabel).unwrap();
+		assert_eq!(label.child_nodes()[0].kind(), SyntaxKind::LiteralExpression);
+
+		let names: Vec<String> = tree.root.traverse(true, false)
+			.filter(|x| x.declaration() == Some(SemanticFlags::LocalVariableDeclarator))
+			.filter_map(|x| x.identifier().map(|x| x.text()))
+			.collect();
+		assert_eq!(names, ["e", "b", "y", "a"]);
+
+		// x is int ? a : b 仍然是条件表达式
+		let tree = parse("class A { int a = x is int ? 1 : 2; }");
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		assert!(kinds(&tree).contains(&SyntaxKind::ConditionalExpression));
+	}
+
+	#[test]
+	fn test_tuples() {
+		let tree = parse("class A { (int x, string) Get() => (x: 1, \"a\"); void F() { var (a, b) = Get(); var c = (a + 1) * 2; } }");
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		let get = tree.root.traverse(true, false).find_map(MethodDeclaration::cast).unwrap();
+		assert_eq!(get.return_type().unwrap().text(), "(int x, string)");
+		let all = kinds(&tree);
+		assert!(all.contains(&SyntaxKind::TupleExpression));
+		assert!(all.contains(&SyntaxKind::ParenthesizedVariableDesignation));
+		assert!(all.contains(&SyntaxKind::ParenthesizedExpression));
+	}
+
+	#[test]
+	fn test_semantic_flags() {
+		let tree = parse(SAMPLE);
+		let class = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::ClassDeclaration).unwrap();
+		assert_eq!(class.declaration(), Some(SemanticFlags::ClassDeclaration));
+		assert_eq!(class.scope(), Some(SemanticFlags::TypeDeclarationScope));
+		let run = class.traverse(true, false).find(|x| x.kind() == SyntaxKind::MethodDeclaration).unwrap();
+		assert_eq!(run.identifier().unwrap().text(), "Run");
+
+		assert_eq!(SemanticFlags::declaration(SemanticFlags::AnonymousMethodDeclaration.bits()),
+			Some(SemanticFlags::AnonymousMethodDeclaration));
+		assert_eq!(SemanticFlags::scope(SemanticFlags::MemberDeclarationScope.bits()),
+			Some(SemanticFlags::MemberDeclarationScope));
+		assert_eq!(SemanticFlags::declaration(SemanticFlags::CodeBlockScope.bits()), None);
+
+		let offset = SAMPLE.find("Use(").unwrap();
+		let scope = tree.scope_at(offset);
+		assert_eq!(scope.scope(), Some(SemanticFlags::CodeBlockScope));
+		let scopes: Vec<SemanticFlags> = scope.acestors().filter_map(|x| x.scope()).collect();
+		assert!(scopes.contains(&SemanticFlags::MethodBodyScope));
+		assert!(scopes.contains(&SemanticFlags::NamespaceBodyScope));
+	}
+
+	#[test]
+	fn test_hello_world() {
+		let code = include_str!("../../examples/hello_world.dy");
+		let tree = parse(code);
+		assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+		assert_eq!(tree.text(), code);
+		let unit = tree.compilation_unit();
+		assert_eq!(unit.namespaces()[0].name(), "HelloWorld");
+	}
+}
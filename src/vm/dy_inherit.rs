@@ -0,0 +1,670 @@
+use vm::dy_diagnostic::{Argument, Diagnostic, Severity};
+use vm::dy_parser::BUILTIN_TYPES;
+use vm::dy_symbols::{Declarations, TypeSymbol, MemberSymbol, LookupContext};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, InvocationExpression};
+use vm::dy_types::{type_arguments, substitute_type};
+
+// ------------------------------------ inheritance --------------------------------------------
+// 类层次的检查: base list 的形状和循环, sealed 和 static 基类, override 和隐藏, abstract 成员的实现,
+// 接口成员的实现, base.X 绑定到的基类成员. 宿主提供的基类型没有声明, 涉及它们的检查都跳过
+
+/// Errors and warnings (hidden members) of `check_inheritance`.
+pub struct InheritanceCheck {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+    /// The base class member each `base.X` binds to, as `A.F(int)`.
+    pub base_members: Vec<(SyntaxRef, String)>,
+}
+
+/// Checks the class hierarchy of the types declared in `root`; `declarations` may have other compilation units,
+/// whose errors are reported with them.
+pub fn check_inheritance(root: &SyntaxRef, declarations: &Declarations) -> InheritanceCheck {
+    let mut hierarchy = Hierarchy {
+        declarations,
+        unit: root,
+        result: InheritanceCheck { errors: Vec::new(), warnings: Vec::new(), base_members: Vec::new() },
+    };
+    let in_unit = |x: &SyntaxRef| x.acestors().last().as_ref() == Some(root);
+    for symbol in declarations.types.iter().filter(|x| x.declarations.iter().any(in_unit)) {
+        hierarchy.check_base_list(symbol);
+        hierarchy.check_cycle(symbol);
+        if symbol.kind == SyntaxKind::ClassDeclaration || symbol.kind == SyntaxKind::StructDeclaration {
+            hierarchy.check_members(symbol);
+            hierarchy.check_abstract_members(symbol);
+            hierarchy.check_interfaces(symbol);
+            hierarchy.check_base_accesses(symbol);
+        }
+    }
+    hierarchy.result
+}
+
+// object 的虚成员: 没有声明基类的类也可以 override
+const OBJECT_MEMBERS: [(&'static str, &'static str, &'static str); 3] = [("ToString", "", "string"),
+    ("Equals", "object", "bool"), ("GetHashCode", "", "int")];
+
+/// A base type with its type arguments, `A<int>` is `A` with `T` = `int`.
+struct Base<'a> {
+    symbol: &'a TypeSymbol,
+    arguments: Vec<(String, String)>,
+}
+
+struct Hierarchy<'a> {
+    declarations: &'a Declarations,
+    unit: &'a SyntaxRef,
+    result: InheritanceCheck,
+}
+
+fn at(declaration: &SyntaxRef) -> SyntaxRef {
+    declaration.identifier().unwrap_or_else(|| declaration.clone())
+}
+
+fn kind_text(kind: SyntaxKind) -> &'static str {
+    match kind {
+        SyntaxKind::MethodDeclaration => "method",
+        SyntaxKind::PropertyDeclaration => "property",
+        SyntaxKind::IndexerDeclaration => "indexer",
+        SyntaxKind::EventFieldDeclaration | SyntaxKind::EventDeclaration => "event",
+        _ => "member",
+    }
+}
+
+fn is_overridable(member: &MemberSymbol) -> bool {
+    member.has_modifier("virtual") || member.has_modifier("abstract") || member.has_modifier("override")
+}
+
+// 没有写访问修饰符的类成员是 private
+fn accessibility(member: &MemberSymbol) -> String {
+    let modifiers: Vec<&str> = member.modifiers.iter().map(|x| x.as_str())
+        .filter(|x| ["public", "private", "protected", "internal"].contains(x)).collect();
+    if modifiers.is_empty() { "private".to_string() } else { modifiers.join(" ") }
+}
+
+fn has_body(member: &MemberSymbol) -> bool {
+    let declaration = &member.declaration;
+    if declaration.child_of(SyntaxKind::Block).is_some() || declaration.child_of(SyntaxKind::ArrowExpressionClause).is_some() {
+        return true;
+    }
+    declaration.child_of(SyntaxKind::AccessorList).is_some_and(|x| x.children_of(SyntaxKind::AccessorDeclaration).iter()
+        .any(|x| x.child_of(SyntaxKind::Block).is_some() || x.child_of(SyntaxKind::ArrowExpressionClause).is_some()))
+}
+
+/// `A.F(int)`, `A.this[int]` or `A.P` for diagnostics.
+pub fn member_text(symbol: &TypeSymbol, member: &MemberSymbol) -> String {
+    let parameters: Vec<String> = member.parameters.iter().map(|x| {
+        let modifiers: Vec<&str> = x.modifiers.iter().map(|x| x.as_str()).filter(|x| ["ref", "out", "in"].contains(x)).collect();
+        if modifiers.is_empty() { x.type_name.clone() } else { format!("{} {}", modifiers.join(" "), x.type_name) }
+    }).collect();
+    let name = match member.explicit_interface {
+        Some(ref interface) => format!("{}.{}", interface, member.name),
+        None => member.name.clone(),
+    };
+    match member.kind {
+        SyntaxKind::MethodDeclaration | SyntaxKind::ConstructorDeclaration => format!("{}.{}({})", symbol.full_name(), name, parameters.join(", ")),
+        SyntaxKind::IndexerDeclaration => format!("{}.{}[{}]", symbol.full_name(), name, parameters.join(", ")),
+        _ => format!("{}.{}", symbol.full_name(), name),
+    }
+}
+
+impl<'a> Hierarchy<'a> {
+    // 部分类型的声明可能在别的编译单元里, 那里的错误在那个单元里报
+    fn in_unit(&self, node: &SyntaxRef) -> bool {
+        node.acestors().last().as_ref() == Some(self.unit)
+    }
+
+    fn error(&mut self, node: &SyntaxRef, code: &'static str, arguments: &[&str]) {
+        self.report(node, Diagnostic::error(code, node.span(), arguments));
+    }
+
+    fn report(&mut self, node: &SyntaxRef, error: Diagnostic) {
+        if self.in_unit(node) {
+            self.result.errors.push(error);
+        }
+    }
+
+    fn warning(&mut self, node: &SyntaxRef, code: &'static str, arguments: &[&str]) {
+        if self.in_unit(node) {
+            self.result.warnings.push(Diagnostic::warning(code, node.span(), arguments));
+        }
+    }
+
+    // ------------------------------------ base types ----------------------------------------
+
+    // base list 里有声明的类型, 没有声明的 (宿主类型) 是 None
+    fn bases(&self, symbol: &TypeSymbol) -> Vec<Option<Base<'a>>> {
+        let context = LookupContext::at(&symbol.declarations[0]);
+        symbol.base_types.iter().map(|name| {
+            self.declarations.resolve_type(name, &context).map(|base| {
+                let arguments = type_arguments(name).iter().map(|x| self.normalize(x, &symbol.declarations[0])).collect::<Vec<_>>();
+                Base { symbol: base, arguments: base.type_parameters.iter().cloned().zip(arguments).collect() }
+            })
+        }).collect()
+    }
+
+    // 直接基类; 没有基类是 object, 基类没有声明时是 Err
+    fn base_class(&self, symbol: &TypeSymbol) -> Result<Option<Base<'a>>, ()> {
+        if symbol.kind != SyntaxKind::ClassDeclaration {
+            return Ok(None);
+        }
+        match self.bases(symbol).into_iter().next() {
+            Some(Some(base)) if base.symbol.kind == SyntaxKind::ClassDeclaration => Ok(Some(base)),
+            Some(Some(_)) => Ok(None),
+            // 第一个基类型没有声明: 可能是宿主的基类
+            Some(None) => Err(()),
+            None => Ok(None),
+        }
+    }
+
+    // 从直接基类到最远的基类, 第二项表示链条是否完整 (没有碰到宿主基类)
+    fn base_chain(&self, symbol: &TypeSymbol) -> (Vec<Base<'a>>, bool) {
+        let mut chain: Vec<Base<'a>> = Vec::new();
+        let mut current = self.base_class(symbol);
+        loop {
+            match current {
+                Ok(Some(base)) => {
+                    if base.symbol.full_name() == symbol.full_name() || chain.iter().any(|x| x.symbol.full_name() == base.symbol.full_name()) {
+                        return (chain, false);
+                    }
+                    // 基类的基类型实参要代入这一层的类型实参
+                    let next = self.base_class(base.symbol).map(|x| x.map(|x| Base {
+                        symbol: x.symbol,
+                        arguments: x.arguments.iter().map(|y| (y.0.clone(), substitute_type(&y.1, &base.arguments))).collect(),
+                    }));
+                    chain.push(base);
+                    current = next;
+                }
+                Ok(None) => return (chain, true),
+                Err(()) => return (chain, false),
+            }
+        }
+    }
+
+    fn normalize(&self, text: &str, at: &SyntaxRef) -> String {
+        let text = text.trim();
+        if let Some(element) = text.strip_suffix("[]") {
+            return format!("{}[]", self.normalize(element, at));
+        }
+        if let Some(inner) = text.strip_suffix('?') {
+            return format!("{}?", self.normalize(inner, at));
+        }
+        if BUILTIN_TYPES.contains(&text) || text.contains('<') || text.starts_with('(') {
+            return text.to_string();
+        }
+        match self.declarations.resolve_type(text, &LookupContext::at(at)) {
+            Some(symbol) => symbol.full_name(),
+            None => text.to_string(),
+        }
+    }
+
+    // 成员在基类型里声明的类型, 代入基类型的类型实参
+    fn member_type(&self, member: &MemberSymbol, arguments: &[(String, String)]) -> Option<String> {
+        member.type_name.as_ref().map(|x| substitute_type(&self.normalize(x, &member.declaration), arguments))
+    }
+
+    // 名字, 类型参数个数, 参数类型; 方法的类型参数按位置比较
+    fn signature(&self, member: &MemberSymbol, arguments: &[(String, String)]) -> (String, usize, Vec<String>) {
+        let mut arguments = arguments.to_vec();
+        for (i, parameter) in member.type_parameters.iter().enumerate() {
+            arguments.push((parameter.clone(), format!("`{}", i)));
+        }
+        let parameters = member.parameters.iter().map(|x| {
+            let modifiers: Vec<&str> = x.modifiers.iter().map(|x| x.as_str()).filter(|x| ["ref", "out", "in"].contains(x)).collect();
+            let type_name = substitute_type(&self.normalize(&x.type_name, &member.declaration), &arguments);
+            if modifiers.is_empty() { type_name } else { format!("{} {}", modifiers.join(" "), type_name) }
+        }).collect();
+        (member.name.clone(), member.type_parameters.len(), parameters)
+    }
+
+    fn same_signature(&self, member: &MemberSymbol, other: &MemberSymbol, arguments: &[(String, String)]) -> bool {
+        let is_method = |x: &MemberSymbol| x.kind == SyntaxKind::MethodDeclaration || x.kind == SyntaxKind::IndexerDeclaration;
+        if is_method(member) || is_method(other) {
+            return member.kind == other.kind && self.signature(member, &[]) == self.signature(other, arguments);
+        }
+        member.name == other.name
+    }
+
+    fn check_base_list(&mut self, symbol: &TypeSymbol) {
+        let bases = self.bases(symbol);
+        let name = at(&symbol.declarations[0]);
+        let mut base_class: Option<String> = None;
+        for (i, base) in bases.iter().enumerate() {
+            let base = match *base {
+                Some(ref x) => x.symbol,
+                None => continue,
+            };
+            let base_name = base.full_name();
+            if base.kind == SyntaxKind::InterfaceDeclaration {
+                continue;
+            }
+            if symbol.kind != SyntaxKind::ClassDeclaration {
+                self.error(&name, "DY0601", &[&base_name]);
+                continue;
+            }
+            if base.kind != SyntaxKind::ClassDeclaration || base.has_modifier("sealed") {
+                self.error(&name, "DY0602", &[&symbol.full_name(), &base_name]);
+            }
+            else if base.has_modifier("static") {
+                self.error(&name, "DY0603", &[&symbol.full_name(), &base_name]);
+            }
+            else if let Some(ref first) = base_class {
+                self.error(&name, "DY0604", &[&symbol.full_name(), first, &base_name]);
+            }
+            else if i > 0 {
+                self.error(&name, "DY0605", &[&base_name]);
+            }
+            base_class.get_or_insert(base_name);
+        }
+    }
+
+    // 沿着基类型能回到自己就是循环
+    fn check_cycle(&mut self, symbol: &TypeSymbol) {
+        let full_name = symbol.full_name();
+        for base in self.bases(symbol).into_iter().flatten() {
+            let mut queue = vec![base.symbol];
+            let mut i = 0;
+            while i < queue.len() {
+                if queue[i].full_name() == full_name {
+                    let name = at(&symbol.declarations[0]);
+                    self.error(&name, "DY0606", &[&full_name, &base.symbol.full_name()]);
+                    return;
+                }
+                for next in self.bases(queue[i]).into_iter().flatten() {
+                    if !queue.iter().any(|x| x.full_name() == next.symbol.full_name()) {
+                        queue.push(next.symbol);
+                    }
+                }
+                i += 1;
+            }
+        }
+    }
+
+    // ------------------------------------ members -------------------------------------------
+
+    // 基类链上第一个签名相同的成员
+    fn inherited(&self, chain: &[Base<'a>], member: &MemberSymbol) -> Option<(Base<'a>, &'a MemberSymbol)> {
+        for base in chain {
+            let found = base.symbol.members_named(&member.name).into_iter()
+                .find(|x| !x.is_static() && x.kind != SyntaxKind::ConstructorDeclaration
+                    && self.same_signature(member, x, &base.arguments));
+            if let Some(found) = found {
+                return Some((Base { symbol: base.symbol, arguments: base.arguments.clone() }, found));
+            }
+        }
+        None
+    }
+
+    fn check_members(&mut self, symbol: &TypeSymbol) {
+        let (chain, is_complete) = self.base_chain(symbol);
+        let is_class = symbol.kind == SyntaxKind::ClassDeclaration;
+        for member in symbol.members.iter().filter(|x| x.explicit_interface.is_none()) {
+            let text = member_text(symbol, member);
+            let name = at(&member.declaration);
+            if member.has_modifier("abstract") {
+                if is_class && !symbol.has_modifier("abstract") {
+                    self.error(&name, "DY0607", &[&text, &symbol.full_name()]);
+                }
+                if has_body(member) {
+                    self.error(&name, "DY0608", &[&text]);
+                }
+            }
+            if is_overridable(member) && accessibility(member) == "private" {
+                self.error(&name, "DY0609", &[&text]);
+            }
+            if member.has_modifier("sealed") && !member.has_modifier("override") {
+                self.error(&name, "DY0610", &[&text]);
+            }
+            if member.kind == SyntaxKind::ConstructorDeclaration || member.is_static() {
+                continue;
+            }
+            let inherited = self.inherited(&chain, member);
+            if member.has_modifier("override") {
+                self.check_override(symbol, member, inherited, is_complete);
+                continue;
+            }
+            match inherited {
+                Some((base, hidden)) if !member.has_modifier("new") => {
+                    let hidden_text = member_text(base.symbol, hidden);
+                    if is_overridable(hidden) {
+                        self.warning(&name, "DY0611", &[&text, &hidden_text]);
+                    }
+                    else {
+                        self.warning(&name, "DY0612", &[&text, &hidden_text]);
+                    }
+                }
+                None if member.has_modifier("new") && is_complete => {
+                    self.warning(&name, "DY0613", &[&text]);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_override(&mut self, symbol: &TypeSymbol, member: &MemberSymbol,
+        inherited: Option<(Base<'a>, &'a MemberSymbol)>, is_complete: bool) {
+        let text = member_text(symbol, member);
+        let name = at(&member.declaration);
+        let (base, overridden) = match inherited {
+            Some(x) => x,
+            None => {
+                let is_object_member = OBJECT_MEMBERS.iter().any(|x| x.0 == member.name
+                    && x.1 == member.parameters.iter().map(|x| x.type_name.as_str()).collect::<Vec<_>>().join(", "));
+                if is_complete && !is_object_member {
+                    let kind = Argument::phrase(kind_text(member.kind), &[]);
+                    let arguments = vec![Argument::from(text), kind];
+                    self.report(&name, Diagnostic::new(Severity::Error, "DY0614", name.span(), arguments));
+                }
+                return;
+            }
+        };
+        let overridden_text = member_text(base.symbol, overridden);
+        if !is_overridable(overridden) {
+            self.error(&name, "DY0615", &[&text, &overridden_text]);
+            return;
+        }
+        if overridden.has_modifier("sealed") {
+            self.error(&name, "DY0616", &[&text, &overridden_text]);
+            return;
+        }
+        let (expected, actual) = (self.member_type(overridden, &base.arguments), self.member_type(member, &[]));
+        if let (Some(expected), Some(actual)) = (expected, actual) {
+            if expected != actual {
+                let what = if member.kind == SyntaxKind::MethodDeclaration { "return-type" } else { "type" };
+                let arguments = vec![Argument::from(text.as_str()), Argument::phrase(what, &[]),
+                    Argument::from(expected), Argument::from(overridden_text.as_str())];
+                self.report(&name, Diagnostic::new(Severity::Error, "DY0617", name.span(), arguments));
+            }
+        }
+        let access = accessibility(overridden);
+        if accessibility(member) != access {
+            self.error(&name, "DY0618", &[&text, &access, &overridden_text]);
+        }
+    }
+
+    // 具体类要 override 基类链上所有没被 override 的 abstract 成员
+    fn check_abstract_members(&mut self, symbol: &TypeSymbol) {
+        if symbol.kind != SyntaxKind::ClassDeclaration || symbol.has_modifier("abstract") || symbol.has_modifier("static") {
+            return;
+        }
+        let (chain, _) = self.base_chain(symbol);
+        let mut overrides: Vec<(&MemberSymbol, Vec<(String, String)>)> = symbol.members.iter()
+            .filter(|x| x.has_modifier("override")).map(|x| (x, Vec::new())).collect();
+        let mut missing = Vec::new();
+        for base in &chain {
+            for member in base.symbol.members.iter().filter(|x| x.has_modifier("abstract")) {
+                // 派生类的 override 按这一层的类型实参比较
+                let is_overridden = overrides.iter().any(|x| {
+                    let arguments: Vec<(String, String)> = base.arguments.iter()
+                        .map(|y| (y.0.clone(), substitute_type(&y.1, &x.1))).collect();
+                    self.same_signature(x.0, member, &arguments)
+                });
+                if !is_overridden {
+                    missing.push(member_text(base.symbol, member));
+                }
+            }
+            overrides.extend(base.symbol.members.iter().filter(|x| x.has_modifier("override")).map(|x| (x, base.arguments.clone())));
+        }
+        let name = at(&symbol.declarations[0]);
+        for member in missing {
+            self.error(&name, "DY0619", &[&symbol.full_name(), &member]);
+        }
+    }
+
+    // ------------------------------------ interfaces ----------------------------------------
+
+    // 类型和基类实现的所有接口, 包括接口继承的接口; 第二项表示是否都有声明
+    fn interfaces(&self, symbol: &TypeSymbol) -> (Vec<Base<'a>>, bool) {
+        let (chain, mut is_complete) = self.base_chain(symbol);
+        let mut queue: Vec<Base<'a>> = Vec::new();
+        let mut sources: Vec<(&TypeSymbol, Vec<(String, String)>)> = vec![(symbol, Vec::new())];
+        sources.extend(chain.iter().map(|x| (x.symbol, x.arguments.clone())));
+        for (source, arguments) in sources {
+            for base in self.bases(source) {
+                match base {
+                    Some(base) if base.symbol.kind == SyntaxKind::InterfaceDeclaration => queue.push(Base {
+                        symbol: base.symbol,
+                        arguments: base.arguments.iter().map(|x| (x.0.clone(), substitute_type(&x.1, &arguments))).collect(),
+                    }),
+                    Some(_) => {}
+                    None => is_complete = false,
+                }
+            }
+        }
+        let mut i = 0;
+        while i < queue.len() {
+            let arguments = queue[i].arguments.clone();
+            for base in self.bases(queue[i].symbol).into_iter().flatten() {
+                let base = Base {
+                    symbol: base.symbol,
+                    arguments: base.arguments.iter().map(|x| (x.0.clone(), substitute_type(&x.1, &arguments))).collect(),
+                };
+                let exists = queue.iter().any(|x| x.symbol.full_name() == base.symbol.full_name() && x.arguments == base.arguments);
+                if !exists && queue.len() < 64 {
+                    queue.push(base);
+                }
+            }
+            i += 1;
+        }
+        let mut interfaces: Vec<Base<'a>> = Vec::new();
+        for interface in queue {
+            if !interfaces.iter().any(|x| x.symbol.full_name() == interface.symbol.full_name() && x.arguments == interface.arguments) {
+                interfaces.push(interface);
+            }
+        }
+        (interfaces, is_complete)
+    }
+
+    fn check_interfaces(&mut self, symbol: &TypeSymbol) {
+        let (interfaces, is_complete) = self.interfaces(symbol);
+        let (chain, _) = self.base_chain(symbol);
+        let name = at(&symbol.declarations[0]);
+        // 显式实现要对应一个实现了的接口
+        for member in symbol.members.iter().filter(|x| x.explicit_interface.is_some()) {
+            let interface = member.explicit_interface.clone().unwrap();
+            let resolved = self.declarations.resolve_type(&interface, &LookupContext::at(&symbol.declarations[0]));
+            let implemented = resolved.map_or(!is_complete, |x| interfaces.iter().any(|y| y.symbol.full_name() == x.full_name()));
+            if !implemented {
+                self.error(&at(&member.declaration), "DY0620", &[&member_text(symbol, member), &interface]);
+            }
+        }
+        let mut types: Vec<(&TypeSymbol, Vec<(String, String)>)> = vec![(symbol, Vec::new())];
+        types.extend(chain.iter().map(|x| (x.symbol, x.arguments.clone())));
+        for interface in &interfaces {
+            // 有默认实现的接口成员不用实现
+            for required in interface.symbol.members.iter().filter(|x| !x.is_static() && !has_body(x)) {
+                let required_text = member_text(interface.symbol, required);
+                let explicit = types.iter().any(|&(owner, _)| owner.members.iter().any(|x| {
+                    let names_interface = x.explicit_interface.as_ref().and_then(|name| {
+                        self.declarations.resolve_type(name, &LookupContext::at(&owner.declarations[0]))
+                    }).is_some_and(|x| x.full_name() == interface.symbol.full_name());
+                    names_interface && x.name == required.name && self.same_signature(x, required, &interface.arguments)
+                }));
+                if explicit {
+                    continue;
+                }
+                let implicit = types.iter().find_map(|&(owner, ref arguments)| {
+                    owner.members_named(&required.name).into_iter()
+                        // 基类成员的签名按基类的类型实参算
+                        .find(|x| !x.is_static() && kind_text(x.kind) == kind_text(required.kind)
+                            && self.signature(x, arguments) == self.signature(required, &interface.arguments))
+                        .map(|x| (owner, x, arguments.clone()))
+                });
+                match implicit {
+                    Some((owner, member, arguments)) => {
+                        let member_text = member_text(owner, member);
+                        if accessibility(member) != "public" {
+                            self.error(&name, "DY0621", &[&symbol.full_name(), &required_text, &member_text]);
+                            continue;
+                        }
+                        let (expected, actual) = (self.member_type(required, &interface.arguments), self.member_type(member, &arguments));
+                        if let (Some(expected), Some(actual)) = (expected, actual) {
+                            if expected != actual {
+                                self.error(&name, "DY0622", &[&symbol.full_name(), &required_text, &member_text,
+                                    &required_text, &expected]);
+                            }
+                        }
+                    }
+                    None if is_complete => self.error(&name, "DY0623", &[&symbol.full_name(), &required_text]),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    // ------------------------------------ base access ---------------------------------------
+
+    // base.X 绑定到基类链上的成员; 静态成员里不能用 base, 不能调用 abstract 的基类成员
+    fn check_base_accesses(&mut self, symbol: &TypeSymbol) {
+        let (chain, _) = self.base_chain(symbol);
+        for declaration in &symbol.declarations {
+            if !self.in_unit(declaration) {
+                continue;
+            }
+            let accesses: Vec<SyntaxRef> = declaration.traverse(true, false)
+                .filter(|x| x.kind() == SyntaxKind::MemberAccessExpression
+                    && x.child_nodes().first().is_some_and(|x| x.kind() == SyntaxKind::BaseExpression))
+                .collect();
+            for access in accesses {
+                // 嵌套类型里的 base 属于嵌套类型
+                let owner = access.acestors().skip(1)
+                    .find(|x| x.kind() == SyntaxKind::ClassDeclaration || x.kind() == SyntaxKind::StructDeclaration);
+                if owner.as_ref() != Some(declaration) {
+                    continue;
+                }
+                let is_static = symbol.members.iter().any(|x| x.is_static() && access.acestors().any(|y| y == x.declaration));
+                if is_static {
+                    self.error(&access, "DY0624", &[]);
+                    continue;
+                }
+                let name = access.child_nodes().last().and_then(|x| x.identifier()).map(|x| x.token_text()).unwrap_or_default();
+                let invocation = access.parent().and_then(InvocationExpression::cast)
+                    .filter(|x| x.callee().is_some_and(|x| x.syntax() == &access));
+                let count = invocation.map(|x| x.arguments().len());
+                let found = chain.iter().find_map(|base| {
+                    let members: Vec<&MemberSymbol> = base.symbol.members_named(&name).into_iter()
+                        .filter(|x| count.is_none_or(|count| x.accepts(count))).collect();
+                    members.first().map(|x| (base.symbol, *x))
+                });
+                if let Some((base, member)) = found {
+                    let text = member_text(base, member);
+                    if member.has_modifier("abstract") {
+                        self.error(&access, "DY0625", &[&text]);
+                    }
+                    self.result.base_members.push((access, text));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_locale::Locale;
+    use vm::dy_parser::DyParser;
+
+    fn check(code: &str) -> InheritanceCheck {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        check_inheritance(&tree.root, &declarations)
+    }
+
+    fn messages(errors: &[Diagnostic]) -> Vec<String> {
+        errors.iter().map(|x| x.message.clone()).collect()
+    }
+
+    #[test]
+    fn test_base_lists() {
+        let result = check("class A { } sealed class S { } static class Z { } interface I { } struct V { }
+            class B : A, S { } class C : I, A { } class D : S { } class E : Z { } class F : V { } struct W : A { }
+            interface J : A { } class X : Y { } class Y : X { } class Host : Unknown, I { }");
+        assert_eq!(messages(&result.errors), [
+            "'B': cannot derive from sealed type 'S'",
+            "base class 'A' must come before any interfaces",
+            "'D': cannot derive from sealed type 'S'",
+            "'E': cannot derive from static class 'Z'",
+            "'F': cannot derive from sealed type 'V'",
+            "type 'A' in interface list is not an interface",
+            "type 'A' in interface list is not an interface",
+            "circular base type dependency involving 'X' and 'Y'",
+            "circular base type dependency involving 'Y' and 'X'",
+        ]);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let code = "abstract class A { public virtual void F(int x) { } public void G() { } public abstract int P { get; }
+                protected abstract void H(); public virtual sealed void K() { } public virtual string N => \"\";
+                protected virtual void M() { } abstract void Q(); public abstract void R() { } }
+            abstract class Base<T> { public abstract T Make(T seed); }
+            class B : A { public override void F(int x) { base.F(x); } public override void F(string s) { }
+                public override void G() { } public override int P => 1; public void H() { }
+                public override string ToString() => \"\"; public new void Z() { } public override int N => 0;
+                public override void M() { } }
+            sealed class C : B { protected override void H() { base.H(); } public override void K() { } }
+            abstract class G : A { protected override void H() { base.H(); } }
+            class D : Base<int> { public override int Make(int seed) => seed; }
+            class E : Base<string> { }
+            class F { public abstract void X(); public static void S() { base.ToString(); } }";
+        let result = check(code);
+        assert_eq!(messages(&result.errors), [
+            "'A.K()' cannot be sealed because it is not an override",
+            "'A.Q()': virtual or abstract members cannot be private",
+            "'A.R()' cannot declare a body because it is marked abstract",
+            "'B.F(string)': no suitable method found to override",
+            "'B.G()': cannot override inherited member 'A.G()' because it is not marked virtual, abstract, or override",
+            "'B.N': type must be 'string' to match overridden member 'A.N'",
+            "'B.M()': cannot change access modifiers when overriding 'protected' inherited member 'A.M()'",
+            "'B' does not implement inherited abstract member 'A.H()'",
+            "'B' does not implement inherited abstract member 'A.Q()'",
+            "'B' does not implement inherited abstract member 'A.R()'",
+            "'C.H()': cannot override inherited member 'B.H()' because it is not marked virtual, abstract, or override",
+            "'C.K()': cannot override inherited member 'A.K()' because it is sealed",
+            "'C' does not implement inherited abstract member 'A.Q()'",
+            "'C' does not implement inherited abstract member 'A.R()'",
+            "cannot call an abstract base member: 'A.H()'",
+            "'E' does not implement inherited abstract member 'Base.Make(T)'",
+            "'F.X()' is abstract but it is contained in non-abstract type 'F'",
+            "keyword 'base' is not available in a static method",
+        ]);
+        assert_eq!(messages(&result.warnings), [
+            "'B.H()' hides inherited member 'A.H()'. To make the current member override that implementation, add the \
+                override keyword. Otherwise add the new keyword.",
+            "the member 'B.Z()' does not hide an accessible member. The new keyword is not required.",
+        ]);
+        let bases: Vec<&str> = result.base_members.iter().map(|x| x.1.as_str()).collect();
+        assert_eq!(bases, ["A.F(int)", "B.H()", "A.H()"]);
+
+        // 返回类型不匹配时中文消息里不应夹带英文
+        let result = check("class A { public virtual int F() => 0; public virtual int P => 0; }
+            class B : A { public override string F() => \"\"; public override string P => \"\"; }");
+        let zh: Vec<String> = result.errors.iter().map(|x| x.message_in(Locale::ZhCn)).collect();
+        assert_eq!(zh, ["'B.F()': 返回类型 必须是 'int' 才能与重写成员 'A.F()' 匹配",
+            "'B.P': 类型 必须是 'int' 才能与重写成员 'A.P' 匹配"]);
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let code = "interface IShape { double Area(); string Name { get; } }
+            interface ISolid : IShape { double Volume(); void Log() { } }
+            interface IBox<T> { T Get(int i); }
+            class Square : IShape { public double Area() => 1; public string Name => \"\"; }
+            class Cube : Square, ISolid { public double Volume() => 1; }
+            class Ball : ISolid { double IShape.Area() => 1; string IShape.Name => \"\"; double Volume() => 1; }
+            class Crate : IBox<string> { public int Get(int i) => 0; }
+            class Bag : IBox<int> { public int Get(int i) => 0; void IShape.Area() { } }
+            struct Point : IShape { }";
+        let result = check(code);
+        assert_eq!(messages(&result.errors), [
+            "'Ball' does not implement interface member 'ISolid.Volume()'. 'Ball.Volume()' cannot implement an interface \
+                member because it is not public.",
+            "'Crate' does not implement interface member 'IBox.Get(int)'. 'Crate.Get(int)' cannot implement \
+                'IBox.Get(int)' because it does not have the matching return type of 'string'.",
+            "'Bag.IShape.Area()': containing type does not implement interface 'IShape'",
+            "'Point' does not implement interface member 'IShape.Area()'",
+            "'Point' does not implement interface member 'IShape.Name'",
+        ]);
+    }
+}
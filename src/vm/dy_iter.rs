@@ -0,0 +1,219 @@
+// ------------------------------------ iterators ----------------------------------------------
+// 编译后的迭代器方法实现 Enumerator: move_next 执行到下一个 yield return
+// foreach 通过 Lazy 按需取值, 提前停止 (break, 出错, 宿主丢弃) 时 Drop 调用 dispose 执行 finally
+
+pub trait Enumerator<V> {
+    /// Runs to the next `yield return` and returns its value, None after `yield break` or the end.
+    fn move_next(&mut self) -> Result<Option<V>, String>;
+    /// Runs the finally blocks around the current yield; called when iteration stops early.
+    fn dispose(&mut self);
+}
+
+/// Consumes a script iterator lazily as a Rust iterator, one `move_next` per item.
+pub struct Lazy<V> {
+    enumerator: Box<dyn Enumerator<V>>,
+    finished: bool,
+}
+
+impl<V> Lazy<V> {
+    pub fn new(enumerator: Box<dyn Enumerator<V>>) -> Lazy<V> {
+        Lazy { enumerator, finished: false }
+    }
+}
+
+impl<V> Iterator for Lazy<V> {
+    type Item = Result<V, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.enumerator.move_next() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(message) => {
+                // 异常从迭代器里抛出来时 finally 已经执行过了
+                self.finished = true;
+                Some(Err(message))
+            }
+        }
+    }
+}
+
+impl<V> Drop for Lazy<V> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finished = true;
+            self.enumerator.dispose();
+        }
+    }
+}
+
+// ------------------------------------ coroutines ---------------------------------------------
+// Unity 风格的协程: 宿主每帧调用 tick, 每个协程前进到下一个 yield return, yield 出来的值交给宿主解释
+// (比如 null 表示下一帧, WaitForSeconds 由宿主调用 wait 跳过若干帧)
+
+struct Running<V> {
+    id: usize,
+    iterator: Lazy<V>,
+    wait: usize,
+}
+
+pub struct Coroutines<V> {
+    running: Vec<Running<V>>,
+    next_id: usize,
+}
+
+impl<V> Coroutines<V> {
+    pub fn new() -> Coroutines<V> {
+        Coroutines { running: Vec::new(), next_id: 1 }
+    }
+
+    /// `StartCoroutine`, the first step runs on the next tick.
+    pub fn start(&mut self, enumerator: Box<dyn Enumerator<V>>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.running.push(Running { id, iterator: Lazy::new(enumerator), wait: 0 });
+        id
+    }
+
+    /// `StopCoroutine`, runs its pending finally blocks. Returns false if it is not running.
+    pub fn stop(&mut self, id: usize) -> bool {
+        match self.running.iter().position(|x| x.id == id) {
+            Some(index) => {
+                self.running.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Skips the coroutine for the next `ticks` ticks.
+    pub fn wait(&mut self, id: usize, ticks: usize) {
+        if let Some(running) = self.running.iter_mut().find(|x| x.id == id) {
+            running.wait = ticks;
+        }
+    }
+
+    pub fn is_running(&self, id: usize) -> bool {
+        self.running.iter().any(|x| x.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// Advances every coroutine one step and returns what each yielded;
+    /// finished coroutines are removed, faulted ones report their error and are removed.
+    pub fn tick(&mut self) -> Vec<(usize, Result<V, String>)> {
+        let mut yielded = Vec::new();
+        let mut finished = Vec::new();
+        for running in &mut self.running {
+            if running.wait > 0 {
+                running.wait -= 1;
+                continue;
+            }
+            match running.iterator.next() {
+                Some(Ok(value)) => yielded.push((running.id, Ok(value))),
+                Some(Err(message)) => {
+                    yielded.push((running.id, Err(message)));
+                    finished.push(running.id);
+                }
+                None => finished.push(running.id),
+            }
+        }
+        self.running.retain(|x| !finished.contains(&x.id));
+        yielded
+    }
+}
+
+impl<V> Default for Coroutines<V> {
+    fn default() -> Self {
+        Coroutines::new()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    // 相当于 IEnumerable<int> Count(int n) { try { for (var i = 0; i < n; i++) yield return i; } finally { log.Add(-1); } }
+    struct Count {
+        i: i32,
+        n: i32,
+        log: Rc<RefCell<Vec<i32>>>,
+    }
+
+    impl Enumerator<i32> for Count {
+        fn move_next(&mut self) -> Result<Option<i32>, String> {
+            if self.i == 3 && self.n < 0 {
+                self.dispose();
+                return Err("index out of range".to_string());
+            }
+            if self.i >= self.n.abs() {
+                self.dispose();
+                return Ok(None);
+            }
+            self.i += 1;
+            Ok(Some(self.i - 1))
+        }
+
+        fn dispose(&mut self) {
+            self.log.borrow_mut().push(-1);
+        }
+    }
+
+    fn count(n: i32, log: &Rc<RefCell<Vec<i32>>>) -> Box<Count> {
+        Box::new(Count { i: 0, n, log: log.clone() })
+    }
+
+    #[test]
+    fn test_lazy() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for value in Lazy::new(count(1000000, &log)) {
+            log.borrow_mut().push(value.unwrap());
+            if log.borrow().len() == 2 {
+                break;
+            }
+        }
+        assert_eq!(*log.borrow(), [0, 1, -1]);
+
+        log.borrow_mut().clear();
+        let values: Vec<Result<i32, String>> = Lazy::new(count(2, &log)).collect();
+        assert_eq!(values, [Ok(0), Ok(1)]);
+        assert_eq!(*log.borrow(), [-1]);
+
+        log.borrow_mut().clear();
+        let values: Vec<Result<i32, String>> = Lazy::new(count(-5, &log)).collect();
+        assert_eq!(values.last(), Some(&Err("index out of range".to_string())));
+        assert_eq!(*log.borrow(), [-1]);
+    }
+
+    #[test]
+    fn test_coroutines() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut coroutines = Coroutines::new();
+        let a = coroutines.start(count(2, &log));
+        let b = coroutines.start(count(10, &log));
+        assert_eq!(coroutines.tick(), [(a, Ok(0)), (b, Ok(0))]);
+        coroutines.wait(a, 1);
+        assert_eq!(coroutines.tick(), [(b, Ok(1))]);
+        assert_eq!(coroutines.tick(), [(a, Ok(1)), (b, Ok(2))]);
+        assert!(coroutines.stop(b));
+        assert_eq!(*log.borrow(), [-1]);
+        assert_eq!(coroutines.tick(), []);
+        assert!(!coroutines.is_running(a));
+        assert!(coroutines.is_empty());
+        assert!(!coroutines.stop(b));
+    }
+}
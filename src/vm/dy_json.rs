@@ -0,0 +1,402 @@
+use std::fmt;
+
+// ------------------------------------ json ---------------------------------------------------
+// 导出语法树和诊断信息用的最小 JSON 实现, 不依赖第三方库
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Members keep their insertion order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut reader = Reader { chars: text.chars().collect(), pos: 0 };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        if reader.pos < reader.chars.len() {
+            return Err(reader.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => members.iter().find(|x| x.0 == key).map(|x| &x.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Json::Number(x) if x >= 0.0 && x.fract() == 0.0 => Some(x as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match *self {
+            Json::Array(ref x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Indented output, two spaces per level; short arrays and objects of scalars stay on one line.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Style::Pretty(0));
+        out
+    }
+
+    fn is_scalar(&self) -> bool {
+        !matches!(*self, Json::Array(_) | Json::Object(_))
+    }
+
+    fn write(&self, out: &mut String, style: Style) {
+        let (separator, inner) = match style {
+            Style::Compact => (":", Style::Compact),
+            Style::Inline => (": ", Style::Inline),
+            Style::Pretty(level) => {
+                let flat = match *self {
+                    Json::Array(ref items) => items.iter().all(|x| x.is_scalar()),
+                    Json::Object(ref members) => members.iter().all(|x| x.1.is_scalar()),
+                    _ => true,
+                };
+                if flat && self.to_string().len() <= 60 {
+                    return self.write(out, Style::Inline);
+                }
+                (": ", Style::Pretty(level + 1))
+            }
+        };
+        let newline = |out: &mut String, first: bool| {
+            if !first {
+                out.push(',');
+            }
+            match style {
+                Style::Pretty(level) => {
+                    out.push('\n');
+                    out.push_str(&"  ".repeat(level + 1));
+                }
+                Style::Inline if !first => out.push(' '),
+                _ => {}
+            }
+        };
+        let close = |out: &mut String, empty: bool| {
+            if let Style::Pretty(level) = style {
+                if !empty {
+                    out.push('\n');
+                    out.push_str(&"  ".repeat(level));
+                }
+            }
+        };
+        match *self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(x) => out.push_str(if x { "true" } else { "false" }),
+            Json::Number(x) => {
+                if x.fract() == 0.0 && x.abs() < 1e15 {
+                    out.push_str(&format!("{}", x as i64));
+                }
+                else if x.is_finite() {
+                    out.push_str(&format!("{}", x));
+                }
+                else {
+                    out.push_str("null");
+                }
+            }
+            Json::String(ref x) => write_string(out, x),
+            Json::Array(ref items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    newline(out, i == 0);
+                    item.write(out, inner);
+                }
+                close(out, items.is_empty());
+                out.push(']');
+            }
+            Json::Object(ref members) => {
+                out.push('{');
+                for (i, member) in members.iter().enumerate() {
+                    newline(out, i == 0);
+                    write_string(out, &member.0);
+                    out.push_str(separator);
+                    member.1.write(out, inner);
+                }
+                close(out, members.is_empty());
+                out.push('}');
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Style {
+    Compact,
+    Inline,
+    Pretty(usize),
+}
+
+impl fmt::Display for Json {
+    /// Compact output without whitespace.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, Style::Compact);
+        f.write_str(&out)
+    }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(x: &'a str) -> Json {
+        Json::String(x.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(x: String) -> Json {
+        Json::String(x)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(x: usize) -> Json {
+        Json::Number(x as f64)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(x: i32) -> Json {
+        Json::Number(f64::from(x))
+    }
+}
+
+impl From<bool> for Json {
+    fn from(x: bool) -> Json {
+        Json::Bool(x)
+    }
+}
+
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+struct Reader {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Reader {
+    fn error(&self, message: &str) -> String {
+        format!("{} at {}", message, self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            return Ok(());
+        }
+        Err(self.error(&format!("expected '{}'", ch)))
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().cloned().eq(word.chars()) {
+            self.pos = end;
+            return Ok(value);
+        }
+        Err(self.error("unexpected character"))
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    let value = self.value()?;
+                    members.push((key, value));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => {
+                let start = self.pos;
+                self.pos += 1;
+                while let Some(ch) = self.peek() {
+                    if !(ch.is_ascii_digit() || ch == '.' || ch == 'e' || ch == 'E' || ch == '+' || ch == '-') {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                text.parse().map(Json::Number).map_err(|_| self.error("invalid number"))
+            }
+            _ => Err(self.error("expected value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            return Err(self.error("expected string"));
+        }
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let ch = match self.peek() {
+                Some(x) => x,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            match ch {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escape = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    match escape {
+                        '"' | '\\' | '/' => text.push(escape),
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            // UTF-16 代理对
+                            if (0xD800..0xDC00).contains(&code) && self.peek() == Some('\\') && self.chars.get(self.pos+1) == Some(&'u') {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            text.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                _ => text.push(ch),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        if self.pos + 4 > self.chars.len() {
+            return Err(self.error("invalid unicode escape"));
+        }
+        let text: String = self.chars[self.pos..self.pos+4].iter().collect();
+        self.pos += 4;
+        u32::from_str_radix(&text, 16).map_err(|_| self.error("invalid unicode escape"))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"{"a": [1, 2.5, -3e2, true, null], "b": {"c": "x\"\né😀"}, "d": []}"#;
+        let value = Json::parse(text).unwrap();
+        assert_eq!(value.get("a").unwrap().as_array().unwrap()[2].as_f64(), Some(-300.0));
+        assert_eq!(value.get("b").unwrap().get("c").unwrap().as_str(), Some("x\"\né😀"));
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(Json::parse(&value.pretty()).unwrap(), value);
+        assert_eq!(value.to_string(), r#"{"a":[1,2.5,-300,true,null],"b":{"c":"x\"\né😀"},"d":[]}"#);
+        assert_eq!(value.pretty(), "{\n  \"a\": [1, 2.5, -300, true, null],\n  \"b\": {\"c\": \"x\\\"\\né😀\"},\n  \"d\": []\n}");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("\"abc").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+}
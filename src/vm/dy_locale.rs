@@ -0,0 +1,398 @@
+use std::cell::Cell;
+use std::env;
+
+use vm::dy_diagnostic::{format_template, MESSAGES};
+
+// ------------------------------------ locale -------------------------------------------------
+// 消息目录: en-US 就是 dy_diagnostic::MESSAGES, 这里是其他语言, 按诊断代码查.
+// 诊断带着代码和模板参数, 输出时按目标语言的模板生成消息, 参数里的词也一起翻译; 找不到的代码用英文
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+    #[default]
+    EnUs,
+    ZhCn,
+}
+
+impl Locale {
+    /// Accepts language tags and POSIX locale names: `zh-CN`, `zh_CN.UTF-8`, `zh`, `en_US`, `C`.
+    pub fn parse(name: &str) -> Option<Locale> {
+        let name = name.split(['.', '@']).next().unwrap_or_default().replace('_', "-").to_lowercase();
+        let mut parts = name.split('-');
+        match (parts.next().unwrap_or_default(), parts.next()) {
+            ("zh", None) | ("zh", Some("cn")) | ("zh", Some("sg")) | ("zh", Some("hans")) => Some(Locale::ZhCn),
+            ("en", _) | ("c", None) | ("posix", None) => Some(Locale::EnUs),
+            _ => None,
+        }
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match *self {
+            Locale::EnUs => "en-US",
+            Locale::ZhCn => "zh-CN",
+        }
+    }
+
+    /// `DY_LANG`, then `LC_ALL`, `LC_MESSAGES` and `LANG`; the first one that is set decides, English if it is not
+    /// a supported language.
+    pub fn from_env() -> Locale {
+        ["DY_LANG", "LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|x| env::var(x).ok())
+            .find(|x| !x.is_empty())
+            .and_then(|x| Locale::parse(&x))
+            .unwrap_or_default()
+    }
+
+    fn messages(&self) -> &'static [(&'static str, &'static str)] {
+        match *self {
+            Locale::EnUs => MESSAGES,
+            Locale::ZhCn => ZH_CN,
+        }
+    }
+
+    fn texts(&self) -> &'static [(&'static str, &'static str)] {
+        match *self {
+            Locale::EnUs => EN_US_TEXTS,
+            Locale::ZhCn => ZH_CN_TEXTS,
+        }
+    }
+}
+
+thread_local! {
+    static LOCALE: Cell<Option<Locale>> = const { Cell::new(None) };
+}
+
+/// Overrides the environment for this thread; for hosts that pick the language themselves.
+pub fn set_locale(locale: Option<Locale>) {
+    LOCALE.with(|x| x.set(locale));
+}
+
+/// The locale set by `set_locale`, otherwise the one of the environment.
+pub fn locale() -> Locale {
+    LOCALE.with(|x| x.get()).unwrap_or_else(Locale::from_env)
+}
+
+/// The message template of a code in a locale, the English one when it has no translation.
+pub fn message(code: &str, locale: Locale) -> Option<&'static str> {
+    locale.messages().iter().chain(MESSAGES.iter()).find(|x| x.0 == code).map(|x| x.1)
+}
+
+/// A runtime error message by code, formatted with `arguments`.
+pub fn error_message(code: &str, arguments: &[String]) -> String {
+    format_template(message(code, locale()).unwrap_or("{}"), arguments)
+}
+
+/// Words of the renderer and texts of labels, notes and fixes, by key.
+pub fn text(key: &str, locale: Locale) -> &'static str {
+    locale.texts().iter().chain(EN_US_TEXTS.iter()).find(|x| x.0 == key).map_or("", |x| x.1)
+}
+
+pub const EN_US_TEXTS: &'static [(&'static str, &'static str)] = &[
+    ("error", "error"),
+    ("warning", "warning"),
+    ("info", "info"),
+    ("note", "note"),
+    ("help", "help"),
+    ("insert", "insert `{}`"),
+    ("remove", "remove `{}`"),
+    ("replace", "replace `{}` with `{}`"),
+    ("insert-token", "insert '{}'"),
+    ("explicit-cast", "add an explicit cast"),
+    ("previous-definition", "previous definition of '{}' here"),
+    ("declared-unassigned", "'{}' declared here without a value"),
+    ("opened-here", "#{} opened here"),
+    ("unchecked", "wrap the expression in unchecked(...) to allow the overflow"),
+    ("null-check", "check the value for null first, or use '?.' to access it"),
+    // 消息参数里的词
+    ("identifier", "identifier"),
+    ("type", "type"),
+    ("expression", "expression"),
+    ("member-declaration", "member declaration"),
+    ("overloadable-operator", "overloadable operator"),
+    ("global-namespace", "<global namespace>"),
+    ("and", "{} and {}"),
+    ("lambda-expression", "lambda expression"),
+    ("anonymous-method", "anonymous method"),
+    ("method", "method"),
+    ("property", "property"),
+    ("indexer", "indexer"),
+    ("event", "event"),
+    ("member", "member"),
+    ("return-type", "return type"),
+    ("base-class", "base class"),
+    ("base-interface", "base interface"),
+    ("parameter-type", "parameter type"),
+    ("field-type", "field type"),
+    ("property-type", "property type"),
+    ("indexer-return-type", "indexer return type"),
+    ("event-type", "event type"),
+    ("class-named", "class '{}'"),
+    ("interface-named", "interface '{}'"),
+    ("delegate-named", "delegate '{}'"),
+    ("method-named", "method '{}'"),
+    ("operator-named", "operator '{}'"),
+    ("field-named", "field '{}'"),
+    ("property-named", "property '{}'"),
+    ("indexer-named", "indexer '{}'"),
+    ("event-named", "event '{}'"),
+    ("constructor-named", "constructor '{}'"),
+    ("indexer-of", "the indexer of '{}'"),
+];
+
+pub const ZH_CN_TEXTS: &'static [(&'static str, &'static str)] = &[
+    ("error", "错误"),
+    ("warning", "警告"),
+    ("info", "提示"),
+    ("note", "注意"),
+    ("help", "帮助"),
+    ("insert", "插入 `{}`"),
+    ("remove", "删除 `{}`"),
+    ("replace", "把 `{}` 替换为 `{}`"),
+    ("insert-token", "插入 '{}'"),
+    ("explicit-cast", "添加显式转换"),
+    ("previous-definition", "'{}' 之前在这里定义"),
+    ("declared-unassigned", "'{}' 在这里声明, 没有赋值"),
+    ("opened-here", "#{} 在这里开始"),
+    ("unchecked", "用 unchecked(...) 包住表达式以允许溢出"),
+    ("null-check", "先检查值是否为 null, 或者用 '?.' 访问"),
+    ("identifier", "标识符"),
+    ("type", "类型"),
+    ("expression", "表达式"),
+    ("member-declaration", "成员声明"),
+    ("overloadable-operator", "可重载运算符"),
+    ("global-namespace", "<全局命名空间>"),
+    ("and", "{} 和 {}"),
+    ("lambda-expression", "lambda 表达式"),
+    ("anonymous-method", "匿名方法"),
+    ("method", "方法"),
+    ("property", "属性"),
+    ("indexer", "索引器"),
+    ("event", "事件"),
+    ("member", "成员"),
+    ("return-type", "返回类型"),
+    ("base-class", "基类"),
+    ("base-interface", "基接口"),
+    ("parameter-type", "参数类型"),
+    ("field-type", "字段类型"),
+    ("property-type", "属性类型"),
+    ("indexer-return-type", "索引器返回类型"),
+    ("event-type", "事件类型"),
+    ("class-named", "类 '{}'"),
+    ("interface-named", "接口 '{}'"),
+    ("delegate-named", "委托 '{}'"),
+    ("method-named", "方法 '{}'"),
+    ("operator-named", "运算符 '{}'"),
+    ("field-named", "字段 '{}'"),
+    ("property-named", "属性 '{}'"),
+    ("indexer-named", "索引器 '{}'"),
+    ("event-named", "事件 '{}'"),
+    ("constructor-named", "构造函数 '{}'"),
+    ("indexer-of", "'{}' 的索引器"),
+];
+
+pub const ZH_CN: &'static [(&'static str, &'static str)] = &[
+    ("DY0101", "意外的字符 '{}'"),
+    ("DY0102", "常量中有换行符"),
+    ("DY0103", "字符文本中的字符太多"),
+    ("DY0104", "空字符文本"),
+    ("DY0105", "文件已结束, 应输入 '*/'"),
+    ("DY0201", "应输入预处理器指令"),
+    ("DY0202", "意外的预处理器指令"),
+    ("DY0203", "应输入单行注释或行尾"),
+    ("DY0204", "应输入 #endif 指令"),
+    ("DY0205", "应输入 #endregion 指令"),
+    ("DY0301", "应输入 '{}', 却找到 '{}'"),
+    ("DY0302", "文件末尾应输入 '{}'"),
+    ("DY0303", "意外的 '{}'"),
+    ("DY0304", "格式化会改变代码的含义"),
+    ("DY0401", "标签 '{}' 重复"),
+    ("DY0402", "已在此范围内定义了名为 '{}' 的局部变量或参数"),
+    ("DY0403", "已在此范围内定义了 '{}'"),
+    ("DY0404", "无法在此范围中声明名为 '{}' 的局部变量或参数, 因为该名称在封闭的局部范围中用于定义局部变量或参数"),
+    ("DY0405", "类型 '{}' 已定义了一个名为 '{}' 的具有相同参数类型的成员"),
+    ("DY0406", "'{}' 已经包含 '{}' 的定义"),
+    ("DY0407", "分部方法不能有多个定义声明"),
+    ("DY0408", "分部方法不能有多个实现声明"),
+    ("DY0409", "两个分部方法声明必须都是静态的, 或者都不是静态的"),
+    ("DY0410", "分部方法必须在分部类或分部结构中声明"),
+    ("DY0411", "分部方法必须具有 void 返回类型"),
+    ("DY0412", "分部方法不能有 out 参数"),
+    ("DY0413", "分部方法不能有访问修饰符或 virtual, abstract, override, new, sealed, extern 修饰符"),
+    ("DY0414", "没有为分部方法 '{}' 的实现声明找到定义声明"),
+    ("DY0415", "类型 '{}' 的声明缺少 partial 修饰符; 存在此类型的其他分部声明"),
+    ("DY0416", "'{}' 的分部声明必须全是类, 全是结构或全是接口"),
+    ("DY0417", "'{}' 的分部声明具有冲突的可访问性修饰符"),
+    ("DY0418", "'{}' 的分部声明必须以相同的顺序使用相同的类型参数名"),
+    ("DY0419", "'{}' 的分部声明不能指定不同的基类"),
+    ("DY0420", "'this' 修饰符只能用于第一个参数"),
+    ("DY0421", "扩展方法必须在非泛型静态类中定义"),
+    ("DY0422", "扩展方法必须在顶级静态类中定义"),
+    ("DY0423", "扩展方法必须是静态的"),
+    ("DY0424", "全局命名空间中不存在类型或命名空间名 '{}'"),
+    ("DY0425", "命名空间 '{1}' 中不存在类型或命名空间名 '{0}'"),
+    ("DY0426", "未在引用中指定外部别名 '{}'"),
+    ("DY0427", "找不到别名 '{}'"),
+    ("DY0428", "'{}' 是 '{}' 和 '{}' 之间的不明确的引用"),
+    ("DY0429", "找不到类型或命名空间名 '{}'"),
+    ("DY0430", "using 别名 '{}' 以前在此命名空间中出现过"),
+    ("DY0431", "'using namespace' 指令只能应用于命名空间; '{}' 是类型而不是命名空间, 请考虑使用 'using static' 指令"),
+    ("DY0432", "'using static' 指令只能应用于类型; '{}' 是命名空间而不是类型"),
+    ("DY0501", "无法将类型 '{}' 隐式转换为 '{}'"),
+    ("DY0502", "无法将类型 '{}' 隐式转换为 '{}'. 存在一个显式转换 (是否缺少强制转换?)"),
+    ("DY0503", "无法将 null 转换为 '{}', 因为它是不可为 null 的值类型"),
+    ("DY0504", "无法将类型 '{}' 转换为 '{}'"),
+    ("DY0505", "'{}' 未包含 '{}' 的定义"),
+    ("DY0506", "'{}' 未包含 '{}' 的定义, 并且找不到可接受第一个 '{3}' 类型参数的可访问扩展方法 '{2}'"),
+    ("DY0507", "隐式类型的变量不能有多个声明符"),
+    ("DY0508", "隐式类型的变量必须已初始化"),
+    ("DY0509", "无法用数组初始值设定项初始化隐式类型的变量"),
+    ("DY0510", "无法将 {} 赋予隐式类型的变量; 无法推断其参数的类型"),
+    ("DY0511", "无法将 {} 赋予隐式类型的变量"),
+    ("DY0512", "由于 '{}' 返回 void, 返回关键字后面不得有对象表达式"),
+    ("DY0513", "需要一个类型可转换为 '{}' 的对象"),
+    ("DY0514", "只有赋值, 调用, 递增, 递减, await 和 new 对象表达式可用作语句"),
+    ("DY0515", "foreach 语句不能作用于 '{}' 类型的变量, 因为 '{}' 不包含 'GetEnumerator' 的公共实例定义"),
+    ("DY0516", "'{}' 是一个类型, 这在给定的上下文中无效"),
+    ("DY0517", "as 运算符必须用于引用类型或可为 null 的类型 ('{}' 是不可为 null 的值类型)"),
+    ("DY0518", "以下方法或属性之间的调用不明确: {}"),
+    ("DY0519", "{} 没有采用 {} 个参数的重载"),
+    ("DY0520", "{} 没有与参数类型 ({}) 匹配的重载; 候选项为: {}"),
+    ("DY0521", "参数 {}: 无法从 '{}' 转换为 '{}'"),
+    ("DY0522", "参数 {} 不可与关键字 '{}' 一起传递"),
+    ("DY0523", "参数 {} 必须与关键字 '{}' 一起传递"),
+    ("DY0524", "'{}' 的最佳重载没有名为 '{}' 的参数"),
+    ("DY0525", "命名实参 '{}' 指定的形参已被赋予位置实参"),
+    ("DY0526", "无法从用法中推断出方法 '{}' 的类型参数; 请尝试显式指定类型参数"),
+    ("DY0527", "泛型方法 '{}' 需要 {} 个类型参数"),
+    ("DY0528", "无法将带 [] 的索引应用于 '{}' 类型的表达式"),
+    ("DY0529", "运算符 '{}' 无法应用于 '{}' 和 '{}' 类型的操作数"),
+    ("DY0530", "运算符 '{}' 对于 {} 类型的操作数不明确"),
+    ("DY0531", "运算符 '{}' 对于 {} 类型的操作数不明确"),
+    ("DY0532", "递增或递减运算符的操作数必须是变量, 属性或索引器"),
+    ("DY0533", "运算符 '{}' 无法应用于 '{}' 类型的操作数"),
+    ("DY0534", "赋值号左边必须是变量, 属性或索引器"),
+    ("DY0535", "委托 '{}' 未采用 {} 个参数"),
+    ("DY0536", "无法将 {} 转换为类型 '{}', 因为它不是委托类型"),
+    ("DY0537", "无法确定条件表达式的类型, 因为 '{}' 和 '{}' 之间没有隐式转换"),
+    ("DY0538", "'{}' 不可访问, 因为它具有一定的保护级别"),
+    ("DY0539", "无法通过 '{1}' 类型的限定符访问受保护的成员 '{0}'; 限定符必须是 '{2}' 类型 (或者从该类型派生)"),
+    ("DY0540", "可访问性不一致: {} '{}' 的可访问性低于 {}"),
+    ("DY0541", "'{}' 的常量值计算涉及循环定义"),
+    ("DY0542", "赋给 '{}' 的表达式必须是常量"),
+    ("DY0543", "常量值 '{}' 无法转换为 '{}'"),
+    ("DY0544", "常量值 '{}' 无法转换为 '{}' (使用 'unchecked' 语法重写)"),
+    ("DY0545", "枚举数值太大, 无法放入其类型"),
+    ("DY0546", "switch 语句包含多个具有标签值 {} 的 case"),
+    ("DY0547", "应输入常量值"),
+    ("DY0548", "'{}' 的默认参数值必须是编译时常量"),
+    ("DY0549", "特性实参必须是常量表达式, typeof 表达式或数组创建表达式"),
+    ("DY0550", "在 checked 模式下, 运算在编译时溢出"),
+    ("DY0551", "被常量零除"),
+    ("DY0552", "表达式没有名称"),
+    ("DY0553", "当前上下文中不存在名称 '{}'"),
+    ("DY0554", "未提供与 '{1}' 的必需参数 '{0}' 对应的实参"),
+    ("DY0601", "接口列表中的类型 '{}' 不是接口"),
+    ("DY0602", "'{}': 无法从密封类型 '{}' 派生"),
+    ("DY0603", "'{}': 无法从静态类 '{}' 派生"),
+    ("DY0604", "类 '{}' 不能具有多个基类: '{}' 和 '{}'"),
+    ("DY0605", "基类 '{}' 必须在任何接口之前"),
+    ("DY0606", "涉及 '{}' 和 '{}' 的循环基类型依赖项"),
+    ("DY0607", "'{}' 是抽象的, 但它包含在非抽象类型 '{}' 中"),
+    ("DY0608", "'{}' 无法声明主体, 因为它标记为 abstract"),
+    ("DY0609", "'{}': 虚拟成员或抽象成员不能是私有的"),
+    ("DY0610", "'{}' 不是重写, 所以无法将其密封"),
+    ("DY0611", "'{}' 隐藏继承的成员 '{}'. 若要使当前成员重写该实现, 请添加关键字 override. 否则, 添加关键字 new."),
+    ("DY0612", "'{}' 隐藏继承的成员 '{}'. 如果是有意隐藏, 请使用关键字 new."),
+    ("DY0613", "成员 '{}' 不会隐藏可访问成员. 不需要关键字 new."),
+    ("DY0614", "'{}': 没有找到适合的{}来重写"),
+    ("DY0615", "'{}': 继承成员 '{}' 未标记为 virtual, abstract 或 override, 无法进行重写"),
+    ("DY0616", "'{}': 继承成员 '{}' 是密封的, 无法进行重写"),
+    ("DY0617", "'{}': {} 必须是 '{}' 才能与重写成员 '{}' 匹配"),
+    ("DY0618", "'{}': 当重写 '{}' 继承成员 '{}' 时, 无法更改访问修饰符"),
+    ("DY0619", "'{}' 不实现继承的抽象成员 '{}'"),
+    ("DY0620", "'{}': 包含类型不实现接口 '{}'"),
+    ("DY0621", "'{}' 不实现接口成员 '{}'. '{}' 无法实现接口成员, 因为它不是公共的."),
+    ("DY0622", "'{}' 不实现接口成员 '{}'. '{}' 无法实现 '{}', 因为它没有 '{}' 的匹配返回类型."),
+    ("DY0623", "'{}' 不实现接口成员 '{}'"),
+    ("DY0624", "关键字 'base' 在静态方法中不可用"),
+    ("DY0625", "无法调用抽象基成员: '{}'"),
+    ("DY0701", "使用了未赋值的局部变量 '{}'"),
+    ("DY0702", "使用了未赋值的 out 参数 '{}'"),
+    ("DY0703", "控制离开当前方法之前必须对 out 参数 '{}' 赋值"),
+    ("DY0704", "'{}': 并非所有的代码路径都返回值"),
+    ("DY0705", "检测到无法访问的代码"),
+    ("DY0706", "控制不能从最后一个 case 标签 ('{}') 脱离 switch 语句"),
+    ("DY0707", "控制不能从一个 case 标签 ('{}') 贯穿到另一个 case 标签"),
+    ("DY0801", "'await' 只能在异步方法中使用"),
+    ("DY0802", "无法在 lock 语句体中等待"),
+    ("DY0803", "无法在 catch 筛选器中等待"),
+    ("DY0804", "'async' 修饰符只能用于方法, lambda 和匿名方法"),
+    ("DY0805", "异步方法不能有 ref 或 out 参数"),
+    ("DY0806", "异步方法的返回类型必须为 void, Task 或 Task<T>"),
+    ("DY0807", "返回 void 或 Task 的异步方法不能返回值"),
+    ("DY0808", "返回 Task<T> 的异步方法必须返回值"),
+    ("DY0809", "yield 不能在 lambda 或匿名方法中使用"),
+    ("DY0810", "不能在 finally 子句体中生成"),
+    ("DY0811", "无法在 catch 子句体中生成值"),
+    ("DY0812", "无法在包含 catch 子句的 try 块体中生成值"),
+    ("DY0813", "'{}' 不是迭代器接口类型"),
+    ("DY0814", "只有方法, 运算符和 get 访问器可以是迭代器"),
+    ("DY0815", "迭代器不能有 ref 或 out 参数"),
+    ("DY0816", "无法从迭代器返回值, 请使用 'yield return' 或 'yield break'"),
+    ("DY0817", "不能在 switch 部分中直接使用 using 变量"),
+    ("DY0818", "using 声明不能是嵌入语句"),
+    ("DY0819", "using 声明需要初始值设定项"),
+    ("DY0820", "无法对 '{}' 赋值, 因为它是 using 变量"),
+    ("DY0821", "无效的匿名类型成员声明符"),
+    ("DY0822", "匿名类型不能有多个名为 '{}' 的成员"),
+    ("DY0823", "无效的初始值设定项成员声明符"),
+    ("DY0824", "成员 '{}' 被多次初始化"),
+    ("DY0825", "无效的析构目标"),
+    ("DY0826", "析构目标必须是变量"),
+    ("DY0827", "无法将 {} 个元素的元组析构为 {} 个变量"),
+    ("DY0828", "'??=' 左边必须是变量, 属性或索引器"),
+    ("DY0829", "无法展开 {} 节点"),
+    ("DY0901", "取消引用可能为 null 的引用"),
+    ("DY0902", "将 null 文本或可能的 null 值转换为不可为 null 的类型"),
+    ("DY0903", "无法将 null 文本转换为不可为 null 的引用类型"),
+    ("DY0904", "可能的 null 引用赋值"),
+    ("DY0905", "可能的 null 引用返回"),
+    ("DY1001", "无法访问已释放的对象"),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(Locale::parse("zh_CN.UTF-8"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("zh-Hans"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("en_US"), Some(Locale::EnUs));
+        assert_eq!(Locale::parse("C.UTF-8"), Some(Locale::EnUs));
+        assert_eq!(Locale::parse("fr_FR"), None);
+        assert_eq!(message("DY0701", Locale::ZhCn), Some("使用了未赋值的局部变量 '{}'"));
+        assert_eq!(text("previous-definition", Locale::ZhCn), "'{}' 之前在这里定义");
+        assert_eq!(text("error", Locale::EnUs), "error");
+        set_locale(Some(Locale::ZhCn));
+        assert_eq!(locale(), Locale::ZhCn);
+        assert_eq!(error_message("DY1001", &[]), "无法访问已释放的对象");
+        set_locale(None);
+    }
+
+    #[test]
+    fn test_catalogs() {
+        // 每个翻译都对应一个英文模板, 参数个数相同
+        for &(code, text) in ZH_CN {
+            let english = MESSAGES.iter().find(|x| x.0 == code).map(|x| x.1);
+            assert!(english.is_some(), "{}", code);
+            assert_eq!(english.unwrap().matches("{}").count(), text.matches('{').count(), "{}", code);
+        }
+        for &(key, text) in ZH_CN_TEXTS {
+            let english = EN_US_TEXTS.iter().find(|x| x.0 == key).map(|x| x.1).unwrap();
+            assert_eq!(english.matches("{}").count(), text.matches('{').count(), "{}", key);
+        }
+        assert!(MESSAGES.iter().all(|x| ZH_CN.iter().any(|y| y.0 == x.0)));
+        assert!(EN_US_TEXTS.iter().all(|x| ZH_CN_TEXTS.iter().any(|y| y.0 == x.0)));
+    }
+}
@@ -0,0 +1,1182 @@
+use std::fmt;
+use vm::dy_diagnostic::Diagnostic;
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, Argument, TypeSyntax, MethodDeclaration, MemberDeclaration,
+    PropertyDeclaration, VariableDeclaration};
+
+// ------------------------------------ lowering -----------------------------------------------
+// 把语法糖展开成简单的操作, 给编译器生成代码用, 临时变量写作 $0, $1, ...
+
+// ------------------------------------ initializers -------------------------------------------
+// new T(args) { .. } 展开成构造调用加上成员赋值和 Add 调用, 新对象是 $0
+
+#[derive(Debug, Clone)]
+pub enum Access {
+    Member(String),
+    Index(Vec<SyntaxRef>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// `$0 = new T(arguments)`; anonymous types get a name made of their members, `<>Anonymous(X, Y)`.
+    Construct { type_name: String, arguments: Vec<SyntaxRef> },
+    /// `$0.Pos.X = value`, `$0["k"] = value`.
+    Assign { target: Vec<Access>, value: SyntaxRef },
+    /// `$0.Items.Add(arguments)`.
+    Add { target: Vec<Access>, arguments: Vec<SyntaxRef> },
+}
+
+fn expression_text(node: &SyntaxRef) -> String {
+    node.text().trim().to_string()
+}
+
+fn write_target(f: &mut fmt::Formatter, target: &[Access]) -> fmt::Result {
+    f.write_str("$0")?;
+    for access in target {
+        match *access {
+            Access::Member(ref name) => write!(f, ".{}", name)?,
+            Access::Index(ref arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(expression_text).collect();
+                write!(f, "[{}]", arguments.join(", "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Step {
+    /// C#-like text, e.g. `$0.Items.Add(1)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Step::Construct { ref type_name, ref arguments } => {
+                let arguments: Vec<String> = arguments.iter().map(expression_text).collect();
+                write!(f, "$0 = new {}({})", type_name, arguments.join(", "))
+            }
+            Step::Assign { ref target, ref value } => {
+                write_target(f, target)?;
+                write!(f, " = {}", expression_text(value))
+            }
+            Step::Add { ref target, ref arguments } => {
+                write_target(f, target)?;
+                let arguments: Vec<String> = arguments.iter().map(expression_text).collect();
+                write!(f, ".Add({})", arguments.join(", "))
+            }
+        }
+    }
+}
+
+// 展开失败的结果, 诊断装箱后 Result 保持小
+type Lowered<T> = Result<T, Box<Diagnostic>>;
+
+fn error(code: &'static str, arguments: &[&str], node: &SyntaxRef) -> Diagnostic {
+    Diagnostic::error(code, node.span(), arguments)
+}
+
+// 展开函数用在不对的节点上
+fn invalid(node: &SyntaxRef) -> Diagnostic {
+    error("DY0829", &[&format!("{:?}", node.kind())], node)
+}
+
+fn arguments_of(list: &SyntaxRef) -> Vec<SyntaxRef> {
+    list.child_nodes().into_iter()
+        .filter_map(Argument::cast)
+        .filter_map(|x| x.expression())
+        .map(|x| x.syntax().clone())
+        .collect()
+}
+
+/// Name of the anonymous type with these members; same names in the same order share a type.
+pub fn anonymous_type_name(members: &[String]) -> String {
+    format!("<>Anonymous({})", members.join(", "))
+}
+
+/// Lowers `new T(..) { .. }` or `new { .. }` into steps on a temporary.
+pub fn lower_creation(node: &SyntaxRef) -> Lowered<Vec<Step>> {
+    match node.kind() {
+        SyntaxKind::ObjectCreationExpression => {
+            let type_name = node.child_nodes().into_iter().find_map(TypeSyntax::cast).map_or(String::new(), |x| x.text());
+            let arguments = node.child_of(SyntaxKind::ArgumentList).map_or(Vec::new(), |x| arguments_of(&x));
+            let mut steps = vec![Step::Construct { type_name, arguments }];
+            let initializer = node.child_nodes().into_iter().find(|x| x.kind() == SyntaxKind::ObjectInitializerExpression
+                || x.kind() == SyntaxKind::CollectionInitializerExpression);
+            if let Some(initializer) = initializer {
+                lower_initializer(&initializer, &mut Vec::new(), &mut steps)?;
+            }
+            Ok(steps)
+        }
+        SyntaxKind::AnonymousObjectCreationExpression => {
+            let mut names: Vec<String> = Vec::new();
+            let mut arguments = Vec::new();
+            for member in node.children_of(SyntaxKind::AnonymousObjectMemberDeclarator) {
+                let value = match member.child_nodes().pop() {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let name = if member.has_child_token("=") {
+                    member.identifier().map(|x| x.text())
+                }
+                else {
+                    inferred_member_name(&value)
+                };
+                let name = name.ok_or_else(|| error("DY0821", &[], &member))?;
+                if names.contains(&name) {
+                    return Err(error("DY0822", &[&name], &member).into());
+                }
+                names.push(name);
+                arguments.push(value);
+            }
+            Ok(vec![Step::Construct { type_name: anonymous_type_name(&names), arguments }])
+        }
+        _ => Err(invalid(node).into()),
+    }
+}
+
+// new { x, p.Name } 的成员名取最后一个标识符
+fn inferred_member_name(value: &SyntaxRef) -> Option<String> {
+    match value.kind() {
+        SyntaxKind::IdentifierName => value.identifier().map(|x| x.text()),
+        SyntaxKind::MemberAccessExpression | SyntaxKind::ConditionalAccessExpression =>
+            value.child_nodes().pop().and_then(|x| inferred_member_name(&x)),
+        SyntaxKind::MemberBindingExpression => value.child_nodes().pop().and_then(|x| inferred_member_name(&x)),
+        _ => None,
+    }
+}
+
+fn lower_initializer(initializer: &SyntaxRef, target: &mut Vec<Access>, steps: &mut Vec<Step>) -> Lowered<()> {
+    if initializer.kind() == SyntaxKind::CollectionInitializerExpression {
+        for element in initializer.child_nodes() {
+            let arguments = if element.kind() == SyntaxKind::ComplexElementInitializerExpression {
+                element.child_nodes()
+            }
+            else {
+                vec![element]
+            };
+            steps.push(Step::Add { target: target.clone(), arguments });
+        }
+        return Ok(());
+    }
+    let mut members: Vec<String> = Vec::new();
+    for element in initializer.child_nodes() {
+        let parts = element.child_nodes();
+        let is_member = element.kind() == SyntaxKind::AssignmentExpression && parts.len() == 2
+            && (parts[0].kind() == SyntaxKind::IdentifierName || parts[0].kind() == SyntaxKind::ImplicitElementAccess);
+        if !is_member {
+            return Err(error("DY0823", &[], &element).into());
+        }
+        let access = if parts[0].kind() == SyntaxKind::IdentifierName {
+            let name = parts[0].identifier().map_or(String::new(), |x| x.text());
+            if members.contains(&name) {
+                return Err(error("DY0824", &[&name], &parts[0]).into());
+            }
+            members.push(name.clone());
+            Access::Member(name)
+        }
+        else {
+            let arguments = parts[0].child_of(SyntaxKind::BracketedArgumentList).map_or(Vec::new(), |x| arguments_of(&x));
+            Access::Index(arguments)
+        };
+        target.push(access);
+        let value = &parts[1];
+        match value.kind() {
+            SyntaxKind::ObjectInitializerExpression | SyntaxKind::CollectionInitializerExpression =>
+                lower_initializer(value, target, steps)?,
+            _ => steps.push(Step::Assign { target: target.clone(), value: value.clone() }),
+        }
+        target.pop();
+    }
+    Ok(())
+}
+
+
+// ------------------------------------ deconstruction -----------------------------------------
+// var (x, y) = e: 先求值右边, 拆开, 最后赋值; 右边是元组字面量时逐个求值, (x, y) = (y, x) 可以交换
+
+#[derive(Debug, Clone)]
+pub enum Deconstruction {
+    /// `$n = value`.
+    Evaluate { temp: usize, value: SyntaxRef },
+    /// `($a, $b) = $n`: the elements of a tuple, otherwise `$n.Deconstruct(out $a, out $b)`.
+    Split { source: usize, outputs: Vec<usize> },
+    /// `x = $n`, or `var x = $n` when the variable is declared here.
+    Store { target: SyntaxRef, declared_type: Option<String>, temp: usize },
+}
+
+impl fmt::Display for Deconstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Deconstruction::Evaluate { temp, ref value } => write!(f, "${} = {}", temp, expression_text(value)),
+            Deconstruction::Split { source, ref outputs } => {
+                let outputs: Vec<String> = outputs.iter().map(|x| format!("${}", x)).collect();
+                write!(f, "({}) = ${}", outputs.join(", "), source)
+            }
+            Deconstruction::Store { ref target, ref declared_type, temp } => match *declared_type {
+                Some(ref x) => write!(f, "{} {} = ${}", x, expression_text(target), temp),
+                None => write!(f, "{} = ${}", expression_text(target), temp),
+            },
+        }
+    }
+}
+
+enum Target {
+    Tuple(Vec<Target>, SyntaxRef),
+    Variable(SyntaxRef, Option<String>),
+    Discard,
+}
+
+fn target_of(node: &SyntaxRef, declared_type: Option<String>) -> Lowered<Target> {
+    match node.kind() {
+        SyntaxKind::TupleExpression => {
+            let elements = node.child_nodes().into_iter()
+                .filter_map(Argument::cast)
+                .filter_map(|x| x.expression())
+                .map(|x| target_of(x.syntax(), None))
+                .collect::<Lowered<Vec<Target>>>()?;
+            Ok(Target::Tuple(elements, node.clone()))
+        }
+        SyntaxKind::DeclarationExpression => {
+            let parts = node.child_nodes();
+            let declared_type = parts.first().and_then(|x| TypeSyntax::cast(x.clone())).map(|x| x.text());
+            match parts.last() {
+                Some(x) if parts.len() == 2 => target_of(x, declared_type),
+                _ => Err(error("DY0825", &[], node).into()),
+            }
+        }
+        SyntaxKind::ParenthesizedVariableDesignation => {
+            let elements = node.child_nodes().iter()
+                .map(|x| target_of(x, declared_type.clone()))
+                .collect::<Lowered<Vec<Target>>>()?;
+            Ok(Target::Tuple(elements, node.clone()))
+        }
+        SyntaxKind::DiscardDesignation => Ok(Target::Discard),
+        SyntaxKind::IdentifierName if node.text().trim() == "_" => Ok(Target::Discard),
+        SyntaxKind::SingleVariableDesignation | SyntaxKind::VariableDeclarator => Ok(Target::Variable(node.clone(), declared_type)),
+        SyntaxKind::IdentifierName | SyntaxKind::MemberAccessExpression | SyntaxKind::ElementAccessExpression =>
+            Ok(Target::Variable(node.clone(), None)),
+        _ => Err(error("DY0826", &[], node).into()),
+    }
+}
+
+struct Deconstructor {
+    steps: Vec<Deconstruction>,
+    stores: Vec<Deconstruction>,
+    temps: usize,
+}
+
+impl Deconstructor {
+    fn temp(&mut self) -> usize {
+        self.temps += 1;
+        self.temps - 1
+    }
+
+    fn assign(&mut self, target: Target, value: &SyntaxRef) -> Lowered<()> {
+        if let Target::Tuple(elements, node) = target {
+            if value.kind() == SyntaxKind::TupleExpression {
+                let values: Vec<SyntaxRef> = value.child_nodes().into_iter()
+                    .filter_map(Argument::cast)
+                    .filter_map(|x| x.expression())
+                    .map(|x| x.syntax().clone())
+                    .collect();
+                if values.len() != elements.len() {
+                    let counts = [values.len().to_string(), elements.len().to_string()];
+                    return Err(error("DY0827", &[&counts[0], &counts[1]], &node).into());
+                }
+                for (element, value) in elements.into_iter().zip(values.iter()) {
+                    self.assign(element, value)?;
+                }
+                return Ok(());
+            }
+            let temp = self.temp();
+            self.steps.push(Deconstruction::Evaluate { temp, value: value.clone() });
+            return self.split(Target::Tuple(elements, node), temp);
+        }
+        let temp = self.temp();
+        self.steps.push(Deconstruction::Evaluate { temp, value: value.clone() });
+        self.split(target, temp)
+    }
+
+    fn split(&mut self, target: Target, source: usize) -> Lowered<()> {
+        match target {
+            Target::Tuple(elements, _) => {
+                let outputs: Vec<usize> = elements.iter().map(|_| self.temp()).collect();
+                self.steps.push(Deconstruction::Split { source, outputs: outputs.clone() });
+                for (element, output) in elements.into_iter().zip(outputs) {
+                    self.split(element, output)?;
+                }
+            }
+            Target::Variable(target, declared_type) => {
+                self.stores.push(Deconstruction::Store { target, declared_type, temp: source });
+            }
+            Target::Discard => {}
+        }
+        Ok(())
+    }
+}
+
+/// Lowers `var (x, y) = e`, `(a, b) = (b, a)` or `foreach (var (k, v) in e)`.
+/// For foreach, `$0` is the current element.
+pub fn lower_deconstruction(node: &SyntaxRef) -> Lowered<Vec<Deconstruction>> {
+    let mut lowering = Deconstructor { steps: Vec::new(), stores: Vec::new(), temps: 0 };
+    match node.kind() {
+        SyntaxKind::AssignmentExpression if node.has_child_token("=") => {
+            let parts = node.child_nodes();
+            let target = target_of(&parts[0], None)?;
+            if let Target::Tuple(..) = target {
+                lowering.assign(target, &parts[1])?;
+            }
+            else {
+                return Err(invalid(node).into());
+            }
+        }
+        SyntaxKind::ForEachStatement => {
+            let variable = node.child_nodes().into_iter().find(|x| x.kind() == SyntaxKind::DeclarationExpression
+                || x.kind() == SyntaxKind::TupleExpression);
+            let variable = variable.ok_or_else(|| invalid(node))?;
+            let temp = lowering.temp();
+            lowering.split(target_of(&variable, None)?, temp)?;
+        }
+        _ => return Err(invalid(node).into()),
+    }
+    let mut steps = lowering.steps;
+    steps.extend(lowering.stores);
+    Ok(steps)
+}
+
+/// Number of values a user-defined `void Deconstruct(out .., out ..)` produces.
+pub fn deconstruct_arity(method: &MethodDeclaration) -> Option<usize> {
+    if method.syntax().kind() != SyntaxKind::MethodDeclaration || method.name().is_none_or(|x| x != "Deconstruct") {
+        return None;
+    }
+    if method.return_type().is_none_or(|x| x.text() != "void") {
+        return None;
+    }
+    let parameters = method.parameters();
+    if parameters.iter().all(|x| x.modifiers() == ["out"]) {
+        return Some(parameters.len());
+    }
+    None
+}
+
+
+// ------------------------------------ async --------------------------------------------------
+// async 方法在每个 await 处切开: 第 n 个 await 完成后从状态 n + 1 继续执行
+// 可能跨 await 存活的参数和局部变量提升成状态机的字段, 和 C# debug 编译一样, 有 await 就全部提升
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsyncReturn {
+    Void,
+    Task,
+    /// `Task<T>`, holds `T`.
+    TaskOf(String),
+    /// Async lambdas and anonymous methods, decided by the delegate type they convert to.
+    Inferred,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsyncStateMachine {
+    pub returns: AsyncReturn,
+    /// Await expressions in source order; resuming after `awaits[n]` enters state `n + 1`.
+    pub awaits: Vec<SyntaxRef>,
+    /// Parameters and locals stored in the state machine instead of the stack.
+    pub hoisted: Vec<String>,
+}
+
+impl fmt::Display for AsyncStateMachine {
+    /// One line per resume point, e.g. `1: $awaiter = F(); hoist (a, x)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0: start")?;
+        for (i, expression) in self.awaits.iter().enumerate() {
+            let operand = expression.child_nodes().pop().map_or(String::new(), |x| expression_text(&x));
+            write!(f, "; {}: $awaiter = {}", i + 1, operand)?;
+        }
+        if !self.hoisted.is_empty() {
+            write!(f, "; hoist ({})", self.hoisted.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Methods, accessors, lambdas and the other nodes with their own body and return.
+pub fn is_function(kind: SyntaxKind) -> bool {
+    matches!(kind,
+        SyntaxKind::MethodDeclaration | SyntaxKind::ConstructorDeclaration | SyntaxKind::DestructorDeclaration
+        | SyntaxKind::OperatorDeclaration | SyntaxKind::ConversionOperatorDeclaration
+        | SyntaxKind::AccessorDeclaration | SyntaxKind::LambdaExpression | SyntaxKind::AnonymousMethodExpression)
+}
+
+pub fn is_async(function: &SyntaxRef) -> bool {
+    match MemberDeclaration::cast(function.clone()) {
+        Some(member) => member.has_modifier("async"),
+        None => function.has_child_token("async"),
+    }
+}
+
+pub fn enclosing_function(node: &SyntaxRef) -> Option<SyntaxRef> {
+    node.acestors().skip(1).find(|x| is_function(x.kind()))
+}
+
+// 函数自己的节点, 不进入嵌套的 lambda 和匿名方法
+fn own_nodes(node: &SyntaxRef, nodes: &mut Vec<SyntaxRef>) {
+    for child in node.child_nodes() {
+        nodes.push(child.clone());
+        if !is_function(child.kind()) {
+            own_nodes(&child, nodes);
+        }
+    }
+}
+
+fn hoisted_locals(nodes: &[SyntaxRef]) -> Vec<String> {
+    let mut hoisted: Vec<String> = Vec::new();
+    for node in nodes {
+        let local = matches!(node.declaration(),
+            Some(SemanticFlags::LocalVariableDeclarator) | Some(SemanticFlags::ForEachVariableDeclaration)
+            | Some(SemanticFlags::CatchExceptionParameterDeclaration) | Some(SemanticFlags::FixedParameterDeclaration)
+            | Some(SemanticFlags::ParameterArrayDeclaration) | Some(SemanticFlags::ImplicitParameterDeclaration)
+            | Some(SemanticFlags::ExplicitParameterDeclaration));
+        if let Some(name) = node.identifier().filter(|_| local).map(|x| x.token_text()) {
+            if !hoisted.contains(&name) {
+                hoisted.push(name);
+            }
+        }
+    }
+    hoisted
+}
+
+fn has_ref_parameter(nodes: &[SyntaxRef]) -> Option<&SyntaxRef> {
+    nodes.iter().find(|x| x.kind() == SyntaxKind::Parameter && (x.has_child_token("ref") || x.has_child_token("out")))
+}
+
+fn async_return(return_type: &TypeSyntax) -> Option<AsyncReturn> {
+    let text = return_type.text();
+    let name = match text.find('<') {
+        Some(i) => &text[..i],
+        None => &text[..],
+    };
+    match name.rsplit('.').next().unwrap_or("") {
+        "void" if name == "void" => Some(AsyncReturn::Void),
+        "Task" | "ValueTask" if name == text => Some(AsyncReturn::Task),
+        "Task" | "ValueTask" => Some(AsyncReturn::TaskOf(text[name.len() + 1..text.len() - 1].trim().to_string())),
+        _ => None,
+    }
+}
+
+fn check_await(node: &SyntaxRef, errors: &mut Vec<Diagnostic>) {
+    let function = enclosing_function(node);
+    if !function.as_ref().is_some_and(is_async) {
+        errors.push(error("DY0801", &[], node));
+        return;
+    }
+    let mut child = node.clone();
+    for ancestor in node.acestors().skip(1) {
+        match ancestor.kind() {
+            SyntaxKind::LockStatement if ancestor.child_nodes().last() == Some(&child) => {
+                errors.push(error("DY0802", &[], node));
+            }
+            SyntaxKind::CatchFilterClause => errors.push(error("DY0803", &[], node)),
+            kind if is_function(kind) => break,
+            _ => {}
+        }
+        child = ancestor;
+    }
+}
+
+fn check_async_function(function: &SyntaxRef, errors: &mut Vec<Diagnostic>) {
+    if let Some(member) = MemberDeclaration::cast(function.clone()) {
+        if member.kind() != SyntaxKind::MethodDeclaration {
+            errors.push(error("DY0804", &[], function));
+            return;
+        }
+    }
+    let mut nodes = Vec::new();
+    own_nodes(function, &mut nodes);
+    if let Some(parameter) = has_ref_parameter(&nodes) {
+        errors.push(error("DY0805", &[], parameter));
+    }
+    let returns = match MethodDeclaration::cast(function.clone()).and_then(|x| x.return_type()) {
+        Some(return_type) => match async_return(&return_type) {
+            Some(returns) => returns,
+            None => {
+                errors.push(error("DY0806", &[], return_type.syntax()));
+                return;
+            }
+        },
+        None => return,
+    };
+    for statement in nodes.iter().filter(|x| x.kind() == SyntaxKind::ReturnStatement) {
+        let has_value = !statement.child_nodes().is_empty();
+        match returns {
+            AsyncReturn::Void | AsyncReturn::Task if has_value =>
+                errors.push(error("DY0807", &[], statement)),
+            AsyncReturn::TaskOf(..) if !has_value =>
+                errors.push(error("DY0808", &[], statement)),
+            _ => {}
+        }
+    }
+}
+
+/// Checks `async`/`await` usage under `root`.
+pub fn check_async(root: &SyntaxRef) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    for node in root.traverse(true, false) {
+        if node.kind() == SyntaxKind::AwaitExpression {
+            check_await(&node, &mut errors);
+        }
+        else if is_function(node.kind()) && is_async(&node) {
+            check_async_function(&node, &mut errors);
+        }
+    }
+    errors
+}
+
+/// Splits an async method, lambda or anonymous method at its await points.
+pub fn lower_async(function: &SyntaxRef) -> Lowered<AsyncStateMachine> {
+    if !is_function(function.kind()) || !is_async(function) {
+        return Err(invalid(function).into());
+    }
+    if let Some(error) = check_async(function).into_iter().next() {
+        return Err(error.into());
+    }
+    let returns = match MethodDeclaration::cast(function.clone()).and_then(|x| x.return_type()) {
+        Some(return_type) => async_return(&return_type).unwrap_or(AsyncReturn::Void),
+        None => AsyncReturn::Inferred,
+    };
+    let mut nodes = Vec::new();
+    own_nodes(function, &mut nodes);
+    let awaits: Vec<SyntaxRef> = nodes.iter().filter(|x| x.kind() == SyntaxKind::AwaitExpression).cloned().collect();
+    let hoisted = if awaits.is_empty() { Vec::new() } else { hoisted_locals(&nodes) };
+    Ok(AsyncStateMachine { returns, awaits, hoisted })
+}
+
+
+// ------------------------------------ iterators ----------------------------------------------
+// 含有 yield 的方法编译成迭代器: 第 n 个 yield return 之后从状态 n + 1 继续
+// 提前停止 (foreach 里 break, 或者宿主停掉协程) 时 Dispose 按由内到外的顺序执行当前状态外面的 finally
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IteratorKind {
+    Enumerable,
+    Enumerator,
+}
+
+#[derive(Debug, Clone)]
+pub struct IteratorStateMachine {
+    pub kind: IteratorKind,
+    /// `T` of `IEnumerable<T>`, `object` for the non-generic interfaces.
+    pub element_type: String,
+    /// `yield return` statements in source order; resuming after `yields[n]` enters state `n + 1`.
+    pub yields: Vec<SyntaxRef>,
+    /// Finally clauses that enclose some `yield return`.
+    pub finally_clauses: Vec<SyntaxRef>,
+    /// For each yield, indices into `finally_clauses` that Dispose runs in that state, innermost first.
+    pub disposes: Vec<Vec<usize>>,
+    pub hoisted: Vec<String>,
+}
+
+impl fmt::Display for IteratorStateMachine {
+    /// One line per resume point, e.g. `1: yield x, finally [0]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0: start")?;
+        for (i, statement) in self.yields.iter().enumerate() {
+            let value = statement.child_nodes().pop().map_or(String::new(), |x| expression_text(&x));
+            write!(f, "; {}: yield {}", i + 1, value)?;
+            if !self.disposes[i].is_empty() {
+                let indices: Vec<String> = self.disposes[i].iter().map(|x| x.to_string()).collect();
+                write!(f, ", finally [{}]", indices.join(", "))?;
+            }
+        }
+        if !self.hoisted.is_empty() {
+            write!(f, "; hoist ({})", self.hoisted.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Declared return type, the property type for get accessors.
+pub fn function_return_type(function: &SyntaxRef) -> Option<TypeSyntax> {
+    if function.kind() == SyntaxKind::AccessorDeclaration && function.has_child_token("get") {
+        return function.acestors().find_map(PropertyDeclaration::cast).and_then(|x| x.property_type());
+    }
+    MethodDeclaration::cast(function.clone()).and_then(|x| x.return_type())
+}
+
+fn iterator_type(return_type: &TypeSyntax) -> Option<(IteratorKind, String)> {
+    let text = return_type.text();
+    let name = match text.find('<') {
+        Some(i) => &text[..i],
+        None => &text[..],
+    };
+    let kind = match name.rsplit('.').next().unwrap_or("") {
+        "IEnumerable" => IteratorKind::Enumerable,
+        "IEnumerator" => IteratorKind::Enumerator,
+        _ => return None,
+    };
+    let element_type = if name == text { "object".to_string() } else { text[name.len() + 1..text.len() - 1].trim().to_string() };
+    Some((kind, element_type))
+}
+
+fn check_yield(node: &SyntaxRef, errors: &mut Vec<Diagnostic>) {
+    let function = match enclosing_function(node) {
+        Some(function) => function,
+        None => return,
+    };
+    if function.kind() == SyntaxKind::LambdaExpression || function.kind() == SyntaxKind::AnonymousMethodExpression {
+        errors.push(error("DY0809", &[], node));
+        return;
+    }
+    let mut child = node.clone();
+    for ancestor in node.acestors().skip(1).take_while(|x| *x != function) {
+        match ancestor.kind() {
+            SyntaxKind::FinallyClause => {
+                errors.push(error("DY0810", &[], node));
+            }
+            SyntaxKind::CatchClause if node.kind() == SyntaxKind::YieldReturnStatement => {
+                errors.push(error("DY0811", &[], node));
+            }
+            SyntaxKind::TryStatement if node.kind() == SyntaxKind::YieldReturnStatement
+                && child.kind() == SyntaxKind::Block && ancestor.child_of(SyntaxKind::CatchClause).is_some() => {
+                errors.push(error("DY0812", &[], node));
+            }
+            _ => {}
+        }
+        child = ancestor;
+    }
+}
+
+fn check_iterator_function(function: &SyntaxRef, nodes: &[SyntaxRef], errors: &mut Vec<Diagnostic>) {
+    if function.kind() == SyntaxKind::LambdaExpression || function.kind() == SyntaxKind::AnonymousMethodExpression {
+        return;
+    }
+    match function_return_type(function) {
+        Some(ref return_type) if iterator_type(return_type).is_some() => {}
+        Some(return_type) => errors.push(error("DY0813", &[&return_type.text()], return_type.syntax())),
+        None => errors.push(error("DY0814", &[], function)),
+    }
+    if let Some(parameter) = has_ref_parameter(nodes) {
+        errors.push(error("DY0815", &[], parameter));
+    }
+    for statement in nodes.iter().filter(|x| x.kind() == SyntaxKind::ReturnStatement) {
+        errors.push(error("DY0816", &[], statement));
+    }
+}
+
+fn is_yield(node: &SyntaxRef) -> bool {
+    node.kind() == SyntaxKind::YieldReturnStatement || node.kind() == SyntaxKind::YieldBreakStatement
+}
+
+/// Checks `yield return`/`yield break` usage under `root`.
+pub fn check_iterators(root: &SyntaxRef) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    for node in root.traverse(true, false) {
+        if is_yield(&node) {
+            check_yield(&node, &mut errors);
+        }
+        else if is_function(node.kind()) {
+            let mut nodes = Vec::new();
+            own_nodes(&node, &mut nodes);
+            if nodes.iter().any(is_yield) {
+                check_iterator_function(&node, &nodes, &mut errors);
+            }
+        }
+    }
+    errors
+}
+
+/// Turns an iterator method or get accessor into its resume states.
+pub fn lower_iterator(function: &SyntaxRef) -> Lowered<IteratorStateMachine> {
+    let mut nodes = Vec::new();
+    own_nodes(function, &mut nodes);
+    if !is_function(function.kind()) || !nodes.iter().any(is_yield) {
+        return Err(invalid(function).into());
+    }
+    if let Some(error) = check_iterators(function).into_iter().next() {
+        return Err(error.into());
+    }
+    let (kind, element_type) = function_return_type(function).and_then(|x| iterator_type(&x))
+        .ok_or_else(|| invalid(function))?;
+    let yields: Vec<SyntaxRef> = nodes.iter().filter(|x| x.kind() == SyntaxKind::YieldReturnStatement).cloned().collect();
+    let mut finally_clauses: Vec<SyntaxRef> = Vec::new();
+    let mut disposes = Vec::new();
+    for statement in &yields {
+        let mut indices = Vec::new();
+        let mut child = statement.clone();
+        for ancestor in statement.acestors().skip(1).take_while(|x| x != function) {
+            if ancestor.kind() == SyntaxKind::TryStatement && child.kind() == SyntaxKind::Block {
+                if let Some(clause) = ancestor.child_of(SyntaxKind::FinallyClause) {
+                    let index = match finally_clauses.iter().position(|x| *x == clause) {
+                        Some(index) => index,
+                        None => {
+                            finally_clauses.push(clause);
+                            finally_clauses.len() - 1
+                        }
+                    };
+                    indices.push(index);
+                }
+            }
+            child = ancestor;
+        }
+        disposes.push(indices);
+    }
+    let hoisted = hoisted_locals(&nodes);
+    Ok(IteratorStateMachine { kind, element_type, yields, finally_clauses, disposes, hoisted })
+}
+
+
+// ------------------------------------ null operators -----------------------------------------
+// a?.b.c()?[0], a ?? b, x ??= y 展开成临时变量和提前返回, 短路的部分不会被求值
+// ??= 的左边读一次写一次, 接收者和下标先存进临时变量, a[i()] ??= v 里的 i() 只求值一次
+
+#[derive(Debug, Clone)]
+pub enum NullStep {
+    /// `$0 = a`.
+    Evaluate { temp: usize, value: String },
+    /// `if ($0 == null) return null`, short-circuits the rest of a `?.` chain.
+    ExitIfNull { temp: usize },
+    /// `if ($0 != null) return $0`, for `??` and `??=`.
+    ExitIfNotNull { temp: usize },
+    /// The value when nothing short-circuited.
+    Result(String),
+}
+
+impl fmt::Display for NullStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NullStep::Evaluate { temp, ref value } => write!(f, "${} = {}", temp, value),
+            NullStep::ExitIfNull { temp } => write!(f, "if (${} == null) return null", temp),
+            NullStep::ExitIfNotNull { temp } => write!(f, "if (${} != null) return ${}", temp, temp),
+            NullStep::Result(ref value) => f.write_str(value),
+        }
+    }
+}
+
+fn lower_conditional_access(node: &SyntaxRef, receiver: &str, steps: &mut Vec<NullStep>) {
+    let parts = node.child_nodes();
+    let temp = steps.iter().filter(|x| matches!(**x, NullStep::Evaluate { .. })).count();
+    steps.push(NullStep::Evaluate { temp, value: format!("{}{}", receiver, expression_text(&parts[0])) });
+    steps.push(NullStep::ExitIfNull { temp });
+    let receiver = format!("${}", temp);
+    match parts.last() {
+        Some(when) if when.kind() == SyntaxKind::ConditionalAccessExpression && parts.len() > 1 =>
+            lower_conditional_access(when, &receiver, steps),
+        Some(when) if parts.len() > 1 => steps.push(NullStep::Result(format!("{}{}", receiver, expression_text(when)))),
+        _ => steps.push(NullStep::Result(receiver)),
+    }
+}
+
+/// Lowers `a?.b`, `a?[i]`, `a ?? b` and `x ??= y`.
+pub fn lower_null_operator(node: &SyntaxRef) -> Lowered<Vec<NullStep>> {
+    let mut steps = Vec::new();
+    let parts = node.child_nodes();
+    match node.kind() {
+        SyntaxKind::ConditionalAccessExpression => lower_conditional_access(node, "", &mut steps),
+        SyntaxKind::BinaryExpression if node.has_child_token("??") => {
+            steps.push(NullStep::Evaluate { temp: 0, value: expression_text(&parts[0]) });
+            steps.push(NullStep::ExitIfNotNull { temp: 0 });
+            steps.push(NullStep::Result(expression_text(&parts[1])));
+        }
+        SyntaxKind::AssignmentExpression if node.has_child_token("??=") => {
+            let target = expression_text(&parts[0]);
+            if !is_assignable(&parts[0]) {
+                return Err(error("DY0828", &[], &parts[0]).into());
+            }
+            let target = spill_target(&parts[0], &mut steps).unwrap_or(target);
+            let temp = steps.len();
+            steps.push(NullStep::Evaluate { temp, value: target.clone() });
+            steps.push(NullStep::ExitIfNotNull { temp });
+            steps.push(NullStep::Result(format!("{} = {}", target, expression_text(&parts[1]))));
+        }
+        _ => return Err(invalid(node).into()),
+    }
+    Ok(steps)
+}
+
+// 名字, this 和常量多求值一次也没有副作用, 不用存
+fn spill(node: &SyntaxRef, steps: &mut Vec<NullStep>) -> String {
+    match node.kind() {
+        SyntaxKind::IdentifierName | SyntaxKind::ThisExpression | SyntaxKind::BaseExpression
+            | SyntaxKind::LiteralExpression | SyntaxKind::PredefinedType => expression_text(node),
+        _ => {
+            let temp = steps.len();
+            steps.push(NullStep::Evaluate { temp, value: expression_text(node) });
+            format!("${}", temp)
+        }
+    }
+}
+
+// ??= 的左边改成只引用临时变量的形式, 比如 a.b[i()] 变成 $0[$1]; 简单名字返回 None
+fn spill_target(node: &SyntaxRef, steps: &mut Vec<NullStep>) -> Option<String> {
+    let parts = node.child_nodes();
+    match node.kind() {
+        SyntaxKind::ParenthesizedExpression => parts.first().and_then(|x| spill_target(x, steps)),
+        SyntaxKind::MemberAccessExpression => {
+            let receiver = spill(&parts[0], steps);
+            Some(format!("{}.{}", receiver, expression_text(parts.last()?)))
+        }
+        SyntaxKind::ElementAccessExpression => {
+            let receiver = spill(&parts[0], steps);
+            let arguments: Vec<String> = node.child_of(SyntaxKind::BracketedArgumentList)
+                .map_or(Vec::new(), |x| arguments_of(&x)).iter()
+                .map(|x| spill(x, steps))
+                .collect();
+            Some(format!("{}[{}]", receiver, arguments.join(", ")))
+        }
+        _ => None,
+    }
+}
+
+fn is_assignable(node: &SyntaxRef) -> bool {
+    match node.kind() {
+        SyntaxKind::IdentifierName | SyntaxKind::MemberAccessExpression | SyntaxKind::ElementAccessExpression => true,
+        SyntaxKind::ParenthesizedExpression => node.child_nodes().first().is_some_and(is_assignable),
+        _ => false,
+    }
+}
+
+
+// ------------------------------------ using --------------------------------------------------
+// using (var a = A(), b = B()) body 展开成嵌套的 try/finally, 后声明的先释放
+// using var a = A(); 的作用域是所在块里后面的语句
+
+#[derive(Debug, Clone)]
+pub enum Resource {
+    /// `var a = A()`, disposes `a`.
+    Declared { declaration: String, name: String },
+    /// `using (expression)`, the value is kept in `$temp`.
+    Expression { temp: usize, value: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct UsingLowering {
+    pub resources: Vec<Resource>,
+    /// Statements that run while the resources are alive.
+    pub body: Vec<SyntaxRef>,
+}
+
+impl fmt::Display for UsingLowering {
+    /// `var a = A(); try { .. } finally { a?.Dispose(); }`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for resource in &self.resources {
+            match *resource {
+                Resource::Declared { ref declaration, .. } => write!(f, "{}; try {{ ", declaration)?,
+                Resource::Expression { temp, ref value } => write!(f, "${} = {}; try {{ ", temp, value)?,
+            }
+        }
+        for statement in &self.body {
+            write!(f, "{} ", expression_text(statement))?;
+        }
+        for (i, resource) in self.resources.iter().rev().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            match *resource {
+                Resource::Declared { ref name, .. } => write!(f, "}} finally {{ {}?.Dispose(); }}", name)?,
+                Resource::Expression { temp, .. } => write!(f, "}} finally {{ ${}?.Dispose(); }}", temp)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_using_declaration(node: &SyntaxRef) -> bool {
+    node.kind() == SyntaxKind::LocalDeclarationStatement && node.has_child_token("using")
+}
+
+fn using_resources(node: &SyntaxRef) -> Vec<Resource> {
+    if let Some(declaration) = node.child_nodes().into_iter().find_map(VariableDeclaration::cast) {
+        let variable_type = declaration.variable_type().map_or(String::new(), |x| x.text());
+        return declaration.declarators().iter()
+            .map(|x| Resource::Declared {
+                declaration: format!("{} {}", variable_type, expression_text(x.syntax())),
+                name: x.name().unwrap_or_default(),
+            })
+            .collect();
+    }
+    node.child_nodes().into_iter().take(1).filter(|x| x.kind().is_expression())
+        .map(|x| Resource::Expression { temp: 0, value: expression_text(&x) })
+        .collect()
+}
+
+fn using_body(node: &SyntaxRef) -> Vec<SyntaxRef> {
+    if is_using_declaration(node) {
+        return node.parent().map_or(Vec::new(), |x| x.child_nodes().into_iter()
+            .skip_while(|x| x != node).skip(1)
+            .filter(|x| x.kind().is_statement())
+            .collect());
+    }
+    match node.child_nodes().pop() {
+        Some(ref body) if body.kind() == SyntaxKind::Block => body.child_nodes(),
+        Some(body) => vec![body],
+        None => Vec::new(),
+    }
+}
+
+fn check_using_node(node: &SyntaxRef, errors: &mut Vec<Diagnostic>) {
+    if is_using_declaration(node) {
+        match node.parent().map(|x| x.kind()) {
+            Some(SyntaxKind::Block) | Some(SyntaxKind::CompilationUnit) => {}
+            Some(SyntaxKind::SwitchSection) =>
+                errors.push(error("DY0817", &[], node)),
+            _ => errors.push(error("DY0818", &[], node)),
+        }
+        for declarator in node.traverse(true, false).filter(|x| x.kind() == SyntaxKind::VariableDeclarator) {
+            if !declarator.has_child_token("=") {
+                errors.push(error("DY0819", &[], &declarator));
+            }
+        }
+    }
+    let names: Vec<String> = using_resources(node).into_iter()
+        .filter_map(|x| if let Resource::Declared { name, .. } = x { Some(name) } else { None })
+        .collect();
+    for statement in using_body(node) {
+        for assignment in statement.traverse(true, false).filter(|x| x.kind() == SyntaxKind::AssignmentExpression) {
+            let target = assignment.child_nodes().remove(0);
+            let name = target.identifier().map(|x| x.token_text()).filter(|_| target.kind() == SyntaxKind::IdentifierName);
+            if let Some(name) = name.filter(|x| names.contains(x)) {
+                errors.push(error("DY0820", &[&name], &target));
+            }
+        }
+    }
+}
+
+/// Checks `using` statements and `using var` declarations under `root`.
+pub fn check_using(root: &SyntaxRef) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    for node in root.traverse(true, false) {
+        if node.kind() == SyntaxKind::UsingStatement || is_using_declaration(&node) {
+            check_using_node(&node, &mut errors);
+        }
+    }
+    errors
+}
+
+/// Lowers a `using` statement or a `using var` declaration to try/finally with Dispose calls.
+pub fn lower_using(node: &SyntaxRef) -> Lowered<UsingLowering> {
+    if node.kind() != SyntaxKind::UsingStatement && !is_using_declaration(node) {
+        return Err(invalid(node).into());
+    }
+    let mut errors = Vec::new();
+    check_using_node(node, &mut errors);
+    if let Some(error) = errors.into_iter().next() {
+        return Err(error.into());
+    }
+    Ok(UsingLowering { resources: using_resources(node), body: using_body(node) })
+}
+
+
+// ------------------------------------ extension methods --------------------------------------
+// 成员查找选中扩展方法以后, recv.F(args) 改写成静态调用 Class.F(recv, args)
+
+/// Rewrites `receiver.Name(arguments)` into a call of the static `class` method.
+pub fn lower_extension_call(invocation: &SyntaxRef, class: &str) -> Lowered<String> {
+    let invalid = || invalid(invocation);
+    if invocation.kind() != SyntaxKind::InvocationExpression {
+        return Err(invalid().into());
+    }
+    let parts = invocation.child_nodes();
+    let callee = parts.first().filter(|x| x.kind() == SyntaxKind::MemberAccessExpression).ok_or_else(invalid)?;
+    let access = callee.child_nodes();
+    let name = access.last().map(expression_text).ok_or_else(invalid)?;
+    let mut arguments = vec![expression_text(&access[0])];
+    arguments.extend(parts.last().map_or(Vec::new(), arguments_of).iter().map(expression_text));
+    Ok(format!("{}.{}({})", class, name, arguments.join(", ")))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+    use vm::dy_syntax::SyntaxTree;
+
+    fn parse(code: &str) -> SyntaxTree {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        dy_parser.parse()
+    }
+
+    fn lower(code: &str) -> Result<Vec<String>, String> {
+        let tree = parse(&format!("class A {{ object x = {}; }}", code));
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let creation = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::ObjectCreationExpression
+            || x.kind() == SyntaxKind::AnonymousObjectCreationExpression).unwrap();
+        lower_creation(&creation).map(|x| x.iter().map(|x| x.to_string()).collect()).map_err(|x| x.message)
+    }
+
+    #[test]
+    fn test_object_initializer() {
+        assert_eq!(lower("new Player(1) { Name = \"a\", Pos = { X = 1, Y = 2 }, Items = { 1, 2 } }").unwrap(),
+            ["$0 = new Player(1)", "$0.Name = \"a\"", "$0.Pos.X = 1", "$0.Pos.Y = 2", "$0.Items.Add(1)",
+                "$0.Items.Add(2)"]);
+        assert_eq!(lower("new Dictionary<string, int> { [\"k\"] = 1, [\"j\"] = 2 }").unwrap(),
+            ["$0 = new Dictionary<string, int>()", "$0[\"k\"] = 1", "$0[\"j\"] = 2"]);
+        assert_eq!(lower("new Player { }").unwrap(), ["$0 = new Player()"]);
+        assert_eq!(lower("new Player { Hp = 1, Hp = 2 }").unwrap_err(), "member 'Hp' is initialized more than once");
+        assert_eq!(lower("new Player { Hp = 1, 2 }").unwrap_err(), "invalid initializer member declarator");
+    }
+
+    #[test]
+    fn test_collection_initializer() {
+        assert_eq!(lower("new List<int> { 1, 2, 3 }").unwrap(),
+            ["$0 = new List<int>()", "$0.Add(1)", "$0.Add(2)", "$0.Add(3)"]);
+        assert_eq!(lower("new Dictionary<string, int>() { { \"a\", 1 }, { \"b\", 2 } }").unwrap(),
+            ["$0 = new Dictionary<string, int>()", "$0.Add(\"a\", 1)", "$0.Add(\"b\", 2)"]);
+    }
+
+    #[test]
+    fn test_anonymous_object() {
+        assert_eq!(lower("new { X = 1, p.Name, y }").unwrap(), ["$0 = new <>Anonymous(X, Name, y)(1, p.Name, y)"]);
+        assert_eq!(lower("new { X = 1, X = 2 }").unwrap_err(), "anonymous type cannot have multiple members named 'X'");
+        assert_eq!(lower("new { 1 }").unwrap_err(), "invalid anonymous type member declarator");
+    }
+
+    fn deconstruct(code: &str) -> Result<Vec<String>, String> {
+        let tree = parse(&format!("class A {{ void F() {{ {} }} }}", code));
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let node = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::AssignmentExpression
+            || x.kind() == SyntaxKind::ForEachStatement).unwrap();
+        lower_deconstruction(&node).map(|x| x.iter().map(|x| x.to_string()).collect()).map_err(|x| x.message)
+    }
+
+    #[test]
+    fn test_deconstruction() {
+        assert_eq!(deconstruct("var (x, (y, _)) = GetPos();").unwrap(),
+            ["$0 = GetPos()", "($1, $2) = $0", "($3, $4) = $2", "var x = $1", "var y = $3"]);
+        assert_eq!(deconstruct("(x, y) = (y, x);").unwrap(), ["$0 = y", "$1 = x", "x = $0", "y = $1"]);
+        assert_eq!(deconstruct("(int a, var b) = (1, p);").unwrap(), ["$0 = 1", "$1 = p", "int a = $0", "var b = $1"]);
+        assert_eq!(deconstruct("foreach (var (k, v) in dict) { }").unwrap(), ["($1, $2) = $0", "var k = $1", "var v = $2"]);
+        assert_eq!(deconstruct("(a, b) = (1, 2, 3);").unwrap_err(), "cannot deconstruct a tuple of 3 elements into 2 variables");
+        assert_eq!(deconstruct("(a, 1) = p;").unwrap_err(), "deconstruction target must be a variable");
+        assert!(deconstruct("a = p;").is_err());
+
+        let tree = parse("class P { public void Deconstruct(out int x, out int y) { } void Deconstruct(int x) { } }");
+        let arities: Vec<Option<usize>> = tree.root.traverse(true, false)
+            .filter_map(MethodDeclaration::cast)
+            .map(|x| deconstruct_arity(&x))
+            .collect();
+        assert_eq!(arities, [Some(2), None]);
+    }
+
+    fn async_errors(code: &str) -> Vec<String> {
+        let tree = parse(code);
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        check_async(&tree.root).into_iter().map(|x| x.message).collect()
+    }
+
+    #[test]
+    fn test_async() {
+        let tree = parse("class A { async Task<int> Run(int a) { var x = await F(a); foreach (var i in xs) { x += await G(i); } \
+            Func<Task> f = async () => { var y = await H(); }; return x; } }");
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let method = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::MethodDeclaration).unwrap();
+        let machine = lower_async(&method).unwrap();
+        assert_eq!(machine.returns, AsyncReturn::TaskOf("int".to_string()));
+        assert_eq!(machine.to_string(), "0: start; 1: $awaiter = F(a); 2: $awaiter = G(i); hoist (a, x, i, f)");
+        let lambda = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::LambdaExpression).unwrap();
+        let machine = lower_async(&lambda).unwrap();
+        assert_eq!(machine.returns, AsyncReturn::Inferred);
+        assert_eq!(machine.to_string(), "0: start; 1: $awaiter = H(); hoist (y)");
+
+        assert_eq!(async_errors("class A { async void F() { G(async delegate { await H(); }); } }"), Vec::<String>::new());
+        assert_eq!(async_errors("class A { void F() { await G(); } }"), ["'await' can only be used in an async method"]);
+        assert_eq!(async_errors("class A { async void F() { G(() => { await H(); }); } }"),
+            ["'await' can only be used in an async method"]);
+        assert_eq!(async_errors("class A { async int F() { return 1; } }"), ["async method must return void, Task or Task<T>"]);
+        assert_eq!(async_errors("class A { async Task F(ref int a) { return 1; } }"),
+            ["async methods cannot have ref or out parameters", "an async method returning void or Task cannot return a value"]);
+        assert_eq!(async_errors("class A { async Task<int> F() { return; } }"), ["an async method returning Task<T> must return a value"]);
+        assert_eq!(async_errors("class A { async A() { } }"),
+            ["the 'async' modifier is only valid on methods, lambdas and anonymous methods"]);
+        assert_eq!(async_errors("class A { async Task F() { lock (await G()) { await H(); } try { } catch (E e) when (await I()) { } } }"),
+            ["cannot await in the body of a lock statement", "cannot await in a catch filter"]);
+    }
+
+    #[test]
+    fn test_iterator() {
+        let tree = parse("class A { IEnumerable<int> Range(int n) { try { for (var i = 0; i < n; i++) { yield return i; \
+            try { yield return -i; } finally { Log(); } } } finally { Close(); } yield return n; yield break; } \
+            IEnumerator Wait { get { yield return null; } } }");
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let method = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::MethodDeclaration).unwrap();
+        let machine = lower_iterator(&method).unwrap();
+        assert_eq!((&machine.kind, machine.element_type.as_str()), (&IteratorKind::Enumerable, "int"));
+        assert_eq!(machine.to_string(), "0: start; 1: yield i, finally [0]; 2: yield -i, finally [1, 0]; 3: yield n; hoist (n, i)");
+        assert_eq!(machine.finally_clauses[1].text().trim(), "finally { Log(); }");
+        let getter = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::AccessorDeclaration).unwrap();
+        let machine = lower_iterator(&getter).unwrap();
+        assert_eq!((&machine.kind, machine.element_type.as_str()), (&IteratorKind::Enumerator, "object"));
+
+        let errors = |code: &str| -> Vec<String> {
+            let tree = parse(code);
+            assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+            check_iterators(&tree.root).into_iter().map(|x| x.message).collect()
+        };
+        assert_eq!(errors("class A { int F() { yield return 1; } }"), ["'int' is not an iterator interface type"]);
+        assert_eq!(errors("class A { IEnumerable F(out int a) { a = 1; yield break; return null; } }"),
+            ["iterators cannot have ref or out parameters", "cannot return a value from an iterator, use 'yield return' or 'yield break'"]);
+        assert_eq!(errors("class A { void F() { G(() => { yield return 1; }); } }"),
+            ["yield cannot be used in a lambda or anonymous method"]);
+        assert_eq!(errors("class A { IEnumerable F() { try { yield return 1; yield break; } catch { yield return 2; } finally { yield break; } } }"),
+            ["cannot yield a value in the body of a try block with a catch clause",
+                "cannot yield a value in the body of a catch clause", "cannot yield in the body of a finally clause"]);
+    }
+
+    fn null_steps(code: &str) -> Result<Vec<String>, String> {
+        let tree = parse(&format!("class A {{ void F() {{ {} }} }}", code));
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let statement = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::ExpressionStatement).unwrap();
+        let node = statement.child_nodes().remove(0);
+        lower_null_operator(&node).map(|x| x.iter().map(|x| x.to_string()).collect()).map_err(|x| x.message)
+    }
+
+    #[test]
+    fn test_null_operators() {
+        assert_eq!(null_steps("a?.b.c(G())?[0]?.d;").unwrap(),
+            ["$0 = a", "if ($0 == null) return null", "$1 = $0.b.c(G())", "if ($1 == null) return null", "$2 = $1[0]",
+                "if ($2 == null) return null", "$2.d"]);
+        assert_eq!(null_steps("x?[i]?.F();").unwrap(),
+            ["$0 = x", "if ($0 == null) return null", "$1 = $0[i]", "if ($1 == null) return null", "$1.F()"]);
+        assert_eq!(null_steps("a ?? F();").unwrap(), ["$0 = a", "if ($0 != null) return $0", "F()"]);
+        assert_eq!(null_steps("p.Name ??= \"none\";").unwrap(), ["$0 = p.Name", "if ($0 != null) return $0", "p.Name = \"none\""]);
+        assert_eq!(null_steps("a.b[i(), 2] ??= v;").unwrap(),
+            ["$0 = a.b", "$1 = i()", "$2 = $0[$1, 2]", "if ($2 != null) return $2", "$0[$1, 2] = v"]);
+        assert_eq!(null_steps("(F().Name) ??= s;").unwrap(),
+            ["$0 = F()", "$1 = $0.Name", "if ($1 != null) return $1", "$0.Name = s"]);
+        assert_eq!(null_steps("F() ??= 1;").unwrap_err(), "the left-hand side of '??=' must be a variable, property or indexer");
+        assert!(null_steps("a + b;").is_err());
+    }
+
+    fn using(code: &str) -> (Result<String, String>, Vec<String>) {
+        let tree = parse(&format!("class A {{ void F() {{ {} }} }}", code));
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let node = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::UsingStatement
+            || is_using_declaration(x)).unwrap();
+        let errors = check_using(&tree.root).into_iter().map(|x| x.message).collect();
+        (lower_using(&node).map(|x| x.to_string()).map_err(|x| x.message), errors)
+    }
+
+    #[test]
+    fn test_using() {
+        assert_eq!(using("using (var a = A(), b = B()) { a.Read(); }").0.unwrap(),
+            "var a = A(); try { var b = B(); try { a.Read(); } finally { b?.Dispose(); } } finally { a?.Dispose(); }");
+        assert_eq!(using("using (Open()) F();").0.unwrap(), "$0 = Open(); try { F(); } finally { $0?.Dispose(); }");
+        assert_eq!(using("G(); using var f = File.Open(\"a\"); f.Read(); H();").0.unwrap(),
+            "var f = File.Open(\"a\"); try { f.Read(); H(); } finally { f?.Dispose(); }");
+        assert_eq!(using("using var f = Open(); f = null;").1, ["cannot assign to 'f' because it is a using variable"]);
+        assert_eq!(using("using var f;").1, ["a using declaration requires an initializer"]);
+        assert_eq!(using("if (x) using var f = Open();").1, ["a using declaration cannot be an embedded statement"]);
+        assert_eq!(using("switch (x) { case 1: using var f = Open(); break; }").1,
+            ["a using variable cannot be used directly within a switch section"]);
+    }
+
+    #[test]
+    fn test_extension_call() {
+        let tree = parse("class A { void F() { transform.MoveTowards(target, 1.5f); items.Where(x => x > 1).Count(); } }");
+        let calls: Vec<SyntaxRef> = tree.root.traverse(true, false).filter(|x| x.kind() == SyntaxKind::InvocationExpression)
+            .collect();
+        assert_eq!(lower_extension_call(&calls[0], "Engine.TransformExtensions").unwrap(),
+            "Engine.TransformExtensions.MoveTowards(transform, target, 1.5f)");
+        assert_eq!(lower_extension_call(&calls[1], "Linq.Enumerable").unwrap(),
+            "Linq.Enumerable.Count(items.Where(x => x > 1))");
+        let tree = parse("class A { void F() { G(); } }");
+        let call = tree.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::InvocationExpression).unwrap();
+        assert!(lower_extension_call(&call, "E").is_err());
+    }
+}
@@ -0,0 +1,660 @@
+use vm::dy_binder::ScopeTree;
+use vm::dy_diagnostic::{Diagnostic, Phrase};
+use vm::dy_flow::{Action, ControlFlowGraph, ENTRY};
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_lower::{is_function, is_async, enclosing_function, function_return_type};
+use vm::dy_parser::{TokenKind, BUILTIN_TYPES};
+use vm::dy_symbols::{Declarations, MemberSymbol};
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, Expression, TypeSyntax, VariableDeclaration, Parameter};
+
+// ------------------------------------ nullable -----------------------------------------------
+// 可空引用类型分析, 由 #nullable enable 或者项目选项打开.
+// 沿控制流跟踪引用类型的局部变量, 参数和函数里用到的字段, 属性是否可能为 null: 赋值带来值的状态,
+// 条件里的 null 检查, is 模式和解引用让变量不再为 null. 解引用可能为 null 的值, 以及把 null 存进不可空的声明时给出警告
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NullState {
+    NotNull,
+    MaybeNull,
+}
+
+impl NullState {
+    fn join(self, other: NullState) -> NullState {
+        if self == NullState::MaybeNull || other == NullState::MaybeNull { NullState::MaybeNull } else { NullState::NotNull }
+    }
+}
+
+/// How a declared type treats null: `string?`, `string`, or a type the analysis does not track (value types, type
+/// parameters, `var` and declarations in a disabled context).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Annotation {
+    Nullable,
+    NonNullable,
+    Oblivious,
+}
+
+/// Nullable warnings of a compilation unit.
+pub struct NullableAnalysis {
+    pub warnings: Vec<Diagnostic>,
+}
+
+// 值存到哪里, 决定警告的说法
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Local,
+    Member,
+    Parameter,
+    Return,
+}
+
+/// `#nullable enable|disable|restore` directives of a unit, by offset; `restore` goes back to the project default.
+pub fn nullable_directives(root: &SyntaxRef, enabled: bool) -> Vec<(usize, bool)> {
+    let tokens = root.tokens();
+    let mut directives = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let is_nullable = token.token_kind() == Some(TokenKind::Preprocessor)
+            && token.token_text().replace([' ', '\t'], "") == "#nullable";
+        if !is_nullable {
+            continue;
+        }
+        let setting = tokens[i + 1..].iter()
+            .take_while(|x| x.token_kind() != Some(TokenKind::EndOfLine))
+            .find(|x| x.token_kind() == Some(TokenKind::PreprocessorArguments))
+            .and_then(|x| x.token_text().split_whitespace().next().map(|x| x.to_string()));
+        let value = match setting.as_deref() {
+            Some("enable") => true,
+            Some("disable") => false,
+            Some("restore") => enabled,
+            _ => continue,
+        };
+        directives.push((token.span().start, value));
+    }
+    directives
+}
+
+/// Runs the analysis on every function of a unit. `enabled` is the project option; `#nullable` directives override it
+/// for the code that follows them.
+pub fn analyze_nullable(root: &SyntaxRef, declarations: &Declarations, enabled: bool) -> NullableAnalysis {
+    let directives = nullable_directives(root, enabled);
+    if !enabled && !directives.iter().any(|x| x.1) {
+        return NullableAnalysis { warnings: Vec::new() };
+    }
+    let scopes = ScopeTree::bind(root);
+    let mut analyzer = Analyzer { declarations, scopes: &scopes, enabled, directives, members: Vec::new(),
+        warnings: Vec::new() };
+    let empty = ControlFlowGraph { blocks: Vec::new(), variables: Vec::new(), end: None, section_ends: Vec::new() };
+    for node in root.traverse(true, false) {
+        if is_function(node.kind()) {
+            analyzer.function(&node);
+            continue;
+        }
+        // 函数外面的初始值: 字段, 参数默认值
+        let value = match node.kind() {
+            SyntaxKind::VariableDeclarator if enclosing_function(&node).is_none() =>
+                node.child_nodes().into_iter().filter(|x| Expression::cast(x.clone()).is_some()).last(),
+            SyntaxKind::Parameter => Parameter::cast(node.clone()).and_then(|x| x.default_value()).map(|x| x.syntax().clone()),
+            _ => None,
+        };
+        if let Some(value) = value {
+            analyzer.store(&empty, &value, &[]);
+        }
+    }
+    let mut warnings = analyzer.warnings;
+    warnings.sort_by_key(|x| x.span.start);
+    NullableAnalysis { warnings }
+}
+
+fn strip(node: &SyntaxRef) -> SyntaxRef {
+    match node.kind() {
+        SyntaxKind::ParenthesizedExpression => node.child_nodes().pop().map_or(node.clone(), |x| strip(&x)),
+        _ => node.clone(),
+    }
+}
+
+fn operator(node: &SyntaxRef) -> String {
+    Expression::cast(node.clone()).and_then(|x| x.operator()).unwrap_or_default()
+}
+
+fn is_null_literal(node: &SyntaxRef) -> bool {
+    let node = strip(node);
+    node.kind() == SyntaxKind::LiteralExpression
+        && node.first_significant_token().is_some_and(|x| x.token_kind() == Some(TokenKind::NullLiteral))
+}
+
+// 声明里写的类型: 局部变量和字段, 参数, foreach 和 catch 的变量
+fn declared_type(declaration: &SyntaxRef) -> Option<SyntaxRef> {
+    let parent = declaration.parent()?;
+    let syntax = match declaration.kind() {
+        SyntaxKind::Parameter => Parameter::cast(declaration.clone())?.parameter_type(),
+        _ => match VariableDeclaration::cast(parent.clone()) {
+            Some(x) => x.variable_type(),
+            None => parent.child_nodes().into_iter().find_map(TypeSyntax::cast),
+        },
+    };
+    syntax.map(|x| x.syntax().clone())
+}
+
+struct Analyzer<'a> {
+    declarations: &'a Declarations,
+    scopes: &'a ScopeTree,
+    enabled: bool,
+    directives: Vec<(usize, bool)>,
+    // 当前函数里跟踪的字段和属性, 状态排在 graph.variables 后面
+    members: Vec<String>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn is_enabled(&self, offset: usize) -> bool {
+        self.directives.iter().rev().find(|x| x.0 <= offset).map_or(self.enabled, |x| x.1)
+    }
+
+    fn warning(&mut self, node: &SyntaxRef, warning: Diagnostic) {
+        if self.is_enabled(node.span().start) {
+            self.warnings.push(warning);
+        }
+    }
+
+    // ------------------------------------ types ---------------------------------------------
+
+    fn is_type_parameter(name: &str, at: &SyntaxRef) -> bool {
+        at.acestors().filter_map(|x| x.child_of(SyntaxKind::TypeParameterList))
+            .any(|x| x.traverse(true, false).any(|x| x.token_kind() == Some(TokenKind::Identifier) && x.token_text() == name))
+    }
+
+    // 引用类型; 找不到的泛型和限定名当作引用类型, 找不到的单个名字可能是类型参数
+    fn is_reference(&self, text: &str, at: &SyntaxRef) -> bool {
+        if text.ends_with(']') || text == "string" || text == "object" {
+            return true;
+        }
+        if BUILTIN_TYPES.contains(&text) || text == "dynamic" || text.starts_with('(') || Self::is_type_parameter(text, at) {
+            return false;
+        }
+        let name = text.split('<').next().unwrap_or(text);
+        match self.declarations.resolve_type_at(name, at) {
+            Some(symbol) => symbol.kind != SyntaxKind::StructDeclaration && symbol.kind != SyntaxKind::EnumDeclaration,
+            None => true,
+        }
+    }
+
+    fn annotation(&self, text: &str, at: &SyntaxRef) -> Annotation {
+        let text: String = text.split_whitespace().collect();
+        if !self.is_enabled(at.span().start) || text == "var" {
+            return Annotation::Oblivious;
+        }
+        match text.strip_suffix('?') {
+            Some(inner) if self.is_reference(inner, at) => Annotation::Nullable,
+            Some(_) => Annotation::Oblivious,
+            None if self.is_reference(&text, at) => Annotation::NonNullable,
+            None => Annotation::Oblivious,
+        }
+    }
+
+    fn declared_annotation(&self, declaration: &SyntaxRef) -> Annotation {
+        declared_type(declaration).map_or(Annotation::Oblivious, |x| self.annotation(&x.text(), &x))
+    }
+
+    fn member_annotation(&self, member: &MemberSymbol) -> Annotation {
+        member.type_name.as_ref().map_or(Annotation::Oblivious, |x| self.annotation(x, &member.declaration))
+    }
+
+    // 当前类型和基类型里的成员
+    fn member(&self, name: &str, at: &SyntaxRef) -> Option<&'a MemberSymbol> {
+        let declarations = self.declarations;
+        let enclosing = declarations.enclosing_types(at);
+        enclosing.into_iter().flat_map(|x| declarations.with_bases(x)).find_map(|x| x.members_named(name).into_iter().next())
+    }
+
+    // 名字引用的局部变量或参数的声明
+    fn local(&self, name: &SyntaxRef) -> Option<SyntaxRef> {
+        let symbol = self.scopes.lookup(&name.token_text(), name.span().start).into_iter().next()?;
+        if symbol.is_local() { Some(symbol.node) } else { None }
+    }
+
+    // this.F 和不是局部变量的名字 F 引用的字段或属性
+    fn member_name(&self, node: &SyntaxRef) -> Option<String> {
+        let name = match node.kind() {
+            SyntaxKind::IdentifierName if self.local(node).is_none() => {
+                let is_qualified = node.parent().is_some_and(|x| matches!(x.kind(),
+                    SyntaxKind::MemberAccessExpression | SyntaxKind::MemberBindingExpression))
+                    && node.preceding_siblings().skip(1).any(|x| !x.is_token());
+                if is_qualified {
+                    return None;
+                }
+                node.token_text()
+            }
+            SyntaxKind::MemberAccessExpression => match node.child_nodes().as_slice() {
+                [receiver, name] if receiver.kind() == SyntaxKind::ThisExpression => name.token_text(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let member = self.member(&name, node)?;
+        let is_value = member.kind == SyntaxKind::FieldDeclaration || member.kind == SyntaxKind::PropertyDeclaration;
+        if is_value { Some(name) } else { None }
+    }
+
+    // ------------------------------------ states --------------------------------------------
+
+    fn tracked(&self, graph: &ControlFlowGraph, node: &SyntaxRef) -> Option<usize> {
+        let node = strip(node);
+        if node.kind() == SyntaxKind::IdentifierName {
+            if let Some(declaration) = self.local(&node) {
+                return graph.variables.iter().position(|x| *x == declaration);
+            }
+        }
+        let name = self.member_name(&node)?;
+        self.members.iter().position(|x| *x == name).map(|i| graph.variables.len() + i)
+    }
+
+    // 没有值的写入 (out 实参, foreach, 模式变量) 按声明的类型
+    fn declared_state(&self, declaration: &SyntaxRef) -> NullState {
+        match self.declared_annotation(declaration) {
+            Annotation::Nullable => NullState::MaybeNull,
+            _ => NullState::NotNull,
+        }
+    }
+
+    fn null_state(&self, graph: &ControlFlowGraph, node: &SyntaxRef, state: &[NullState]) -> NullState {
+        let operands: Vec<SyntaxRef> = node.child_nodes().into_iter().filter(|x| Expression::cast(x.clone()).is_some()).collect();
+        let op = operator(node);
+        match node.kind() {
+            SyntaxKind::LiteralExpression if is_null_literal(node) || op == "default" => NullState::MaybeNull,
+            SyntaxKind::DefaultExpression => {
+                let syntax = node.child_nodes().into_iter().find_map(TypeSyntax::cast);
+                match syntax {
+                    Some(x) if !self.is_reference(&x.text(), node) => NullState::NotNull,
+                    _ => NullState::MaybeNull,
+                }
+            }
+            SyntaxKind::ParenthesizedExpression if operands.len() == 1 => self.null_state(graph, &operands[0], state),
+            SyntaxKind::PostfixUnaryExpression if op == "!" => NullState::NotNull,
+            SyntaxKind::AsExpression | SyntaxKind::ConditionalAccessExpression => NullState::MaybeNull,
+            SyntaxKind::BinaryExpression if op == "as" => NullState::MaybeNull,
+            SyntaxKind::BinaryExpression if op == "??" && operands.len() == 2 => self.null_state(graph, &operands[1], state),
+            SyntaxKind::ConditionalExpression if operands.len() == 3 =>
+                self.null_state(graph, &operands[1], state).join(self.null_state(graph, &operands[2], state)),
+            SyntaxKind::AssignmentExpression if (op == "=" || op == "??=") && operands.len() == 2 =>
+                self.null_state(graph, &operands[1], state),
+            SyntaxKind::CastExpression => {
+                let children = node.child_nodes();
+                match children.first() {
+                    Some(x) if x.kind() == SyntaxKind::NullableType => NullState::MaybeNull,
+                    _ => children.last().map_or(NullState::NotNull, |x| self.null_state(graph, x, state)),
+                }
+            }
+            SyntaxKind::IdentifierName => {
+                if let Some(i) = self.tracked(graph, node) {
+                    return state[i];
+                }
+                if let Some(declaration) = self.local(node) {
+                    return self.declared_state(&declaration);
+                }
+                self.member_state(&node.token_text(), node)
+            }
+            // this.F 和调用返回的值按成员声明的类型
+            SyntaxKind::MemberAccessExpression if self.tracked(graph, node).is_some() =>
+                state[self.tracked(graph, node).unwrap()],
+            SyntaxKind::MemberAccessExpression => {
+                let children = node.child_nodes();
+                match (children.first(), children.last()) {
+                    (Some(receiver), Some(name)) if receiver.kind() == SyntaxKind::ThisExpression =>
+                        self.member_state(&name.token_text(), node),
+                    _ => NullState::NotNull,
+                }
+            }
+            SyntaxKind::InvocationExpression => {
+                let callee = node.child_nodes().into_iter().next();
+                match callee {
+                    Some(ref x) if x.kind() == SyntaxKind::IdentifierName && self.local(x).is_none() =>
+                        self.member_state(&x.token_text(), node),
+                    Some(ref x) if x.kind() == SyntaxKind::MemberAccessExpression => self.null_state(graph, x, state),
+                    _ => NullState::NotNull,
+                }
+            }
+            _ => NullState::NotNull,
+        }
+    }
+
+    fn member_state(&self, name: &str, at: &SyntaxRef) -> NullState {
+        match self.member(name, at).map(|x| self.member_annotation(x)) {
+            Some(Annotation::Nullable) => NullState::MaybeNull,
+            _ => NullState::NotNull,
+        }
+    }
+
+    fn set(&self, graph: &ControlFlowGraph, node: &SyntaxRef, value: NullState, state: &mut [NullState]) {
+        let node = strip(node);
+        match node.kind() {
+            // x?.F != null 成立时 x 不是 null
+            SyntaxKind::ConditionalAccessExpression if value == NullState::NotNull => {
+                if let Some(receiver) = node.child_nodes().first() {
+                    self.set(graph, receiver, value, state);
+                }
+            }
+            SyntaxKind::PostfixUnaryExpression if operator(&node) == "!" => {
+                if let Some(operand) = node.child_nodes().first() {
+                    self.set(graph, operand, value, state);
+                }
+            }
+            _ => {
+                if let Some(i) = self.tracked(graph, &node) {
+                    state[i] = value;
+                }
+            }
+        }
+    }
+
+    // 条件为 sense 时学到的状态
+    fn learn(&self, graph: &ControlFlowGraph, condition: &SyntaxRef, sense: bool, state: &mut [NullState]) {
+        let condition = strip(condition);
+        let operands: Vec<SyntaxRef> = condition.child_nodes();
+        let op = operator(&condition);
+        match condition.kind() {
+            SyntaxKind::PrefixUnaryExpression if op == "!" && operands.len() == 1 => self.learn(graph, &operands[0], !sense, state),
+            SyntaxKind::BinaryExpression if (op == "==" || op == "!=") && operands.len() == 2 => {
+                let is_equal = (op == "==") == sense;
+                let (left, right) = (&operands[0], &operands[1]);
+                for (x, y) in [(left, right), (right, left)].iter() {
+                    if is_null_literal(y) {
+                        let value = if is_equal { NullState::MaybeNull } else { NullState::NotNull };
+                        self.set(graph, x, value, state);
+                    }
+                    // 和不为 null 的值相等
+                    else if is_equal && self.null_state(graph, y, state) == NullState::NotNull {
+                        self.set(graph, x, NullState::NotNull, state);
+                    }
+                }
+            }
+            SyntaxKind::IsPatternExpression if operands.len() == 2 => {
+                let pattern = &operands[1];
+                let (is_null, is_not_null) = match pattern.kind() {
+                    SyntaxKind::ConstantPattern => (pattern.child_nodes().first().is_some_and(is_null_literal), false),
+                    SyntaxKind::UnaryPattern => {
+                        let inner = pattern.child_nodes().pop();
+                        let is_null = inner.is_some_and(|x| x.kind() == SyntaxKind::ConstantPattern
+                            && x.child_nodes().first().is_some_and(is_null_literal));
+                        (false, is_null)
+                    }
+                    _ => (false, true),
+                };
+                match (is_null, is_not_null, sense) {
+                    (true, _, true) | (_, true, false) => self.set(graph, &operands[0], NullState::MaybeNull, state),
+                    (true, _, false) | (_, true, true) => self.set(graph, &operands[0], NullState::NotNull, state),
+                    _ => {}
+                }
+            }
+            SyntaxKind::BinaryExpression if op == "is" && sense && !operands.is_empty() =>
+                self.set(graph, &operands[0], NullState::NotNull, state),
+            // string.IsNullOrEmpty(x) 不成立时 x 不是 null
+            SyntaxKind::InvocationExpression if !sense => {
+                let callee = condition.child_nodes().into_iter().next().map(|x| x.text()).unwrap_or_default();
+                let is_check = callee.ends_with("IsNullOrEmpty") || callee.ends_with("IsNullOrWhiteSpace");
+                let argument = condition.child_of(SyntaxKind::ArgumentList).and_then(|x| x.child_nodes().into_iter().next())
+                    .and_then(|x| x.child_nodes().pop());
+                if let (true, Some(argument)) = (is_check, argument) {
+                    self.set(graph, &argument, NullState::NotNull, state);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn apply(&self, graph: &ControlFlowGraph, action: &Action, state: &mut [NullState]) {
+        match *action {
+            Action::Write(i, ref value) => {
+                state[i] = match *value {
+                    Some(ref value) => self.null_state(graph, value, state),
+                    None => self.declared_state(&graph.variables[i]),
+                };
+            }
+            Action::Assign(ref target, ref value) => {
+                if let Some(i) = self.tracked(graph, target) {
+                    state[i] = match *value {
+                        Some(ref value) => self.null_state(graph, value, state),
+                        None => self.member_state(&self.members[i - graph.variables.len()], target),
+                    };
+                }
+            }
+            // 解引用之后就不是 null 了, 同一个变量只报一次
+            Action::Dereference(ref receiver) => self.set(graph, receiver, NullState::NotNull, state),
+            Action::Assume(ref condition, sense) => self.learn(graph, condition, sense, state),
+            _ => {}
+        }
+    }
+
+    fn transfer(&self, graph: &ControlFlowGraph, block: usize, state: &[NullState]) -> Vec<NullState> {
+        let mut state = state.to_vec();
+        for action in &graph.blocks[block].actions {
+            self.apply(graph, action, &mut state);
+        }
+        state
+    }
+
+    /// States at the start of each block; parameters, fields and properties start as declared.
+    fn states(&self, graph: &ControlFlowGraph, function: &SyntaxRef) -> Vec<Vec<NullState>> {
+        let reachable = graph.reachable();
+        let predecessors: Vec<Vec<usize>> = (0..graph.blocks.len())
+            .map(|x| graph.predecessors(x).into_iter().filter(|&p| reachable[p]).collect())
+            .collect();
+        let count = graph.variables.len() + self.members.len();
+        let mut states = vec![vec![NullState::NotNull; count]; graph.blocks.len()];
+        states[ENTRY] = graph.variables.iter().map(|x| match x.kind() {
+            SyntaxKind::Parameter => self.declared_state(x),
+            _ => NullState::NotNull,
+        }).chain(self.members.iter().map(|x| self.member_state(x, function))).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 0..graph.blocks.len() {
+                if block == ENTRY || !reachable[block] {
+                    continue;
+                }
+                let mut state = vec![NullState::NotNull; count];
+                for &p in &predecessors[block] {
+                    let out = self.transfer(graph, p, &states[p]);
+                    for i in 0..count {
+                        state[i] = state[i].join(out[i]);
+                    }
+                }
+                if state != states[block] {
+                    states[block] = state;
+                    changed = true;
+                }
+            }
+        }
+        states
+    }
+
+    // ------------------------------------ warnings ------------------------------------------
+
+    // 值存进去的声明和它的类型
+    fn target(&self, value: &SyntaxRef) -> Option<(Target, Annotation)> {
+        let parent = value.parent()?;
+        match parent.kind() {
+            SyntaxKind::VariableDeclarator => {
+                let target = match parent.declaration() {
+                    Some(SemanticFlags::LocalVariableDeclarator) => Target::Local,
+                    Some(SemanticFlags::ConstantDeclarator) => return None,
+                    _ => Target::Member,
+                };
+                Some((target, self.declared_annotation(&parent)))
+            }
+            SyntaxKind::Parameter => Some((Target::Parameter, self.declared_annotation(&parent))),
+            SyntaxKind::AssignmentExpression => {
+                let left = strip(&parent.child_nodes().into_iter().next()?);
+                let name = match left.kind() {
+                    SyntaxKind::IdentifierName => left.clone(),
+                    SyntaxKind::MemberAccessExpression
+                        if left.child_nodes().first().is_some_and(|x| x.kind() == SyntaxKind::ThisExpression) =>
+                        left.child_nodes().pop()?,
+                    _ => return None,
+                };
+                if left.kind() == SyntaxKind::IdentifierName {
+                    if let Some(declaration) = self.local(&name) {
+                        return Some((Target::Local, self.declared_annotation(&declaration)));
+                    }
+                }
+                let member = self.member(&name.token_text(), &left)?;
+                Some((Target::Member, self.member_annotation(member)))
+            }
+            SyntaxKind::ReturnStatement | SyntaxKind::ArrowExpressionClause => {
+                let function = enclosing_function(value)?;
+                let is_iterator = function.traverse(true, false).any(|x| x.kind() == SyntaxKind::YieldReturnStatement
+                    && enclosing_function(&x).as_ref() == Some(&function));
+                if is_async(&function) || is_iterator {
+                    return None;
+                }
+                let syntax = function_return_type(&function)?;
+                Some((Target::Return, self.annotation(&syntax.text(), syntax.syntax())))
+            }
+            _ => None,
+        }
+    }
+
+    fn store(&mut self, graph: &ControlFlowGraph, value: &SyntaxRef, state: &[NullState]) {
+        let target = match self.target(value) {
+            Some((target, Annotation::NonNullable)) => target,
+            _ => return,
+        };
+        let is_null = is_null_literal(value);
+        if !is_null && self.null_state(graph, value, state) == NullState::NotNull {
+            return;
+        }
+        let code = match target {
+            Target::Local => "DY0902",
+            Target::Return => "DY0905",
+            Target::Member | Target::Parameter if is_null => "DY0903",
+            Target::Member => "DY0904",
+            Target::Parameter => return,
+        };
+        self.warning(value, Diagnostic::warning(code, value.span(), &[]));
+    }
+
+    fn function(&mut self, function: &SyntaxRef) {
+        let graph = ControlFlowGraph::build(function, self.scopes);
+        let reachable = graph.reachable();
+        self.members = Vec::new();
+        for node in function.traverse(true, false) {
+            match self.member_name(&node) {
+                Some(name) if !self.members.contains(&name) => self.members.push(name),
+                _ => {}
+            }
+        }
+        let states = self.states(&graph, function);
+        for (block, actions) in graph.blocks.iter().enumerate().filter(|x| reachable[x.0]) {
+            let mut state = states[block].clone();
+            for action in &actions.actions {
+                match *action {
+                    Action::Dereference(ref receiver)
+                        if self.null_state(&graph, receiver, &state) == NullState::MaybeNull => {
+                        let warning = Diagnostic::warning("DY0901", receiver.span(), &[])
+                            .with_note(Phrase::new("null-check", &[]));
+                        self.warning(receiver, warning);
+                    }
+                    Action::Store(ref value) => self.store(&graph, value, &state),
+                    _ => {}
+                }
+                self.apply(&graph, action, &mut state);
+            }
+        }
+        self.members.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+
+    fn analyze(code: &str, enabled: bool) -> Vec<String> {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        let result = analyze_nullable(&tree.root, &declarations, enabled);
+        result.warnings.iter().map(|x| format!("{}: {}", &code[x.span.start..x.span.end], x.message)).collect()
+    }
+
+    #[test]
+    fn test_null_checks() {
+        let code = "#nullable enable
+            class A { string? name; string title = \"\";
+                int F(string? s, string t, A? a) {
+                    int n = s.Length + t.Length;
+                    if (s != null) { n += s.Length; }
+                    if (a == null || a.name == null) { return 0; }
+                    n += a.name.Length;
+                    string? u = s?.Trim();
+                    if (u is not null && u.Length > 0) { n++; }
+                    if (!string.IsNullOrEmpty(u)) { n += u.Length; }
+                    if (s is string v) { n += v.Length + s.Length; }
+                    var w = s ?? \"\";
+                    n += w.Length + (s ?? t).Length;
+                    string? x = null;
+                    x ??= t;
+                    n += x.Length + name!.Length;
+                    while (u != null) { u = null; }
+                    return n + u.Length; } }";
+        assert_eq!(analyze(code, false), [
+            "s: dereference of a possibly null reference",
+            "u: dereference of a possibly null reference",
+        ]);
+        assert_eq!(analyze(&code.replace("#nullable enable", "#nullable disable"), false), Vec::<String>::new());
+        assert_eq!(analyze(&code.replace("#nullable enable", ""), true).len(), 2);
+    }
+
+    #[test]
+    fn test_member_states() {
+        let code = "#nullable enable
+            class A { string? maybe; string? Prop { get; set; } string sure = \"\";
+                string? Find() => null;
+                int F() {
+                    int n = maybe.Length;
+                    if (maybe == null) { return 0; }
+                    n += maybe.Length + this.maybe.Length;
+                    if (this.Prop is not null) { n += Prop.Length; }
+                    if (Prop is { } p) { n += Prop.Length + p.Length; }
+                    maybe = Find();
+                    n += this.maybe.Length;
+                    Prop = sure;
+                    return n + Prop.Length + sure.Length; }
+                int G() => maybe.Length; }";
+        assert_eq!(analyze(code, false), [
+            "maybe: dereference of a possibly null reference",
+            "this.maybe: dereference of a possibly null reference",
+            "maybe: dereference of a possibly null reference",
+        ]);
+    }
+
+    #[test]
+    fn test_null_assignments() {
+        let code = "#nullable enable
+            class A { string name = null; string? alias = null; int count;
+                string F(string s = null, A? other = null) {
+                    string t = null;
+                    string? u = other?.name;
+                    name = u;
+                    this.name = null;
+                    alias = u;
+                    if (other != null) { name = other.alias ?? \"\"; }
+                    t = u;
+                    return u; }
+                string? G() => null;
+                string H() => G();
+#nullable disable
+                string K() { string v = null; return v; } }";
+        assert_eq!(analyze(code, false), [
+            "null: cannot convert null literal to non-nullable reference type",
+            "null: cannot convert null literal to non-nullable reference type",
+            "null: converting null literal or possible null value to non-nullable type",
+            "u: possible null reference assignment",
+            "null: cannot convert null literal to non-nullable reference type",
+            "u: converting null literal or possible null value to non-nullable type",
+            "u: possible null reference return",
+            "G(): possible null reference return",
+        ]);
+    }
+}
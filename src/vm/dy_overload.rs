@@ -0,0 +1,577 @@
+use std::slice;
+
+use vm::dy_symbols::MemberSymbol;
+use vm::dy_syntax::{SyntaxKind, SyntaxRef};
+use vm::dy_types::{generic_name, type_arguments, substitute_type, uses_type_parameters, underlying_type, is_value_type,
+    delegate_signature, UserTypes};
+
+// ------------------------------------ overload resolution ------------------------------------
+// 按 C# 的规则从一组重载 (方法, 构造函数, 索引器, 运算符) 里选一个:
+// 先挑出可用的 (参数个数, 命名参数, 可选参数, params, ref/out, 泛型推断, 隐式转换), 再两两比较实参的转换
+
+/// What overload resolution needs to know about types.
+pub trait TypeContext: UserTypes {
+    /// The type `type_name` written in the declaration of `member` refers to; None if it depends on
+    /// type parameters of the containing type, such a parameter accepts any argument.
+    fn declared_type(&self, member: &MemberSymbol, type_name: &str) -> Option<String>;
+
+    /// Whether `value` of type `from` converts implicitly to `to`; the value lets constants such as `1`
+    /// convert to `byte`.
+    fn is_implicit(&self, value: Option<&SyntaxRef>, from: &str, to: &str) -> bool;
+
+    /// Parameter types and return type of a delegate type, None for other types.
+    fn delegate_signature(&self, type_name: &str) -> Option<(Vec<String>, String)> {
+        delegate_signature(type_name)
+    }
+
+    /// The return type `lambda` has when its parameters are `parameter_types`, `void` when it returns
+    /// nothing; None when the type of its body is not known.
+    fn lambda_return(&self, lambda: &SyntaxRef, parameter_types: &[String]) -> Option<String>;
+}
+
+#[derive(Debug, Clone)]
+pub struct CallArgument {
+    pub node: Option<SyntaxRef>,
+    pub name: Option<String>,
+    /// `ref`, `out`, `in` or empty.
+    pub ref_kind: String,
+    /// None when the type is not known, such an argument converts to anything.
+    pub type_name: Option<String>,
+}
+
+impl CallArgument {
+    pub fn new(node: &SyntaxRef, type_name: Option<String>) -> CallArgument {
+        CallArgument { node: Some(node.clone()), name: None, ref_kind: String::new(), type_name }
+    }
+
+    /// The lambda or anonymous method passed as this argument; its type comes from the parameter.
+    pub fn lambda(&self) -> Option<&SyntaxRef> {
+        self.node.as_ref().filter(|x| is_lambda(x))
+    }
+}
+
+pub fn is_lambda(node: &SyntaxRef) -> bool {
+    node.kind() == SyntaxKind::LambdaExpression || node.kind() == SyntaxKind::AnonymousMethodExpression
+}
+
+/// Parameters of a lambda, `x` in `x => x + 1` or `a` and `b` in `(int a, int b) => a + b`.
+pub fn lambda_parameters(lambda: &SyntaxRef) -> Vec<SyntaxRef> {
+    match lambda.child_of(SyntaxKind::ParameterList) {
+        Some(list) => list.children_of(SyntaxKind::Parameter),
+        None => lambda.children_of(SyntaxKind::Parameter),
+    }
+}
+
+// delegate { } 不写参数列表时可以转换成任何参数的委托
+fn has_parameter_list(lambda: &SyntaxRef) -> bool {
+    lambda.kind() == SyntaxKind::LambdaExpression || lambda.child_of(SyntaxKind::ParameterList).is_some()
+}
+
+/// A candidate in the form it applies to the arguments.
+#[derive(Debug, Clone)]
+pub struct Applicable<'a> {
+    pub member: &'a MemberSymbol,
+    /// For each argument, the index of its parameter.
+    pub parameters: Vec<usize>,
+    /// For each argument, the parameter type after substituting the type arguments.
+    pub parameter_types: Vec<Option<String>>,
+    /// Method type parameters with their inferred or given type arguments.
+    pub type_arguments: Vec<(String, String)>,
+    /// The `params` parameter takes the arguments one by one.
+    pub is_expanded: bool,
+    /// Optional parameters without an argument.
+    pub defaults: usize,
+}
+
+impl<'a> Applicable<'a> {
+    /// Return type with the type arguments substituted.
+    pub fn return_type<C: TypeContext>(&self, context: &C) -> Option<String> {
+        let type_name = self.member.type_name.as_ref()?;
+        context.declared_type(self.member, &substitute_type(type_name, &self.type_arguments))
+    }
+}
+
+/// Why a candidate does not apply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Count,
+    /// A named argument without a parameter of that name.
+    UnknownName(String),
+    /// A named argument for a parameter that already has an argument.
+    DuplicateName(String),
+    /// A required parameter left without an argument by named arguments.
+    Missing(String),
+    /// Argument index and the expected `ref`/`out`/`in` or empty.
+    RefKind(usize, String),
+    /// Argument index, argument type and parameter type.
+    Conversion(usize, String, String),
+    /// Type parameters whose type arguments could not be inferred.
+    Inference(Vec<String>),
+    /// Given type argument count.
+    TypeArgumentCount(usize),
+}
+
+pub enum Resolution<'a> {
+    Resolved(Applicable<'a>),
+    /// The best candidates, none better than the others.
+    Ambiguous(Vec<Applicable<'a>>),
+    NoMatch(Vec<(&'a MemberSymbol, Mismatch)>),
+}
+
+fn has_modifier(modifiers: &[String], modifier: &str) -> bool {
+    modifiers.iter().any(|x| x == modifier)
+}
+
+fn ref_kind(modifiers: &[String]) -> &'static str {
+    ["ref", "out", "in"].iter().find(|x| has_modifier(modifiers, x)).cloned().unwrap_or("")
+}
+
+/// `F(int, ref string, params object[])` for diagnostics.
+pub fn signature_text(member: &MemberSymbol) -> String {
+    let parameters: Vec<String> = member.parameters.iter().map(|x| {
+        let modifiers: Vec<&str> = x.modifiers.iter().map(|x| x.as_str()).filter(|x| *x != "this").collect();
+        if modifiers.is_empty() { x.type_name.clone() } else { format!("{} {}", modifiers.join(" "), x.type_name) }
+    }).collect();
+    let type_parameters = if member.type_parameters.is_empty() {
+        String::new()
+    }
+    else {
+        format!("<{}>", member.type_parameters.join(", "))
+    };
+    format!("{}{}({})", member.name, type_parameters, parameters.join(", "))
+}
+
+// ------------------------------------ inference ----------------------------------------------
+// 从实参类型推断泛型方法的类型实参: 形参类型里的类型参数和实参类型对应位置的类型
+
+fn collect_bounds<C: TypeContext>(context: &C, pattern: &str, actual: &str, type_parameters: &[String],
+    bounds: &mut Vec<(String, String)>) {
+    let (pattern, actual) = (pattern.trim(), actual.trim());
+    if type_parameters.iter().any(|x| x == pattern) {
+        if !bounds.iter().any(|x| x.0 == pattern && x.1 == actual) {
+            bounds.push((pattern.to_string(), actual.to_string()));
+        }
+        return;
+    }
+    if let (Some(pattern), Some(actual)) = (pattern.strip_suffix("[]"), actual.strip_suffix("[]")) {
+        return collect_bounds(context, pattern, actual, type_parameters, bounds);
+    }
+    if let Some(pattern) = pattern.strip_suffix('?') {
+        let actual = underlying_type(actual, context).unwrap_or(actual);
+        return collect_bounds(context, pattern, actual, type_parameters, bounds);
+    }
+    let (patterns, actuals) = (type_arguments(pattern), type_arguments(actual));
+    if !patterns.is_empty() && patterns.len() == actuals.len() && generic_name(pattern) == generic_name(actual) {
+        for (pattern, actual) in patterns.iter().zip(actuals.iter()) {
+            collect_bounds(context, pattern, actual, type_parameters, bounds);
+        }
+    }
+}
+
+// 每个类型参数从候选类型里取其他候选都能隐式转换过去的那个
+fn fix<C: TypeContext>(context: &C, type_parameters: &[String], bounds: &[(String, String)])
+    -> Result<Vec<(String, String)>, Mismatch> {
+    let mut fixed = Vec::new();
+    let mut failed = Vec::new();
+    for parameter in type_parameters {
+        let candidates: Vec<&String> = bounds.iter().filter(|x| x.0 == *parameter && x.1 != "null").map(|x| &x.1).collect();
+        let best = candidates.iter().find(|x| candidates.iter().all(|y| context.is_implicit(None, y, x)));
+        match best {
+            Some(best) => fixed.push((parameter.clone(), best.to_string())),
+            None => failed.push(parameter.clone()),
+        }
+    }
+    if failed.is_empty() { Ok(fixed) } else { Err(Mismatch::Inference(failed)) }
+}
+
+// 分几轮推断: 先用有类型的实参, 然后参数类型都已确定的 lambda 按推断出的返回类型给委托的返回类型加候选,
+// 直到没有新的 lambda 可用. 返回 lambda 的返回类型是否都知道
+fn infer_lambdas<C: TypeContext>(context: &C, member: &MemberSymbol, declared: &[String], arguments: &[CallArgument],
+    bounds: &mut Vec<(String, String)>) -> bool {
+    let type_parameters = &member.type_parameters;
+    let mut pending: Vec<usize> = (0..arguments.len()).filter(|&i| arguments[i].lambda().is_some()).collect();
+    let mut known = true;
+    loop {
+        let fixed: Vec<(String, String)> = type_parameters.iter()
+            .filter_map(|x| fix(context, slice::from_ref(x), bounds).ok())
+            .flatten()
+            .collect();
+        let ready: Vec<usize> = pending.iter().cloned().filter(|&i| {
+            context.delegate_signature(&declared[i]).is_none_or(|(inputs, _)| {
+                inputs.iter().all(|x| !uses_type_parameters(&substitute_type(x, &fixed), type_parameters))
+            })
+        }).collect();
+        if ready.is_empty() {
+            return known && pending.is_empty();
+        }
+        pending.retain(|x| !ready.contains(x));
+        for i in ready {
+            let (inputs, output) = match context.delegate_signature(&declared[i]) {
+                Some(x) => x,
+                None => continue,
+            };
+            let inputs: Vec<String> = inputs.iter().map(|x| substitute_type(x, &fixed)).collect();
+            match context.lambda_return(arguments[i].lambda().unwrap(), &inputs) {
+                Some(ref returns) if returns != "void" =>
+                    collect_bounds(context, &output, returns, type_parameters, bounds),
+                Some(_) => {}
+                None => known = false,
+            }
+        }
+    }
+}
+
+// lambda 能不能转换成 target: 参数个数要一样, 有返回值的委托要求返回类型能隐式转换
+fn lambda_converts<C: TypeContext>(context: &C, lambda: &SyntaxRef, target: &str) -> bool {
+    let (inputs, output) = match context.delegate_signature(target) {
+        Some(x) => x,
+        None => return !is_value_type(target, context) && target != "string",
+    };
+    if has_parameter_list(lambda) && lambda_parameters(lambda).len() != inputs.len() {
+        return false;
+    }
+    match context.lambda_return(lambda, &inputs) {
+        Some(ref returns) if output != "void" => returns != "void" && context.is_implicit(None, returns, &output),
+        _ => true,
+    }
+}
+
+// ------------------------------------ applicability ------------------------------------------
+
+fn applicable<'a, C: TypeContext>(context: &C, member: &'a MemberSymbol, arguments: &[CallArgument],
+    type_arguments: &[String], is_expanded: bool) -> Result<Applicable<'a>, Mismatch> {
+    let count = member.parameters.len();
+    let params = member.parameters.last().filter(|x| has_modifier(&x.modifiers, "params")).map(|_| count - 1);
+    if is_expanded && params.is_none() {
+        return Err(Mismatch::Count);
+    }
+    // 实参对应的形参: 位置实参按顺序, 命名实参按名字, 展开形式的多余实参都给 params 形参
+    let mut parameters = Vec::new();
+    for (i, argument) in arguments.iter().enumerate() {
+        let parameter = match argument.name {
+            Some(ref name) => match member.parameters.iter().position(|x| x.name == *name) {
+                Some(index) if parameters.contains(&index) => return Err(Mismatch::DuplicateName(name.clone())),
+                Some(index) => index,
+                None => return Err(Mismatch::UnknownName(name.clone())),
+            },
+            None => match params {
+                Some(params) if is_expanded && i >= params => params,
+                _ if i < count && !parameters.contains(&i) => i,
+                _ => return Err(Mismatch::Count),
+            },
+        };
+        parameters.push(parameter);
+    }
+    let mut defaults = 0;
+    for (index, parameter) in member.parameters.iter().enumerate() {
+        if parameters.contains(&index) || (is_expanded && Some(index) == params) {
+            continue;
+        }
+        if !parameter.has_default && arguments.iter().any(|x| x.name.is_some()) {
+            return Err(Mismatch::Missing(parameter.name.clone()));
+        }
+        if !parameter.has_default {
+            return Err(Mismatch::Count);
+        }
+        defaults += 1;
+    }
+    // 形参类型, 展开形式的 params 形参用元素类型
+    let declared: Vec<String> = parameters.iter().map(|&index| {
+        let type_name = &member.parameters[index].type_name;
+        match type_name.strip_suffix("[]") {
+            Some(element) if is_expanded && Some(index) == params => element.trim().to_string(),
+            _ => type_name.clone(),
+        }
+    }).collect();
+    let substitution = if !type_arguments.is_empty() {
+        if type_arguments.len() != member.type_parameters.len() {
+            return Err(Mismatch::TypeArgumentCount(type_arguments.len()));
+        }
+        member.type_parameters.iter().cloned().zip(type_arguments.iter().cloned()).collect()
+    }
+    else {
+        let mut bounds = Vec::new();
+        for (pattern, argument) in declared.iter().zip(arguments.iter()) {
+            if let Some(ref actual) = argument.type_name {
+                collect_bounds(context, pattern, actual, &member.type_parameters, &mut bounds);
+            }
+        }
+        let lambdas_known = infer_lambdas(context, member, &declared, arguments, &mut bounds);
+        // 实参类型不知道时推断不出来的类型参数不算错
+        let known = lambdas_known && arguments.iter().all(|x| x.type_name.is_some() || x.lambda().is_some());
+        match fix(context, &member.type_parameters, &bounds) {
+            Ok(fixed) => fixed,
+            Err(Mismatch::Inference(failed)) if known => return Err(Mismatch::Inference(failed)),
+            Err(_) => Vec::new(),
+        }
+    };
+    let mut parameter_types = Vec::new();
+    for (i, argument) in arguments.iter().enumerate() {
+        let parameter = &member.parameters[parameters[i]];
+        let expected = if is_expanded && Some(parameters[i]) == params { "" } else { ref_kind(&parameter.modifiers) };
+        let matches = argument.ref_kind == expected || (expected == "in" && argument.ref_kind.is_empty());
+        if !matches {
+            return Err(Mismatch::RefKind(i, expected.to_string()));
+        }
+        let parameter_type = if substitution.is_empty() && uses_type_parameters(&declared[i], &member.type_parameters) {
+            None
+        }
+        else {
+            context.declared_type(member, &substitute_type(&declared[i], &substitution))
+        };
+        if let (Some(lambda), Some(to)) = (argument.lambda(), parameter_type.as_ref()) {
+            if !lambda_converts(context, lambda, to) {
+                return Err(Mismatch::Conversion(i, "lambda expression".to_string(), to.clone()));
+            }
+        }
+        if let (Some(from), Some(to)) = (argument.type_name.as_ref(), parameter_type.as_ref()) {
+            // ref 和 out 要求类型完全一样
+            let converts = if argument.ref_kind.is_empty() || argument.ref_kind == "in" {
+                context.is_implicit(argument.node.as_ref(), from, to)
+            }
+            else {
+                from == to
+            };
+            if !converts {
+                return Err(Mismatch::Conversion(i, from.clone(), to.clone()));
+            }
+        }
+        parameter_types.push(parameter_type);
+    }
+    Ok(Applicable { member, parameters, parameter_types, type_arguments: substitution, is_expanded, defaults })
+}
+
+// ------------------------------------ betterness ---------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Better {
+    First,
+    Second,
+    Neither,
+}
+
+fn is_signed_better(first: &str, second: &str) -> bool {
+    match first {
+        "sbyte" => ["byte", "ushort", "uint", "ulong"].contains(&second),
+        "short" => ["ushort", "uint", "ulong"].contains(&second),
+        "int" => ["uint", "ulong"].contains(&second),
+        "long" => second == "ulong",
+        _ => false,
+    }
+}
+
+// 实参从 argument 转换到 first 和 second 哪个更好: 恒等转换最好, 其次是更具体的目标类型
+fn better_conversion<C: TypeContext>(context: &C, argument: &CallArgument, first: &Option<String>, second: &Option<String>)
+    -> Better {
+    let (argument, first, second) = match (argument.type_name.as_ref(), first.as_ref(), second.as_ref()) {
+        (Some(argument), Some(first), Some(second)) if first != second => (argument, first, second),
+        _ => return Better::Neither,
+    };
+    if argument == first {
+        return Better::First;
+    }
+    if argument == second {
+        return Better::Second;
+    }
+    let (first_to_second, second_to_first) = (context.is_implicit(None, first, second), context.is_implicit(None, second, first));
+    if first_to_second && !second_to_first {
+        return Better::First;
+    }
+    if second_to_first && !first_to_second {
+        return Better::Second;
+    }
+    if is_signed_better(first, second) {
+        return Better::First;
+    }
+    if is_signed_better(second, first) {
+        return Better::Second;
+    }
+    Better::Neither
+}
+
+fn is_better<C: TypeContext>(context: &C, first: &Applicable, second: &Applicable, arguments: &[CallArgument]) -> bool {
+    let mut is_better = false;
+    for (i, argument) in arguments.iter().enumerate() {
+        match better_conversion(context, argument, &first.parameter_types[i], &second.parameter_types[i]) {
+            Better::First => is_better = true,
+            Better::Second => return false,
+            Better::Neither => {}
+        }
+    }
+    if is_better {
+        return true;
+    }
+    // 形参类型一样时: 非泛型优先, 不展开 params 优先, 不用默认值优先
+    if first.parameter_types != second.parameter_types {
+        return false;
+    }
+    let (first_generic, second_generic) = (!first.member.type_parameters.is_empty(), !second.member.type_parameters.is_empty());
+    if first_generic != second_generic {
+        return !first_generic;
+    }
+    if first.is_expanded != second.is_expanded {
+        return !first.is_expanded;
+    }
+    first.defaults == 0 && second.defaults > 0
+}
+
+/// Picks the overload of `candidates` that best matches `arguments`; `type_arguments` are the explicit
+/// type arguments of a generic call.
+pub fn resolve<'a, C: TypeContext>(context: &C, candidates: &[&'a MemberSymbol], arguments: &[CallArgument],
+    type_arguments: &[String]) -> Resolution<'a> {
+    let mut found = Vec::new();
+    let mut mismatches = Vec::new();
+    for &member in candidates {
+        // 普通形式不行才用展开形式
+        match applicable(context, member, arguments, type_arguments, false) {
+            Ok(x) => found.push(x),
+            Err(mismatch) => match applicable(context, member, arguments, type_arguments, true) {
+                Ok(x) => found.push(x),
+                // 展开形式的个数对得上时报它的错
+                Err(Mismatch::Count) => mismatches.push((member, mismatch)),
+                Err(expanded) => mismatches.push((member, expanded)),
+            },
+        }
+    }
+    if found.is_empty() {
+        return Resolution::NoMatch(mismatches);
+    }
+    let best: Vec<usize> = (0..found.len())
+        .filter(|&i| (0..found.len()).all(|j| i == j || is_better(context, &found[i], &found[j], arguments)))
+        .collect();
+    if best.len() == 1 {
+        return Resolution::Resolved(found.swap_remove(best[0]));
+    }
+    // 没有一个比其他都好: 留下没有被别的候选比下去的
+    let undominated: Vec<Applicable> = (0..found.len())
+        .filter(|&i| !(0..found.len()).any(|j| i != j && is_better(context, &found[j], &found[i], arguments)))
+        .map(|i| found[i].clone())
+        .collect();
+    Resolution::Ambiguous(undominated)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+    use vm::dy_symbols::Declarations;
+    use vm::dy_types::classify_conversion;
+
+    struct Predefined;
+
+    impl UserTypes for Predefined {
+        fn is_user_value_type(&self, _: &str) -> bool {
+            false
+        }
+    }
+
+    impl TypeContext for Predefined {
+        fn declared_type(&self, _: &MemberSymbol, type_name: &str) -> Option<String> {
+            Some(type_name.to_string())
+        }
+
+        fn is_implicit(&self, _: Option<&SyntaxRef>, from: &str, to: &str) -> bool {
+            to == "object" || classify_conversion(from, to, self).is_implicit()
+        }
+
+        fn lambda_return(&self, _: &SyntaxRef, _: &[String]) -> Option<String> {
+            None
+        }
+    }
+
+    fn declarations(code: &str) -> Declarations {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        let mut declarations = Declarations::new();
+        declarations.add(&tree.compilation_unit());
+        declarations
+    }
+
+    fn argument(type_name: &str) -> CallArgument {
+        CallArgument { node: None, name: None, ref_kind: String::new(), type_name: Some(type_name.to_string()) }
+    }
+
+    fn resolve_call(declarations: &Declarations, name: &str, arguments: &[CallArgument]) -> String {
+        let candidates = declarations.types[0].members_named(name);
+        match resolve(&Predefined, &candidates, arguments, &[]) {
+            Resolution::Resolved(x) => signature_text(x.member),
+            Resolution::Ambiguous(x) => format!("ambiguous {}", x.iter().map(|x| signature_text(x.member))
+                .collect::<Vec<_>>().join(", ")),
+            Resolution::NoMatch(x) => format!("no match {:?}", x.iter().map(|x| x.1.clone()).collect::<Vec<_>>()),
+        }
+    }
+
+    #[test]
+    fn test_betterness() {
+        let declarations = declarations("class Host { void Log(string s) { } void Log(string s, object o) { } \
+            void Log(object o) { } void Num(long x) { } void Num(double x) { } void Num(uint x) { } \
+            void Pair(int a, long b) { } void Pair(long a, int b) { } void Id<T>(T x) { } void Id(int x) { } \
+            void Opt(int a, int b = 0) { } void Opt(int a) { } void Many(params int[] xs) { } void Many(int a, int b) { } }");
+        let call = |name, arguments: &[&str]| {
+            let arguments: Vec<CallArgument> = arguments.iter().map(|x| argument(x)).collect();
+            resolve_call(&declarations, name, &arguments)
+        };
+        assert_eq!(call("Log", &["string"]), "Log(string)");
+        assert_eq!(call("Log", &["int"]), "Log(object)");
+        assert_eq!(call("Log", &["null"]), "Log(string)");
+        assert_eq!(call("Log", &["string", "int"]), "Log(string, object)");
+        // int 到 long 比到 double 好, 有符号的比无符号的好
+        assert_eq!(call("Num", &["int"]), "Num(long)");
+        assert_eq!(call("Num", &["float"]), "Num(double)");
+        assert_eq!(call("Num", &["ushort"]), "Num(uint)");
+        assert_eq!(call("Pair", &["int", "int"]), "ambiguous Pair(int, long), Pair(long, int)");
+        assert_eq!(call("Id", &["int"]), "Id(int)");
+        assert_eq!(call("Id", &["string"]), "Id<T>(T)");
+        assert_eq!(call("Opt", &["int"]), "Opt(int)");
+        assert_eq!(call("Many", &["int", "int"]), "Many(int, int)");
+        assert_eq!(call("Many", &["int", "int", "int"]), "Many(params int[])");
+        assert_eq!(call("Many", &[]), "Many(params int[])");
+        assert_eq!(call("Many", &["int[]"]), "Many(params int[])");
+        assert_eq!(call("Many", &["string"]), "no match [Conversion(0, \"string\", \"int\"), Count]");
+    }
+
+    #[test]
+    fn test_arguments() {
+        let declarations = declarations("class Host { void Named(int a, string b = \"\", bool c = false) { } \
+            void Ref(ref int a, out string b) { } void First<T>(T[] items, T other) { } \
+            void Map<K, V>(Dictionary<K, V> map, K key) { } }");
+        let named = |name: &str, type_name: &str| CallArgument { name: Some(name.to_string()), ..argument(type_name) };
+        let passed = |kind: &str, type_name: &str| CallArgument { ref_kind: kind.to_string(), ..argument(type_name) };
+        assert_eq!(resolve_call(&declarations, "Named", &[argument("int"), named("c", "bool")]), "Named(int, string, bool)");
+        assert_eq!(resolve_call(&declarations, "Named", &[named("c", "bool"), named("a", "int")]), "Named(int, string, bool)");
+        assert_eq!(resolve_call(&declarations, "Named", &[argument("int"), named("d", "bool")]),
+            "no match [UnknownName(\"d\")]");
+        assert_eq!(resolve_call(&declarations, "Named", &[argument("int"), named("a", "int")]),
+            "no match [DuplicateName(\"a\")]");
+        assert_eq!(resolve_call(&declarations, "Named", &[named("b", "string")]), "no match [Missing(\"a\")]");
+        assert_eq!(resolve_call(&declarations, "Ref", &[passed("ref", "int"), passed("out", "string")]),
+            "Ref(ref int, out string)");
+        assert_eq!(resolve_call(&declarations, "Ref", &[argument("int"), passed("out", "string")]),
+            "no match [RefKind(0, \"ref\")]");
+        assert_eq!(resolve_call(&declarations, "Ref", &[passed("ref", "short"), passed("out", "string")]),
+            "no match [Conversion(0, \"short\", \"int\")]");
+
+        let candidates = declarations.types[0].members_named("First");
+        let arguments = [argument("int[]"), argument("short")];
+        match resolve(&Predefined, &candidates, &arguments, &[]) {
+            Resolution::Resolved(x) => assert_eq!(x.type_arguments, [("T".to_string(), "int".to_string())]),
+            _ => panic!(),
+        }
+        assert_eq!(resolve_call(&declarations, "First", &[argument("int[]"), argument("string")]),
+            "no match [Inference([\"T\"])]");
+        let candidates = declarations.types[0].members_named("Map");
+        let arguments = [argument("Dictionary<string, int>"), argument("string")];
+        match resolve(&Predefined, &candidates, &arguments, &[]) {
+            Resolution::Resolved(x) => assert_eq!(x.parameter_types, [Some("Dictionary<string, int>".to_string()),
+                Some("string".to_string())]),
+            _ => panic!(),
+        }
+        match resolve(&Predefined, &candidates, &arguments, &["string".to_string()]) {
+            Resolution::NoMatch(x) => assert_eq!(x[0].1, Mismatch::TypeArgumentCount(1)),
+            _ => panic!(),
+        }
+    }
+}
@@ -1,595 +1,523 @@
-use std::rc::Weak;
-use std::rc::Rc;
-use std::cell::RefCell;
 use std::iter::FromIterator;
 use vm::dy_util::VecExtend;
 use vm::dy_common::DyRef;
+use vm::dy_grammar::Grammar;
+use vm::dy_syntax::SyntaxTree;
 
 // https://docs.microsoft.com/en-us/dotnet/csharp/language-reference/keywords/index
-const KEYWORDS: [&'static str; 78] = ["abstract", "as", "base", "bool", "break", "byte", "case", "catch",
-    "char", "checked", "class", "const", "contine", "decimal", "default", "delegate", "do", "double", "else", "enum",
+pub const KEYWORDS: [&'static str; 78] = ["abstract", "as", "base", "bool", "break", "byte", "case", "catch",
+    "char", "checked", "class", "const", "continue", "decimal", "default", "delegate", "do", "double", "else", "enum",
     "event", "explicit", "extern", "false", "finally", "fixed", "float", "for", "foreach", "goto", "if", "implicit",
-    "in", "int", "interface", "internal", "is", "lock", "long", "namepsace", "new", "null", "object", "operator",
+    "in", "int", "interface", "internal", "is", "lock", "long", "namespace", "new", "null", "object", "operator",
     "out", "override", "params", "private", "protected", "public", "readonly", "ref", "return", "sbyte", "sealed",
     "short", "sizeof", "stackalloc", "static", "string", "struct", "switch", "this", "throw", "true", "try", "typeof", "uint",
     "ulong", "unchecked", "unsafe", "ushort", "using static", "using", "virtual", "void", "volatile", "while",];
 
 // contextual keywords
-const CONTEXTUALS: [&'static str; 28] = ["add", "alias", "ascending", "async", "await", "by", "desending", "dynamic", "equals", "from", "get", "global", "group",
+pub const CONTEXTUALS: [&'static str; 28] = ["add", "alias", "ascending", "async", "await", "by", "descending", "dynamic", "equals", "from", "get", "global", "group",
     "into", "join", "let", "nameof", "on", "orderby", "partial", "remove", "select", "set", "value", "var", "when", "where",
     "yield", ];
 
 
-const OPERATORS: [&'static str; 44] = ["++", "--", "->", "+", "-", "!", "~", "++", "--", "&", "*", "/", "%", "+", "-", "<<", ">>", "<", ">",
+const OPERATORS: [&'static str; 45] = ["++", "--", "->", "+", "-", "!", "~", "++", "--", "&", "*", "/", "%", "+", "-", "<<", ">>", "<", ">",
 		"<=", ">=", "==", "!=", "&", "^", "|", "&&", "||", "??", "?", "::", ":",
-		"=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=", "=>"];
+		"=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=", "??=", "=>"];
 
-const BUILTIN_TYPES: [&'static str; 16] = ["bool", "byte", "char", "decimal", "double", "float", "int", "long", "object", "sbyte", "short",
+pub const BUILTIN_TYPES: [&'static str; 16] = ["bool", "byte", "char", "decimal", "double", "float", "int", "long", "object", "sbyte", "short",
 		"string", "uint", "ulong", "ushort", "void"];
 
-const PREPROCESSOR: [&'static str; 12] = ["define", "elif", "else", "endif", "endregion", "error", "if", "line", "pragma", "region", "undef", "warning"];
+const PREPROCESSOR: [&'static str; 13] = ["define", "elif", "else", "endif", "endregion", "error", "if", "line", "nullable", "pragma", "region", "undef", "warning"];
 
 
-const PUNCTUATORS: [&'static str; 45] = [">>=", "<<=", "=>", "^=", "|=", "&=", "%=", "/=", "*=", "+=", "-=",
+// 最长匹配优先, 所以多字符的要排在前面
+// ">>" 和 ">>=" 在关闭泛型参数时由 parser 拆开
+const PUNCTUATORS: [&'static str; 52] = [">>=", "<<=", "??=", "=>", "^=", "|=", "&=", "%=", "/=", "*=", "+=", "-=",
     ">=", "<=", "==", "!=", ">>", "<<", "||", "&&", "--", "++", "?:", "??", "?.", "?[", "::", "->", ">", "<",
-    "=", "~", "!", "^", "|", "&", "%", "*", "/", "+", "-", ".", "(", ")", "[", "]"];
+    "=", "~", "!", "^", "|", "&", "%", "*", "/", "+", "-", ".", "(", ")", "[", "]",
+    "{", "}", ";", ",", ":", "?"];
 
 
 // https://docs.microsoft.com/en-us/dotnet/csharp/language-reference/language-specification/lexical-structure
 
-#[derive(Debug, Clone, PartialEq)]
+/// A half-open `[start, end)` range of char offsets into the source.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
 }
 
 impl Span {
-    fn new(start: usize, end: usize) -> Span {
+    pub fn new(start: usize, end: usize) -> Span {
         Span { start, end, }
     }
-}
-
-
-trait Scanner {
-    fn scan(dy_parser: &DyParser) -> Option<Token>;
-}
 
-
-#[derive(Debug)]
-pub struct Whitespace {
-    pub span: Span,
-}
-
-impl Whitespace {
-    fn new (start: usize, end: usize) -> Whitespace {
-        Whitespace { span: Span::new(start, end),}
+    pub fn len(&self) -> usize {
+        self.end - self.start
     }
-}
 
-impl Scanner for Whitespace {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        let (mut start_at, end_at) = dy_parser.cursor_span();
-        let begin = start_at;
-        while start_at <= end_at {
-            let ch = dy_parser.source[start_at];
-            if ch == '\t' || ch == ' ' || ch == '\r' || ch == '\n' {
-                start_at += 1;
-            }
-            else {
-                break;
-            }
-        }
-        if start_at == begin {
-            return None;
-        }
-        return Some(Token::Whitespace(Whitespace::new(begin, start_at-1 as usize)));
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
     }
 
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
 }
 
 
 #[derive(Debug)]
-pub enum CommentKind {
-    Single,
-    Multiple,
+pub struct DyParser {
+    source: Vec<char>,
+    tokens: Vec<SyntaxToken>,
+    formated_lines: Vec<FormatedLine>,
+    root_region: RegionRef,
+    line_offset: Vec<usize>,
+    defines: Vec<String>,
 }
 
-#[derive(Debug)]
-pub struct Comment {
-    pub kind: CommentKind,
-    pub span: Span,
+
+// #if/#elif/#else 分组, 以及 #region
+struct RegionFrame {
+    region: RegionRef,
+    parent_active: bool,
+    // 当前分支是否生效
+    taken: bool,
+    // 分组里是否已经有分支生效
+    done: bool,
 }
 
-impl Comment {
-    fn new(kind: CommentKind, start: usize, end: usize) -> Comment {
-        Comment {
-            kind,
-            span: Span::new(start, end),
+
+impl DyParser {
+
+    pub fn new(code : String) -> DyParser  {
+        let source = code.chars().collect();
+        DyParser {
+            source,
+            formated_lines: Vec::new(),
+            root_region: RegionRef::default(),
+            tokens: Vec::new(),
+            line_offset: Vec::new(),
+            defines: Vec::new(),
         }
     }
-}
 
-impl Scanner for Comment {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        if dy_parser.look_ahead("//") {
-            let begin = dy_parser.cursor;
-            let line_ending = dy_parser.find_line_ending(dy_parser.cursor+2);
-            // dy_parser.cursor = line_ending + 1;
-            return Some(Token::Comment(Comment::new(CommentKind::Single, begin, line_ending)));
-        }
-        else if dy_parser.look_ahead("/*") {
-            let begin = dy_parser.cursor;
-            let comment_ending = dy_parser.find_next("*/", begin+2);
-            if comment_ending != 0 {
-                return Some(Token::Comment(Comment::new(CommentKind::Multiple, begin, comment_ending)));
-            }
-            // missing
+    // 预处理符号, 需要在 lexer 之前定义
+    pub fn define(&mut self, symbol: &str) {
+        if !self.defines.iter().any(|x| x == symbol) {
+            self.defines.push(symbol.to_string());
         }
-        return None
     }
 
-}
-
-
-#[derive(Debug)]
-pub enum StringKind {
-    Normal,
-    Verbatim,
-    Interpolated,
-}
+    pub fn lexer(&mut self) {
+        self.tokens.clear();
+        self.formated_lines.clear();
+        self.line_offset.clear();
+        self.root_region = RegionRef::default();
 
-#[derive(Debug)]
-pub struct StringLiteral {
-    pub kind: StringKind,
-    pub span: Span,
-}
+        let len = self.source.len();
+        let mut begin = 0;
+        let mut block_state = BlockState::None;
+        let mut frames: Vec<RegionFrame> = Vec::new();
+        let mut defines = self.defines.clone();
+        loop {
+            let mut end = begin;
+            while end < len && self.source[end] != '\n' && self.source[end] != '\r' {
+                end += 1;
+            }
+            self.line_offset.push(begin);
+            let index = self.formated_lines.len();
+            let mut formated_line = FormatedLine::new(index, begin, if end > begin { end - 1 } else { begin });
+            formated_line.block_state = block_state;
+            if end > begin {
+                block_state = self.scan_line(&mut formated_line, block_state, &mut frames, &mut defines);
+            }
+            if end < len {
+                let line_ending = if self.source[end] == '\r' && end + 1 < len && self.source[end+1] == '\n' {
+                    end + 1
+                }
+                else {
+                    end
+                };
+                formated_line.push_token(SyntaxToken::new(TokenKind::EndOfLine, end, line_ending));
+                begin = line_ending + 1;
+                self.formated_lines.push(formated_line);
+            }
+            else {
+                self.formated_lines.push(formated_line);
+                break;
+            }
+        }
 
-impl StringLiteral {
-    fn new(kind: StringKind, start: usize, end: usize) -> StringLiteral {
-        StringLiteral {
-            kind,
-            span: Span::new(start, end),
+        for formated_line in self.formated_lines.iter() {
+            for token in formated_line.tokens.iter() {
+                self.tokens.push(token.clone());
+            }
         }
     }
-}
-
-
-impl Scanner for StringLiteral {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        if dy_parser.look_ahead("@\"") {
 
+    pub fn parse(&mut self) -> SyntaxTree {
+        if self.line_offset.is_empty() {
+            self.lexer();
         }
-        else if dy_parser.look_ahead("$\"") {
+        Grammar::new(self).parse()
+    }
 
-        }
-        else if dy_parser.look_ahead("\"") {
-            let begin = dy_parser.cursor;
-            let mut next_quote = dy_parser.find_next("\"", begin+1);
-            while next_quote != 0 {
-                if dy_parser.source[next_quote-1] != '\\' {
-                    return Some(Token::StringLiteral(StringLiteral::new(StringKind::Normal, begin, next_quote)));
-                }
-                else {
-                    next_quote = dy_parser.find_next("\"", next_quote+1);
-                }
-            }
+    pub fn source(&self) -> &[char] {
+        &self.source
+    }
 
-        }
-        return None
+    pub fn tokens(&self) -> &Vec<SyntaxToken> {
+        &self.tokens
     }
 
-}
+    pub fn root_region(&self) -> RegionRef {
+        self.root_region.clone()
+    }
 
+    pub fn line_offsets(&self) -> &Vec<usize> {
+        &self.line_offset
+    }
 
-#[derive(Debug)]
-pub struct IntegerLiteral {
-    pub span: Span,
-}
+    pub fn token_text(&self, token: &SyntaxToken) -> String {
+        String::from_iter(&self.source[token.begin_at..token.end_at+1])
+    }
 
-impl IntegerLiteral {
-    fn new(start: usize, end: usize) -> IntegerLiteral {
-        IntegerLiteral {
-            span: Span::new(start, end),
-        }
+    // 0 开始的行号和列号
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        line_column(&self.line_offset, offset)
     }
-}
 
-impl Scanner for IntegerLiteral {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        let begin = dy_parser.cursor;
-        let len = dy_parser.source.len();
-        let mut radix = 10;
-        let mut start_at = begin;
-        if dy_parser.look_ahead("0x") || dy_parser.look_ahead("0X") {
-            start_at = begin + 2;
-            if start_at >= len {
-                return None;
+    fn scan_line(&self, formated_line: &mut FormatedLine, block_state: BlockState, frames: &mut Vec<RegionFrame>,
+                 defines: &mut Vec<String>) -> BlockState {
+        let end_at = formated_line.end_at;
+        let mut start_at = formated_line.begin_at;
+
+        // 上一行没结束的多行注释或者 verbatim 字符串
+        if block_state == BlockState::Comment {
+            let ending = self.find_next("*/", start_at);
+            if ending == 0 || ending > end_at {
+                let mut token = SyntaxToken::new(TokenKind::Comment, start_at, end_at);
+                token.block_state = BlockState::Comment;
+                formated_line.push_token(token);
+                return BlockState::Comment;
             }
-            radix = 16;
-        }
-        let mut ch = dy_parser.source[start_at];
-        let mut has_integer = false;
-        while ch.is_digit(radix) {
-            has_integer = true;
-            start_at += 1;
-            if start_at >= len {
-                break;
+            formated_line.push_token(SyntaxToken::new(TokenKind::Comment, start_at, ending));
+            start_at = ending + 1;
+        }
+        else if block_state == BlockState::String {
+            let (ending, closed) = self.scan_verbatim_rest(start_at, end_at);
+            let mut token = SyntaxToken::new(TokenKind::StringLiteral, start_at, ending);
+            if !closed {
+                token.block_state = BlockState::String;
+                formated_line.push_token(token);
+                return BlockState::String;
             }
-            ch = dy_parser.source[start_at];
+            formated_line.push_token(token);
+            start_at = ending + 1;
         }
-        if has_integer {
-            if dy_parser.find_next("ul", start_at) != 0 || dy_parser.find_next("Ul", start_at) !=0
-                || dy_parser.find_next("UL", start_at) != 0 || dy_parser.find_next("uL", start_at) != 0 {
-                start_at += 2;
+
+        let active = frames.last().is_none_or(|x| x.parent_active && x.taken);
+        if block_state == BlockState::None {
+            let mut peek = start_at;
+            while peek <= end_at && (self.source[peek] == ' ' || self.source[peek] == '\t') {
+                peek += 1;
             }
-            else if dy_parser.find_next("u", start_at) != 0 || dy_parser.find_next("l", start_at) !=0
-                || dy_parser.find_next("L", start_at) != 0 || dy_parser.find_next("L", start_at) != 0 {
-                start_at += 1;
+            if peek <= end_at && self.source[peek] == '#' {
+                self.push_whitespace(formated_line, &mut start_at);
+                self.scan_preprocessor(formated_line, &mut start_at, frames, defines, active);
+                return BlockState::None;
             }
-            if has_integer && !dy_parser.is_alphanumeric_at(start_at) {
-                return Some(Token::IntegerLiteral(IntegerLiteral::new(begin, start_at-1)));
+            if !active {
+                formated_line.push_token(SyntaxToken::new(TokenKind::InactiveCode, start_at, end_at));
+                return BlockState::None;
             }
         }
-        return None;
-    }
-}
-
-
-#[derive(Debug)]
-pub struct RealLiteral {
-    pub span: Span,
-}
-
-impl RealLiteral {
-    fn new(start: usize, end: usize) -> RealLiteral {
-        RealLiteral {
-            span: Span::new(start, end),
-        }
-    }
-}
 
-impl Scanner for RealLiteral {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        let begin = dy_parser.cursor;
-        let mut ch = dy_parser.source[begin];
-        let mut start_at = begin;
-        let mut is_number = false;
-        let mut is_real = false;
-        let len = dy_parser.source.len();
-        while ch.is_digit(10) {
-            start_at += 1;
-            if start_at >= len {
-                return None;
+        while start_at <= end_at {
+            let ch = self.source[start_at];
+            if ch == ' ' || ch == '\t' {
+                self.push_whitespace(formated_line, &mut start_at);
+                continue;
             }
-            ch = dy_parser.source[start_at];
-            is_number = true;
-        }
-        if ch == '.' {
-            is_real = true;
-            is_number = false;
-            start_at += 1;
-            if start_at >= len {
-                return None;
+            if self.source_equal(start_at, end_at, "//") {
+                formated_line.push_token(SyntaxToken::new(TokenKind::Comment, start_at, end_at));
+                break;
             }
-            ch = dy_parser.source[start_at];
-            while ch.is_digit(10) {
-                is_number = true;
-                start_at += 1;
-                if start_at >= len {
-                    break;
+            if self.source_equal(start_at, end_at, "/*") {
+                let ending = self.find_next("*/", start_at + 2);
+                if ending == 0 || ending > end_at {
+                    let mut token = SyntaxToken::new(TokenKind::Comment, start_at, end_at);
+                    token.block_state = BlockState::Comment;
+                    formated_line.push_token(token);
+                    return BlockState::Comment;
                 }
-                ch = dy_parser.source[start_at];
+                formated_line.push_token(SyntaxToken::new(TokenKind::Comment, start_at, ending));
+                start_at = ending + 1;
+                continue;
             }
-            if !is_number {
-                return None;
+            if self.source_equal(start_at, end_at, "@\"") || self.source_equal(start_at, end_at, "$@\"")
+                || self.source_equal(start_at, end_at, "@$\"") {
+                let begin = start_at;
+                let quote = if ch == '@' && self.source[start_at+1] == '"' { start_at + 1 } else { start_at + 2 };
+                let (ending, closed) = self.scan_verbatim_rest(quote + 1, end_at);
+                let mut token = SyntaxToken::new(TokenKind::StringLiteral, begin, ending);
+                if !closed {
+                    token.block_state = BlockState::String;
+                    formated_line.push_token(token);
+                    return BlockState::String;
+                }
+                formated_line.push_token(token);
+                start_at = ending + 1;
+                continue;
             }
-        }
-        if is_number && (ch == 'E' || ch == 'e') {
-            is_real = false;
-            start_at += 1;
-            if start_at > len {
-                return None
+            if self.source_equal(start_at, end_at, "$\"") {
+                let token = self.scan_interpolated_string(&mut start_at, end_at);
+                formated_line.push_token(token);
+                continue;
             }
-            ch = dy_parser.source[start_at];
-            if ch == '+' || ch == '-' {
-                start_at += 1;
-                if start_at > len {
-                    return None
-                }
-                ch = dy_parser.source[start_at];
+            let token = if ch == '"' {
+                self.scan_string_literal(&mut start_at, end_at)
             }
-            while ch.is_digit(10) {
-                is_real = true;
-                start_at += 1;
-                ch = dy_parser.source[start_at];
+            else if ch == '\'' {
+                self.scan_char_literal(&mut start_at, end_at)
             }
-            if !is_real {
-                return None;
+            else if ch.is_ascii_digit() || (ch == '.' && start_at < end_at && self.source[start_at+1].is_ascii_digit()) {
+                self.scan_number_literal(&mut start_at, end_at)
+            }
+            else if ch.is_alphabetic() || ch == '_' || ch == '@' || ch == '\\' {
+                self.scan_identifier_or_keyword(&mut start_at, end_at).map(|mut x| {
+                    if x.kind == TokenKind::Keyword {
+                        if self.source_equal(x.begin_at, x.end_at, "true") && x.end_at - x.begin_at == 3
+                            || self.source_equal(x.begin_at, x.end_at, "false") && x.end_at - x.begin_at == 4 {
+                            x.kind = TokenKind::BoolLiteral;
+                        }
+                        else if self.source_equal(x.begin_at, x.end_at, "null") && x.end_at - x.begin_at == 3 {
+                            x.kind = TokenKind::NullLiteral;
+                        }
+                    }
+                    x
+                })
+            }
+            else {
+                self.scan_punctuator(&mut start_at, end_at)
+            };
+            match token {
+                Some(x) => {
+                    formated_line.push_token(x);
+                }
+                None => {
+                    formated_line.push_token(SyntaxToken::new(TokenKind::Unknown, start_at, start_at));
+                    start_at += 1;
+                }
             }
         }
-        if is_number && (ch == 'F' || ch == 'f' || ch == 'm' || ch == 'M') {
-            start_at += 1;
-            is_real = true;
-        }
-        if is_number && is_real && !dy_parser.is_alphanumeric_at(start_at){
-            return Some(Token::RealLiteral(RealLiteral::new(begin, start_at-1)));
-        }
-        return None;
+        return BlockState::None;
     }
-}
-
 
-#[derive(Debug)]
-pub struct CharLiteral {
-    pub span: Span,
-}
-
-impl CharLiteral {
-    fn new(start: usize, end: usize) -> CharLiteral {
-        return CharLiteral {
-            span: Span::new(start, end),
+    fn scan_preprocessor(&self, formated_line: &mut FormatedLine, start_at: &mut usize, frames: &mut Vec<RegionFrame>,
+                         defines: &mut Vec<String>, active: bool) {
+        let end_at = formated_line.end_at;
+        let begin = *start_at;
+        *start_at += 1;
+        while *start_at <= end_at && (self.source[*start_at] == ' ' || self.source[*start_at] == '\t') {
+            *start_at += 1;
         }
-    }
-}
-
-
-impl Scanner for CharLiteral {
-    // https://docs.microsoft.com/en-us/dotnet/csharp/language-reference/keywords/char
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        if dy_parser.look_ahead("\'") {
-            let begin = dy_parser.cursor;
-            let end = dy_parser.find_next("\'", begin+1);
-            // Character literal
-            if end == begin + 2 {
-                let ch = dy_parser.source[begin+1];
-                if ch == '\\' {
+        let word_begin = *start_at;
+        let word = self.scan_identifier_or_keyword_raw(start_at, end_at);
+        let directive = match word {
+            Some(_) => self.get_string(word_begin, *start_at - 1).unwrap_or_default(),
+            None => String::new(),
+        };
+        if directive.is_empty() {
+            formated_line.push_token(SyntaxToken::new(TokenKind::PreprocessorDirectiveExpected, begin, *start_at - 1));
+            self.push_preprocessor_rest(formated_line, start_at, TokenKind::PreprocessorCommentExpected);
+            return;
+        }
+        if !PREPROCESSOR.contains(&directive.as_str()) {
+            formated_line.push_token(SyntaxToken::new(TokenKind::PreprocessorUnexpectedDirective, begin, *start_at - 1));
+            self.push_preprocessor_rest(formated_line, start_at, TokenKind::PreprocessorCommentExpected);
+            return;
+        }
+        formated_line.push_token(SyntaxToken::new(TokenKind::Preprocessor, begin, *start_at - 1));
+        self.push_whitespace(formated_line, start_at);
 
+        let line_index = formated_line.index;
+        match directive.as_str() {
+            "if" => {
+                let result = self.parse_pp_or_expression(formated_line, start_at, defines);
+                let parent = frames.last().map_or(self.root_region.clone(), |x| x.region.clone());
+                let taken = active && result;
+                let kind = if taken { RegionKind::If } else { RegionKind::InactiveIf };
+                let region = RegionRef::new(Region::new(kind, line_index));
+                parent.append(&region);
+                frames.push(RegionFrame { region, parent_active: active, taken, done: taken });
+            }
+            "elif" | "else" => {
+                let is_elif = directive == "elif";
+                let result = if is_elif { self.parse_pp_or_expression(formated_line, start_at, defines) } else { true };
+                let is_group = frames.last().is_some_and(|x| {
+                    let kind = x.region.borrow().kind;
+                    kind != RegionKind::Region && kind != RegionKind::InactiveRegion
+                });
+                if !is_group {
+                    formated_line.tokens.last_mut().unwrap().kind = TokenKind::PreprocessorUnexpectedDirective;
                 }
                 else {
-                    return Some(Token::CharLiteral(CharLiteral::new(begin, end)))
+                    let frame = frames.last_mut().unwrap();
+                    let branch = frame.parent_active && !frame.done && result;
+                    let kind = match (is_elif, branch) {
+                        (true, true) => RegionKind::Elif,
+                        (true, false) => RegionKind::InactiveElif,
+                        (false, true) => RegionKind::Else,
+                        (false, false) => RegionKind::InactiveElse,
+                    };
+                    let region = RegionRef::new(Region::new(kind, line_index));
+                    frame.region.insert_after(&region);
+                    frame.region = region;
+                    frame.taken = branch;
+                    frame.done = frame.done || branch;
                 }
             }
-            else if end == begin + 7 {
-
+            "endif" => {
+                let is_group = frames.last().is_some_and(|x| {
+                    let kind = x.region.borrow().kind;
+                    kind != RegionKind::Region && kind != RegionKind::InactiveRegion
+                });
+                if is_group {
+                    frames.pop();
+                }
+                else {
+                    formated_line.tokens.last_mut().unwrap().kind = TokenKind::PreprocessorUnexpectedDirective;
+                }
+            }
+            "region" => {
+                let parent = frames.last().map_or(self.root_region.clone(), |x| x.region.clone());
+                let kind = if active { RegionKind::Region } else { RegionKind::InactiveRegion };
+                let region = RegionRef::new(Region::new(kind, line_index));
+                parent.append(&region);
+                frames.push(RegionFrame { region, parent_active: active, taken: true, done: true });
+                self.push_preprocessor_rest(formated_line, start_at, TokenKind::PreprocessorArguments);
+            }
+            "endregion" => {
+                let is_region = frames.last().is_some_and(|x| {
+                    let kind = x.region.borrow().kind;
+                    kind == RegionKind::Region || kind == RegionKind::InactiveRegion
+                });
+                if is_region {
+                    frames.pop();
+                }
+                else {
+                    formated_line.tokens.last_mut().unwrap().kind = TokenKind::PreprocessorUnexpectedDirective;
+                }
+                self.push_preprocessor_rest(formated_line, start_at, TokenKind::PreprocessorArguments);
+            }
+            "define" | "undef" => {
+                let symbol_begin = *start_at;
+                if let Some(mut x) = self.scan_identifier_or_keyword_raw(start_at, end_at) {
+                    x.kind = TokenKind::PreprocessorSymbol;
+                    formated_line.push_token(x);
+                    let symbol = self.get_string(symbol_begin, *start_at - 1).unwrap_or_default();
+                    if active {
+                        defines.retain(|x| *x != symbol);
+                        if directive == "define" {
+                            defines.push(symbol);
+                        }
+                    }
+                }
+            }
+            _ => {
+                // error, warning, line, pragma
+                self.push_preprocessor_rest(formated_line, start_at, TokenKind::PreprocessorArguments);
             }
         }
-        return None;
-    }
-}
-
-
-#[derive(Debug)]
-pub struct BoolLiteral {
-    pub span: Span,
-}
-
-
-impl BoolLiteral {
-    fn new(start: usize, end: usize) -> BoolLiteral {
-        BoolLiteral{
-            span: Span::new(start, end),
-        }
-    }
-}
-
-
-impl Scanner for BoolLiteral {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        let begin = dy_parser.cursor;
-        let mut start_at = begin;
-        if dy_parser.look_ahead("true") {
-            start_at += 4;
-        }
-        else if dy_parser.look_ahead("false") {
-            start_at += 5;
-        }
-        if start_at != begin && !dy_parser.is_alphanumeric_at(start_at){
-            return Some(Token::BoolLiteral(BoolLiteral::new(begin, start_at-1)));
-        }
-        return None;
-    }
-}
-
-
-#[derive(Debug)]
-pub struct NullLiteral {
-    pub span: Span,
-}
-
-
-impl NullLiteral {
-    fn new(start: usize, end: usize) -> NullLiteral {
-        NullLiteral{
-            span: Span::new(start, end),
+        self.push_whitespace(formated_line, start_at);
+        if *start_at <= end_at {
+            if self.source_equal(*start_at, end_at, "//") {
+                formated_line.push_token(SyntaxToken::new(TokenKind::Comment, *start_at, end_at));
+            }
+            else {
+                formated_line.push_token(SyntaxToken::new(TokenKind::PreprocessorCommentExpected, *start_at, end_at));
+            }
+            *start_at = end_at + 1;
         }
     }
-}
 
-
-impl Scanner for NullLiteral {
-    fn scan(dy_parser: &DyParser) -> Option<Token> {
-        let begin = dy_parser.cursor;
-        let mut start_at = begin;
-        if dy_parser.look_ahead("null") {
-            start_at += 4;
+    // 行剩余部分(去掉尾部空白)作为一个 token
+    fn push_preprocessor_rest(&self, formated_line: &mut FormatedLine, start_at: &mut usize, kind: TokenKind) {
+        let end_at = formated_line.end_at;
+        self.push_whitespace(formated_line, start_at);
+        if *start_at > end_at {
+            return;
         }
-        if start_at != begin && !dy_parser.is_alphanumeric_at(start_at){
-            return Some(Token::NullLiteral(NullLiteral::new(begin, start_at-1)));
+        let mut last = end_at;
+        while last > *start_at && (self.source[last] == ' ' || self.source[last] == '\t') {
+            last -= 1;
         }
-        return None;
+        formated_line.push_token(SyntaxToken::new(kind, *start_at, last));
+        *start_at = last + 1;
     }
-}
-
 
-#[derive(Debug)]
-pub struct Keyword {
-    pub span: Span,
-}
-
-impl Scanner for Keyword {
-    fn scan(dy_sparser: &DyParser) -> Option<Token> {
-        let begin = dy_sparser.cursor;
-        let mut start_at = begin;
-        for keyword in KEYWORDS.iter() {
-            if dy_sparser.look_ahead(keyword) {
-                start_at += keyword.len();
-                if !dy_sparser.is_alphanumeric_at(start_at) {
-                    return Some(Token::Keyword(Keyword {
-                        span: Span::new(begin, start_at-1),
-                    }));
+    // 找 verbatim 字符串的结束引号, "" 是转义
+    // return: (结束位置, 是否找到)
+    fn scan_verbatim_rest(&self, start_at: usize, end_at: usize) -> (usize, bool) {
+        let mut i = start_at;
+        while i <= end_at {
+            if self.source[i] == '"' {
+                if i < end_at && self.source[i+1] == '"' {
+                    i += 2;
+                    continue;
                 }
+                return (i, true);
             }
+            i += 1;
         }
-        return None;
+        return (end_at, false);
     }
-}
 
-
-#[derive(Debug)]
-pub struct Identifier {
-    pub span: Span,
-}
-
-impl Scanner for Identifier {
-    fn scan(dy_sparser: &DyParser) -> Option<Token> {
-        let begin = dy_sparser.cursor;
-        let mut start_at = begin;
-        let len = dy_sparser.source.len();
-        let mut ch = dy_sparser.source[start_at];
-
-        if ch.is_ascii_alphabetic() || ch == '_' {
-            start_at += 1;
-            while start_at < len {
-                if dy_sparser.is_alphanumeric_at(start_at) {
-                    start_at += 1;
+    fn scan_interpolated_string(&self, start_at: &mut usize, end_at: usize) -> SyntaxToken {
+        let begin = *start_at;
+        *start_at += 2;
+        let mut depth = 0;
+        while *start_at <= end_at {
+            let ch = self.source[*start_at];
+            if depth == 0 && ch == '"' {
+                *start_at += 1;
+                break;
+            }
+            if ch == '\\' && *start_at < end_at {
+                *start_at += 1;
+            }
+            else if ch == '{' {
+                if depth == 0 && *start_at < end_at && self.source[*start_at+1] == '{' {
+                    *start_at += 1;
+                }
+                else {
+                    depth += 1;
                 }
             }
-            return Some(Token::Identifier(Identifier {
-                span: Span::new(begin, start_at-1),
-            }));
-        }
-        return None;
-    }
-}
-
-
-#[derive(Debug)]
-pub struct Contextual {
-    pub span: Span,
-}
-
-impl Scanner for Contextual {
-    fn scan(dy_sparser: &DyParser) -> Option<Token> {
-        let begin = dy_sparser.cursor;
-        let mut start_at = begin;
-        for contextual in CONTEXTUALS.iter() {
-            if dy_sparser.look_ahead(contextual) {
-                start_at += contextual.len();
-                if !dy_sparser.is_alphanumeric_at(start_at) {
-                    return Some(Token::Contextual(Contextual {
-                        span: Span::new(begin, start_at-1),
-                    }));
+            else if ch == '}' && depth > 0 {
+                depth -= 1;
+            }
+            else if ch == '"' {
+                // 插值表达式里的字符串
+                if self.scan_string_literal(start_at, end_at).is_some() {
+                    continue;
                 }
             }
+            *start_at += 1;
         }
-        return None
+        return SyntaxToken::new(TokenKind::StringLiteral, begin, *start_at-1);
     }
-}
-
 
-#[derive(Debug)]
-pub struct Punctuator {
-    pub span: Span,
-}
-
-impl Scanner for Punctuator {
-    fn scan(dy_sparser: &DyParser) -> Option<Token> {
-        let begin = dy_sparser.cursor;
-        let mut start_at = begin;
+    fn scan_punctuator(&self, start_at: &mut usize, end_at: usize) -> Option<SyntaxToken> {
         for punctuator in PUNCTUATORS.iter() {
-            if dy_sparser.look_ahead(punctuator) {
-                start_at += punctuator.len();
-                if !dy_sparser.is_alphanumeric_at(start_at) {
-                    return Some(Token::Punctuator(Punctuator {
-                        span: Span::new(begin, start_at-1),
-                    }));
-                }
+            if self.source_equal(*start_at, end_at, punctuator) {
+                let begin = *start_at;
+                *start_at += punctuator.len();
+                return Some(SyntaxToken::new(TokenKind::PunctuatorOrOperator, begin, *start_at-1));
             }
         }
         return None;
-
-    }
-}
-
-#[derive(Debug)]
-pub enum Token {
-    Whitespace(Whitespace),
-    Comment(Comment),
-
-    StringLiteral(StringLiteral),
-    IntegerLiteral(IntegerLiteral),
-    RealLiteral(RealLiteral),
-    CharLiteral(CharLiteral),
-    BoolLiteral(BoolLiteral),
-    NullLiteral(NullLiteral),
-
-    Keyword(Keyword),
-    Contextual(Contextual),
-    Identifier(Identifier),
-    Punctuator(Punctuator),
-
-    Preprocessor,
-    EoF,
-}
-
-
-
-#[derive(Debug)]
-pub struct DyParser {
-    source: Vec<char>,
-    tokens: Vec<Token>,
-    formated_lines: Vec<FormatedLine>,
-    root_region: RegionRef,
-    cursor: usize,
-    line_offset: Vec<usize>,
-}
-
-
-impl DyParser {
-
-    pub fn new(code : String) -> DyParser  {
-        let source = code.chars().collect();
-        DyParser {
-            source,
-            formated_lines: Vec::new(),
-            root_region: RegionRef::default(),
-            tokens: Vec::new(),
-            cursor: 0,
-            line_offset: Vec::new(),
-        }
-    }
-
-    pub fn lexer(&mut self) {
-        let len = self.source.len();
-//        let scans = vec![Keyword::scan, Contextual::scan, Punctuator::scan, Identifier::scan, RealLiteral::scan, CharLiteral::scan,
-//            StringLiteral::scan, IntegerLiteral::scan, BoolLiteral::scan, NullLiteral::scan, Comment::scan, Whitespace::scan];
-        while self.cursor < len {
-            let begin = self.cursor;
-            let keyword_op = Keyword::scan(self);
-            let Some(keyword) = keyword_op {
-               
-            }
-        }
-    }
-
-
-    fn cursor_span(&self) -> (usize, usize) {
-        return (self.cursor, self.source.len()-1)
-    }
-
-
-    fn look_ahead(&self, text: &str) -> bool {
-        return self.find_next(text, self.cursor) != 0;
     }
 
     // todo: 优化?
@@ -605,60 +533,21 @@ impl DyParser {
             if target_len + i > len{
                 return 0;
             }
-            let mut found = true;
-            for j in 0..target_len {
-                if target[j] != self.source[i] {
-                    found = false;
-                    break;
-                }
-            }
-            if found {
-                return i + target_len -1 as usize;
+            if target[..] == self.source[i..i+target_len] {
+                return i + target_len - 1;
             }
         }
         return 0;
     }
 
-    fn find_line_ending(&self, start_at: usize) -> usize {
-        let len = self.source.len();
-        for i in start_at..len {
-            let ch = self.source[i];
-            if ch == '\n' {
-                return i;
-            }
-            else if ch == '\r' {
-                if i < len -1 && self.source[i+1] == '\n' {
-                    return i+1;
-                }
-                return i;
-            }
-        }
-        return len-1;
-    }
-
-    fn is_alphanumeric_at(&self, start_at: usize) -> bool {
-        if start_at >= self.source.len() {
-            return false;
-        }
-        let ch = self.source[start_at];
-        return ch.is_alphanumeric() || ch == '_';
-    }
-
     fn get_string(&self, start_at: usize, end_at: usize) -> Option<String> {
-        if start_at < end_at && end_at < self.source.len() {
+        if start_at <= end_at && end_at < self.source.len() {
             let it = &self.source[start_at..end_at+1];
             return Some(String::from_iter(it));
         }
         return None;
     }
 
-    fn is_keyword(&self, start_at: usize, end_at: usize) -> bool {
-        if let Some(x) = self.get_string(start_at, end_at) {
-            return KEYWORDS.contains(&x.as_str());
-        }
-        return false;
-    }
-
     fn is_keyword_or_built_type(&self, start_at: usize, end_at: usize) -> bool {
         if let Some(x) = self.get_string(start_at, end_at) {
             let word = x.as_str();
@@ -667,7 +556,7 @@ impl DyParser {
         return false;
     }
 
-    fn is_operator(&self, start_at: usize, end_at: usize) -> bool {
+    pub fn is_operator(&self, start_at: usize, end_at: usize) -> bool {
         if let Some(x) = self.get_string(start_at, end_at) {
             return OPERATORS.contains(&x.as_str());
         }
@@ -675,7 +564,7 @@ impl DyParser {
     }
 
     fn scan_whitespace(&self, start_at: &mut usize, end_at: usize) -> Option<SyntaxToken> {
-        if *start_at < 0 || *start_at > end_at {
+        if *start_at > end_at {
             return None;
         }
         let begin = *start_at;
@@ -689,31 +578,20 @@ impl DyParser {
 
     }
 
-    fn scan_word(&self, start_at: &mut usize, end_at: usize) -> Option<SyntaxToken> {
-        let begin = *start_at;
-        while *start_at <= end_at {
-            let ch = self.source[*start_at];
-            if !ch.is_digit(10) && !ch.is_alphabetic() && ch != '_' {
-                break;
-            }
-            *start_at += 1;
-        }
-        return Some(SyntaxToken::new(TokenKind::Identifier, begin, *start_at-1));
-    }
-
     // todo:需要检查异常情况？？
     fn scan_char_literal(&self, start_at: &mut usize, end_at: usize) -> Option<SyntaxToken> {
         if self.source[*start_at] != '\'' {
             return None;
         }
         let begin = *start_at;
+        *start_at += 1;
         while *start_at <= end_at {
             let ch = self.source[*start_at];
             if ch == '\'' {
                 *start_at += 1;
                 break;
             }
-            if ch == '\\' && *start_at < end_at -1 {
+            if ch == '\\' && *start_at < end_at {
                 *start_at += 1;
             }
             *start_at += 1;
@@ -726,6 +604,7 @@ impl DyParser {
             return None;
         }
         let begin = *start_at;
+        *start_at += 1;
         while *start_at <= end_at {
             let ch = self.source[*start_at];
             if ch == '"' {
@@ -749,23 +628,14 @@ impl DyParser {
             && (self.source[*start_at+1] == 'x' || self.source[*start_at+1] == 'X') {
             *start_at += 2;
             hex = true;
-            while *start_at <= end_at {
-                let ch = self.source[*start_at];
-                if (ch >= '0' && ch <= '9') || (ch >= 'a' && ch <= 'f') || (ch >= 'A' || ch < 'F') {
-                    *start_at += 1;
-                }
-                    else {
-                        break;
-                    }
+            while *start_at <= end_at && self.source[*start_at].is_ascii_hexdigit() {
+                *start_at += 1;
             }
         }
-            else {
-                while *start_at <= end_at &&  '0' <= self.source[*start_at] && self.source[*start_at] <= '9'{
-                    *start_at += 1;
-                }
+        else {
+            while *start_at <= end_at &&  '0' <= self.source[*start_at] && self.source[*start_at] <= '9'{
+                *start_at += 1;
             }
-        if hex {
-            return Some(SyntaxToken::new(TokenKind::IntegerLiteral, begin, *start_at-1));
         }
         if *start_at > begin && *start_at <= end_at {
             let ch = self.source[*start_at];
@@ -773,16 +643,17 @@ impl DyParser {
                 *start_at += 1;
                 if *start_at <= end_at {
                     let ch_next = self.source[*start_at];
-                    if (ch == 'l' || ch == 'L') && (ch_next == 'u' || ch_next == 'U') {
+                    if ((ch == 'l' || ch == 'L') && (ch_next == 'u' || ch_next == 'U'))
+                        || ((ch == 'u' || ch == 'U') && (ch_next == 'l' || ch_next == 'L')) {
                         *start_at += 1;
                     }
-                        else if ch_next == 'l' || ch_next == 'L' {
-                            *start_at += 1;
-                        }
                 }
                 return Some(SyntaxToken::new(TokenKind::IntegerLiteral, begin, *start_at-1));
             }
         }
+        if hex {
+            return Some(SyntaxToken::new(TokenKind::IntegerLiteral, begin, *start_at-1));
+        }
         while *start_at <= end_at {
             let ch = self.source[*start_at];
             if !point && !exponent && ch == '.' {
@@ -791,14 +662,14 @@ impl DyParser {
                     point = true;
                     continue;
                 }
-                    else {
-                        break;
-                    }
+                else {
+                    break;
+                }
             }
             if !exponent && *start_at > begin && (ch == 'e' || ch == 'E'){
                 exponent = true;
                 *start_at += 1;
-                if *start_at <= end_at &&  ('0' == self.source[*start_at] || self.source[*start_at] <= '9') {
+                if *start_at <= end_at && (self.source[*start_at] == '+' || self.source[*start_at] == '-') {
                     *start_at += 1;
                 }
                 continue;
@@ -808,7 +679,7 @@ impl DyParser {
                 *start_at += 1;
                 break;
             }
-            if ch < '0' || ch > '9' {
+            if !ch.is_ascii_digit() {
                 break;
             }
             *start_at += 1;
@@ -817,9 +688,9 @@ impl DyParser {
         let kind = if point || exponent {
             TokenKind::RealLiteral
         }
-            else {
-                TokenKind::IntegerLiteral
-            };
+        else {
+            TokenKind::IntegerLiteral
+        };
         return Some(SyntaxToken::new(kind, begin, *start_at-1));
 
     }
@@ -828,8 +699,7 @@ impl DyParser {
         if *start_at > end_at {
             return false;
         }
-        let ch = self.source[*start_at];
-        if (ch >= '0' && ch <= '9') || (ch >= 'a' && ch <= 'f') || (ch >= 'A' || ch < 'F') {
+        if self.source[*start_at].is_ascii_hexdigit() {
             *start_at += 1;
             return true;
         }
@@ -837,25 +707,25 @@ impl DyParser {
     }
 
     fn scan_unicode_escape_char(&self, start_at: &mut usize, end_at: usize) -> bool {
-        if *start_at > end_at - 5 {
+        if *start_at + 5 > end_at {
             return false;
         }
         if self.source[*start_at] != '\\' {
             return false;
         }
         let mut begin = *start_at + 1;
-        let mut n = 0;
+        let mut n;
         if self.source[begin] == 'u' {
             n = 4;
         }
-            else if self.source[begin] == 'U' {
-                n = 8;
-            }
-                else {
-                    return false;
-                }
+        else if self.source[begin] == 'U' {
+            n = 8;
+        }
+        else {
+            return false;
+        }
         begin += 1;
-        while n >= 0 {
+        while n > 0 {
             if !self.scan_hex_digit(&mut begin, end_at) {
                 break;
             }
@@ -895,34 +765,36 @@ impl DyParser {
             if ch.is_alphabetic() || ch == '_' {
                 *start_at += 1;
             }
-                else if !self.scan_unicode_escape_char(start_at, end_at) {
-                    if begin == *start_at {
-                        return None;
-                    }
-                    return Some(SyntaxToken::new(TokenKind::Identifier, begin, *start_at-1));
-                }
-                    else {
-                        identifier = true;
-                    }
+            else if !self.scan_unicode_escape_char(start_at, end_at) {
+                *start_at = begin;
+                return None;
+            }
+            else {
+                identifier = true;
+            }
             while *start_at <= end_at {
                 let ch = self.source[*start_at];
-                if ch.is_alphabetic() || ch.is_digit(10) || ch == '_' {
+                if ch.is_alphabetic() || ch.is_ascii_digit() || ch == '_' {
                     *start_at += 1;
                 }
-                    else if !self.scan_unicode_escape_char(start_at, end_at) {
-                        break;
-                    }
-                        else {
-                            identifier = true;
-                        }
+                else if !self.scan_unicode_escape_char(start_at, end_at) {
+                    break;
+                }
+                else {
+                    identifier = true;
+                }
             }
         }
+        else {
+            *start_at = begin;
+            return None;
+        }
         let kind = if identifier {
             TokenKind::Identifier
         }
-            else {
-                TokenKind::Keyword
-            };
+        else {
+            TokenKind::Keyword
+        };
         return Some(SyntaxToken::new(kind, begin, *start_at-1));
     }
 
@@ -943,7 +815,7 @@ impl DyParser {
     fn push_whitespace(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
         let ws = self.scan_whitespace(start_at, formated_line.end_at);
         if let Some(x) = ws {
-            formated_line.tokens.push(x);
+            formated_line.push_token(x);
             return true;
         }
         return false;
@@ -951,184 +823,156 @@ impl DyParser {
     // ------------------------------ help function end --------------------------------------------
 
     // ---------------------------------- pp expression --------------------------------------------
-    fn parse_pp_or_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
+    fn parse_pp_or_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize, defines: &[String]) -> bool {
         let end_at = formated_line.end_at;
         if *start_at > end_at {
             // todo: insert missing token
             return true;
         }
-        let lhs = self.parse_pp_and_expression(formated_line, start_at);
-        let begin = *start_at;
+        let lhs = self.parse_pp_and_expression(formated_line, start_at, defines);
         self.push_whitespace(formated_line, start_at);
-        let end_at = formated_line.end_at;
         if self.source_equal(*start_at, end_at, "||")  {
-            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+2);
-            formated_line.tokens.push(token);
+            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+1);
+            formated_line.push_token(token);
             *start_at += 2;
             self.push_whitespace(formated_line, start_at);
-            let rhs = self.parse_pp_or_expression(formated_line, start_at);
+            let rhs = self.parse_pp_or_expression(formated_line, start_at, defines);
             self.push_whitespace(formated_line, start_at);
             return rhs || lhs
         }
         return lhs
     }
 
-    fn parse_pp_and_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
+    fn parse_pp_and_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize, defines: &[String]) -> bool {
         let end_at = formated_line.end_at;
         if *start_at > end_at {
             // todo: insert missing token
             return true;
         }
-        let lhs = self.parse_pp_equal_expression(formated_line, start_at);
-        let begin = *start_at;
+        let lhs = self.parse_pp_equal_expression(formated_line, start_at, defines);
         self.push_whitespace(formated_line, start_at);
-        let end_at = formated_line.end_at;
         if self.source_equal(*start_at, end_at, "&&") {
-            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+2);
-            formated_line.tokens.push(token);
+            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+1);
+            formated_line.push_token(token);
             *start_at += 2;
             self.push_whitespace(formated_line, start_at);
-            let rhs = self.parse_pp_and_expression(formated_line, start_at);
+            let rhs = self.parse_pp_and_expression(formated_line, start_at, defines);
             self.push_whitespace(formated_line, start_at);
             return lhs && rhs;
         }
         return lhs
     }
 
-    fn parse_pp_equal_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
+    fn parse_pp_equal_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize, defines: &[String]) -> bool {
         let end_at = formated_line.end_at;
         if *start_at > end_at {
             // todo: insert missing token
             return true;
         }
-        let lhs = self.parse_pp_unary_expression(formated_line, start_at);
-        let begin = *start_at;
+        let lhs = self.parse_pp_unary_expression(formated_line, start_at, defines);
         self.push_whitespace(formated_line, start_at);
-        let end_at = formated_line.end_at;
         if self.source_equal(*start_at, end_at, "==") || self.source_equal(*start_at, end_at, "!=") {
             let is_equal = self.source_equal(*start_at, end_at, "==");
-            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+2);
-            formated_line.tokens.push(token);
+            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+1);
+            formated_line.push_token(token);
             *start_at += 2;
             self.push_whitespace(formated_line, start_at);
-            let rhs = self.parse_pp_equal_expression(formated_line, start_at);
+            let rhs = self.parse_pp_equal_expression(formated_line, start_at, defines);
             self.push_whitespace(formated_line, start_at);
             if is_equal {
                 return lhs == rhs;
             }
-                else {
-                    return lhs != rhs;
-                }
+            else {
+                return lhs != rhs;
+            }
         }
         return lhs
     }
 
-    fn parse_pp_unary_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
+    fn parse_pp_unary_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize, defines: &[String]) -> bool {
         let end_at = formated_line.end_at;
         if *start_at > end_at {
             // todo: insert missing token
             return true;
         }
-        let begin = *start_at;
         self.push_whitespace(formated_line, start_at);
-        let end_at = formated_line.end_at;
-        if self.source[*start_at] == '!' {
-            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+1);
-            formated_line.tokens.push(token);
+        if *start_at <= end_at && self.source[*start_at] == '!' {
+            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at);
+            formated_line.push_token(token);
             *start_at += 1;
             self.push_whitespace(formated_line, start_at);
-            let result = self.parse_pp_unary_expression(formated_line, start_at);
+            let result = self.parse_pp_unary_expression(formated_line, start_at, defines);
             self.push_whitespace(formated_line, start_at);
             return !result;
         }
-        return self.parse_pp_primary_expression(formated_line, start_at);
+        return self.parse_pp_primary_expression(formated_line, start_at, defines);
     }
 
-    fn parse_pp_primary_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
+    fn parse_pp_primary_expression(&self, formated_line: &mut FormatedLine, start_at: &mut usize, defines: &[String]) -> bool {
+        if *start_at > formated_line.end_at {
+            // todo: insert missing token
+            return true;
+        }
         if self.source[*start_at] == '(' {
-            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+1);
-            formated_line.tokens.push(token);
+            let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at);
+            formated_line.push_token(token);
             *start_at += 1;
             self.push_whitespace(formated_line, start_at);
-            let result = self.parse_pp_or_expression(formated_line, start_at);
+            let result = self.parse_pp_or_expression(formated_line, start_at, defines);
             if *start_at > formated_line.end_at {
                 // todo: insert missing token
                 return result;
             }
 
-            if self.source[*start_at] == '）' {
-                let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at+1);
-                formated_line.tokens.push(token);
+            if self.source[*start_at] == ')' {
+                let token = SyntaxToken::new(TokenKind::PreprocessorArguments, *start_at, *start_at);
+                formated_line.push_token(token);
                 *start_at += 1;
                 self.push_whitespace(formated_line, start_at);
             }
             return result;
         }
-        let result = self.parse_pp_symbol(formated_line, start_at);
+        let result = self.parse_pp_symbol(formated_line, start_at, defines);
         self.push_whitespace(formated_line, start_at);
         return result;
     }
 
-    fn parse_pp_symbol(&self, formated_line: &mut FormatedLine, start_at: &mut usize) -> bool {
+    fn parse_pp_symbol(&self, formated_line: &mut FormatedLine, start_at: &mut usize, defines: &[String]) -> bool {
         let word = self.scan_identifier_or_keyword(start_at, formated_line.end_at);
         if let Some(mut x) = word {
             x.kind = TokenKind::PreprocessorSymbol;
-            let x = formated_line.tokens.put(x);
-            // formated_line.tokens.push(x);
+            let x = formated_line.push_token(x);
             if self.source_equal(x.begin_at, x.end_at, "true") {
                 return true;
             }
-                else if self.source_equal(x.begin_at, x.end_at, "false") {
-                    return false;
-                }
-            // todo: vm compilation define
-            return false;
+            else if self.source_equal(x.begin_at, x.end_at, "false") {
+                return false;
+            }
+            let symbol = self.get_string(x.begin_at, x.end_at).unwrap_or_default();
+            return defines.contains(&symbol);
         }
         return true;
     }
-
-    // ----------------------------------- region --------------------------------------------------
-//    fn open_region(&mut self, formated_line: &mut FormatedLine, region_kind: RegionKind) {
-//        // todo: 同一行不能有两个region
-//        let mut parent_region = Weak::clone(&formated_line.region);
-//        if region_kind == RegionKind::InactiveElif ||
-//            region_kind == RegionKind::Elif ||
-//            region_kind == RegionKind::Else ||
-//            region_kind == RegionKind::InactiveElse {
-//            let mut region_rc_op = formated_line.region.upgrade();
-//            if let Some(ref mut region_rc) = region_rc_op {
-//                let region_op = Rc::get_mut(region_rc);
-//                if let Some(region) = region_op {
-//                    parent_region = Weak::clone(&region.parent)
-//                }
-//            }
-//            // todo error
-//        }
-//        let mut parent_rc = parent_region.upgrade().unwrap();
-//        let parent = Rc::get_mut(&mut parent_rc).unwrap();
-//        let region = Region::new(region_kind, formated_line.index);
-//        parent.children.push(region);
-//    }
-//
-//    fn close_region(formated_line: &mut FormatedLine) {
-//        let region_rc = formated_line.region.upgrade();
-//        match region_rc {
-//            None => formated_line.region = Weak::new(),
-//            Some(region) => formated_line.region = Weak::clone(&region.parent),
-//        }
-//    }
 }
 
 
-
-
+pub fn line_column(line_offset: &[usize], offset: usize) -> (usize, usize) {
+    let line = match line_offset.binary_search(&offset) {
+        Ok(x) => x,
+        Err(x) => if x > 0 { x - 1 } else { 0 },
+    };
+    let begin = if line < line_offset.len() { line_offset[line] } else { 0 };
+    return (line, offset - begin);
+}
 
 
 #[derive(Debug)]
 #[derive(PartialEq)]
-enum TokenKind {
+#[derive(Copy, Clone)]
+pub enum TokenKind {
     Missing,
     Whitespace,
+    EndOfLine,
     Comment,
     Preprocessor,
     PreprocessorArguments,
@@ -1136,6 +980,7 @@ enum TokenKind {
     PreprocessorDirectiveExpected,
     PreprocessorCommentExpected,
     PreprocessorUnexpectedDirective,
+    InactiveCode,
 
 
     VerbatimPrefix,    // @
@@ -1149,34 +994,68 @@ enum TokenKind {
     PunctuatorOrOperator,  // 分隔符
     Keyword,
     Identifier,
+    Unknown,
     EOF,
 
 }
 
+const TOKEN_KINDS: [TokenKind; 24] = [TokenKind::Missing, TokenKind::Whitespace, TokenKind::EndOfLine,
+    TokenKind::Comment, TokenKind::Preprocessor, TokenKind::PreprocessorArguments, TokenKind::PreprocessorSymbol,
+    TokenKind::PreprocessorDirectiveExpected, TokenKind::PreprocessorCommentExpected,
+    TokenKind::PreprocessorUnexpectedDirective, TokenKind::InactiveCode, TokenKind::VerbatimPrefix,
+    TokenKind::InterpolatedPrefix, TokenKind::BoolLiteral, TokenKind::NullLiteral, TokenKind::CharLiteral,
+    TokenKind::StringLiteral, TokenKind::IntegerLiteral, TokenKind::RealLiteral, TokenKind::PunctuatorOrOperator,
+    TokenKind::Keyword, TokenKind::Identifier, TokenKind::Unknown, TokenKind::EOF];
+
+impl TokenKind {
+    pub fn from_name(name: &str) -> Option<TokenKind> {
+        TOKEN_KINDS.iter().find(|x| format!("{:?}", x) == name).cloned()
+    }
+
+    // 不参与语法分析的 token
+    pub fn is_trivia(&self) -> bool {
+        matches!(*self,
+            TokenKind::Whitespace | TokenKind::EndOfLine | TokenKind::Comment | TokenKind::InactiveCode
+            | TokenKind::Preprocessor | TokenKind::PreprocessorArguments | TokenKind::PreprocessorSymbol
+            | TokenKind::PreprocessorDirectiveExpected | TokenKind::PreprocessorCommentExpected
+            | TokenKind::PreprocessorUnexpectedDirective)
+    }
+
+    pub fn is_literal(&self) -> bool {
+        matches!(*self,
+            TokenKind::BoolLiteral | TokenKind::NullLiteral | TokenKind::CharLiteral | TokenKind::StringLiteral
+            | TokenKind::IntegerLiteral | TokenKind::RealLiteral)
+    }
+}
+
 
 #[derive(Debug)]
 #[derive(PartialEq)]
 #[repr(u8)]
 #[derive(Copy, Clone)]
-enum BlockState {
+pub enum BlockState {
     None,
     Comment,
     String,
 }
 
-#[derive(Debug)]
-struct SyntaxToken {
-    kind: TokenKind,
-    begin_at: usize,
-    end_at: usize,
-    block_state: BlockState,
-    line_index: usize,
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    pub kind: TokenKind,
+    pub begin_at: usize,
+    pub end_at: usize,
+    pub block_state: BlockState,
+    pub line_index: usize,
 }
 
 impl SyntaxToken {
     fn new(kind: TokenKind, begin_at: usize, end_at: usize) -> SyntaxToken {
         SyntaxToken{kind, begin_at, end_at, block_state: BlockState::None, line_index: 0}
     }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.begin_at, self.end_at + 1)
+    }
 }
 
 #[derive(Debug)]
@@ -1186,7 +1065,6 @@ struct FormatedLine {
     end_at: usize,
     tokens: Vec<SyntaxToken>,
     block_state: BlockState,
-    // region: Option<RegionRef>,
 }
 
 impl FormatedLine {
@@ -1197,7 +1075,6 @@ impl FormatedLine {
             end_at,
             tokens: Vec::new(),
             block_state: BlockState::None,
-            // region: None,
         }
     }
 
@@ -1225,9 +1102,9 @@ pub enum RegionKind {
 
 
 #[derive(Debug)]
-struct Region {
-    kind: RegionKind,
-    line_index: usize,
+pub struct Region {
+    pub kind: RegionKind,
+    pub line_index: usize,
 }
 
 impl Default for Region {
@@ -1245,6 +1122,82 @@ impl Region {
     }
 }
 
-type RegionRef = DyRef<Region>;
+pub type RegionRef = DyRef<Region>;
 
+// ------------------------ test --------------------------
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lex(code: &str) -> DyParser {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        dy_parser
+    }
+
+    fn significant(dy_parser: &DyParser) -> Vec<(TokenKind, String)> {
+        dy_parser.tokens().iter()
+            .filter(|x| !x.kind.is_trivia())
+            .map(|x| (x.kind, dy_parser.token_text(x)))
+            .collect()
+    }
+
+    #[test]
+    fn test_token_kind_names() {
+        for (i, kind) in TOKEN_KINDS.iter().enumerate() {
+            assert_eq!(*kind as usize, i);
+            assert_eq!(TokenKind::from_name(&format!("{:?}", kind)), Some(*kind));
+        }
+    }
+
+    #[test]
+    fn test_lossless() {
+        let code = "namespace A\r\n{\n  /* a\n b */ int x = 0x1F; // c\n}";
+        let dy_parser = lex(code);
+        let text: String = dy_parser.tokens().iter().map(|x| dy_parser.token_text(x)).collect();
+        assert_eq!(text, code);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let dy_parser = lex("a.b >>= 1.5e3f + 'c' ?? \"s\\\"\" @x true null");
+        let tokens = significant(&dy_parser);
+        let kinds: Vec<TokenKind> = tokens.iter().map(|x| x.0).collect();
+        assert_eq!(kinds, vec![TokenKind::Identifier, TokenKind::PunctuatorOrOperator, TokenKind::Identifier,
+            TokenKind::PunctuatorOrOperator, TokenKind::RealLiteral, TokenKind::PunctuatorOrOperator,
+            TokenKind::CharLiteral, TokenKind::PunctuatorOrOperator, TokenKind::StringLiteral, TokenKind::Identifier,
+            TokenKind::BoolLiteral, TokenKind::NullLiteral]);
+        assert_eq!(tokens[3].1, ">>=");
+        assert_eq!(tokens[8].1, "\"s\\\"\"");
+    }
+
+    #[test]
+    fn test_keyword() {
+        let dy_parser = lex("namespace continue var");
+        let tokens = significant(&dy_parser);
+        assert_eq!(tokens[0].0, TokenKind::Keyword);
+        assert_eq!(tokens[1].0, TokenKind::Keyword);
+        assert_eq!(tokens[2].0, TokenKind::Identifier);
+    }
+
+    #[test]
+    fn test_preprocessor() {
+        let dy_parser = lex("#define A\n#if A && !B\nx\n#else\ny\n#endif\n#region R\nz\n#endregion");
+        let tokens = significant(&dy_parser);
+        let words: Vec<&str> = tokens.iter().map(|x| x.1.as_str()).collect();
+        assert_eq!(words, vec!["x", "z"]);
+        let inactive = dy_parser.tokens().iter().filter(|x| x.kind == TokenKind::InactiveCode).count();
+        assert_eq!(inactive, 1);
+        let kinds: Vec<RegionKind> = dy_parser.root_region().children().map(|x| x.borrow().kind).collect();
+        assert_eq!(kinds, vec![RegionKind::If, RegionKind::InactiveElse, RegionKind::Region]);
+    }
+
+    #[test]
+    fn test_line_column() {
+        let dy_parser = lex("a\nbc\r\nd");
+        assert_eq!(dy_parser.line_column(0), (0, 0));
+        assert_eq!(dy_parser.line_column(3), (1, 1));
+        assert_eq!(dy_parser.line_column(6), (2, 0));
+    }
+}
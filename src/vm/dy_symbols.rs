@@ -0,0 +1,1233 @@
+use vm::dy_diagnostic::{Argument, Diagnostic, Severity};
+use vm::dy_parser::Span;
+use vm::dy_syntax::{SyntaxKind, SyntaxRef, CompilationUnit, NamespaceDeclaration, TypeDeclaration,
+    MemberDeclaration, MethodDeclaration, PropertyDeclaration, VariableDeclaration, Parameter, UsingDirective};
+use vm::dy_types::{generic_name, is_integral, UserTypes};
+
+// ------------------------------------ declarations -------------------------------------------
+// 声明收集: 从一个或多个编译单元里收集类型和成员, 类型用命名空间加名字标识
+// 成员的类型保留源码里的写法, 绑定以后才能解析
+
+#[derive(Debug, Clone)]
+pub struct ParameterSymbol {
+    pub name: String,
+    pub type_name: String,
+    /// `ref`, `out`, `in`, `params`, `this`.
+    pub modifiers: Vec<String>,
+    pub has_default: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemberSymbol {
+    pub name: String,
+    pub kind: SyntaxKind,
+    pub modifiers: Vec<String>,
+    /// Return type of methods, type of fields and properties.
+    pub type_name: Option<String>,
+    pub type_parameters: Vec<String>,
+    pub parameters: Vec<ParameterSymbol>,
+    /// The implementing declaration of a partial method if there is one.
+    pub declaration: SyntaxRef,
+    /// For partial methods, the declaration without a body.
+    pub definition: Option<SyntaxRef>,
+    /// The interface of an explicit implementation, `I` in `void I.F()`.
+    pub explicit_interface: Option<String>,
+}
+
+impl MemberSymbol {
+    pub fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers.iter().any(|x| x == modifier)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.has_modifier("static") || self.has_modifier("const")
+    }
+
+    /// A method whose first parameter is marked `this`.
+    pub fn is_extension(&self) -> bool {
+        self.kind == SyntaxKind::MethodDeclaration
+            && self.parameters.first().is_some_and(|x| x.modifiers.iter().any(|x| x == "this"))
+    }
+
+    pub fn is_partial_method(&self) -> bool {
+        self.kind == SyntaxKind::MethodDeclaration && self.has_modifier("partial")
+    }
+
+    /// A partial method without an implementing declaration; calls to it are removed.
+    pub fn is_unimplemented(&self) -> bool {
+        self.is_partial_method() && !has_body(&self.declaration)
+    }
+
+    // 名字, 类型参数个数, 参数类型和 ref/out/in
+    fn signature(&self) -> String {
+        let parameters: Vec<String> = self.parameters.iter()
+            .map(|x| {
+                let modifiers: Vec<&str> = x.modifiers.iter().map(|x| x.as_str())
+                    .filter(|x| ["ref", "out", "in"].contains(x)).collect();
+                format!("{}{}", modifiers.iter().map(|x| format!("{} ", x)).collect::<String>(), x.type_name)
+            })
+            .collect();
+        let name = match self.explicit_interface {
+            Some(ref interface) => format!("{}.{}", interface, self.name),
+            None => self.name.clone(),
+        };
+        format!("{}`{}({})", name, self.type_parameters.len(), parameters.join(", "))
+    }
+
+    /// Whether `count` arguments can be passed, counting optional and params parameters.
+    pub fn accepts(&self, count: usize) -> bool {
+        let required = self.parameters.iter().filter(|x| !x.has_default && !x.modifiers.iter().any(|x| x == "params"))
+            .count();
+        let is_params = self.parameters.last().is_some_and(|x| x.modifiers.iter().any(|x| x == "params"));
+        count >= required && (is_params || count <= self.parameters.len())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeSymbol {
+    pub name: String,
+    /// Dotted namespace, empty for the global namespace.
+    pub namespace: String,
+    /// Full name of the enclosing type for nested types.
+    pub containing_type: Option<String>,
+    pub kind: SyntaxKind,
+    pub modifiers: Vec<String>,
+    pub type_parameters: Vec<String>,
+    pub base_types: Vec<String>,
+    pub members: Vec<MemberSymbol>,
+    pub declarations: Vec<SyntaxRef>,
+}
+
+impl TypeSymbol {
+    pub fn full_name(&self) -> String {
+        match self.containing_type {
+            Some(ref outer) => format!("{}.{}", outer, self.name),
+            None if self.namespace.is_empty() => self.name.clone(),
+            None => format!("{}.{}", self.namespace, self.name),
+        }
+    }
+
+    pub fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers.iter().any(|x| x == modifier)
+    }
+
+    /// The integral type under an enum, `int` unless declared as in `enum E : byte`.
+    pub fn enum_underlying_type(&self) -> String {
+        let declared = self.base_types.first().filter(|x| is_integral(x) && x.as_str() != "char");
+        declared.cloned().unwrap_or_else(|| "int".to_string())
+    }
+
+    /// Members accessible by `name`; explicit interface implementations are not.
+    pub fn members_named(&self, name: &str) -> Vec<&MemberSymbol> {
+        self.members.iter().filter(|x| x.name == name && x.explicit_interface.is_none()).collect()
+    }
+}
+
+fn parameter_symbols(parameters: Vec<Parameter>) -> Vec<ParameterSymbol> {
+    parameters.iter()
+        .map(|x| ParameterSymbol {
+            name: x.name().unwrap_or_default(),
+            type_name: x.parameter_type().map_or(String::new(), |x| x.text()),
+            modifiers: x.modifiers(),
+            has_default: x.default_value().is_some(),
+        })
+        .collect()
+}
+
+fn member_symbols(member: &MemberDeclaration) -> Vec<MemberSymbol> {
+    let node = member.syntax();
+    let symbol = |name: String, type_name: Option<String>, parameters: Vec<ParameterSymbol>, declaration: &SyntaxRef| {
+        let type_parameters = declaration.child_of(SyntaxKind::TypeParameterList)
+            .map_or(Vec::new(), |x| x.children_of(SyntaxKind::TypeParameter).iter()
+                .filter_map(|x| x.identifier().map(|x| x.token_text())).collect());
+        let modifiers = member.modifiers();
+        let definition = if member.kind() == SyntaxKind::MethodDeclaration && modifiers.iter().any(|x| x == "partial")
+            && !has_body(declaration) {
+            Some(declaration.clone())
+        }
+        else {
+            None
+        };
+        let explicit_interface = node.child_of(SyntaxKind::ExplicitInterfaceSpecifier)
+            .and_then(|x| x.child_nodes().first().map(|x| x.text().trim().to_string()));
+        MemberSymbol { name, kind: member.kind(), modifiers, type_name, type_parameters, parameters,
+            declaration: declaration.clone(), definition, explicit_interface }
+    };
+    match member.kind() {
+        SyntaxKind::FieldDeclaration | SyntaxKind::EventFieldDeclaration => {
+            let declaration = match node.child_nodes().into_iter().find_map(VariableDeclaration::cast) {
+                Some(declaration) => declaration,
+                None => return Vec::new(),
+            };
+            let type_name = declaration.variable_type().map(|x| x.text());
+            declaration.declarators().iter()
+                .filter_map(|x| x.name().map(|name| symbol(name, type_name.clone(), Vec::new(), x.syntax())))
+                .collect()
+        }
+        SyntaxKind::PropertyDeclaration | SyntaxKind::IndexerDeclaration => {
+            let property = PropertyDeclaration::cast(node.clone()).unwrap();
+            let name = if member.kind() == SyntaxKind::IndexerDeclaration { Some("this".to_string()) } else { property.name() };
+            let parameters = node.child_of(SyntaxKind::BracketedParameterList)
+                .map_or(Vec::new(), |x| parameter_symbols(x.child_nodes().into_iter().filter_map(Parameter::cast).collect()));
+            name.map(|name| symbol(name, property.property_type().map(|x| x.text()), parameters, node)).into_iter().collect()
+        }
+        SyntaxKind::MethodDeclaration | SyntaxKind::ConstructorDeclaration | SyntaxKind::DestructorDeclaration
+        | SyntaxKind::OperatorDeclaration | SyntaxKind::ConversionOperatorDeclaration => {
+            let method = MethodDeclaration::cast(node.clone()).unwrap();
+            let name = match member.kind() {
+                SyntaxKind::OperatorDeclaration => node.significant_tokens().iter()
+                    .skip_while(|x| x.token_text() != "operator").nth(1).map(|x| format!("operator {}", x.token_text())),
+                SyntaxKind::ConversionOperatorDeclaration => Some("operator".to_string()),
+                SyntaxKind::DestructorDeclaration => method.name().map(|x| format!("~{}", x)),
+                _ => method.name(),
+            };
+            let parameters = parameter_symbols(method.parameters());
+            name.map(|name| symbol(name, method.return_type().map(|x| x.text()), parameters, node)).into_iter().collect()
+        }
+        SyntaxKind::EventDeclaration => member.name()
+            .map(|name| symbol(name, None, Vec::new(), node)).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+const ACCESS_MODIFIERS: [&'static str; 4] = ["public", "private", "protected", "internal"];
+
+fn has_body(declaration: &SyntaxRef) -> bool {
+    declaration.child_of(SyntaxKind::Block).is_some() || declaration.child_of(SyntaxKind::ArrowExpressionClause).is_some()
+}
+
+// 多个编译单元共用声明, 错误报在声明所在的单元里
+fn in_unit(node: &SyntaxRef, root: &SyntaxRef) -> bool {
+    node.acestors().last().as_ref() == Some(root)
+}
+
+fn member_error(code: &'static str, arguments: &[&str], member: &MemberSymbol) -> Diagnostic {
+    Diagnostic::error(code, member.declaration.span(), arguments)
+}
+
+// partial 方法的定义和实现合并成一个成员, 其他同名同签名的成员重复
+fn add_member(symbol: &mut TypeSymbol, mut member: MemberSymbol, errors: &mut Vec<Diagnostic>) {
+    if member.is_partial_method() {
+        check_partial_method(symbol, &member, errors);
+    }
+    let is_method = |x: &MemberSymbol| x.kind == SyntaxKind::MethodDeclaration || x.kind == SyntaxKind::ConstructorDeclaration
+        || x.kind == SyntaxKind::OperatorDeclaration || x.kind == SyntaxKind::ConversionOperatorDeclaration
+        || x.kind == SyntaxKind::IndexerDeclaration;
+    let existing = symbol.members.iter().position(|x| if is_method(x) && is_method(&member) {
+        x.signature() == member.signature()
+    }
+    else {
+        x.name == member.name && x.kind != SyntaxKind::ConstructorDeclaration && x.explicit_interface == member.explicit_interface
+    });
+    let i = match existing {
+        Some(i) => i,
+        None => {
+            symbol.members.push(member);
+            return;
+        }
+    };
+    let other = &mut symbol.members[i];
+    if !(other.is_partial_method() && member.is_partial_method()) {
+        errors.push(member_error("DY0405", &[&symbol.name, &member.name], &member));
+        return;
+    }
+    match (other.definition.is_some(), member.definition.is_some()) {
+        (true, true) => errors.push(member_error("DY0407", &[], &member)),
+        (false, false) => errors.push(member_error("DY0408", &[], &member)),
+        _ => {
+            if other.is_static() != member.is_static() {
+                errors.push(member_error("DY0409", &[], &member));
+            }
+            // 保留实现部分, 记下定义部分
+            if member.definition.is_none() {
+                member.definition = other.definition.take();
+                *other = member;
+            }
+            else {
+                other.definition = member.definition.take();
+            }
+        }
+    }
+}
+
+fn check_partial_method(symbol: &TypeSymbol, member: &MemberSymbol, errors: &mut Vec<Diagnostic>) {
+    if !symbol.has_modifier("partial") || (symbol.kind != SyntaxKind::ClassDeclaration && symbol.kind != SyntaxKind::StructDeclaration) {
+        errors.push(member_error("DY0410", &[], member));
+    }
+    if member.type_name.as_ref().is_none_or(|x| x != "void") {
+        errors.push(member_error("DY0411", &[], member));
+    }
+    if member.parameters.iter().any(|x| x.modifiers.iter().any(|x| x == "out")) {
+        errors.push(member_error("DY0412", &[], member));
+    }
+    let forbidden = ["public", "private", "protected", "internal", "virtual", "abstract", "override", "new", "sealed", "extern"];
+    if member.modifiers.iter().any(|x| forbidden.contains(&x.as_str())) {
+        errors.push(member_error("DY0413", &[], member));
+    }
+}
+
+/// Types and members declared in a set of compilation units.
+#[derive(Debug, Clone, Default)]
+pub struct Declarations {
+    pub types: Vec<TypeSymbol>,
+    /// Declared namespaces and their enclosing namespaces, plus those the host provides.
+    pub namespaces: Vec<String>,
+    /// References only reachable through `extern alias`, by alias.
+    pub externs: Vec<(String, Declarations)>,
+    /// Compilation units added with `add_to_assembly`, the others are in the unnamed assembly.
+    pub assemblies: Vec<(SyntaxRef, String)>,
+}
+
+impl Declarations {
+    pub fn new() -> Declarations {
+        Declarations { types: Vec::new(), namespaces: Vec::new(), externs: Vec::new(), assemblies: Vec::new() }
+    }
+
+    /// Makes a namespace known without declarations in it, e.g. `System` provided by the host.
+    pub fn declare_namespace(&mut self, name: &str) {
+        let mut prefix = String::new();
+        for part in name.split('.') {
+            if !prefix.is_empty() {
+                prefix.push('.');
+            }
+            prefix.push_str(part);
+            if !self.namespaces.contains(&prefix) {
+                self.namespaces.push(prefix.clone());
+            }
+        }
+    }
+
+    pub fn has_namespace(&self, name: &str) -> bool {
+        name.is_empty() || self.namespaces.iter().any(|x| x == name)
+    }
+
+    /// Adds a reference whose names are only visible as `alias::Name` after `extern alias alias;`.
+    pub fn add_extern(&mut self, alias: &str, reference: Declarations) {
+        self.externs.push((alias.to_string(), reference));
+    }
+
+    fn reference(&self, alias: &str) -> Option<&Declarations> {
+        self.externs.iter().find(|x| x.0 == alias).map(|x| &x.1)
+    }
+
+    /// Collects the declarations of one compilation unit, returns its declaration errors.
+    pub fn add(&mut self, unit: &CompilationUnit) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        self.add_members(unit.members(), "", None, &mut errors);
+        for namespace in unit.namespaces() {
+            self.add_namespace(&namespace, "", &mut errors);
+        }
+        errors
+    }
+
+    /// Like `add` for a unit of the script assembly `assembly`; `internal` is only accessible within it.
+    pub fn add_to_assembly(&mut self, unit: &CompilationUnit, assembly: &str) -> Vec<Diagnostic> {
+        self.assemblies.push((unit.syntax().clone(), assembly.to_string()));
+        self.add(unit)
+    }
+
+    /// The assembly of the compilation unit containing `node`.
+    pub fn assembly_of(&self, node: &SyntaxRef) -> &str {
+        let root = node.acestors().last();
+        self.assemblies.iter().find(|x| Some(&x.0) == root.as_ref()).map_or("", |x| x.1.as_str())
+    }
+
+    fn add_namespace(&mut self, namespace: &NamespaceDeclaration, outer: &str, errors: &mut Vec<Diagnostic>) {
+        let name = if outer.is_empty() { namespace.name() } else { format!("{}.{}", outer, namespace.name()) };
+        self.declare_namespace(&name);
+        self.add_members(namespace.members(), &name, None, errors);
+        for inner in namespace.namespaces() {
+            self.add_namespace(&inner, &name, errors);
+        }
+    }
+
+    fn add_members(&mut self, members: Vec<MemberDeclaration>, namespace: &str, containing_type: Option<String>,
+        errors: &mut Vec<Diagnostic>) {
+        for declaration in members.into_iter().filter_map(|x| TypeDeclaration::cast(x.syntax().clone())) {
+            let name = match declaration.name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let mut symbol = TypeSymbol {
+                name,
+                namespace: namespace.to_string(),
+                containing_type: containing_type.clone(),
+                kind: declaration.kind(),
+                modifiers: declaration.modifiers(),
+                type_parameters: declaration.type_parameters(),
+                base_types: declaration.base_types().iter().map(|x| x.text()).collect(),
+                members: Vec::new(),
+                declarations: vec![declaration.syntax().clone()],
+            };
+            let full_name = symbol.full_name();
+            let existing = self.types.iter().position(|x| x.full_name() == full_name);
+            match existing {
+                Some(i) if self.types[i].has_modifier("partial") || symbol.has_modifier("partial") => {
+                    self.check_parts(&self.types[i], &symbol, errors);
+                    let merged = &mut self.types[i];
+                    for modifier in symbol.modifiers {
+                        if !merged.modifiers.contains(&modifier) {
+                            merged.modifiers.push(modifier);
+                        }
+                    }
+                    for base in symbol.base_types {
+                        if !merged.base_types.contains(&base) {
+                            merged.base_types.push(base);
+                        }
+                    }
+                    merged.declarations.push(declaration.syntax().clone());
+                    for member in declaration.members().iter().flat_map(member_symbols) {
+                        add_member(merged, member, errors);
+                    }
+                }
+                Some(..) => {
+                    let location = if namespace.is_empty() {
+                        Argument::phrase("global-namespace", &[])
+                    }
+                    else {
+                        Argument::from(namespace)
+                    };
+                    let arguments = vec![location, Argument::from(symbol.name.as_str())];
+                    errors.push(Diagnostic::new(Severity::Error, "DY0406", declaration.syntax().span(), arguments));
+                    continue;
+                }
+                None => {
+                    for member in declaration.members().iter().flat_map(member_symbols) {
+                        add_member(&mut symbol, member, errors);
+                    }
+                    self.types.push(symbol);
+                }
+            }
+            self.add_members(declaration.members(), namespace, Some(full_name), errors);
+        }
+    }
+
+    // partial 类型的各个部分: 都要有 partial, 种类, 访问修饰符, 类型参数和基类一致
+    fn check_parts(&self, existing: &TypeSymbol, part: &TypeSymbol, errors: &mut Vec<Diagnostic>) {
+        let span = part.declarations[0].span();
+        let name = part.name.as_str();
+        let mut error = |code: &'static str| errors.push(Diagnostic::error(code, span, &[name]));
+        if !existing.has_modifier("partial") || !part.has_modifier("partial") {
+            error("DY0415");
+        }
+        if existing.kind != part.kind {
+            error("DY0416");
+            return;
+        }
+        let access = |x: &TypeSymbol| -> Vec<String> {
+            x.modifiers.iter().filter(|x| ACCESS_MODIFIERS.contains(&x.as_str())).cloned().collect()
+        };
+        let (access1, access2) = (access(existing), access(part));
+        if !access1.is_empty() && !access2.is_empty() && access1 != access2 {
+            error("DY0417");
+        }
+        if existing.type_parameters != part.type_parameters {
+            error("DY0418");
+        }
+        if part.kind == SyntaxKind::ClassDeclaration {
+            let base_class = |x: &TypeSymbol| x.base_types.first().filter(|x| !self.is_interface(x)).cloned();
+            if let (Some(base1), Some(base2)) = (base_class(existing), base_class(part)) {
+                if base1 != base2 {
+                    error("DY0419");
+                }
+            }
+        }
+    }
+
+    // 绑定之前只能按声明或者 IName 的命名习惯判断
+    fn is_interface(&self, name: &str) -> bool {
+        let name = generic_name(name);
+        let simple = name.rsplit('.').next().unwrap_or(name);
+        if let Some(symbol) = self.types.iter().find(|x| x.name == simple) {
+            return symbol.kind == SyntaxKind::InterfaceDeclaration;
+        }
+        let mut chars = simple.chars();
+        chars.next() == Some('I') && chars.next().is_some_and(|x| x.is_uppercase())
+    }
+
+    /// Implementing declarations of partial methods in the compilation unit `root` need a defining declaration,
+    /// possibly in another file.
+    pub fn check_partial_methods(&self, root: &SyntaxRef) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        for symbol in &self.types {
+            let members = symbol.members.iter().filter(|x| in_unit(&x.declaration, root));
+            for member in members.filter(|x| x.is_partial_method() && x.definition.is_none()) {
+                errors.push(member_error("DY0414", &[&member.name], member));
+            }
+        }
+        errors
+    }
+
+    pub fn find_type(&self, full_name: &str) -> Option<&TypeSymbol> {
+        self.types.iter().find(|x| x.full_name() == full_name)
+    }
+
+    /// Top-level types of a namespace.
+    pub fn types_in(&self, namespace: &str) -> Vec<&TypeSymbol> {
+        self.types.iter().filter(|x| x.namespace == namespace && x.containing_type.is_none()).collect()
+    }
+
+    /// Resolves a type name written in `context`, see `resolve_name`.
+    pub fn resolve_type(&self, name: &str, context: &LookupContext) -> Option<&TypeSymbol> {
+        match self.resolve_name(name, context) {
+            Ok(NameTarget::Type(_, symbol)) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    /// Types whose declarations contain `node`, innermost first.
+    pub fn enclosing_types(&self, node: &SyntaxRef) -> Vec<&TypeSymbol> {
+        node.acestors().filter(|x| TypeDeclaration::cast(x.clone()).is_some())
+            .filter_map(|x| self.types.iter().find(|y| y.declarations.contains(&x)))
+            .collect()
+    }
+
+    /// `symbol` and its base types that have declarations.
+    pub fn with_bases<'a>(&'a self, symbol: &'a TypeSymbol) -> Vec<&'a TypeSymbol> {
+        let mut queue = vec![symbol];
+        let mut i = 0;
+        while i < queue.len() {
+            let context = LookupContext::at(&queue[i].declarations[0]);
+            for name in &queue[i].base_types {
+                if let Some(next) = self.resolve_type(name, &context) {
+                    if !queue.iter().any(|x| x.full_name() == next.full_name()) {
+                        queue.push(next);
+                    }
+                }
+            }
+            i += 1;
+        }
+        queue
+    }
+
+    /// Resolves a type name written at `at`: nested types of the enclosing types and their bases come first.
+    pub fn resolve_type_at(&self, name: &str, at: &SyntaxRef) -> Option<&TypeSymbol> {
+        if !name.contains("::") {
+            for outer in self.enclosing_types(at).into_iter().flat_map(|x| self.with_bases(x)) {
+                if let Some(nested) = self.find_type(&format!("{}.{}", outer.full_name(), generic_name(name))) {
+                    return Some(nested);
+                }
+            }
+        }
+        self.resolve_type(name, &LookupContext::at(at))
+    }
+
+    // 类型自己和沿着 base list 找到的基类型, 找不到声明的基类型只保留名字
+    fn base_names(&self, name: &str, context: &LookupContext) -> Vec<String> {
+        let mut names = vec![name.to_string()];
+        let mut i = 0;
+        while i < names.len() {
+            let bases = match self.resolve_type(&names[i], context) {
+                Some(symbol) => symbol.base_types.clone(),
+                None => COLLECTION_TYPES.iter().find(|x| x.0 == generic_name(&names[i]))
+                    .map_or(Vec::new(), |x| x.1.iter().map(|x| x.to_string()).collect()),
+            };
+            for base in bases {
+                if !names.contains(&base) {
+                    names.push(base);
+                }
+            }
+            i += 1;
+        }
+        names
+    }
+}
+
+impl UserTypes for Declarations {
+    fn is_user_value_type(&self, name: &str) -> bool {
+        self.find_type(generic_name(name))
+            .is_some_and(|x| x.kind == SyntaxKind::StructDeclaration || x.kind == SyntaxKind::EnumDeclaration)
+    }
+}
+
+// 没有声明的宿主集合类型实现的接口, 扩展方法按接口名匹配
+const COLLECTION_TYPES: [(&'static str, &'static [&'static str]); 6] = [
+    ("List", &["IList", "ICollection", "IEnumerable"]),
+    ("Dictionary", &["IDictionary", "ICollection", "IEnumerable"]),
+    ("HashSet", &["ISet", "ICollection", "IEnumerable"]),
+    ("Queue", &["ICollection", "IEnumerable"]),
+    ("Stack", &["ICollection", "IEnumerable"]),
+    ("string", &["IEnumerable"]),
+];
+
+// ------------------------------------ member lookup ------------------------------------------
+// 实例方法优先; 没有可用的实例方法时, 从里到外逐层查找扩展方法, 每层是该命名空间里的静态类
+// 加上该层 using 导入的命名空间里的静态类, 第一个有候选的层胜出
+
+/// Directives of one namespace declaration, or of the compilation unit.
+#[derive(Debug, Clone, Default)]
+pub struct ImportLevel {
+    /// Full name of the namespace, empty for the global namespace.
+    pub namespace: String,
+    /// `extern alias E;`
+    pub externs: Vec<String>,
+    /// `using X.Y;`, names as written.
+    pub usings: Vec<String>,
+    /// `using A = X.Y;`
+    pub aliases: Vec<(String, String)>,
+    /// `using static X.Y;`
+    pub statics: Vec<String>,
+}
+
+impl ImportLevel {
+    fn new(namespace: String, externs: Vec<String>, usings: &[UsingDirective]) -> ImportLevel {
+        let mut level = ImportLevel { namespace, externs, ..Default::default() };
+        for using in usings {
+            let name = match using.name() {
+                Some(name) => name_text(&name.text()),
+                None => continue,
+            };
+            match using.alias() {
+                Some(alias) => level.aliases.push((alias, name)),
+                None if using.is_static() => level.statics.push(name),
+                None => level.usings.push(name),
+            }
+        }
+        level
+    }
+}
+
+/// Namespaces visible at a position, innermost first, each with its directives.
+#[derive(Debug, Clone, Default)]
+pub struct LookupContext {
+    pub levels: Vec<ImportLevel>,
+}
+
+impl LookupContext {
+    pub fn at(node: &SyntaxRef) -> LookupContext {
+        let mut declarations: Vec<ImportLevel> = Vec::new();
+        let mut unit_level = ImportLevel::default();
+        for ancestor in node.acestors() {
+            if let Some(namespace) = NamespaceDeclaration::cast(ancestor.clone()) {
+                declarations.push(ImportLevel::new(namespace.name(), namespace.extern_aliases(), &namespace.usings()));
+            }
+            else if let Some(unit) = CompilationUnit::cast(ancestor.clone()) {
+                unit_level = ImportLevel::new(String::new(), unit.extern_aliases(), &unit.usings());
+            }
+        }
+        // namespace A { namespace B.C { } } 的层次是 A.B.C, A.B, A, 全局
+        let mut full_names = Vec::new();
+        for i in 0..declarations.len() {
+            let names: Vec<&str> = declarations[i..].iter().rev().map(|x| x.namespace.as_str()).collect();
+            full_names.push(names.join("."));
+        }
+        let mut levels = Vec::new();
+        let mut current = full_names.first().cloned().unwrap_or_default();
+        while !current.is_empty() {
+            let outer = current.rfind('.').map_or(String::new(), |i| current[..i].to_string());
+            let level = match full_names.iter().position(|x| *x == current) {
+                Some(i) => ImportLevel { namespace: current, ..declarations[i].clone() },
+                None => ImportLevel { namespace: current, ..Default::default() },
+            };
+            levels.push(level);
+            current = outer;
+        }
+        levels.push(unit_level);
+        LookupContext { levels }
+    }
+
+    // 解析第 index 层的 using 指令时的上下文: 本层只看得到 extern alias, 看不到同层的其他 using
+    fn enclosing(&self, index: usize) -> LookupContext {
+        let mut levels = self.levels[index..].to_vec();
+        levels[0].usings.clear();
+        levels[0].aliases.clear();
+        levels[0].statics.clear();
+        LookupContext { levels }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MethodLookup {
+    Instance(Vec<MemberSymbol>),
+    /// Candidates from the innermost scope that has any, with the full name of their static class.
+    Extension(Vec<(String, MemberSymbol)>),
+}
+
+impl Declarations {
+    /// Finds the methods `receiver.name(..)` with `arguments` arguments can call.
+    pub fn lookup_method(&self, receiver: &str, name: &str, arguments: usize, context: &LookupContext)
+        -> Result<MethodLookup, String> {
+        let receiver_types = self.base_names(receiver, context);
+        // 派生类型在前: 同签名的方法隐藏基类型和接口里的, override 换成它重写的 virtual 或 abstract 声明
+        let mut instance: Vec<MemberSymbol> = Vec::new();
+        let methods = receiver_types.iter()
+            .filter_map(|x| self.resolve_type(x, context))
+            .flat_map(|x| x.members_named(name))
+            .filter(|x| x.kind == SyntaxKind::MethodDeclaration && !x.is_static() && x.accepts(arguments));
+        for method in methods {
+            match instance.iter().position(|x| x.signature() == method.signature()) {
+                Some(i) if instance[i].has_modifier("override") => instance[i] = method.clone(),
+                Some(_) => {}
+                None => instance.push(method.clone()),
+            }
+        }
+        if !instance.is_empty() {
+            return Ok(MethodLookup::Instance(instance));
+        }
+        for (index, level) in context.levels.iter().enumerate() {
+            let mut classes = self.types_in(&level.namespace);
+            for import in self.imports(context, index) {
+                match import {
+                    NameTarget::Namespace(declarations, namespace) => classes.extend(declarations.types_in(&namespace)),
+                    NameTarget::Type(_, symbol) => classes.push(symbol),
+                }
+            }
+            let candidates: Vec<(String, MemberSymbol)> = classes.into_iter()
+                .filter(|x| x.kind == SyntaxKind::ClassDeclaration && x.has_modifier("static"))
+                .flat_map(|x| x.members_named(name).into_iter().map(move |m| (x.full_name(), m)))
+                .filter(|&(_, m)| m.is_extension() && m.accepts(arguments + 1)
+                    && self.is_receiver_of(&receiver_types, m))
+                .map(|(class, m)| (class, m.clone()))
+                .collect();
+            if !candidates.is_empty() {
+                return Ok(MethodLookup::Extension(candidates));
+            }
+        }
+        Err(format!("'{}' does not contain a definition for '{}' and no accessible extension method '{}' accepting \
+            a first argument of type '{}' could be found", receiver, name, name, receiver))
+    }
+
+    // this 参数只接受恒等, 引用和装箱转换
+    fn is_receiver_of(&self, receiver_types: &[String], method: &MemberSymbol) -> bool {
+        let parameter = &method.parameters[0].type_name;
+        if method.type_parameters.contains(parameter) || parameter == "object" {
+            return true;
+        }
+        let is_array = receiver_types[0].ends_with("[]");
+        receiver_types.iter().any(|x| x == parameter || generic_name(x) == generic_name(parameter))
+            || (is_array && ["IEnumerable", "ICollection", "IList"].contains(&generic_name(parameter)))
+    }
+
+    /// Extension methods in the compilation unit `root` must be static members of non-generic, non-nested static
+    /// classes.
+    pub fn check_extension_methods(&self, root: &SyntaxRef) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        for symbol in &self.types {
+            let members = symbol.members.iter().filter(|x| in_unit(&x.declaration, root));
+            for member in members.filter(|x| x.kind == SyntaxKind::MethodDeclaration) {
+                let error = |code: &'static str| member_error(code, &[], member);
+                if member.parameters.iter().skip(1).any(|x| x.modifiers.iter().any(|x| x == "this")) {
+                    errors.push(error("DY0420"));
+                }
+                if !member.is_extension() {
+                    continue;
+                }
+                if symbol.kind != SyntaxKind::ClassDeclaration || !symbol.has_modifier("static")
+                    || !symbol.type_parameters.is_empty() {
+                    errors.push(error("DY0421"));
+                }
+                else if symbol.containing_type.is_some() {
+                    errors.push(error("DY0422"));
+                }
+                else if !member.is_static() {
+                    errors.push(error("DY0423"));
+                }
+            }
+        }
+        errors
+    }
+}
+
+
+// ------------------------------------ names --------------------------------------------------
+// 限定名的第一段从里到外逐层查找: 先找该层命名空间自己的成员, 再找该层的别名, 最后找该层 using 导入的类型,
+// 同一层两个导入提供同名类型是二义性错误. 'global::' 从全局命名空间开始, 'E::' 从 extern alias 引用的程序集开始
+
+/// Why a name did not resolve: the code of the diagnostic and its arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameError {
+    NotFound(&'static str, Vec<String>),
+    Ambiguous(&'static str, Vec<String>),
+    UnknownAlias(&'static str, Vec<String>),
+}
+
+impl NameError {
+    /// The diagnostic for the name at `span`.
+    pub fn at(&self, span: Span) -> Diagnostic {
+        match *self {
+            NameError::NotFound(code, ref arguments) | NameError::Ambiguous(code, ref arguments)
+            | NameError::UnknownAlias(code, ref arguments) => {
+                let arguments: Vec<&str> = arguments.iter().map(|x| x.as_str()).collect();
+                Diagnostic::error(code, span, &arguments)
+            }
+        }
+    }
+
+    pub fn message(&self) -> String {
+        self.at(Span::new(0, 0)).message
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NameTarget<'a> {
+    /// A namespace of the declarations it was found in, which are a reference for `E::Name`.
+    Namespace(&'a Declarations, String),
+    Type(&'a Declarations, &'a TypeSymbol),
+}
+
+impl<'a> NameTarget<'a> {
+    pub fn full_name(&self) -> String {
+        match *self {
+            NameTarget::Namespace(_, ref name) => name.clone(),
+            NameTarget::Type(_, symbol) => symbol.full_name(),
+        }
+    }
+}
+
+// 去掉空白和类型参数, 拆出别名: global::A.B<int>.C -> (global, [A, B, C])
+fn split_name(name: &str) -> (Option<String>, Vec<String>) {
+    let name = name_text(name);
+    let (alias, rest) = match name.find("::") {
+        Some(i) if name.find('<').is_none_or(|x| i < x) => (Some(name[..i].to_string()), &name[i + 2..]),
+        _ => (None, name.as_str()),
+    };
+    let mut segments = vec![String::new()];
+    let mut depth = 0;
+    for c in rest.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            '.' if depth == 0 => segments.push(String::new()),
+            _ if depth == 0 => segments.last_mut().unwrap().push(c),
+            _ => {}
+        }
+    }
+    (alias, segments)
+}
+
+fn name_text(text: &str) -> String {
+    text.chars().filter(|x| !x.is_whitespace()).collect()
+}
+
+// 命名空间里的嵌套命名空间和顶层类型, 类型里的嵌套类型
+fn member_of<'a>(container: &NameTarget<'a>, name: &str) -> Option<NameTarget<'a>> {
+    match *container {
+        NameTarget::Namespace(declarations, ref namespace) => {
+            if let Some(symbol) = declarations.types_in(namespace).into_iter().find(|x| x.name == name) {
+                return Some(NameTarget::Type(declarations, symbol));
+            }
+            let full_name = if namespace.is_empty() { name.to_string() } else { format!("{}.{}", namespace, name) };
+            if declarations.has_namespace(&full_name) {
+                return Some(NameTarget::Namespace(declarations, full_name));
+            }
+            None
+        }
+        NameTarget::Type(declarations, symbol) => {
+            let outer = symbol.full_name();
+            declarations.types.iter().find(|x| x.name == name && x.containing_type.as_ref() == Some(&outer))
+                .map(|x| NameTarget::Type(declarations, x))
+        }
+    }
+}
+
+fn not_found_in(name: &str, container: &NameTarget) -> NameError {
+    match *container {
+        NameTarget::Namespace(_, ref namespace) if namespace.is_empty() =>
+            NameError::NotFound("DY0424", vec![name.to_string()]),
+        NameTarget::Namespace(_, ref namespace) =>
+            NameError::NotFound("DY0425", vec![name.to_string(), namespace.clone()]),
+        NameTarget::Type(_, symbol) => NameError::NotFound("DY0505", vec![symbol.full_name(), name.to_string()]),
+    }
+}
+
+impl Declarations {
+    /// Resolves a namespace or type name written in `context`, e.g. `List<int>`, `A.B`, `global::A` or `E::A.B`.
+    pub fn resolve_name(&self, name: &str, context: &LookupContext) -> Result<NameTarget<'_>, NameError> {
+        let (alias, segments) = split_name(name);
+        let mut target = match alias {
+            Some(alias) => {
+                let root = self.alias_root(&alias, context)?;
+                member_of(&root, &segments[0]).ok_or_else(|| not_found_in(&segments[0], &root))?
+            }
+            None => self.resolve_simple(&segments[0], context)?,
+        };
+        for segment in &segments[1..] {
+            target = member_of(&target, segment).ok_or_else(|| not_found_in(segment, &target))?;
+        }
+        Ok(target)
+    }
+
+    // 'global', extern alias 或者指向命名空间的 using 别名
+    fn alias_root(&self, alias: &str, context: &LookupContext) -> Result<NameTarget<'_>, NameError> {
+        if alias == "global" {
+            return Ok(NameTarget::Namespace(self, String::new()));
+        }
+        for (index, level) in context.levels.iter().enumerate() {
+            if level.externs.iter().any(|x| x == alias) {
+                return self.reference(alias).map(|x| NameTarget::Namespace(x, String::new())).ok_or_else(|| {
+                    NameError::UnknownAlias("DY0426", vec![alias.to_string()])
+                });
+            }
+            if let Some((_, target)) = level.aliases.iter().find(|x| x.0 == alias) {
+                if let Ok(namespace @ NameTarget::Namespace(..)) = self.resolve_name(target, &context.enclosing(index)) {
+                    return Ok(namespace);
+                }
+            }
+        }
+        Err(NameError::UnknownAlias("DY0427", vec![alias.to_string()]))
+    }
+
+    fn resolve_simple(&self, name: &str, context: &LookupContext) -> Result<NameTarget<'_>, NameError> {
+        for (index, level) in context.levels.iter().enumerate() {
+            if let Some(target) = member_of(&NameTarget::Namespace(self, level.namespace.clone()), name) {
+                return Ok(target);
+            }
+            if let Some((_, target)) = level.aliases.iter().find(|x| x.0 == name) {
+                return self.resolve_name(target, &context.enclosing(index));
+            }
+            if level.externs.iter().any(|x| x == name) {
+                return self.alias_root(name, context);
+            }
+            let mut found: Vec<NameTarget> = Vec::new();
+            for import in self.imports(context, index) {
+                if let Some(target @ NameTarget::Type(..)) = member_of(&import, name) {
+                    if !found.iter().any(|x| x.full_name() == target.full_name()) {
+                        found.push(target);
+                    }
+                }
+            }
+            match found.len() {
+                0 => continue,
+                1 => return Ok(found.remove(0)),
+                _ => return Err(NameError::Ambiguous("DY0428",
+                    vec![name.to_string(), found[0].full_name(), found[1].full_name()])),
+            }
+        }
+        Err(NameError::NotFound("DY0429", vec![name.to_string()]))
+    }
+
+    // 第 index 层 using 导入的命名空间和 using static 导入的类型, 解析不了的 (比如宿主的命名空间) 跳过
+    fn imports(&self, context: &LookupContext, index: usize) -> Vec<NameTarget<'_>> {
+        let level = &context.levels[index];
+        let enclosing = context.enclosing(index);
+        let namespaces = level.usings.iter().filter_map(|x| match self.resolve_name(x, &enclosing) {
+            Ok(target @ NameTarget::Namespace(..)) => Some(target),
+            _ => None,
+        });
+        let types = level.statics.iter().filter_map(|x| match self.resolve_name(x, &enclosing) {
+            Ok(target @ NameTarget::Type(..)) => Some(target),
+            _ => None,
+        });
+        namespaces.chain(types).collect()
+    }
+
+    /// Static members named `name` brought in by `using static`, from the innermost level that has any.
+    /// Methods from several types are left to overload resolution, other members are ambiguous.
+    pub fn lookup_imported(&self, name: &str, context: &LookupContext) -> Result<Vec<(String, &MemberSymbol)>, NameError> {
+        for index in 0..context.levels.len() {
+            let mut found: Vec<(String, &MemberSymbol)> = Vec::new();
+            for import in self.imports(context, index) {
+                if let NameTarget::Type(_, symbol) = import {
+                    found.extend(symbol.members_named(name).into_iter().filter(|x| x.is_static() && !x.is_extension())
+                        .map(|x| (symbol.full_name(), x)));
+                }
+            }
+            let owners: Vec<&String> = found.iter().map(|x| &x.0).fold(Vec::new(), |mut owners, x| {
+                if !owners.contains(&x) {
+                    owners.push(x);
+                }
+                owners
+            });
+            if owners.len() > 1 && found.iter().any(|x| x.1.kind != SyntaxKind::MethodDeclaration) {
+                return Err(NameError::Ambiguous("DY0428",
+                    vec![name.to_string(), format!("{}.{}", owners[0], name), format!("{}.{}", owners[1], name)]));
+            }
+            if !found.is_empty() {
+                return Ok(found);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Using and extern alias directives of a compilation unit must name something this set of declarations knows.
+    pub fn check_usings(&self, unit: &CompilationUnit) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        for node in unit.syntax().traverse(true, false) {
+            let error = |code: &'static str, name: &str| Diagnostic::error(code, node.span(), &[name]);
+            if node.kind() == SyntaxKind::ExternAliasDirective {
+                let alias = node.identifier().map(|x| x.token_text()).unwrap_or_default();
+                if self.reference(&alias).is_none() {
+                    errors.push(error("DY0426", &alias));
+                }
+                continue;
+            }
+            let using = match UsingDirective::cast(node.clone()) {
+                Some(using) => using,
+                None => continue,
+            };
+            let name = match using.name() {
+                Some(name) => name_text(&name.text()),
+                None => continue,
+            };
+            if let Some(alias) = using.alias() {
+                let is_duplicate = node.preceding_siblings().skip(1).filter_map(UsingDirective::cast)
+                    .any(|x| x.alias().as_ref() == Some(&alias));
+                if is_duplicate {
+                    errors.push(error("DY0430", &alias));
+                    continue;
+                }
+            }
+            match self.resolve_name(&name, &LookupContext::at(&node).enclosing(0)) {
+                Err(e) => errors.push(e.at(node.span())),
+                Ok(NameTarget::Type(..)) if using.alias().is_none() && !using.is_static() =>
+                    errors.push(error("DY0431", &name)),
+                Ok(NameTarget::Namespace(..)) if using.is_static() => errors.push(error("DY0432", &name)),
+                Ok(..) => {}
+            }
+        }
+        errors
+    }
+
+    /// Ambiguous names and unknown aliases in a compilation unit; names that resolve to nothing may be locals,
+    /// members or host types and are left to the binder.
+    pub fn check_names(&self, unit: &CompilationUnit) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+        for node in unit.syntax().traverse(true, false) {
+            match node.kind() {
+                SyntaxKind::IdentifierName | SyntaxKind::GenericName | SyntaxKind::QualifiedName
+                | SyntaxKind::AliasQualifiedName => {}
+                _ => continue,
+            }
+            let is_outermost = node.parent().is_none_or(|x| !matches!(x.kind(),
+                SyntaxKind::QualifiedName | SyntaxKind::AliasQualifiedName | SyntaxKind::UsingDirective
+                | SyntaxKind::NamespaceDeclaration));
+            if !is_outermost {
+                continue;
+            }
+            let context = LookupContext::at(&node);
+            match self.resolve_name(&node.text(), &context) {
+                Err(e @ NameError::Ambiguous(..)) | Err(e @ NameError::UnknownAlias(..)) =>
+                    errors.push(e.at(node.span())),
+                // 简单名字找不到由类型检查报告, 'global::' 和别名限定的名字在这里报
+                Err(e @ NameError::NotFound(..)) if node.text().contains("::")
+                    && !self.in_host_namespace(&node.text(), &context) => errors.push(e.at(node.span())),
+                _ => {}
+            }
+        }
+        errors
+    }
+
+    // 找不到的那一段所在的命名空间只有名字没有类型 (宿主提供的命名空间), 里面有什么不知道
+    fn in_host_namespace(&self, name: &str, context: &LookupContext) -> bool {
+        let (alias, segments) = split_name(name);
+        let alias = alias.unwrap_or_default();
+        let container = (1..segments.len()).rev()
+            .find_map(|n| self.resolve_name(&format!("{}::{}", alias, segments[..n].join(".")), context).ok())
+            .or_else(|| self.alias_root(&alias, context).ok());
+        match container {
+            Some(NameTarget::Namespace(declarations, namespace)) => declarations.is_host_namespace(&namespace),
+            _ => false,
+        }
+    }
+
+    fn is_host_namespace(&self, namespace: &str) -> bool {
+        !namespace.is_empty() && !self.types.iter()
+            .any(|x| x.namespace == namespace || x.namespace.starts_with(&format!("{}.", namespace)))
+    }
+
+    /// Whether the host provides names: a namespace without declared types or an extern reference.
+    /// Without them a name that resolves to nothing does not exist.
+    pub fn has_host_names(&self) -> bool {
+        !self.externs.is_empty() || self.namespaces.iter().any(|x| self.is_host_namespace(x))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::DyParser;
+    use vm::dy_syntax::SyntaxTree;
+
+    fn parse(code: &str) -> SyntaxTree {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        let tree = dy_parser.parse();
+        assert_eq!(tree.errors.len(), 0, "{:?}", tree.errors);
+        tree
+    }
+
+    #[test]
+    fn test_declarations() {
+        let tree = parse("namespace Game { class Player : Actor { int hp, mp; public string Name { get; set; } \
+            public void Move(int x, int y = 0) { } class Inner { } } } class Player { }");
+        let mut declarations = Declarations::new();
+        assert!(declarations.add(&tree.compilation_unit()).is_empty());
+        let names: Vec<String> = declarations.types.iter().map(|x| x.full_name()).collect();
+        assert_eq!(names, ["Player", "Game.Player", "Game.Player.Inner"]);
+        let player = declarations.find_type("Game.Player").unwrap();
+        assert_eq!(player.base_types, ["Actor"]);
+        let members: Vec<&str> = player.members.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(members, ["hp", "mp", "Name", "Move"]);
+        let method = &player.members_named("Move")[0];
+        assert!(method.accepts(1) && method.accepts(2) && !method.accepts(3));
+
+        let errors = declarations.add(&parse("namespace Game { class Player { } }").compilation_unit());
+        assert_eq!(errors[0].message, "'Game' already contains a definition for 'Player'");
+    }
+
+    #[test]
+    fn test_extension_methods() {
+        let library = parse("namespace Engine { class Transform { public void Move(float x) { } } \
+            static class TransformExtensions { public static void MoveTowards(this Transform t, Vector3 to, float step) { } \
+                public static void Move(this Transform t, float x, float y) { } } } \
+            namespace Linq { static class Enumerable { public static int Count<T>(this IEnumerable<T> source) => 0; } }");
+        let game = parse("using Engine; namespace Game { using Linq; class Player { void F() { } } \
+            static class Local { public static void MoveTowards(this Transform t, Vector3 to, float step) { } } }");
+        let mut declarations = Declarations::new();
+        declarations.add(&library.compilation_unit());
+        declarations.add(&game.compilation_unit());
+        assert!(declarations.check_extension_methods(&library.root).is_empty());
+        assert!(declarations.check_extension_methods(&game.root).is_empty());
+
+        let position = game.root.traverse(true, false).find(|x| x.kind() == SyntaxKind::Block).unwrap();
+        let context = LookupContext::at(&position);
+        let levels: Vec<&str> = context.levels.iter().map(|x| x.namespace.as_str()).collect();
+        assert_eq!(levels, ["Game", ""]);
+
+        let lookup = |receiver, name, count| match declarations.lookup_method(receiver, name, count, &context) {
+            Ok(MethodLookup::Instance(methods)) => format!("instance {}", methods.len()),
+            Ok(MethodLookup::Extension(methods)) => methods.iter().map(|x| x.0.clone()).collect::<Vec<_>>().join(", "),
+            Err(message) => message,
+        };
+        // 实例方法优先, 参数个数不匹配时才找扩展方法
+        assert_eq!(lookup("Transform", "Move", 1), "instance 1");
+        assert_eq!(lookup("Transform", "Move", 2), "Engine.TransformExtensions");
+        // 里层命名空间的扩展方法遮住外层导入的
+        assert_eq!(lookup("Transform", "MoveTowards", 2), "Game.Local");
+        assert_eq!(lookup("List<int>", "Count", 0), "Linq.Enumerable");
+        assert_eq!(lookup("IEnumerable<string>", "Count", 0), "Linq.Enumerable");
+        assert_eq!(lookup("int[]", "Count", 0), "Linq.Enumerable");
+        assert_eq!(lookup("Transform", "Jump", 0), "'Transform' does not contain a definition for 'Jump' and no \
+            accessible extension method 'Jump' accepting a first argument of type 'Transform' could be found");
+
+        let mut declarations = Declarations::new();
+        let tree = parse("class A { static void F(this int x) { } } static class B<T> { static void F(this T x) { } } \
+            static class C { static void F(int a, this int x) { } void G(this int x) { } static class D { static void F(this int x) { } } }");
+        declarations.add(&tree.compilation_unit());
+        let errors: Vec<String> = declarations.check_extension_methods(&tree.root).into_iter()
+            .map(|x| x.message).collect();
+        assert_eq!(errors, ["extension methods must be defined in a non-generic static class",
+            "extension methods must be defined in a non-generic static class",
+            "the 'this' modifier can only be applied to the first parameter", "extension methods must be static",
+            "extension methods must be defined in a top level static class"]);
+    }
+
+    #[test]
+    fn test_partial_types() {
+        let generated = parse("namespace Game { public partial class Player : Actor, ISerializable { int hp; \
+            partial void OnCreated(); partial void OnDamaged(int amount); public void Save() { } } }");
+        let written = parse("namespace Game { partial class Player : IComparable { partial void OnCreated() { Log(); } \
+            public void Load() { } } }");
+        let mut declarations = Declarations::new();
+        assert!(declarations.add(&generated.compilation_unit()).is_empty());
+        assert!(declarations.add(&written.compilation_unit()).is_empty());
+        assert!(declarations.check_partial_methods(&generated.root).is_empty());
+        assert!(declarations.check_partial_methods(&written.root).is_empty());
+        let player = declarations.find_type("Game.Player").unwrap();
+        assert_eq!(player.declarations.len(), 2);
+        assert_eq!(player.modifiers, ["public", "partial"]);
+        assert_eq!(player.base_types, ["Actor", "ISerializable", "IComparable"]);
+        let members: Vec<&str> = player.members.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(members, ["hp", "OnCreated", "OnDamaged", "Save", "Load"]);
+        let created = &player.members_named("OnCreated")[0];
+        assert!(!created.is_unimplemented() && created.definition.is_some());
+        assert!(player.members_named("OnDamaged")[0].is_unimplemented());
+
+        let errors = |parts: &[&str]| -> Vec<String> {
+            let mut declarations = Declarations::new();
+            let mut errors = Vec::new();
+            let trees: Vec<SyntaxTree> = parts.iter().map(|x| parse(x)).collect();
+            for tree in &trees {
+                errors.extend(declarations.add(&tree.compilation_unit()));
+            }
+            for tree in &trees {
+                errors.extend(declarations.check_partial_methods(&tree.root));
+            }
+            errors.into_iter().map(|x| x.message).collect()
+        };
+        assert_eq!(errors(&["partial class A { }", "class A { }"]),
+            ["missing partial modifier on declaration of type 'A'; another partial declaration of this type exists"]);
+        assert_eq!(errors(&["partial class A { }", "partial struct A { }"]),
+            ["partial declarations of 'A' must be all classes, all structs or all interfaces"]);
+        assert_eq!(errors(&["public partial class A { }", "internal partial class A { }"]),
+            ["partial declarations of 'A' have conflicting accessibility modifiers"]);
+        assert_eq!(errors(&["partial class A<T> { }", "partial class A<U> { }"]),
+            ["partial declarations of 'A' must have the same type parameter names in the same order"]);
+        assert_eq!(errors(&["partial class A : B { }", "partial class A : C, IDisposable { }"]),
+            ["partial declarations of 'A' must not specify different base classes"]);
+        assert_eq!(errors(&["partial class A { int x; }", "partial class A { int x; void F(int a) { } void F(long a) { } }"]),
+            ["type 'A' already defines a member called 'x' with the same parameter types"]);
+        assert_eq!(errors(&["partial class A { partial void F() { } }", "partial class A { partial void F() { } }"]),
+            ["a partial method may not have multiple implementing declarations",
+                "no defining declaration found for implementing declaration of partial method 'F'"]);
+        assert_eq!(errors(&["class A { partial int F(out int x); public partial void G(); }"]),
+            ["a partial method must be declared within a partial class or partial struct",
+                "partial methods must have a void return type", "partial methods cannot have out parameters",
+                "a partial method must be declared within a partial class or partial struct",
+                "a partial method cannot have access modifiers or the virtual, abstract, override, new, sealed, or extern modifiers"]);
+        assert_eq!(errors(&["partial class A { partial void F(); static partial void F() { } }"]),
+            ["both partial method declarations must be static or neither may be static"]);
+    }
+
+    #[test]
+    fn test_names() {
+        let library = parse("namespace Engine { class Vector { } namespace Physics { class Body { class Shape { } } \
+            class Vector { } } } namespace Audio { class Clip { } class Vector { } } \
+            namespace Util { static class MathF { public static float Max(float a, float b) => a; \
+                public const float PI = 3.14f; } static class Limits { public static int Max(int a, int b) => a; } }");
+        let game = parse("extern alias Legacy; using Engine; using Audio; using P = Engine.Physics; using static Util.MathF; \
+            using static Util.Limits; namespace Game { using Engine.Physics; class Player { void F() { } } } \
+            class Top { Vector v; global::Engine.Vector w; Nope::Vector x; global::Nope.T t; P::Missing m; \
+                global::System.Console c; }");
+        let mut legacy = Declarations::new();
+        legacy.add(&parse("namespace Old { class Vector { } }").compilation_unit());
+        let mut declarations = Declarations::new();
+        declarations.add(&library.compilation_unit());
+        declarations.add(&game.compilation_unit());
+        declarations.add_extern("Legacy", legacy);
+        declarations.declare_namespace("System");
+        assert_eq!(declarations.check_usings(&game.compilation_unit()), []);
+
+        let node = |kind| game.root.traverse(true, false).find(|x| x.kind() == kind).unwrap();
+        let resolve = |name, context: &LookupContext| match declarations.resolve_name(name, context) {
+            Ok(target) => target.full_name(),
+            Err(error) => error.message().to_string(),
+        };
+        // 里层 using 导入的类型优先, 不和外层的导入冲突
+        let context = LookupContext::at(&node(SyntaxKind::Block));
+        assert_eq!(resolve("Vector", &context), "Engine.Physics.Vector");
+        assert_eq!(resolve("Body.Shape", &context), "Engine.Physics.Body.Shape");
+        assert_eq!(resolve("Clip", &context), "Audio.Clip");
+        assert_eq!(resolve("P.Body", &context), "Engine.Physics.Body");
+        assert_eq!(resolve("Legacy::Old.Vector", &context), "Old.Vector");
+        assert_eq!(resolve("global::Engine", &context), "Engine");
+        assert_eq!(resolve("Engine.Nothing", &context), "the type or namespace name 'Nothing' does not exist in the \
+            namespace 'Engine'");
+        // using 只导入类型, 不导入嵌套的命名空间
+        assert_eq!(resolve("Physics", &context), "the type or namespace name 'Physics' could not be found");
+
+        let top = LookupContext::at(&node(SyntaxKind::FieldDeclaration));
+        assert_eq!(resolve("Vector", &top), "'Vector' is an ambiguous reference between 'Engine.Vector' and 'Audio.Vector'");
+        assert_eq!(resolve("Nope::Vector", &top), "the alias 'Nope' could not be found");
+        let errors: Vec<String> = declarations.check_names(&game.compilation_unit()).into_iter().map(|x| x.message).collect();
+        assert_eq!(errors, ["'Vector' is an ambiguous reference between 'Engine.Vector' and 'Audio.Vector'",
+            "the alias 'Nope' could not be found",
+            "the type or namespace name 'Nope' does not exist in the global namespace",
+            "the type or namespace name 'Missing' does not exist in the namespace 'Engine.Physics'"]);
+
+        let imported = declarations.lookup_imported("Max", &top).unwrap();
+        let owners: Vec<&str> = imported.iter().map(|x| x.0.as_str()).collect();
+        assert_eq!(owners, ["Util.MathF", "Util.Limits"]);
+        assert_eq!(declarations.lookup_imported("PI", &top).unwrap()[0].1.name, "PI");
+
+        let bad = parse("extern alias Missing; using Engine.Vector; using static Engine; using Nowhere; using A = Engine; \
+            using A = Audio;");
+        let errors: Vec<String> = declarations.check_usings(&bad.compilation_unit()).into_iter().map(|x| x.message).collect();
+        assert_eq!(errors, ["the extern alias 'Missing' was not specified in a reference",
+            "a 'using namespace' directive can only be applied to namespaces; 'Engine.Vector' is a type not a namespace, \
+                consider a 'using static' directive instead",
+            "a 'using static' directive can only be applied to types; 'Engine' is a namespace not a type",
+            "the type or namespace name 'Nowhere' could not be found",
+            "the using alias 'A' appeared previously in this namespace"]);
+    }
+}
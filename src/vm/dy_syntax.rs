@@ -0,0 +1,994 @@
+use std::fmt;
+use vm::dy_common::DyRef;
+use vm::dy_diagnostic::Diagnostic;
+use vm::dy_grammar::SemanticFlags;
+use vm::dy_parser::{Span, TokenKind, line_column};
+
+// 无损语法树: 所有 token (包括空白, 注释, 预处理指令) 都是叶子节点,
+// 按顺序拼起来就是源码
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntaxKind {
+    Token,
+    Error,
+
+    CompilationUnit,
+    ExternAliasDirective,
+    UsingDirective,
+    NamespaceDeclaration,
+    NamespaceBody,
+    AttributeList,
+    Attribute,
+    AttributeArgumentList,
+    AttributeArgument,
+
+    ClassDeclaration,
+    StructDeclaration,
+    InterfaceDeclaration,
+    EnumDeclaration,
+    DelegateDeclaration,
+    TypeParameterList,
+    TypeParameter,
+    TypeParameterConstraintClause,
+    BaseList,
+    TypeBody,
+    EnumBody,
+    EnumMemberDeclaration,
+
+    FieldDeclaration,
+    EventFieldDeclaration,
+    VariableDeclaration,
+    VariableDeclarator,
+    MethodDeclaration,
+    ConstructorDeclaration,
+    ConstructorInitializer,
+    DestructorDeclaration,
+    PropertyDeclaration,
+    IndexerDeclaration,
+    EventDeclaration,
+    OperatorDeclaration,
+    ConversionOperatorDeclaration,
+    ExplicitInterfaceSpecifier,
+    AccessorList,
+    AccessorDeclaration,
+    ParameterList,
+    BracketedParameterList,
+    Parameter,
+    ArrowExpressionClause,
+
+    // types
+    PredefinedType,
+    IdentifierName,
+    GenericName,
+    QualifiedName,
+    AliasQualifiedName,
+    TypeArgumentList,
+    ArrayType,
+    ArrayRankSpecifier,
+    NullableType,
+    TupleType,
+    TupleElement,
+
+    // statements
+    Block,
+    LocalDeclarationStatement,
+    ExpressionStatement,
+    EmptyStatement,
+    IfStatement,
+    ElseClause,
+    WhileStatement,
+    DoStatement,
+    ForStatement,
+    ForEachStatement,
+    SwitchStatement,
+    SwitchSection,
+    CaseSwitchLabel,
+    CasePatternSwitchLabel,
+    DefaultSwitchLabel,
+    WhenClause,
+    BreakStatement,
+    ContinueStatement,
+    ReturnStatement,
+    ThrowStatement,
+    TryStatement,
+    CatchClause,
+    CatchDeclaration,
+    CatchFilterClause,
+    FinallyClause,
+    UsingStatement,
+    LockStatement,
+    YieldReturnStatement,
+    YieldBreakStatement,
+    GotoStatement,
+    LabeledStatement,
+    CheckedStatement,
+
+    // expressions
+    LiteralExpression,
+    ThisExpression,
+    BaseExpression,
+    ParenthesizedExpression,
+    MemberAccessExpression,
+    ConditionalAccessExpression,
+    MemberBindingExpression,
+    ElementBindingExpression,
+    InvocationExpression,
+    ArgumentList,
+    BracketedArgumentList,
+    Argument,
+    DeclarationExpression,
+    ElementAccessExpression,
+    PostfixUnaryExpression,
+    PrefixUnaryExpression,
+    CastExpression,
+    AwaitExpression,
+    BinaryExpression,
+    AssignmentExpression,
+    ConditionalExpression,
+    IsExpression,
+    IsPatternExpression,
+    AsExpression,
+    ObjectCreationExpression,
+    ArrayCreationExpression,
+    ImplicitArrayCreationExpression,
+    InitializerExpression,
+    TupleExpression,
+    ObjectInitializerExpression,
+    CollectionInitializerExpression,
+    ComplexElementInitializerExpression,
+    ImplicitElementAccess,
+    AnonymousObjectCreationExpression,
+    AnonymousObjectMemberDeclarator,
+    TypeOfExpression,
+    DefaultExpression,
+    SizeOfExpression,
+    CheckedExpression,
+    LambdaExpression,
+    AnonymousMethodExpression,
+    SwitchExpression,
+    SwitchExpressionArm,
+
+    // patterns
+    ConstantPattern,
+    TypePattern,
+    DeclarationPattern,
+    VarPattern,
+    DiscardPattern,
+    RelationalPattern,
+    RecursivePattern,
+    PositionalPatternClause,
+    PropertyPatternClause,
+    Subpattern,
+    ParenthesizedPattern,
+    UnaryPattern,
+    BinaryPattern,
+    SingleVariableDesignation,
+    DiscardDesignation,
+    ParenthesizedVariableDesignation,
+}
+
+// 按声明顺序, from_name 用
+const SYNTAX_KINDS: [SyntaxKind; 145] = [SyntaxKind::Token, SyntaxKind::Error, SyntaxKind::CompilationUnit,
+    SyntaxKind::ExternAliasDirective, SyntaxKind::UsingDirective, SyntaxKind::NamespaceDeclaration,
+    SyntaxKind::NamespaceBody, SyntaxKind::AttributeList, SyntaxKind::Attribute, SyntaxKind::AttributeArgumentList,
+    SyntaxKind::AttributeArgument, SyntaxKind::ClassDeclaration, SyntaxKind::StructDeclaration,
+    SyntaxKind::InterfaceDeclaration, SyntaxKind::EnumDeclaration, SyntaxKind::DelegateDeclaration,
+    SyntaxKind::TypeParameterList, SyntaxKind::TypeParameter, SyntaxKind::TypeParameterConstraintClause,
+    SyntaxKind::BaseList, SyntaxKind::TypeBody, SyntaxKind::EnumBody, SyntaxKind::EnumMemberDeclaration,
+    SyntaxKind::FieldDeclaration, SyntaxKind::EventFieldDeclaration, SyntaxKind::VariableDeclaration,
+    SyntaxKind::VariableDeclarator, SyntaxKind::MethodDeclaration, SyntaxKind::ConstructorDeclaration,
+    SyntaxKind::ConstructorInitializer, SyntaxKind::DestructorDeclaration, SyntaxKind::PropertyDeclaration,
+    SyntaxKind::IndexerDeclaration, SyntaxKind::EventDeclaration, SyntaxKind::OperatorDeclaration,
+    SyntaxKind::ConversionOperatorDeclaration, SyntaxKind::ExplicitInterfaceSpecifier, SyntaxKind::AccessorList,
+    SyntaxKind::AccessorDeclaration, SyntaxKind::ParameterList, SyntaxKind::BracketedParameterList,
+    SyntaxKind::Parameter, SyntaxKind::ArrowExpressionClause, SyntaxKind::PredefinedType, SyntaxKind::IdentifierName,
+    SyntaxKind::GenericName, SyntaxKind::QualifiedName, SyntaxKind::AliasQualifiedName, SyntaxKind::TypeArgumentList,
+    SyntaxKind::ArrayType, SyntaxKind::ArrayRankSpecifier, SyntaxKind::NullableType, SyntaxKind::TupleType,
+    SyntaxKind::TupleElement, SyntaxKind::Block,
+    SyntaxKind::LocalDeclarationStatement, SyntaxKind::ExpressionStatement, SyntaxKind::EmptyStatement,
+    SyntaxKind::IfStatement, SyntaxKind::ElseClause, SyntaxKind::WhileStatement, SyntaxKind::DoStatement,
+    SyntaxKind::ForStatement, SyntaxKind::ForEachStatement, SyntaxKind::SwitchStatement, SyntaxKind::SwitchSection,
+    SyntaxKind::CaseSwitchLabel, SyntaxKind::CasePatternSwitchLabel, SyntaxKind::DefaultSwitchLabel,
+    SyntaxKind::WhenClause, SyntaxKind::BreakStatement,
+    SyntaxKind::ContinueStatement, SyntaxKind::ReturnStatement, SyntaxKind::ThrowStatement, SyntaxKind::TryStatement,
+    SyntaxKind::CatchClause, SyntaxKind::CatchDeclaration, SyntaxKind::CatchFilterClause, SyntaxKind::FinallyClause,
+    SyntaxKind::UsingStatement, SyntaxKind::LockStatement, SyntaxKind::YieldReturnStatement,
+    SyntaxKind::YieldBreakStatement, SyntaxKind::GotoStatement, SyntaxKind::LabeledStatement,
+    SyntaxKind::CheckedStatement, SyntaxKind::LiteralExpression, SyntaxKind::ThisExpression,
+    SyntaxKind::BaseExpression, SyntaxKind::ParenthesizedExpression, SyntaxKind::MemberAccessExpression,
+    SyntaxKind::ConditionalAccessExpression, SyntaxKind::MemberBindingExpression,
+    SyntaxKind::ElementBindingExpression, SyntaxKind::InvocationExpression, SyntaxKind::ArgumentList,
+    SyntaxKind::BracketedArgumentList, SyntaxKind::Argument, SyntaxKind::DeclarationExpression,
+    SyntaxKind::ElementAccessExpression, SyntaxKind::PostfixUnaryExpression, SyntaxKind::PrefixUnaryExpression,
+    SyntaxKind::CastExpression, SyntaxKind::AwaitExpression, SyntaxKind::BinaryExpression,
+    SyntaxKind::AssignmentExpression, SyntaxKind::ConditionalExpression, SyntaxKind::IsExpression,
+    SyntaxKind::IsPatternExpression, SyntaxKind::AsExpression, SyntaxKind::ObjectCreationExpression, SyntaxKind::ArrayCreationExpression,
+    SyntaxKind::ImplicitArrayCreationExpression, SyntaxKind::InitializerExpression, SyntaxKind::TupleExpression,
+    SyntaxKind::ObjectInitializerExpression, SyntaxKind::CollectionInitializerExpression,
+    SyntaxKind::ComplexElementInitializerExpression, SyntaxKind::ImplicitElementAccess,
+    SyntaxKind::AnonymousObjectCreationExpression, SyntaxKind::AnonymousObjectMemberDeclarator,
+    SyntaxKind::TypeOfExpression,
+    SyntaxKind::DefaultExpression, SyntaxKind::SizeOfExpression, SyntaxKind::CheckedExpression,
+    SyntaxKind::LambdaExpression, SyntaxKind::AnonymousMethodExpression, SyntaxKind::SwitchExpression,
+    SyntaxKind::SwitchExpressionArm, SyntaxKind::ConstantPattern, SyntaxKind::TypePattern,
+    SyntaxKind::DeclarationPattern, SyntaxKind::VarPattern, SyntaxKind::DiscardPattern, SyntaxKind::RelationalPattern,
+    SyntaxKind::RecursivePattern, SyntaxKind::PositionalPatternClause, SyntaxKind::PropertyPatternClause,
+    SyntaxKind::Subpattern, SyntaxKind::ParenthesizedPattern, SyntaxKind::UnaryPattern, SyntaxKind::BinaryPattern,
+    SyntaxKind::SingleVariableDesignation, SyntaxKind::DiscardDesignation,
+    SyntaxKind::ParenthesizedVariableDesignation];
+
+impl SyntaxKind {
+    pub fn from_name(name: &str) -> Option<SyntaxKind> {
+        SYNTAX_KINDS.iter().find(|x| format!("{:?}", x) == name).cloned()
+    }
+
+    pub fn is_type_declaration(&self) -> bool {
+        matches!(*self,
+            SyntaxKind::ClassDeclaration | SyntaxKind::StructDeclaration | SyntaxKind::InterfaceDeclaration
+            | SyntaxKind::EnumDeclaration | SyntaxKind::DelegateDeclaration)
+    }
+
+    pub fn is_type(&self) -> bool {
+        matches!(*self,
+            SyntaxKind::PredefinedType | SyntaxKind::IdentifierName | SyntaxKind::GenericName
+            | SyntaxKind::QualifiedName | SyntaxKind::AliasQualifiedName | SyntaxKind::ArrayType
+            | SyntaxKind::NullableType | SyntaxKind::TupleType)
+    }
+
+    pub fn is_statement(&self) -> bool {
+        matches!(*self,
+            SyntaxKind::Block | SyntaxKind::LocalDeclarationStatement | SyntaxKind::ExpressionStatement
+            | SyntaxKind::EmptyStatement | SyntaxKind::IfStatement | SyntaxKind::WhileStatement
+            | SyntaxKind::DoStatement | SyntaxKind::ForStatement | SyntaxKind::ForEachStatement
+            | SyntaxKind::SwitchStatement | SyntaxKind::BreakStatement | SyntaxKind::ContinueStatement
+            | SyntaxKind::ReturnStatement | SyntaxKind::ThrowStatement | SyntaxKind::TryStatement
+            | SyntaxKind::UsingStatement | SyntaxKind::LockStatement | SyntaxKind::YieldReturnStatement
+            | SyntaxKind::YieldBreakStatement | SyntaxKind::GotoStatement | SyntaxKind::LabeledStatement
+            | SyntaxKind::CheckedStatement)
+    }
+
+    pub fn is_expression(&self) -> bool {
+        matches!(*self,
+            SyntaxKind::LiteralExpression | SyntaxKind::ThisExpression | SyntaxKind::BaseExpression
+            | SyntaxKind::ParenthesizedExpression | SyntaxKind::MemberAccessExpression
+            | SyntaxKind::ConditionalAccessExpression | SyntaxKind::MemberBindingExpression
+            | SyntaxKind::ElementBindingExpression | SyntaxKind::InvocationExpression
+            | SyntaxKind::DeclarationExpression | SyntaxKind::ElementAccessExpression
+            | SyntaxKind::PostfixUnaryExpression | SyntaxKind::PrefixUnaryExpression | SyntaxKind::CastExpression
+            | SyntaxKind::AwaitExpression | SyntaxKind::BinaryExpression | SyntaxKind::AssignmentExpression
+            | SyntaxKind::ConditionalExpression
+            | SyntaxKind::IsExpression | SyntaxKind::IsPatternExpression | SyntaxKind::AsExpression
+            | SyntaxKind::SwitchExpression | SyntaxKind::ObjectCreationExpression
+            | SyntaxKind::ArrayCreationExpression | SyntaxKind::ImplicitArrayCreationExpression
+            | SyntaxKind::InitializerExpression | SyntaxKind::TupleExpression | SyntaxKind::ObjectInitializerExpression
+            | SyntaxKind::CollectionInitializerExpression | SyntaxKind::ComplexElementInitializerExpression
+            | SyntaxKind::ImplicitElementAccess | SyntaxKind::AnonymousObjectCreationExpression
+            | SyntaxKind::TypeOfExpression | SyntaxKind::DefaultExpression
+            | SyntaxKind::SizeOfExpression | SyntaxKind::CheckedExpression | SyntaxKind::LambdaExpression
+            | SyntaxKind::AnonymousMethodExpression | SyntaxKind::IdentifierName | SyntaxKind::GenericName
+            | SyntaxKind::QualifiedName | SyntaxKind::AliasQualifiedName | SyntaxKind::PredefinedType)
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        matches!(*self,
+            SyntaxKind::ConstantPattern | SyntaxKind::TypePattern | SyntaxKind::DeclarationPattern
+            | SyntaxKind::VarPattern | SyntaxKind::DiscardPattern | SyntaxKind::RelationalPattern
+            | SyntaxKind::RecursivePattern | SyntaxKind::ParenthesizedPattern | SyntaxKind::UnaryPattern
+            | SyntaxKind::BinaryPattern)
+    }
+}
+
+
+// 叶子节点的 token
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lexeme {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind: SyntaxKind,
+    // SemanticFlags: 低 8 位是声明类型, 高位是作用域类型
+    pub flags: i32,
+    pub span: Span,
+    // 只有叶子节点有 token
+    pub token: Option<Lexeme>,
+}
+
+impl SyntaxNode {
+    pub fn new(kind: SyntaxKind, flags: i32) -> SyntaxNode {
+        SyntaxNode {
+            kind,
+            flags,
+            span: Span::default(),
+            token: None,
+        }
+    }
+
+    pub fn token(kind: TokenKind, text: String, span: Span) -> SyntaxNode {
+        SyntaxNode {
+            kind: SyntaxKind::Token,
+            flags: 0,
+            span,
+            token: Some(Lexeme { kind, text }),
+        }
+    }
+
+    pub fn declaration(&self) -> Option<SemanticFlags> {
+        SemanticFlags::declaration(self.flags)
+    }
+
+    pub fn scope(&self) -> Option<SemanticFlags> {
+        SemanticFlags::scope(self.flags)
+    }
+}
+
+
+pub type SyntaxRef = DyRef<SyntaxNode>;
+
+impl DyRef<SyntaxNode> {
+    pub fn kind(&self) -> SyntaxKind {
+        self.borrow().kind
+    }
+
+    pub fn flags(&self) -> i32 {
+        self.borrow().flags
+    }
+
+    pub fn span(&self) -> Span {
+        self.borrow().span
+    }
+
+    pub fn declaration(&self) -> Option<SemanticFlags> {
+        self.borrow().declaration()
+    }
+
+    pub fn scope(&self) -> Option<SemanticFlags> {
+        self.borrow().scope()
+    }
+
+    pub fn is_token(&self) -> bool {
+        self.borrow().kind == SyntaxKind::Token
+    }
+
+    pub fn token_kind(&self) -> Option<TokenKind> {
+        self.borrow().token.as_ref().map(|x| x.kind)
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        self.token_kind().is_some_and(|x| x.is_trivia())
+    }
+
+    /// Source text of the node, trivia included.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for token in self.tokens() {
+            if let Some(ref x) = token.borrow().token {
+                text.push_str(&x.text);
+            }
+        }
+        text
+    }
+
+    /// Token text of a leaf, or the significant tokens of the node joined by a space.
+    pub fn token_text(&self) -> String {
+        if let Some(ref x) = self.borrow().token {
+            return x.text.clone();
+        }
+        let words: Vec<String> = self.significant_tokens().iter().map(|x| x.token_text()).collect();
+        words.join(" ")
+    }
+
+    /// Returns the leaves of this node in source order.
+    pub fn tokens(&self) -> Vec<SyntaxRef> {
+        self.traverse(true, false).filter(|x| x.is_token()).collect()
+    }
+
+    /// Returns the leaves of this node that are not trivia.
+    pub fn significant_tokens(&self) -> Vec<SyntaxRef> {
+        self.traverse(true, false).filter(|x| x.is_token() && !x.is_trivia()).collect()
+    }
+
+    pub fn child_nodes(&self) -> Vec<SyntaxRef> {
+        self.children().filter(|x| !x.is_token()).collect()
+    }
+
+    pub fn child_of(&self, kind: SyntaxKind) -> Option<SyntaxRef> {
+        self.children().find(|x| x.kind() == kind)
+    }
+
+    pub fn children_of(&self, kind: SyntaxKind) -> Vec<SyntaxRef> {
+        self.children().filter(|x| x.kind() == kind).collect()
+    }
+
+    /// Direct child token with the given text.
+    pub fn child_token(&self, text: &str) -> Option<SyntaxRef> {
+        self.children().find(|x| x.borrow().token.as_ref().is_some_and(|t| t.text == text && !t.kind.is_trivia()))
+    }
+
+    pub fn has_child_token(&self, text: &str) -> bool {
+        self.child_token(text).is_some()
+    }
+
+    /// Last direct identifier child, which is the declared name for declaration nodes
+    /// (contextual modifiers like `async` or `partial` come before it).
+    pub fn identifier(&self) -> Option<SyntaxRef> {
+        self.reverse_children().find(|x| x.token_kind() == Some(TokenKind::Identifier))
+    }
+
+    pub fn first_significant_token(&self) -> Option<SyntaxRef> {
+        self.traverse(true, false).find(|x| x.is_token() && !x.is_trivia())
+    }
+
+    pub fn last_significant_token(&self) -> Option<SyntaxRef> {
+        self.traverse(true, true).find(|x| x.is_token() && !x.is_trivia())
+    }
+
+    /// Nearest ancestor (self included) that opens a scope.
+    pub fn enclosing_scope(&self) -> Option<SyntaxRef> {
+        self.acestors().find(|x| x.scope().is_some())
+    }
+
+    /// Deepest node whose span covers `offset`.
+    pub fn covering_node(&self, offset: usize) -> SyntaxRef {
+        let mut node = self.clone();
+        loop {
+            let child = node.children().find(|x| !x.is_token() && covers(x.span(), offset));
+            match child {
+                Some(x) => node = x,
+                None => return node,
+            }
+        }
+    }
+}
+
+fn covers(span: Span, offset: usize) -> bool {
+    span.contains(offset) || (span.end == offset && !span.is_empty())
+}
+
+
+pub struct SyntaxTree {
+    pub root: SyntaxRef,
+    pub errors: Vec<Diagnostic>,
+    line_offset: Vec<usize>,
+}
+
+impl SyntaxTree {
+    pub fn new(root: SyntaxRef, errors: Vec<Diagnostic>, line_offset: Vec<usize>) -> SyntaxTree {
+        SyntaxTree { root, errors, line_offset }
+    }
+
+    pub fn compilation_unit(&self) -> CompilationUnit {
+        CompilationUnit(self.root.clone())
+    }
+
+    pub fn text(&self) -> String {
+        self.root.text()
+    }
+
+    pub fn line_offsets(&self) -> &Vec<usize> {
+        &self.line_offset
+    }
+
+    // 0 开始的行号和列号
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        line_column(&self.line_offset, offset)
+    }
+
+    pub fn node_at(&self, offset: usize) -> SyntaxRef {
+        self.root.covering_node(offset)
+    }
+
+    /// Innermost scope node at `offset`, e.g. the method body the cursor is in.
+    pub fn scope_at(&self, offset: usize) -> SyntaxRef {
+        self.node_at(offset).enclosing_scope().unwrap_or_else(|| self.root.clone())
+    }
+}
+
+impl fmt::Debug for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_node(f: &mut fmt::Formatter, node: &SyntaxRef, depth: usize) -> fmt::Result {
+            let data = node.borrow();
+            match data.token {
+                Some(ref x) => {
+                    if x.kind.is_trivia() {
+                        return Ok(());
+                    }
+                    writeln!(f, "{:indent$}{:?} {:?} {}..{}", "", x.kind, x.text, data.span.start, data.span.end, indent = depth * 2)
+                }
+                None => {
+                    writeln!(f, "{:indent$}{:?} {}..{}", "", data.kind, data.span.start, data.span.end, indent = depth * 2)?;
+                    for child in node.children() {
+                        write_node(f, &child, depth + 1)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+        write_node(f, &self.root, 0)?;
+        for error in self.errors.iter() {
+            writeln!(f, "error: {} at {}..{}", error.message, error.span.start, error.span.end)?;
+        }
+        Ok(())
+    }
+}
+
+
+// ---------------------------------- typed views ----------------------------------------------
+
+macro_rules! ast_node {
+    ($name: ident, $($kind: ident)|+) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name(pub SyntaxRef);
+
+        impl $name {
+            pub fn cast(node: SyntaxRef) -> Option<$name> {
+                match node.kind() {
+                    $(SyntaxKind::$kind)|+ => Some($name(node)),
+                    _ => None,
+                }
+            }
+
+            pub fn syntax(&self) -> &SyntaxRef {
+                &self.0
+            }
+        }
+    }
+}
+
+fn name_of(node: &SyntaxRef) -> Option<String> {
+    node.identifier().map(|x| x.token_text())
+}
+
+fn modifiers_of(node: &SyntaxRef) -> Vec<String> {
+    node.children()
+        .filter(|x| x.token_kind().is_some_and(|k| k == TokenKind::Keyword || k == TokenKind::Identifier))
+        .take_while(|x| MODIFIERS.contains(&x.token_text().as_str()))
+        .map(|x| x.token_text())
+        .collect()
+}
+
+pub const MODIFIERS: [&'static str; 17] = ["public", "private", "protected", "internal", "static", "readonly", "const",
+    "abstract", "virtual", "override", "sealed", "extern", "new", "unsafe", "volatile", "async", "partial"];
+
+fn extern_aliases_of(node: &SyntaxRef) -> Vec<String> {
+    node.children_of(SyntaxKind::ExternAliasDirective).iter().filter_map(name_of).collect()
+}
+
+fn cast_children<T, F: Fn(SyntaxRef) -> Option<T>>(node: &SyntaxRef, cast: F) -> Vec<T> {
+    node.children().filter_map(cast).collect()
+}
+
+
+ast_node!(CompilationUnit, CompilationUnit);
+ast_node!(UsingDirective, UsingDirective);
+ast_node!(NamespaceDeclaration, NamespaceDeclaration);
+ast_node!(TypeDeclaration, ClassDeclaration | StructDeclaration | InterfaceDeclaration | EnumDeclaration | DelegateDeclaration);
+ast_node!(MemberDeclaration, FieldDeclaration | EventFieldDeclaration | MethodDeclaration | ConstructorDeclaration
+    | DestructorDeclaration | PropertyDeclaration | IndexerDeclaration | EventDeclaration | OperatorDeclaration
+    | ConversionOperatorDeclaration | ClassDeclaration | StructDeclaration | InterfaceDeclaration | EnumDeclaration
+    | DelegateDeclaration);
+ast_node!(MethodDeclaration, MethodDeclaration | ConstructorDeclaration | DestructorDeclaration | OperatorDeclaration
+    | ConversionOperatorDeclaration);
+ast_node!(PropertyDeclaration, PropertyDeclaration | IndexerDeclaration);
+ast_node!(VariableDeclaration, VariableDeclaration);
+ast_node!(VariableDeclarator, VariableDeclarator);
+ast_node!(Parameter, Parameter);
+ast_node!(Block, Block);
+ast_node!(Statement, Block | LocalDeclarationStatement | ExpressionStatement | EmptyStatement | IfStatement
+    | WhileStatement | DoStatement | ForStatement | ForEachStatement | SwitchStatement | BreakStatement
+    | ContinueStatement | ReturnStatement | ThrowStatement | TryStatement | UsingStatement | LockStatement
+    | YieldReturnStatement | YieldBreakStatement | GotoStatement | LabeledStatement | CheckedStatement);
+ast_node!(Expression, LiteralExpression | ThisExpression | BaseExpression | ParenthesizedExpression
+    | MemberAccessExpression | ConditionalAccessExpression | MemberBindingExpression | ElementBindingExpression
+    | InvocationExpression | DeclarationExpression | ElementAccessExpression | PostfixUnaryExpression
+    | PrefixUnaryExpression | CastExpression | AwaitExpression | BinaryExpression | AssignmentExpression
+    | ConditionalExpression | IsExpression | IsPatternExpression | AsExpression | SwitchExpression | ObjectCreationExpression | ArrayCreationExpression
+    | ImplicitArrayCreationExpression | InitializerExpression | TupleExpression | ObjectInitializerExpression
+    | CollectionInitializerExpression | ComplexElementInitializerExpression | ImplicitElementAccess
+    | AnonymousObjectCreationExpression | TypeOfExpression | DefaultExpression
+    | SizeOfExpression | CheckedExpression | LambdaExpression | AnonymousMethodExpression | IdentifierName
+    | GenericName | QualifiedName | AliasQualifiedName | PredefinedType);
+ast_node!(TypeSyntax, PredefinedType | IdentifierName | GenericName | QualifiedName | AliasQualifiedName | ArrayType
+    | NullableType | TupleType);
+ast_node!(InvocationExpression, InvocationExpression);
+ast_node!(Argument, Argument);
+ast_node!(Pattern, ConstantPattern | TypePattern | DeclarationPattern | VarPattern | DiscardPattern
+    | RelationalPattern | RecursivePattern | ParenthesizedPattern | UnaryPattern | BinaryPattern);
+ast_node!(Subpattern, Subpattern);
+ast_node!(SwitchExpressionArm, SwitchExpressionArm);
+
+
+impl CompilationUnit {
+    pub fn extern_aliases(&self) -> Vec<String> {
+        extern_aliases_of(&self.0)
+    }
+
+    pub fn usings(&self) -> Vec<UsingDirective> {
+        cast_children(&self.0, UsingDirective::cast)
+    }
+
+    pub fn namespaces(&self) -> Vec<NamespaceDeclaration> {
+        cast_children(&self.0, NamespaceDeclaration::cast)
+    }
+
+    pub fn members(&self) -> Vec<MemberDeclaration> {
+        cast_children(&self.0, MemberDeclaration::cast)
+    }
+}
+
+impl UsingDirective {
+    pub fn is_static(&self) -> bool {
+        self.0.has_child_token("static")
+    }
+
+    pub fn alias(&self) -> Option<String> {
+        if self.0.has_child_token("=") {
+            return name_of(&self.0);
+        }
+        None
+    }
+
+    pub fn name(&self) -> Option<TypeSyntax> {
+        cast_children(&self.0, TypeSyntax::cast).pop()
+    }
+}
+
+impl NamespaceDeclaration {
+    pub fn name(&self) -> String {
+        cast_children(&self.0, TypeSyntax::cast).first().map_or(String::new(), |x| x.text())
+    }
+
+    fn body(&self) -> Option<SyntaxRef> {
+        self.0.child_of(SyntaxKind::NamespaceBody)
+    }
+
+    pub fn extern_aliases(&self) -> Vec<String> {
+        self.body().map_or(Vec::new(), |x| extern_aliases_of(&x))
+    }
+
+    pub fn usings(&self) -> Vec<UsingDirective> {
+        self.body().map_or(Vec::new(), |x| cast_children(&x, UsingDirective::cast))
+    }
+
+    pub fn namespaces(&self) -> Vec<NamespaceDeclaration> {
+        self.body().map_or(Vec::new(), |x| cast_children(&x, NamespaceDeclaration::cast))
+    }
+
+    pub fn members(&self) -> Vec<MemberDeclaration> {
+        self.body().map_or(Vec::new(), |x| cast_children(&x, MemberDeclaration::cast))
+    }
+}
+
+impl TypeDeclaration {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    pub fn name(&self) -> Option<String> {
+        name_of(&self.0)
+    }
+
+    pub fn modifiers(&self) -> Vec<String> {
+        modifiers_of(&self.0)
+    }
+
+    pub fn type_parameters(&self) -> Vec<String> {
+        self.0.child_of(SyntaxKind::TypeParameterList)
+            .map_or(Vec::new(), |x| x.children_of(SyntaxKind::TypeParameter).iter().filter_map(name_of).collect())
+    }
+
+    pub fn base_types(&self) -> Vec<TypeSyntax> {
+        self.0.child_of(SyntaxKind::BaseList).map_or(Vec::new(), |x| cast_children(&x, TypeSyntax::cast))
+    }
+
+    pub fn members(&self) -> Vec<MemberDeclaration> {
+        self.0.child_of(SyntaxKind::TypeBody).map_or(Vec::new(), |x| cast_children(&x, MemberDeclaration::cast))
+    }
+
+    pub fn enum_members(&self) -> Vec<SyntaxRef> {
+        self.0.child_of(SyntaxKind::EnumBody).map_or(Vec::new(), |x| x.children_of(SyntaxKind::EnumMemberDeclaration))
+    }
+}
+
+impl MemberDeclaration {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    pub fn name(&self) -> Option<String> {
+        name_of(&self.0)
+    }
+
+    pub fn modifiers(&self) -> Vec<String> {
+        modifiers_of(&self.0)
+    }
+
+    pub fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers().iter().any(|x| x == modifier)
+    }
+}
+
+impl MethodDeclaration {
+    pub fn name(&self) -> Option<String> {
+        name_of(&self.0)
+    }
+
+    pub fn modifiers(&self) -> Vec<String> {
+        modifiers_of(&self.0)
+    }
+
+    pub fn return_type(&self) -> Option<TypeSyntax> {
+        match self.0.kind() {
+            SyntaxKind::MethodDeclaration | SyntaxKind::OperatorDeclaration | SyntaxKind::ConversionOperatorDeclaration =>
+                cast_children(&self.0, TypeSyntax::cast).into_iter().next(),
+            _ => None,
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter> {
+        self.0.child_of(SyntaxKind::ParameterList).map_or(Vec::new(), |x| cast_children(&x, Parameter::cast))
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        cast_children(&self.0, Block::cast).pop()
+    }
+
+    pub fn expression_body(&self) -> Option<Expression> {
+        self.0.child_of(SyntaxKind::ArrowExpressionClause)
+            .and_then(|x| cast_children(&x, Expression::cast).pop())
+    }
+}
+
+impl PropertyDeclaration {
+    pub fn name(&self) -> Option<String> {
+        name_of(&self.0)
+    }
+
+    pub fn property_type(&self) -> Option<TypeSyntax> {
+        cast_children(&self.0, TypeSyntax::cast).into_iter().next()
+    }
+
+    pub fn accessors(&self) -> Vec<SyntaxRef> {
+        self.0.child_of(SyntaxKind::AccessorList).map_or(Vec::new(), |x| x.children_of(SyntaxKind::AccessorDeclaration))
+    }
+}
+
+impl VariableDeclaration {
+    pub fn variable_type(&self) -> Option<TypeSyntax> {
+        cast_children(&self.0, TypeSyntax::cast).into_iter().next()
+    }
+
+    pub fn declarators(&self) -> Vec<VariableDeclarator> {
+        cast_children(&self.0, VariableDeclarator::cast)
+    }
+}
+
+impl VariableDeclarator {
+    pub fn name(&self) -> Option<String> {
+        name_of(&self.0)
+    }
+
+    pub fn initializer(&self) -> Option<Expression> {
+        cast_children(&self.0, Expression::cast).pop()
+    }
+}
+
+impl Parameter {
+    pub fn name(&self) -> Option<String> {
+        name_of(&self.0)
+    }
+
+    pub fn modifiers(&self) -> Vec<String> {
+        self.0.children()
+            .filter(|x| x.token_kind() == Some(TokenKind::Keyword))
+            .map(|x| x.token_text())
+            .filter(|x| ["ref", "out", "in", "params", "this"].contains(&x.as_str()))
+            .collect()
+    }
+
+    pub fn parameter_type(&self) -> Option<TypeSyntax> {
+        cast_children(&self.0, TypeSyntax::cast).into_iter().next()
+    }
+
+    pub fn default_value(&self) -> Option<Expression> {
+        if self.0.has_child_token("=") {
+            return cast_children(&self.0, Expression::cast).pop();
+        }
+        None
+    }
+}
+
+impl Block {
+    pub fn statements(&self) -> Vec<Statement> {
+        cast_children(&self.0, Statement::cast)
+    }
+}
+
+impl Statement {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    pub fn expressions(&self) -> Vec<Expression> {
+        cast_children(&self.0, Expression::cast)
+    }
+
+    pub fn statements(&self) -> Vec<Statement> {
+        cast_children(&self.0, Statement::cast)
+    }
+}
+
+impl Expression {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    pub fn operands(&self) -> Vec<Expression> {
+        cast_children(&self.0, Expression::cast)
+    }
+
+    /// The first operator or keyword token directly under the node.
+    pub fn operator(&self) -> Option<String> {
+        self.0.children()
+            .find(|x| x.token_kind().is_some_and(|k| k == TokenKind::PunctuatorOrOperator || k == TokenKind::Keyword))
+            .map(|x| x.token_text())
+    }
+
+    pub fn text(&self) -> String {
+        self.0.token_text()
+    }
+}
+
+impl TypeSyntax {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    /// Normalized type text without trivia, e.g. `List<int>[]` or `(int x, string)`.
+    pub fn text(&self) -> String {
+        let words: Vec<String> = self.0.significant_tokens().iter().map(|x| x.token_text()).collect();
+        let mut text = String::new();
+        let is_word = |x: Option<char>| x.is_some_and(|x| x.is_alphanumeric() || x == '_');
+        for word in words.iter() {
+            if word == "," {
+                text.push_str(", ");
+                continue;
+            }
+            // (int x, int y)
+            if is_word(text.chars().last()) && is_word(word.chars().next()) {
+                text.push(' ');
+            }
+            text.push_str(word);
+        }
+        text
+    }
+}
+
+impl InvocationExpression {
+    pub fn callee(&self) -> Option<Expression> {
+        cast_children(&self.0, Expression::cast).into_iter().next()
+    }
+
+    pub fn arguments(&self) -> Vec<Argument> {
+        self.0.child_of(SyntaxKind::ArgumentList).map_or(Vec::new(), |x| cast_children(&x, Argument::cast))
+    }
+}
+
+impl Argument {
+    pub fn name(&self) -> Option<String> {
+        if self.0.has_child_token(":") {
+            return name_of(&self.0);
+        }
+        None
+    }
+
+    pub fn ref_kind(&self) -> Option<String> {
+        self.0.children()
+            .find(|x| x.token_kind() == Some(TokenKind::Keyword))
+            .map(|x| x.token_text())
+            .filter(|x| x == "ref" || x == "out" || x == "in")
+    }
+
+    pub fn expression(&self) -> Option<Expression> {
+        cast_children(&self.0, Expression::cast).pop()
+    }
+}
+
+impl Pattern {
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.kind()
+    }
+
+    /// Operands of `and`, `or`, `not` and parenthesized patterns.
+    pub fn patterns(&self) -> Vec<Pattern> {
+        cast_children(&self.0, Pattern::cast)
+    }
+
+    pub fn pattern_type(&self) -> Option<TypeSyntax> {
+        cast_children(&self.0, TypeSyntax::cast).into_iter().next()
+    }
+
+    /// The constant of a constant pattern or the operand of a relational one.
+    pub fn expression(&self) -> Option<Expression> {
+        match self.0.kind() {
+            SyntaxKind::ConstantPattern | SyntaxKind::RelationalPattern => cast_children(&self.0, Expression::cast).pop(),
+            _ => None,
+        }
+    }
+
+    /// `and`, `or`, `not` or the relational operator.
+    pub fn operator(&self) -> Option<String> {
+        self.0.children()
+            .find(|x| x.is_token() && !x.is_trivia() && x.token_text() != "(" && x.token_text() != ")")
+            .map(|x| x.token_text())
+    }
+
+    pub fn positional(&self) -> Vec<Subpattern> {
+        self.0.child_of(SyntaxKind::PositionalPatternClause).map_or(Vec::new(), |x| cast_children(&x, Subpattern::cast))
+    }
+
+    pub fn properties(&self) -> Vec<Subpattern> {
+        self.0.child_of(SyntaxKind::PropertyPatternClause).map_or(Vec::new(), |x| cast_children(&x, Subpattern::cast))
+    }
+
+    /// Variables declared directly by this pattern, `var (a, b)` gives both.
+    pub fn designations(&self) -> Vec<SyntaxRef> {
+        let designation = self.0.children().find(|x| {
+            matches!(x.kind(), SyntaxKind::SingleVariableDesignation | SyntaxKind::ParenthesizedVariableDesignation)
+        });
+        designation.map_or(Vec::new(), |x| {
+            x.traverse(true, false).filter(|x| x.kind() == SyntaxKind::SingleVariableDesignation).collect()
+        })
+    }
+}
+
+impl Subpattern {
+    /// `Hp` in `{ Hp: > 0 }`, `Target.Hp` for nested members.
+    pub fn name(&self) -> Option<String> {
+        if !self.0.has_child_token(":") {
+            return None;
+        }
+        let words: Vec<String> = self.0.children()
+            .filter(|x| x.is_token() && !x.is_trivia())
+            .take_while(|x| x.token_text() != ":")
+            .map(|x| x.token_text())
+            .collect();
+        Some(words.concat())
+    }
+
+    pub fn pattern(&self) -> Option<Pattern> {
+        cast_children(&self.0, Pattern::cast).pop()
+    }
+}
+
+impl SwitchExpressionArm {
+    pub fn pattern(&self) -> Option<Pattern> {
+        cast_children(&self.0, Pattern::cast).pop()
+    }
+
+    pub fn guard(&self) -> Option<Expression> {
+        self.0.child_of(SyntaxKind::WhenClause).and_then(|x| cast_children(&x, Expression::cast).pop())
+    }
+
+    pub fn expression(&self) -> Option<Expression> {
+        cast_children(&self.0, Expression::cast).pop()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kind_names() {
+        for (i, kind) in SYNTAX_KINDS.iter().enumerate() {
+            assert_eq!(*kind as usize, i);
+            assert_eq!(SyntaxKind::from_name(&format!("{:?}", kind)), Some(*kind));
+        }
+        assert_eq!(SyntaxKind::from_name("Nope"), None);
+    }
+}
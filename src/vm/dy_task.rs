@@ -0,0 +1,263 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::fmt;
+
+// ------------------------------------ tasks --------------------------------------------------
+// 脚本里 await 的对象. 宿主 (Rust) 在 I/O 或者引擎事件完成时 complete/fail, 执行器下次 run 时恢复等待它的脚本
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskState<V> {
+    Pending,
+    Completed(V),
+    Faulted(String),
+    Cancelled,
+}
+
+impl<V> TaskState<V> {
+    pub fn is_done(&self) -> bool {
+        !matches!(*self, TaskState::Pending)
+    }
+}
+
+/// Shared handle to a Task-like awaitable; clones refer to the same task.
+pub struct Task<V>(Rc<RefCell<TaskState<V>>>);
+
+impl<V> Clone for Task<V> {
+    fn clone(&self) -> Self {
+        Task(Rc::clone(&self.0))
+    }
+}
+
+impl<V> PartialEq for Task<V> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Task<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Task({:?})", self.0.borrow())
+    }
+}
+
+impl<V: Clone> Task<V> {
+    pub fn new() -> Task<V> {
+        Task(Rc::new(RefCell::new(TaskState::Pending)))
+    }
+
+    /// `Task.FromResult(value)`.
+    pub fn from_result(value: V) -> Task<V> {
+        Task(Rc::new(RefCell::new(TaskState::Completed(value))))
+    }
+
+    pub fn state(&self) -> TaskState<V> {
+        self.0.borrow().clone()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.0.borrow().is_done()
+    }
+
+    pub fn result(&self) -> Option<V> {
+        match *self.0.borrow() {
+            TaskState::Completed(ref value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Completes the task, returns false if it was already done.
+    pub fn complete(&self, value: V) -> bool {
+        self.finish(TaskState::Completed(value))
+    }
+
+    pub fn fail(&self, message: &str) -> bool {
+        self.finish(TaskState::Faulted(message.to_string()))
+    }
+
+    pub fn cancel(&self) -> bool {
+        self.finish(TaskState::Cancelled)
+    }
+
+    fn finish(&self, state: TaskState<V>) -> bool {
+        let mut current = self.0.borrow_mut();
+        if current.is_done() {
+            return false;
+        }
+        *current = state;
+        true
+    }
+}
+
+impl<V: Clone> Default for Task<V> {
+    fn default() -> Self {
+        Task::new()
+    }
+}
+
+// ------------------------------------ executor -----------------------------------------------
+// 编译后的 async 方法实现 Coroutine: resume 一直执行到下一个 await 或者 return
+// 执行器不自己开线程, 由宿主在合适的时候 (比如每帧, 或者 I/O 回调之后) 调用 run_until_stalled
+
+pub enum Poll<V> {
+    /// The coroutine returned, its task completes with the value.
+    Ready(V),
+    /// The coroutine is suspended on `await task`.
+    Await(Task<V>),
+    /// An exception escaped the coroutine.
+    Faulted(String),
+}
+
+pub trait Coroutine<V> {
+    /// Runs until the next await or the end. `input` is None on the first resume,
+    /// afterwards the final state of the awaited task.
+    fn resume(&mut self, input: Option<TaskState<V>>) -> Poll<V>;
+}
+
+struct Entry<V> {
+    coroutine: Box<dyn Coroutine<V>>,
+    task: Task<V>,
+    awaiting: Option<Task<V>>,
+    started: bool,
+}
+
+pub struct Executor<V> {
+    entries: Vec<Entry<V>>,
+}
+
+impl<V: Clone> Executor<V> {
+    pub fn new() -> Executor<V> {
+        Executor { entries: Vec::new() }
+    }
+
+    /// Schedules a coroutine, returns the task it completes. Nothing runs before `run_until_stalled`.
+    pub fn spawn(&mut self, coroutine: Box<dyn Coroutine<V>>) -> Task<V> {
+        let task = Task::new();
+        self.entries.push(Entry { coroutine, task: task.clone(), awaiting: None, started: false });
+        task
+    }
+
+    /// Number of coroutines that have not finished.
+    pub fn pending(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Resumes every coroutine whose awaited task is done until none can make progress.
+    /// Returns the number of resumes.
+    pub fn run_until_stalled(&mut self) -> usize {
+        let mut resumes = 0;
+        loop {
+            let ready = self.entries.iter().position(|x| !x.started || x.awaiting.as_ref().is_some_and(Task::is_done)
+                || x.task.is_done());
+            let index = match ready {
+                Some(index) => index,
+                None => return resumes,
+            };
+            // 外部取消了脚本自己的 task, 直接丢掉协程
+            if self.entries[index].task.is_done() {
+                self.entries.remove(index);
+                continue;
+            }
+            let poll = {
+                let entry = &mut self.entries[index];
+                let input = entry.awaiting.take().map(|x| x.state());
+                entry.started = true;
+                entry.coroutine.resume(input)
+            };
+            resumes += 1;
+            match poll {
+                Poll::Await(task) => self.entries[index].awaiting = Some(task),
+                Poll::Ready(value) => {
+                    self.entries.remove(index).task.complete(value);
+                }
+                Poll::Faulted(message) => {
+                    self.entries.remove(index).task.fail(&message);
+                }
+            }
+        }
+    }
+}
+
+impl<V: Clone> Default for Executor<V> {
+    fn default() -> Self {
+        Executor::new()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 手写的状态机, 相当于
+    // async Task<int> Sum(Task<int> a, Task<int> b) { var x = await a; var y = await b; return x + y; }
+    struct Sum {
+        state: usize,
+        a: Task<i32>,
+        b: Task<i32>,
+        x: i32,
+    }
+
+    impl Coroutine<i32> for Sum {
+        fn resume(&mut self, input: Option<TaskState<i32>>) -> Poll<i32> {
+            let value = match input {
+                Some(TaskState::Completed(value)) => value,
+                Some(TaskState::Faulted(message)) => return Poll::Faulted(message),
+                Some(TaskState::Cancelled) => return Poll::Faulted("task was cancelled".to_string()),
+                Some(TaskState::Pending) | None => 0,
+            };
+            self.state += 1;
+            match self.state {
+                1 => Poll::Await(self.a.clone()),
+                2 => {
+                    self.x = value;
+                    Poll::Await(self.b.clone())
+                }
+                _ => Poll::Ready(self.x + value),
+            }
+        }
+    }
+
+    fn sum(a: &Task<i32>, b: &Task<i32>) -> Box<Sum> {
+        Box::new(Sum { state: 0, a: a.clone(), b: b.clone(), x: 0 })
+    }
+
+    #[test]
+    fn test_executor() {
+        let mut executor = Executor::new();
+        let (a, b) = (Task::new(), Task::new());
+        let task = executor.spawn(sum(&a, &b));
+        assert_eq!(executor.run_until_stalled(), 1);
+        assert_eq!(task.state(), TaskState::Pending);
+
+        // 宿主完成 I/O
+        assert!(a.complete(1));
+        assert!(!a.complete(5));
+        assert_eq!(executor.run_until_stalled(), 1);
+        b.complete(2);
+        assert_eq!(executor.run_until_stalled(), 1);
+        assert_eq!(task.result(), Some(3));
+        assert_eq!(executor.pending(), 0);
+
+        // 等待另一个脚本, 已完成的 task 在同一次 run 里继续
+        let c = Task::new();
+        let inner = executor.spawn(sum(&c, &Task::from_result(10)));
+        let outer = executor.spawn(sum(&inner, &Task::from_result(100)));
+        executor.run_until_stalled();
+        c.complete(1);
+        assert_eq!(executor.run_until_stalled(), 4);
+        assert_eq!(outer.result(), Some(111));
+
+        let d = Task::new();
+        let faulted = executor.spawn(sum(&d, &d));
+        executor.run_until_stalled();
+        d.fail("connection reset");
+        executor.run_until_stalled();
+        assert_eq!(faulted.state(), TaskState::Faulted("connection reset".to_string()));
+
+        let cancelled = executor.spawn(sum(&Task::new(), &Task::new()));
+        executor.run_until_stalled();
+        cancelled.cancel();
+        assert_eq!(executor.run_until_stalled(), 0);
+        assert_eq!(executor.pending(), 0);
+    }
+}
@@ -0,0 +1,451 @@
+// ------------------------------------ types --------------------------------------------------
+// 类型规则, 类型用源码里的写法表示: "int", "int?", "Nullable<int>", "string"
+// 用户的结构和枚举也是值类型, 要通过 UserTypes 问声明
+
+const NUMERIC_TYPES: [&'static str; 12] = ["sbyte", "byte", "short", "ushort", "int", "uint", "long", "ulong", "char",
+    "float", "double", "decimal"];
+
+// 二元运算的数值提升, 排在前面的优先
+const PROMOTIONS: [&'static str; 7] = ["decimal", "double", "float", "ulong", "long", "uint", "int"];
+
+const ARITHMETIC_OPERATORS: [&'static str; 5] = ["+", "-", "*", "/", "%"];
+
+const COMPARISON_OPERATORS: [&'static str; 6] = ["==", "!=", "<", ">", "<=", ">="];
+
+pub fn is_numeric(name: &str) -> bool {
+    NUMERIC_TYPES.contains(&name)
+}
+
+pub fn is_integral(name: &str) -> bool {
+    is_numeric(name) && !["float", "double", "decimal"].contains(&name)
+}
+
+/// What the type rules need to know about the types a program declares.
+pub trait UserTypes {
+    /// Whether `name` is a user struct or enum.
+    fn is_user_value_type(&self, name: &str) -> bool;
+}
+
+/// No user types, for rules that only see predefined types.
+impl UserTypes for () {
+    fn is_user_value_type(&self, _: &str) -> bool {
+        false
+    }
+}
+
+/// Predefined structs, tuples and user structs and enums.
+pub fn is_value_type(name: &str, types: &dyn UserTypes) -> bool {
+    is_numeric(name) || name == "bool" || name.starts_with('(') || types.is_user_value_type(name)
+        || underlying_type(name, types).is_some()
+}
+
+/// `int` for `int?`, `Nullable<int>` and `System.Nullable<int>`. `string?` is a nullable annotation on a
+/// reference type, not `Nullable<string>`.
+pub fn underlying_type<'a>(name: &'a str, types: &dyn UserTypes) -> Option<&'a str> {
+    let name = name.trim();
+    if let Some(name) = name.strip_suffix('?') {
+        return Some(name.trim()).filter(|x| is_value_type(x, types));
+    }
+    let name = name.strip_prefix("System.").unwrap_or(name);
+    name.strip_prefix("Nullable<").and_then(|x| x.strip_suffix('>')).map(|x| x.trim())
+}
+
+pub fn is_nullable_value_type(name: &str, types: &dyn UserTypes) -> bool {
+    underlying_type(name, types).is_some()
+}
+
+/// `T?` for a non-nullable value type, otherwise the type itself.
+pub fn make_nullable(name: &str, types: &dyn UserTypes) -> String {
+    if is_value_type(name, types) && !is_nullable_value_type(name, types) {
+        return format!("{}?", name);
+    }
+    name.to_string()
+}
+
+fn strip_nullable<'a>(name: &'a str, types: &dyn UserTypes) -> &'a str {
+    underlying_type(name, types).unwrap_or(name)
+}
+
+fn implicit_numeric(from: &str, to: &str) -> bool {
+    let targets: &[&str] = match from {
+        "sbyte" => &["short", "int", "long", "float", "double", "decimal"],
+        "byte" => &["short", "ushort", "int", "uint", "long", "ulong", "float", "double", "decimal"],
+        "short" => &["int", "long", "float", "double", "decimal"],
+        "ushort" => &["int", "uint", "long", "ulong", "float", "double", "decimal"],
+        "int" => &["long", "float", "double", "decimal"],
+        "uint" => &["long", "ulong", "float", "double", "decimal"],
+        "long" | "ulong" => &["float", "double", "decimal"],
+        "char" => &["ushort", "int", "uint", "long", "ulong", "float", "double", "decimal"],
+        "float" => &["double"],
+        _ => &[],
+    };
+    targets.contains(&to)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conversion {
+    Identity,
+    /// `int` to `long`.
+    ImplicitNumeric,
+    /// `T` to `T?`, `int` to `long?`, `int?` to `long?`.
+    ImplicitNullable,
+    /// `null` to `T?`.
+    NullLiteral,
+    /// `T?` to `T` or `int?` to `long`; throws when the value is null, needs a cast.
+    ExplicitNullable,
+    None,
+}
+
+impl Conversion {
+    pub fn is_implicit(self) -> bool {
+        !matches!(self, Conversion::ExplicitNullable | Conversion::None)
+    }
+}
+
+/// Conversion from `from` to `to`; the type of the `null` literal is written `null`.
+pub fn classify_conversion(from: &str, to: &str, types: &dyn UserTypes) -> Conversion {
+    let (from, to) = (from.trim(), to.trim());
+    let is_nullable = |x: &str| is_nullable_value_type(x, types);
+    let is_same = |x: &str, y: &str| strip_nullable(x, types) == strip_nullable(y, types);
+    if from == to || (is_nullable(from) && is_nullable(to) && is_same(from, to)) {
+        return Conversion::Identity;
+    }
+    if from == "null" {
+        return if is_nullable(to) || !is_value_type(to, types) { Conversion::NullLiteral } else { Conversion::None };
+    }
+    let underlying = |x: &str, y: &str| x == y || implicit_numeric(x, y);
+    match (underlying_type(from, types), underlying_type(to, types)) {
+        (None, None) if implicit_numeric(from, to) => Conversion::ImplicitNumeric,
+        (None, Some(to)) if underlying(from, to) => Conversion::ImplicitNullable,
+        (Some(from), Some(to)) if underlying(from, to) => Conversion::ImplicitNullable,
+        (Some(from), None) if underlying(from, to) => Conversion::ExplicitNullable,
+        _ => Conversion::None,
+    }
+}
+
+fn promote(left: &str, right: &str) -> Option<&'static str> {
+    if !is_numeric(left) || !is_numeric(right) {
+        return None;
+    }
+    // decimal 不能和 float/double 混用, ulong 不能和有符号整数混用
+    let mixes = |a: &str, b: &[&str]| (left == a && b.contains(&right)) || (right == a && b.contains(&left));
+    if mixes("decimal", &["float", "double"]) || mixes("ulong", &["sbyte", "short", "int", "long"]) {
+        return None;
+    }
+    for promotion in PROMOTIONS.iter() {
+        if left == *promotion || right == *promotion {
+            // uint 和有符号整数一起提升到 long
+            if *promotion == "uint" && ["sbyte", "short", "int"].iter().any(|x| left == *x || right == *x) {
+                return Some("long");
+            }
+            return Some(promotion);
+        }
+    }
+    Some("int")
+}
+
+/// Result type of `left op right` for non-nullable operands.
+pub fn binary_result(op: &str, left: &str, right: &str) -> Option<String> {
+    if ARITHMETIC_OPERATORS.contains(&op) {
+        return promote(left, right).map(|x| x.to_string());
+    }
+    if op == "<<" || op == ">>" {
+        if is_integral(left) && (right == "int" || implicit_numeric(right, "int")) {
+            return promote(left, "int").map(|x| x.to_string());
+        }
+        return None;
+    }
+    if ["&", "|", "^"].contains(&op) {
+        if left == "bool" && right == "bool" {
+            return Some("bool".to_string());
+        }
+        if is_integral(left) && is_integral(right) {
+            return promote(left, right).map(|x| x.to_string());
+        }
+        return None;
+    }
+    if COMPARISON_OPERATORS.contains(&op) {
+        let equality = (op == "==" || op == "!=") && left == "bool" && right == "bool";
+        return if equality || promote(left, right).is_some() { Some("bool".to_string()) } else { None };
+    }
+    if op == "&&" || op == "||" {
+        return if left == "bool" && right == "bool" { Some("bool".to_string()) } else { None };
+    }
+    None
+}
+
+/// Result type of `left op right` with lifting: arithmetic, shift and logical operators on `T?` give `R?`,
+/// comparisons stay `bool`, `&&`/`||` are not lifted.
+pub fn lifted_binary(op: &str, left: &str, right: &str, types: &dyn UserTypes) -> Option<String> {
+    let lifted = is_nullable_value_type(left, types) || is_nullable_value_type(right, types)
+        || left == "null" || right == "null";
+    if !lifted {
+        return binary_result(op, left, right);
+    }
+    if op == "&&" || op == "||" {
+        return None;
+    }
+    // x == null 这种和 null 字面量比较
+    let left = if left == "null" { strip_nullable(right, types) } else { strip_nullable(left, types) };
+    let right = if right == "null" { left } else { strip_nullable(right, types) };
+    let result = binary_result(op, left, right)?;
+    if COMPARISON_OPERATORS.contains(&op) {
+        return Some(result);
+    }
+    Some(make_nullable(&result, types))
+}
+
+/// Result type of `left ?? right`.
+pub fn coalesce_result(left: &str, right: &str, types: &dyn UserTypes) -> Option<String> {
+    if is_value_type(left, types) && !is_nullable_value_type(left, types) {
+        return None;
+    }
+    let underlying = strip_nullable(left, types);
+    if classify_conversion(right, underlying, types).is_implicit() && !is_nullable_value_type(right, types)
+        && right != "null" {
+        return Some(underlying.to_string());
+    }
+    if classify_conversion(right, left, types).is_implicit() {
+        return Some(left.to_string());
+    }
+    None
+}
+
+// ------------------------------------ generic types ------------------------------------------
+
+/// `List` for `List<int>`.
+pub fn generic_name(name: &str) -> &str {
+    match name.find('<') {
+        Some(i) => name[..i].trim(),
+        None => name.trim(),
+    }
+}
+
+/// `[string, List<int>]` for `Dictionary<string, List<int>>`.
+pub fn type_arguments(name: &str) -> Vec<String> {
+    match (name.find('<'), name.rfind('>')) {
+        (Some(start), Some(end)) if start < end => split_list(&name[start + 1..end]),
+        _ => Vec::new(),
+    }
+}
+
+/// Element types and names of a tuple type: `[(int, Some(X)), (List<int, int>, None)]` for
+/// `(int X, List<int, int>)`.
+pub fn tuple_elements(name: &str) -> Vec<(String, Option<String>)> {
+    let inner = match name.strip_prefix('(').and_then(|x| x.strip_suffix(')')) {
+        Some(inner) => inner,
+        None => return Vec::new(),
+    };
+    split_list(inner).into_iter().map(|element| {
+        let split = element.rfind(' ').map(|i| (&element[..i], &element[i + 1..]))
+            .filter(|x| x.1.chars().all(|c| c.is_alphanumeric() || c == '_'));
+        match split {
+            Some((element_type, name)) => (element_type.trim().to_string(), Some(name.to_string())),
+            None => (element, None),
+        }
+    }).collect()
+}
+
+// 按最外层的逗号分开
+fn split_list(inner: &str) -> Vec<String> {
+    let mut arguments = vec![String::new()];
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        arguments.last_mut().unwrap().push(c);
+    }
+    arguments.iter().map(|x| x.trim().to_string()).collect()
+}
+
+/// Whether `name` mentions one of `type_parameters`, as `T` in `List<T[]>`.
+pub fn uses_type_parameters(name: &str, type_parameters: &[String]) -> bool {
+    name.split(|x: char| !x.is_alphanumeric() && x != '_' && x != '.').any(|x| type_parameters.iter().any(|y| y == x))
+}
+
+/// Replaces type parameters by name: `List<T>` with `T` = `int` is `List<int>`.
+pub fn substitute_type(name: &str, arguments: &[(String, String)]) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    let replace = |word: &mut String, result: &mut String| {
+        match arguments.iter().find(|x| x.0 == *word) {
+            Some(x) => result.push_str(&x.1),
+            None => result.push_str(word),
+        }
+        word.clear();
+    };
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || (c == '.' && !word.is_empty()) {
+            word.push(c);
+            continue;
+        }
+        replace(&mut word, &mut result);
+        result.push(c);
+    }
+    replace(&mut word, &mut result);
+    result
+}
+
+/// Parameter types and return type of the delegate types every host has: `Func<int, bool>` is
+/// `([int], bool)`, `Action<string>` is `([string], void)`; None for other types.
+pub fn delegate_signature(name: &str) -> Option<(Vec<String>, String)> {
+    let mut arguments = type_arguments(name);
+    match generic_name(name) {
+        "Func" => arguments.pop().map(|returns| (arguments, returns)),
+        "Action" => Some((arguments, "void".to_string())),
+        "Predicate" if arguments.len() == 1 => Some((arguments, "bool".to_string())),
+        "Comparison" if arguments.len() == 1 => Some((vec![arguments[0].clone(), arguments[0].clone()], "int".to_string())),
+        _ => None,
+    }
+}
+
+// ------------------------------------ lifted operators ---------------------------------------
+// 运行时的提升运算, 值类型的 null 用 None 表示
+
+/// `a op b` on `T?`: null if either side is null.
+pub fn lift<A, B, R, F: FnOnce(A, B) -> R>(left: Option<A>, right: Option<B>, op: F) -> Option<R> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(op(left, right)),
+        _ => None,
+    }
+}
+
+/// Lifted comparison: `==` treats two nulls as equal, the relational operators are false if either side is null.
+pub fn lifted_compare<T: PartialOrd>(op: &str, left: Option<T>, right: Option<T>) -> bool {
+    match op {
+        "==" => left == right,
+        "!=" => left != right,
+        _ => match (left, right) {
+            (Some(left), Some(right)) => match op {
+                "<" => left < right,
+                ">" => left > right,
+                "<=" => left <= right,
+                ">=" => left >= right,
+                _ => false,
+            },
+            _ => false,
+        },
+    }
+}
+
+/// `bool? & bool?`: false wins over null.
+pub fn lifted_and(left: Option<bool>, right: Option<bool>) -> Option<bool> {
+    match (left, right) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+/// `bool? | bool?`: true wins over null.
+pub fn lifted_or(left: Option<bool>, right: Option<bool>) -> Option<bool> {
+    match (left, right) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nullable_conversions() {
+        assert_eq!(underlying_type("int?", &()), Some("int"));
+        assert_eq!(underlying_type("System.Nullable<double>", &()), Some("double"));
+        assert_eq!(underlying_type("string", &()), None);
+        assert_eq!(underlying_type("string?", &()), None);
+        assert_eq!(underlying_type("Player?", &()), None);
+        assert_eq!(underlying_type("(int, string)?", &()), Some("(int, string)"));
+        assert!(!is_value_type("string?", &()) && !is_value_type("Player?", &()));
+        assert_eq!(make_nullable("int", &()), "int?");
+        assert_eq!(make_nullable("int?", &()), "int?");
+        assert_eq!(make_nullable("string", &()), "string");
+
+        assert_eq!(classify_conversion("int", "int?", &()), Conversion::ImplicitNullable);
+        assert_eq!(classify_conversion("int", "long?", &()), Conversion::ImplicitNullable);
+        assert_eq!(classify_conversion("int?", "Nullable<int>", &()), Conversion::Identity);
+        assert_eq!(classify_conversion("int?", "int", &()), Conversion::ExplicitNullable);
+        assert_eq!(classify_conversion("long?", "int?", &()), Conversion::None);
+        assert_eq!(classify_conversion("null", "int?", &()), Conversion::NullLiteral);
+        assert_eq!(classify_conversion("null", "string", &()), Conversion::NullLiteral);
+        assert_eq!(classify_conversion("null", "int", &()), Conversion::None);
+        assert_eq!(classify_conversion("byte", "int", &()), Conversion::ImplicitNumeric);
+        assert!(!classify_conversion("int?", "int", &()).is_implicit());
+        assert_eq!(classify_conversion("string?", "string", &()), Conversion::None);
+        assert_eq!(classify_conversion("Player", "Player?", &()), Conversion::None);
+        assert_eq!(classify_conversion("null", "Player?", &()), Conversion::NullLiteral);
+
+        // 用户的结构和枚举是值类型, S? 和 int? 一样
+        struct Declared;
+        impl UserTypes for Declared {
+            fn is_user_value_type(&self, name: &str) -> bool {
+                name == "S" || name == "E"
+            }
+        }
+        assert_eq!(underlying_type("S?", &Declared), Some("S"));
+        assert!(is_value_type("E", &Declared) && !is_value_type("Player", &Declared));
+        assert_eq!(make_nullable("S", &Declared), "S?");
+        assert_eq!(classify_conversion("S", "S?", &Declared), Conversion::ImplicitNullable);
+        assert_eq!(classify_conversion("S?", "S", &Declared), Conversion::ExplicitNullable);
+        assert_eq!(classify_conversion("null", "S", &Declared), Conversion::None);
+        assert_eq!(coalesce_result("S?", "S", &Declared).as_deref(), Some("S"));
+    }
+
+    #[test]
+    fn test_lifted_operators() {
+        let result = |op, left, right| lifted_binary(op, left, right, &());
+        assert_eq!(result("+", "int", "int").as_deref(), Some("int"));
+        assert_eq!(result("+", "int?", "long").as_deref(), Some("long?"));
+        assert_eq!(result("*", "byte?", "byte?").as_deref(), Some("int?"));
+        assert_eq!(result("+", "uint", "int?").as_deref(), Some("long?"));
+        assert_eq!(result("<", "int?", "double").as_deref(), Some("bool"));
+        assert_eq!(result("==", "int?", "null").as_deref(), Some("bool"));
+        assert_eq!(result("&", "bool?", "bool").as_deref(), Some("bool?"));
+        assert_eq!(result("&&", "bool?", "bool"), None);
+        assert_eq!(result("+", "decimal?", "double"), None);
+        assert_eq!(result("+", "string?", "int"), None);
+        assert_eq!(result("<", "Player?", "Player?"), None);
+        assert_eq!(coalesce_result("int?", "int", &()).as_deref(), Some("int"));
+        assert_eq!(coalesce_result("int?", "int?", &()).as_deref(), Some("int?"));
+        assert_eq!(coalesce_result("string", "null", &()).as_deref(), Some("string"));
+        assert_eq!(coalesce_result("int", "int", &()), None);
+
+        assert_eq!(lift(Some(1), Some(2), |a, b| a + b), Some(3));
+        assert_eq!(lift(Some(1), None, |a: i32, b: i32| a + b), None);
+        assert!(lifted_compare("==", None::<i32>, None));
+        assert!(!lifted_compare("<=", None, Some(1)));
+        assert!(lifted_compare(">", Some(2), Some(1)));
+        assert_eq!(lifted_and(None, Some(false)), Some(false));
+        assert_eq!(lifted_and(None, Some(true)), None);
+        assert_eq!(lifted_or(None, Some(true)), Some(true));
+        assert_eq!(lifted_or(Some(false), Some(false)), Some(false));
+    }
+
+    #[test]
+    fn test_generic_types() {
+        assert_eq!(generic_name("Dictionary<string, int>"), "Dictionary");
+        assert_eq!(type_arguments("Dictionary<string, List<(int, int)>>"), ["string", "List<(int, int)>"]);
+        assert!(type_arguments("int[]").is_empty());
+        assert_eq!(tuple_elements("(int X, Dictionary<int, string>, (int, int) P)"), [("int".to_string(),
+            Some("X".to_string())), ("Dictionary<int, string>".to_string(), None), ("(int, int)".to_string(),
+            Some("P".to_string()))]);
+        let arguments = [("T".to_string(), "int".to_string()), ("U".to_string(), "N.Point".to_string())];
+        assert_eq!(substitute_type("Dictionary<T, List<U>>", &arguments), "Dictionary<int, List<N.Point>>");
+        assert_eq!(substitute_type("T[]", &arguments), "int[]");
+        assert_eq!(substitute_type("Tuple<TT, N.T>", &arguments), "Tuple<TT, N.T>");
+        assert_eq!(delegate_signature("Func<int, string, bool>"), Some((vec!["int".to_string(), "string".to_string()],
+            "bool".to_string())));
+        assert_eq!(delegate_signature("Action"), Some((Vec::new(), "void".to_string())));
+        assert_eq!(delegate_signature("List<int>"), None);
+    }
+}
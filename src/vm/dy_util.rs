@@ -1,4 +1,3 @@
-use std::rc::Rc;
 use std::rc::Weak;
 //use std::ops::Index;
 
@@ -20,6 +19,7 @@ impl<T> VecExtend for Vec<T> {
     }
 }
 
+#[allow(dead_code)]
 pub trait WeakExtend {
 	// type ItemType;
 	// borrow_mut 已经有实现了
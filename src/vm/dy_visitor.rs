@@ -0,0 +1,316 @@
+use vm::dy_syntax::{SyntaxKind, SyntaxRef};
+
+// ------------------------------------ visitor ------------------------------------------------
+// 每种节点都有一个 visit 方法, 默认实现遍历子节点, 重写某个方法时调用 walk 继续遍历
+
+/// Change to apply to the visited node when a `VisitorMut` method returns.
+pub enum Edit {
+    Keep,
+    /// Put the nodes in place of the visited one.
+    Replace(Vec<SyntaxRef>),
+    Remove,
+    InsertBefore(Vec<SyntaxRef>),
+    InsertAfter(Vec<SyntaxRef>),
+}
+
+/// Visits every child of `node`.
+pub fn walk<V: Visitor + ?Sized>(visitor: &mut V, node: &SyntaxRef) {
+    for child in node.children() {
+        visitor.visit(&child);
+    }
+}
+
+/// Visits every child of `node` and applies the returned edits.
+/// Nodes added by an edit are not visited.
+pub fn walk_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &SyntaxRef) {
+    // 先取出子节点, 编辑不影响遍历
+    let children: Vec<SyntaxRef> = node.children().collect();
+    for child in children {
+        let edit = visitor.visit_mut(&child);
+        apply(&child, edit);
+    }
+}
+
+pub fn apply(node: &SyntaxRef, edit: Edit) {
+    match edit {
+        Edit::Keep => {}
+        Edit::Replace(nodes) => {
+            for x in &nodes {
+                node.insert_before(x);
+            }
+            node.detach();
+        }
+        Edit::Remove => node.detach(),
+        Edit::InsertBefore(nodes) => {
+            for x in &nodes {
+                node.insert_before(x);
+            }
+        }
+        Edit::InsertAfter(nodes) => {
+            for x in nodes.iter().rev() {
+                node.insert_after(x);
+            }
+        }
+    }
+}
+
+macro_rules! visitor {
+    ($($kind: ident => $visit: ident, $visit_mut: ident;)*) => {
+        /// Read-only walk over the syntax tree.
+        pub trait Visitor {
+            fn visit(&mut self, node: &SyntaxRef) {
+                match node.kind() {
+                    SyntaxKind::Token => self.visit_token(node),
+                    $(SyntaxKind::$kind => self.$visit(node),)*
+                }
+            }
+
+            fn visit_token(&mut self, _token: &SyntaxRef) {}
+
+            $(fn $visit(&mut self, node: &SyntaxRef) {
+                walk(self, node)
+            })*
+        }
+
+        /// Walk over the syntax tree that can replace, remove and insert nodes.
+        pub trait VisitorMut {
+            fn visit_mut(&mut self, node: &SyntaxRef) -> Edit {
+                match node.kind() {
+                    SyntaxKind::Token => self.visit_token_mut(node),
+                    $(SyntaxKind::$kind => self.$visit_mut(node),)*
+                }
+            }
+
+            fn visit_token_mut(&mut self, _token: &SyntaxRef) -> Edit {
+                Edit::Keep
+            }
+
+            $(fn $visit_mut(&mut self, node: &SyntaxRef) -> Edit {
+                walk_mut(self, node);
+                Edit::Keep
+            })*
+        }
+    }
+}
+
+visitor! {
+    Error => visit_error, visit_error_mut;
+    CompilationUnit => visit_compilation_unit, visit_compilation_unit_mut;
+    ExternAliasDirective => visit_extern_alias_directive, visit_extern_alias_directive_mut;
+    UsingDirective => visit_using_directive, visit_using_directive_mut;
+    NamespaceDeclaration => visit_namespace_declaration, visit_namespace_declaration_mut;
+    NamespaceBody => visit_namespace_body, visit_namespace_body_mut;
+    AttributeList => visit_attribute_list, visit_attribute_list_mut;
+    Attribute => visit_attribute, visit_attribute_mut;
+    AttributeArgumentList => visit_attribute_argument_list, visit_attribute_argument_list_mut;
+    AttributeArgument => visit_attribute_argument, visit_attribute_argument_mut;
+    ClassDeclaration => visit_class_declaration, visit_class_declaration_mut;
+    StructDeclaration => visit_struct_declaration, visit_struct_declaration_mut;
+    InterfaceDeclaration => visit_interface_declaration, visit_interface_declaration_mut;
+    EnumDeclaration => visit_enum_declaration, visit_enum_declaration_mut;
+    DelegateDeclaration => visit_delegate_declaration, visit_delegate_declaration_mut;
+    TypeParameterList => visit_type_parameter_list, visit_type_parameter_list_mut;
+    TypeParameter => visit_type_parameter, visit_type_parameter_mut;
+    TypeParameterConstraintClause => visit_type_parameter_constraint_clause, visit_type_parameter_constraint_clause_mut;
+    BaseList => visit_base_list, visit_base_list_mut;
+    TypeBody => visit_type_body, visit_type_body_mut;
+    EnumBody => visit_enum_body, visit_enum_body_mut;
+    EnumMemberDeclaration => visit_enum_member_declaration, visit_enum_member_declaration_mut;
+    FieldDeclaration => visit_field_declaration, visit_field_declaration_mut;
+    EventFieldDeclaration => visit_event_field_declaration, visit_event_field_declaration_mut;
+    VariableDeclaration => visit_variable_declaration, visit_variable_declaration_mut;
+    VariableDeclarator => visit_variable_declarator, visit_variable_declarator_mut;
+    MethodDeclaration => visit_method_declaration, visit_method_declaration_mut;
+    ConstructorDeclaration => visit_constructor_declaration, visit_constructor_declaration_mut;
+    ConstructorInitializer => visit_constructor_initializer, visit_constructor_initializer_mut;
+    DestructorDeclaration => visit_destructor_declaration, visit_destructor_declaration_mut;
+    PropertyDeclaration => visit_property_declaration, visit_property_declaration_mut;
+    IndexerDeclaration => visit_indexer_declaration, visit_indexer_declaration_mut;
+    EventDeclaration => visit_event_declaration, visit_event_declaration_mut;
+    OperatorDeclaration => visit_operator_declaration, visit_operator_declaration_mut;
+    ConversionOperatorDeclaration => visit_conversion_operator_declaration, visit_conversion_operator_declaration_mut;
+    ExplicitInterfaceSpecifier => visit_explicit_interface_specifier, visit_explicit_interface_specifier_mut;
+    AccessorList => visit_accessor_list, visit_accessor_list_mut;
+    AccessorDeclaration => visit_accessor_declaration, visit_accessor_declaration_mut;
+    ParameterList => visit_parameter_list, visit_parameter_list_mut;
+    BracketedParameterList => visit_bracketed_parameter_list, visit_bracketed_parameter_list_mut;
+    Parameter => visit_parameter, visit_parameter_mut;
+    ArrowExpressionClause => visit_arrow_expression_clause, visit_arrow_expression_clause_mut;
+    PredefinedType => visit_predefined_type, visit_predefined_type_mut;
+    IdentifierName => visit_identifier_name, visit_identifier_name_mut;
+    GenericName => visit_generic_name, visit_generic_name_mut;
+    QualifiedName => visit_qualified_name, visit_qualified_name_mut;
+    AliasQualifiedName => visit_alias_qualified_name, visit_alias_qualified_name_mut;
+    TypeArgumentList => visit_type_argument_list, visit_type_argument_list_mut;
+    ArrayType => visit_array_type, visit_array_type_mut;
+    ArrayRankSpecifier => visit_array_rank_specifier, visit_array_rank_specifier_mut;
+    NullableType => visit_nullable_type, visit_nullable_type_mut;
+    TupleType => visit_tuple_type, visit_tuple_type_mut;
+    TupleElement => visit_tuple_element, visit_tuple_element_mut;
+    Block => visit_block, visit_block_mut;
+    LocalDeclarationStatement => visit_local_declaration_statement, visit_local_declaration_statement_mut;
+    ExpressionStatement => visit_expression_statement, visit_expression_statement_mut;
+    EmptyStatement => visit_empty_statement, visit_empty_statement_mut;
+    IfStatement => visit_if_statement, visit_if_statement_mut;
+    ElseClause => visit_else_clause, visit_else_clause_mut;
+    WhileStatement => visit_while_statement, visit_while_statement_mut;
+    DoStatement => visit_do_statement, visit_do_statement_mut;
+    ForStatement => visit_for_statement, visit_for_statement_mut;
+    ForEachStatement => visit_for_each_statement, visit_for_each_statement_mut;
+    SwitchStatement => visit_switch_statement, visit_switch_statement_mut;
+    SwitchSection => visit_switch_section, visit_switch_section_mut;
+    CaseSwitchLabel => visit_case_switch_label, visit_case_switch_label_mut;
+    CasePatternSwitchLabel => visit_case_pattern_switch_label, visit_case_pattern_switch_label_mut;
+    DefaultSwitchLabel => visit_default_switch_label, visit_default_switch_label_mut;
+    WhenClause => visit_when_clause, visit_when_clause_mut;
+    BreakStatement => visit_break_statement, visit_break_statement_mut;
+    ContinueStatement => visit_continue_statement, visit_continue_statement_mut;
+    ReturnStatement => visit_return_statement, visit_return_statement_mut;
+    ThrowStatement => visit_throw_statement, visit_throw_statement_mut;
+    TryStatement => visit_try_statement, visit_try_statement_mut;
+    CatchClause => visit_catch_clause, visit_catch_clause_mut;
+    CatchDeclaration => visit_catch_declaration, visit_catch_declaration_mut;
+    CatchFilterClause => visit_catch_filter_clause, visit_catch_filter_clause_mut;
+    FinallyClause => visit_finally_clause, visit_finally_clause_mut;
+    UsingStatement => visit_using_statement, visit_using_statement_mut;
+    LockStatement => visit_lock_statement, visit_lock_statement_mut;
+    YieldReturnStatement => visit_yield_return_statement, visit_yield_return_statement_mut;
+    YieldBreakStatement => visit_yield_break_statement, visit_yield_break_statement_mut;
+    GotoStatement => visit_goto_statement, visit_goto_statement_mut;
+    LabeledStatement => visit_labeled_statement, visit_labeled_statement_mut;
+    CheckedStatement => visit_checked_statement, visit_checked_statement_mut;
+    LiteralExpression => visit_literal_expression, visit_literal_expression_mut;
+    ThisExpression => visit_this_expression, visit_this_expression_mut;
+    BaseExpression => visit_base_expression, visit_base_expression_mut;
+    ParenthesizedExpression => visit_parenthesized_expression, visit_parenthesized_expression_mut;
+    MemberAccessExpression => visit_member_access_expression, visit_member_access_expression_mut;
+    ConditionalAccessExpression => visit_conditional_access_expression, visit_conditional_access_expression_mut;
+    MemberBindingExpression => visit_member_binding_expression, visit_member_binding_expression_mut;
+    ElementBindingExpression => visit_element_binding_expression, visit_element_binding_expression_mut;
+    InvocationExpression => visit_invocation_expression, visit_invocation_expression_mut;
+    ArgumentList => visit_argument_list, visit_argument_list_mut;
+    BracketedArgumentList => visit_bracketed_argument_list, visit_bracketed_argument_list_mut;
+    Argument => visit_argument, visit_argument_mut;
+    DeclarationExpression => visit_declaration_expression, visit_declaration_expression_mut;
+    ElementAccessExpression => visit_element_access_expression, visit_element_access_expression_mut;
+    PostfixUnaryExpression => visit_postfix_unary_expression, visit_postfix_unary_expression_mut;
+    PrefixUnaryExpression => visit_prefix_unary_expression, visit_prefix_unary_expression_mut;
+    CastExpression => visit_cast_expression, visit_cast_expression_mut;
+    AwaitExpression => visit_await_expression, visit_await_expression_mut;
+    BinaryExpression => visit_binary_expression, visit_binary_expression_mut;
+    AssignmentExpression => visit_assignment_expression, visit_assignment_expression_mut;
+    ConditionalExpression => visit_conditional_expression, visit_conditional_expression_mut;
+    IsExpression => visit_is_expression, visit_is_expression_mut;
+    IsPatternExpression => visit_is_pattern_expression, visit_is_pattern_expression_mut;
+    AsExpression => visit_as_expression, visit_as_expression_mut;
+    ObjectCreationExpression => visit_object_creation_expression, visit_object_creation_expression_mut;
+    ArrayCreationExpression => visit_array_creation_expression, visit_array_creation_expression_mut;
+    ImplicitArrayCreationExpression => visit_implicit_array_creation_expression, visit_implicit_array_creation_expression_mut;
+    InitializerExpression => visit_initializer_expression, visit_initializer_expression_mut;
+    TupleExpression => visit_tuple_expression, visit_tuple_expression_mut;
+    ObjectInitializerExpression => visit_object_initializer_expression, visit_object_initializer_expression_mut;
+    CollectionInitializerExpression => visit_collection_initializer_expression, visit_collection_initializer_expression_mut;
+    ComplexElementInitializerExpression => visit_complex_element_initializer_expression, visit_complex_element_initializer_expression_mut;
+    ImplicitElementAccess => visit_implicit_element_access, visit_implicit_element_access_mut;
+    AnonymousObjectCreationExpression => visit_anonymous_object_creation_expression, visit_anonymous_object_creation_expression_mut;
+    AnonymousObjectMemberDeclarator => visit_anonymous_object_member_declarator, visit_anonymous_object_member_declarator_mut;
+    TypeOfExpression => visit_type_of_expression, visit_type_of_expression_mut;
+    DefaultExpression => visit_default_expression, visit_default_expression_mut;
+    SizeOfExpression => visit_size_of_expression, visit_size_of_expression_mut;
+    CheckedExpression => visit_checked_expression, visit_checked_expression_mut;
+    LambdaExpression => visit_lambda_expression, visit_lambda_expression_mut;
+    AnonymousMethodExpression => visit_anonymous_method_expression, visit_anonymous_method_expression_mut;
+    SwitchExpression => visit_switch_expression, visit_switch_expression_mut;
+    SwitchExpressionArm => visit_switch_expression_arm, visit_switch_expression_arm_mut;
+    ConstantPattern => visit_constant_pattern, visit_constant_pattern_mut;
+    TypePattern => visit_type_pattern, visit_type_pattern_mut;
+    DeclarationPattern => visit_declaration_pattern, visit_declaration_pattern_mut;
+    VarPattern => visit_var_pattern, visit_var_pattern_mut;
+    DiscardPattern => visit_discard_pattern, visit_discard_pattern_mut;
+    RelationalPattern => visit_relational_pattern, visit_relational_pattern_mut;
+    RecursivePattern => visit_recursive_pattern, visit_recursive_pattern_mut;
+    PositionalPatternClause => visit_positional_pattern_clause, visit_positional_pattern_clause_mut;
+    PropertyPatternClause => visit_property_pattern_clause, visit_property_pattern_clause_mut;
+    Subpattern => visit_subpattern, visit_subpattern_mut;
+    ParenthesizedPattern => visit_parenthesized_pattern, visit_parenthesized_pattern_mut;
+    UnaryPattern => visit_unary_pattern, visit_unary_pattern_mut;
+    BinaryPattern => visit_binary_pattern, visit_binary_pattern_mut;
+    SingleVariableDesignation => visit_single_variable_designation, visit_single_variable_designation_mut;
+    DiscardDesignation => visit_discard_designation, visit_discard_designation_mut;
+    ParenthesizedVariableDesignation => visit_parenthesized_variable_designation, visit_parenthesized_variable_designation_mut;
+}
+
+pub use self::VisitorMut as Rewriter;
+
+/// Runs `rewriter` over `root` and its descendants; `root` itself is never removed.
+pub fn rewrite<V: VisitorMut + ?Sized>(rewriter: &mut V, root: &SyntaxRef) {
+    walk_mut(rewriter, root);
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::dy_parser::{DyParser, Span, TokenKind};
+    use vm::dy_syntax::{SyntaxNode, SyntaxTree};
+
+    fn parse(code: &str) -> SyntaxTree {
+        let mut dy_parser = DyParser::new(code.to_string());
+        dy_parser.lexer();
+        dy_parser.parse()
+    }
+
+    struct Counter {
+        methods: Vec<String>,
+        invocations: usize,
+    }
+
+    impl Visitor for Counter {
+        fn visit_method_declaration(&mut self, node: &SyntaxRef) {
+            self.methods.push(node.identifier().unwrap().text());
+            walk(self, node);
+        }
+
+        fn visit_invocation_expression(&mut self, node: &SyntaxRef) {
+            self.invocations += 1;
+            walk(self, node);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let tree = parse(include_str!("../../examples/hello_world.dy"));
+        let mut counter = Counter { methods: Vec::new(), invocations: 0 };
+        counter.visit(&tree.root);
+        assert_eq!(counter.methods, vec!["Add", "Main"]);
+        assert_eq!(counter.invocations, 3);
+    }
+
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_token_mut(&mut self, token: &SyntaxRef) -> Edit {
+            if token.token_kind() == Some(TokenKind::Identifier) && token.text() == "a" {
+                let span = token.span();
+                return Edit::Replace(vec![SyntaxRef::new(SyntaxNode::token(TokenKind::Identifier, "x".to_string(), span))]);
+            }
+            Edit::Keep
+        }
+
+        fn visit_expression_statement_mut(&mut self, node: &SyntaxRef) -> Edit {
+            if node.text().contains("remove") {
+                return Edit::Remove;
+            }
+            walk_mut(self, node);
+            Edit::InsertAfter(vec![SyntaxRef::new(SyntaxNode::token(TokenKind::Comment, " /*after*/".to_string(),
+                Span::default()))])
+        }
+    }
+
+    #[test]
+    fn test_rewriter() {
+        let tree = parse("void F(int a) { remove(); G(a); }");
+        rewrite(&mut Rename, &tree.root);
+        assert_eq!(tree.text(), "void F(int x) { G(x); /*after*/ }");
+    }
+}
@@ -1,4 +1,25 @@
 pub mod dy_parser;
-mod dy_grammar;
+pub mod dy_grammar;
+pub mod dy_syntax;
+pub mod dy_format;
+pub mod dy_visitor;
+pub mod dy_json;
+pub mod dy_export;
+pub mod dy_lower;
+pub mod dy_task;
+pub mod dy_iter;
+pub mod dy_types;
+pub mod dy_dispose;
+pub mod dy_symbols;
+pub mod dy_binder;
+pub mod dy_check;
+pub mod dy_overload;
+pub mod dy_flow;
+pub mod dy_inherit;
+pub mod dy_access;
+pub mod dy_const;
+pub mod dy_nullable;
+pub mod dy_diagnostic;
+pub mod dy_locale;
 mod dy_util;
 pub mod dy_common;